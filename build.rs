@@ -0,0 +1,55 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc::generate();
+}
+
+/// generates the service of `proto/galaxy.proto` without protoc, the message types are
+/// written by hand in `src/grpc.rs`
+#[cfg(feature = "grpc")]
+mod grpc {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    const METHODS: [(&str, &str, &str, &str, bool); 7] = [
+        ("pause", "Pause", "Empty", "Empty", false),
+        ("resume", "Resume", "Empty", "Empty", false),
+        (
+            "set_time_factor",
+            "SetTimeFactor",
+            "TimeFactor",
+            "Empty",
+            false,
+        ),
+        ("get_stats", "GetStats", "Empty", "Stats", false),
+        (
+            "stream_state",
+            "StreamState",
+            "StreamRequest",
+            "State",
+            true,
+        ),
+        ("get_snapshot", "GetSnapshot", "Empty", "Snapshot", false),
+        ("load_snapshot", "LoadSnapshot", "Snapshot", "Empty", false),
+    ];
+
+    pub fn generate() {
+        println!("cargo:rerun-if-changed=build.rs");
+        let mut service = Service::builder().name("Galaxy").package("galaxy");
+        for &(name, route, input, output, streaming) in METHODS.iter() {
+            let method = Method::builder()
+                .name(name)
+                .route_name(route)
+                .input_type(format!("crate::grpc::proto::{}", input))
+                .output_type(format!("crate::grpc::proto::{}", output))
+                .codec_path("tonic::codec::ProstCodec");
+            let method = if streaming {
+                method.server_streaming()
+            } else {
+                method
+            };
+            service = service.method(method.build());
+        }
+        Builder::new()
+            .build_client(false)
+            .compile(&[service.build()]);
+    }
+}
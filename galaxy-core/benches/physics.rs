@@ -0,0 +1,119 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use galaxy_core::{
+    gravity, initial_conditions, integrator,
+    octree::{self, Octree},
+    Integrator, Particles, SimulationParameters, SpatialHash,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+const COUNTS: [usize; 3] = [100, 1000, 4000];
+
+fn particles(count: usize) -> (Particles, SimulationParameters) {
+    let parameters = SimulationParameters {
+        number_of_stars: count,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(1);
+    let particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    (particles, parameters)
+}
+
+fn forces(c: &mut Criterion) {
+    let mut group = c.benchmark_group("forces");
+    for &count in COUNTS.iter() {
+        let (mut particles, parameters) = particles(count);
+        group.bench_with_input(BenchmarkId::new("direct_sum", count), &count, |b, _| {
+            b.iter(|| gravity::direct_sum(black_box(&mut particles), &parameters))
+        });
+    }
+    // direct sums of that many take too long to bench
+    for &count in COUNTS.iter().chain(&[50_000]) {
+        let (mut particles, mut parameters) = particles(count);
+        parameters.opening_angle = 0.5;
+        group.bench_with_input(BenchmarkId::new("barnes_hut", count), &count, |b, _| {
+            b.iter(|| octree::barnes_hut(black_box(&mut particles), &parameters))
+        });
+    }
+    group.finish();
+}
+
+fn octree_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("octree_build");
+    for &count in COUNTS.iter().chain(&[50_000]) {
+        let (particles, _) = particles(count);
+        group.bench_with_input(BenchmarkId::new("serial", count), &count, |b, _| {
+            b.iter(|| Octree::new(black_box(&particles)))
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", count), &count, |b, _| {
+            b.iter(|| Octree::with_threads(black_box(&particles), 0))
+        });
+    }
+    group.finish();
+}
+
+fn neighbors(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbors");
+    for &count in COUNTS.iter() {
+        let (particles, parameters) = particles(count);
+        let cell_size = parameters.galaxy_diameter / 20.0;
+        group.bench_with_input(BenchmarkId::new("spatial_hash", count), &count, |b, _| {
+            let mut hash = SpatialHash::new(cell_size);
+            b.iter(|| {
+                hash.rebuild(&particles.positions, &particles.frozen);
+                let mut pairs = 0;
+                hash.pairs(&particles.positions, cell_size, |_, _| pairs += 1);
+                pairs
+            })
+        });
+    }
+    group.finish();
+}
+
+/// one step of every integrator with the force sums it takes within the step, the
+/// accelerations at its start are left from the step before
+fn integrators(c: &mut Criterion) {
+    let mut group = c.benchmark_group("integrators");
+    for integrator in Integrator::ALL {
+        for &count in COUNTS.iter() {
+            let (mut particles, mut parameters) = particles(count);
+            parameters.integrator = integrator;
+            gravity::accelerations(&mut particles, &parameters);
+            let id = BenchmarkId::new(integrator.name(), count);
+            group.bench_with_input(id, &count, |b, _| {
+                b.iter(|| {
+                    integrator::advance(black_box(&mut particles), &parameters, |particles| {
+                        gravity::accelerations(particles, &parameters)
+                    })
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn initial_conditions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("initial_conditions");
+    for &count in COUNTS.iter() {
+        let parameters = SimulationParameters {
+            number_of_stars: count,
+            ..Default::default()
+        };
+        group.bench_with_input(BenchmarkId::new("random_disk", count), &count, |b, _| {
+            let mut rng = StdRng::seed_from_u64(1);
+            b.iter(|| initial_conditions::random_disk(&mut rng, &parameters))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    forces,
+    octree_build,
+    neighbors,
+    integrators,
+    initial_conditions
+);
+criterion_main!(benches);
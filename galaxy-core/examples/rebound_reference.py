@@ -0,0 +1,92 @@
+"""Integrates initial conditions exported by the validate example with REBOUND.
+
+usage: python3 rebound_reference.py [--rk4] initial.json reference.csv
+
+Needs `pip install rebound`. Writes time, star, x, y, z, vx, vy, vz in SI units for every
+star every INTERVAL seconds, the constants must match galaxy_core::reference.
+
+`--rk4` integrates without REBOUND in classic Runge-Kutta steps of RK4_STEP seconds
+instead, ten times shorter than the steps of the solver. It wrote
+galaxy-core/tests/reference/moons.csv, so the tests don't need REBOUND installed.
+"""
+
+import json
+import sys
+
+G = 6.674e-11
+DURATION = 20_000
+INTERVAL = 1_000
+RK4_STEP = 0.1
+
+
+def rebound_samples(stars):
+    import rebound
+
+    sim = rebound.Simulation()
+    sim.G = G
+    sim.integrator = "ias15"
+    for star in stars:
+        x, y, z = star["position"]
+        vx, vy, vz = star["velocity"]
+        sim.add(m=star["mass"], x=x, y=y, z=z, vx=vx, vy=vy, vz=vz)
+    for time in range(0, DURATION + 1, INTERVAL):
+        sim.integrate(time, exact_finish_time=1)
+        yield time, [(p.x, p.y, p.z, p.vx, p.vy, p.vz) for p in sim.particles]
+
+
+def derivatives(masses, state):
+    n = len(masses)
+    result = [[v[0], v[1], v[2], 0.0, 0.0, 0.0] for v in (s[3:] for s in state)]
+    for i in range(n):
+        for j in range(i + 1, n):
+            d = [state[j][k] - state[i][k] for k in range(3)]
+            r2 = d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+            f = G / (r2 * r2**0.5)
+            for k in range(3):
+                result[i][3 + k] += f * masses[j] * d[k]
+                result[j][3 + k] -= f * masses[i] * d[k]
+    return result
+
+
+def rk4_samples(stars):
+    masses = [star["mass"] for star in stars]
+    state = [list(star["position"]) + list(star["velocity"]) for star in stars]
+    steps_per_interval = round(INTERVAL / RK4_STEP)
+    h = RK4_STEP
+
+    def shifted(state, k, factor):
+        return [[s + factor * d for s, d in zip(a, b)] for a, b in zip(state, k)]
+
+    for time in range(0, DURATION + 1, INTERVAL):
+        yield time, [tuple(s) for s in state]
+        for _ in range(steps_per_interval):
+            k1 = derivatives(masses, state)
+            k2 = derivatives(masses, shifted(state, k1, h / 2))
+            k3 = derivatives(masses, shifted(state, k2, h / 2))
+            k4 = derivatives(masses, shifted(state, k3, h))
+            state = [
+                [s + h / 6 * (a + 2 * b + 2 * c + d) for s, a, b, c, d in zip(*rows)]
+                for rows in zip(state, k1, k2, k3, k4)
+            ]
+
+
+def main(args):
+    rk4 = args[:1] == ["--rk4"]
+    if rk4:
+        args = args[1:]
+    if len(args) != 2:
+        sys.exit(__doc__)
+    initial_path, reference_path = args
+    with open(initial_path) as f:
+        stars = json.load(f)["stars"]
+
+    samples = rk4_samples(stars) if rk4 else rebound_samples(stars)
+    with open(reference_path, "w") as f:
+        f.write("time,star,x,y,z,vx,vy,vz\n")
+        for time, states in samples:
+            for i, (x, y, z, vx, vy, vz) in enumerate(states):
+                f.write(f"{time},{i},{x!r},{y!r},{z!r},{vx!r},{vy!r},{vz!r}\n")
+
+
+if __name__ == "__main__":
+    main(sys.argv[1:])
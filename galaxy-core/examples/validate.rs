@@ -0,0 +1,73 @@
+//! Compares the integrator with a reference trajectory from another N-body code.
+//!
+//! ```text
+//! cargo run -p galaxy-core --example validate -- export initial.json
+//! python3 galaxy-core/examples/rebound_reference.py initial.json reference.csv
+//! cargo run -p galaxy-core --example validate -- compare initial.json reference.csv rk4
+//! ```
+//!
+//! `rebound_reference.py` writes the reference with REBOUND's IAS15, the format and the
+//! integration are described in `galaxy_core::reference`. The last argument picks the
+//! integrator, by default the one of the simulation.
+
+use galaxy_core::{reference, Integrator, SimulationParameters, Snapshot};
+use std::{path::Path, process};
+
+fn compare(initial: &Path, reference: &Path, integrator: Integrator) -> galaxy_core::Result<()> {
+    let parameters = SimulationParameters {
+        integrator,
+        ..Default::default()
+    };
+    let particles = Snapshot::load(initial)?.particles();
+    let reference = reference::read(reference)?;
+
+    println!("{} integrator", integrator.name());
+    println!("time s, max position error m, max relative position error, energy drift, reference energy drift");
+    let mut max_relative_error: f64 = 0.0;
+    for sample in reference::compare(particles, &parameters, &reference) {
+        max_relative_error = max_relative_error.max(sample.relative_error);
+        println!(
+            "{}, {:e}, {:e}, {:e}, {:e}",
+            sample.time,
+            sample.error,
+            sample.relative_error,
+            sample.energy_drift,
+            sample.reference_energy_drift
+        );
+    }
+    println!("maximum relative position error: {:e}", max_relative_error);
+    Ok(())
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: validate export <initial.json> | compare <initial.json> <reference.csv> [integrator]"
+    );
+    process::exit(2);
+}
+
+fn run(args: &[String]) -> galaxy_core::Result<()> {
+    match args {
+        [command, initial] if command == "export" => Snapshot {
+            time: 0.0,
+            stars: reference::moons(&SimulationParameters::default()),
+        }
+        .save(Path::new(initial)),
+        [command, initial, reference, rest @ ..] if command == "compare" && rest.len() <= 1 => {
+            let integrator = match rest.first() {
+                Some(name) => name.parse().unwrap_or_else(|()| usage()),
+                None => SimulationParameters::default().integrator,
+            };
+            compare(Path::new(initial), Path::new(reference), integrator)
+        }
+        _ => usage(),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,33 @@
+//! Stars falling into the black hole, the first particle.
+//!
+//! A star that comes closer to the black hole than `capture_radius` is torn apart by its
+//! tides and swallowed: its mass joins the black hole and the star is gone.
+
+use crate::{Particles, SimulationParameters, Star};
+
+/// indices of the unfrozen stars within `parameters.capture_radius` of the black hole, in
+/// descending order, so they can be removed one after the other
+pub fn captured(particles: &Particles, parameters: &SimulationParameters) -> Vec<usize> {
+    let radius = parameters.capture_radius;
+    match particles.positions.first() {
+        Some(&black_hole) if radius > 0.0 => (1..particles.len())
+            .rev()
+            .filter(|&i| {
+                !particles.frozen[i] && particles.positions[i].distance(black_hole) < radius
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// moves the captured stars into the black hole and returns them
+pub fn accrete(particles: &mut Particles, parameters: &SimulationParameters) -> Vec<Star> {
+    captured(particles, parameters)
+        .into_iter()
+        .map(|i| {
+            let star = particles.swap_remove(i);
+            particles.masses[0] += star.mass;
+            star
+        })
+        .collect()
+}
@@ -0,0 +1,460 @@
+//! Writes recorded star positions as an Alembic point cache for Blender, Houdini and other
+//! DCC tools.
+//!
+//! The file uses the Ogawa container of Alembic 1.5 and later with one `AbcGeom_Points_v1`
+//! object named `galaxy`. Every frame stores the positions as `P`, the stable particle ids
+//! as `id` and the bounding box. The sample data is written as it is recorded, so only the
+//! file offsets stay in memory, the object and property headers follow in `finish`.
+//!
+//! Ogawa layout: the 16 byte header `Ogawa`, frozen flag, version 1 and the offset of the
+//! root group. A group is a child count and the child offsets, a data block its size and
+//! bytes. The highest bit of a child offset marks data, offset 0 is an empty group.
+
+use crate::{Error, Particles, Result};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+const DATA: u64 = 1 << 63;
+const EMPTY_GROUP: u64 = 0;
+const EMPTY_DATA: u64 = DATA;
+/// offset of the frozen flag, set when the file is complete
+const FROZEN: u64 = 5;
+const ROOT: u64 = 8;
+const HEADER: u64 = 16;
+/// Alembic 1.7.16, only informational for readers
+const LIBRARY_VERSION: i32 = 10716;
+
+const POINTS_OBJECT: &str =
+    "schema=AbcGeom_Points_v1;schemaBaseType=AbcGeom_GeomBase_v1;schemaObjTitle=AbcGeom_Points_v1:.geom";
+const POINTS_SCHEMA: &str = "schema=AbcGeom_Points_v1;schemaBaseType=AbcGeom_GeomBase_v1";
+const ARCHIVE: &str = "_ai_Application=galaxy";
+
+/// POD type codes of Alembic
+const UINT64: u32 = 7;
+const FLOAT32: u32 = 10;
+const FLOAT64: u32 = 11;
+
+/// property types of Alembic, array properties with one value per sample add 1
+const COMPOUND: u32 = 0;
+const SCALAR: u32 = 1;
+const ARRAY: u32 = 2;
+
+/// the time sampling of all recorded properties, 0 is Alembic's default sampling
+const TIME_SAMPLING: u32 = 1;
+
+/// offsets of the data and dimension blocks of one property, per frame
+#[derive(Default)]
+struct Samples {
+    data: Vec<u64>,
+    dims: Vec<u64>,
+    /// keys of all samples, hashed together for the object hash
+    keys: Vec<u8>,
+}
+
+/// streams recorded frames to an Alembic file, call `finish` to make it readable
+pub struct PointCache {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// where the next block goes
+    end: u64,
+    /// frames per second of scene time
+    fps: f64,
+    /// scene units per meter
+    scale: f64,
+    bounds: Samples,
+    positions: Samples,
+    ids: Samples,
+    /// smallest and largest star count of all frames
+    counts: Option<(usize, usize)>,
+}
+
+impl PointCache {
+    /// starts a new file, frame `n` is shown at `n / fps` seconds and positions are
+    /// multiplied by `scale` to get scene units
+    pub fn create(path: &Path, fps: f64, scale: f64) -> Result<Self> {
+        let mut header = [0; HEADER as usize];
+        header[..5].copy_from_slice(b"Ogawa");
+        // not frozen yet, version 1
+        header[7] = 1;
+        let mut file = File::create(path).map_err(|e| Error::io(path, e))?;
+        file.write_all(&header).map_err(|e| Error::io(path, e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: BufWriter::new(file),
+            end: HEADER,
+            fps,
+            scale,
+            bounds: Samples::default(),
+            positions: Samples::default(),
+            ids: Samples::default(),
+            counts: None,
+        })
+    }
+
+    /// frames recorded so far
+    pub fn frames(&self) -> usize {
+        self.positions.data.len()
+    }
+
+    pub fn write_frame(&mut self, particles: &Particles) -> Result<()> {
+        self.write_frame_io(particles)
+            .map_err(|e| Error::io(&self.path, e))
+    }
+
+    fn write_frame_io(&mut self, particles: &Particles) -> io::Result<()> {
+        let mut min = [f64::MAX; 3];
+        let mut max = [f64::MIN; 3];
+        let mut positions = Vec::with_capacity(12 * particles.len());
+        for p in &particles.positions {
+            let p = [p.x * self.scale, p.y * self.scale, p.z * self.scale];
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+                positions.extend_from_slice(&(p[axis] as f32).to_le_bytes());
+            }
+        }
+        let bounds: Vec<u8> = min
+            .iter()
+            .chain(&max)
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let ids: Vec<u8> = particles
+            .ids
+            .iter()
+            .flat_map(|id| id.0.to_le_bytes())
+            .collect();
+
+        let (data, key) = self.sample(&bounds)?;
+        self.bounds.data.push(data);
+        self.bounds.keys.extend_from_slice(&key);
+        let count = particles.len();
+        let positions = self.array_sample(&positions, count)?;
+        push_sample(&mut self.positions, positions);
+        let ids = self.array_sample(&ids, count)?;
+        push_sample(&mut self.ids, ids);
+        self.counts = Some(match self.counts {
+            Some((low, high)) => (low.min(count), high.max(count)),
+            None => (count, count),
+        });
+        Ok(())
+    }
+
+    /// writes the headers and marks the file complete, returns the number of frames
+    pub fn finish(mut self) -> Result<usize> {
+        let frames = self.frames();
+        self.finish_io().map_err(|e| Error::io(&self.path, e))?;
+        Ok(frames)
+    }
+
+    fn finish_io(&mut self) -> io::Result<()> {
+        let frames = self.frames() as u32;
+        let (low, high) = self.counts.unwrap_or((0, 0));
+        let homogeneous = low == high;
+        let scalar_like = high == 1;
+
+        // the .geom compound with bounds, positions and ids
+        let bounds = self.group(&self.bounds.data.clone())?;
+        let positions = self.group(&interleave(&self.positions))?;
+        let ids = self.group(&interleave(&self.ids))?;
+        let mut headers = Vec::new();
+        let scalar = Property::Scalar {
+            pod: FLOAT64,
+            extent: 6,
+        };
+        property_header(
+            &mut headers,
+            ".selfBnds",
+            "interpretation=box",
+            scalar,
+            frames,
+        );
+        let array = |pod, extent| Property::Array {
+            pod,
+            extent,
+            homogeneous,
+            scalar_like,
+        };
+        property_header(
+            &mut headers,
+            "P",
+            "interpretation=point",
+            array(FLOAT32, 3),
+            frames,
+        );
+        property_header(&mut headers, "id", "", array(UINT64, 1), frames);
+        let headers = self.data(&[&headers])?;
+        let geom = self.group(&[bounds, positions, ids, headers])?;
+
+        // the properties of the points object, only .geom
+        let mut headers = Vec::new();
+        property_header(&mut headers, ".geom", POINTS_SCHEMA, Property::Compound, 0);
+        let headers = self.data(&[&headers])?;
+        let properties = self.group(&[geom, headers])?;
+
+        let keys = [&self.bounds.keys[..], &self.positions.keys, &self.ids.keys];
+        let properties_hash = murmur3(&keys.concat());
+        let points_headers = self.data(&[&properties_hash, &[0; 16]])?;
+        let points = self.group(&[properties, points_headers])?;
+
+        // the top object holds the points object and no properties
+        let mut headers = Vec::new();
+        object_header(&mut headers, "galaxy", POINTS_OBJECT);
+        let children_hash = murmur3(&properties_hash);
+        let top_headers = self.data(&[&headers, &[0; 16], &children_hash])?;
+        let top = self.group(&[EMPTY_GROUP, points, top_headers])?;
+
+        let version = self.data(&[&0i32.to_le_bytes()])?;
+        let library = self.data(&[&LIBRARY_VERSION.to_le_bytes()])?;
+        let metadata = self.data(&[ARCHIVE.as_bytes()])?;
+        let time_samplings = self.data(&[&time_samplings(frames, self.fps)])?;
+        let indexed_metadata = EMPTY_DATA;
+        let root = self.group(&[
+            version,
+            library,
+            top,
+            metadata,
+            time_samplings,
+            indexed_metadata,
+        ])?;
+
+        self.file.seek(SeekFrom::Start(ROOT))?;
+        self.file.write_all(&root.to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(FROZEN))?;
+        self.file.write_all(&[0xff])?;
+        self.file.flush()
+    }
+
+    /// a data block prefixed with the key readers use to share identical samples
+    fn sample(&mut self, bytes: &[u8]) -> io::Result<(u64, [u8; 16])> {
+        let key = murmur3(bytes);
+        let data = if bytes.is_empty() {
+            EMPTY_DATA
+        } else {
+            self.data(&[&key, bytes])?
+        };
+        Ok((data, key))
+    }
+
+    /// an array sample and its dimensions, which is one length for all recorded arrays
+    fn array_sample(&mut self, bytes: &[u8], count: usize) -> io::Result<(u64, u64, [u8; 16])> {
+        let (data, key) = self.sample(bytes)?;
+        let dims = self.data(&[&(count as u64).to_le_bytes()])?;
+        Ok((data, dims, key))
+    }
+
+    fn data(&mut self, parts: &[&[u8]]) -> io::Result<u64> {
+        let size: usize = parts.iter().map(|part| part.len()).sum();
+        if size == 0 {
+            return Ok(EMPTY_DATA);
+        }
+        let offset = self.end;
+        self.file.write_all(&(size as u64).to_le_bytes())?;
+        for part in parts {
+            self.file.write_all(part)?;
+        }
+        self.end += 8 + size as u64;
+        Ok(offset | DATA)
+    }
+
+    fn group(&mut self, children: &[u64]) -> io::Result<u64> {
+        if children.is_empty() {
+            return Ok(EMPTY_GROUP);
+        }
+        let offset = self.end;
+        self.file
+            .write_all(&(children.len() as u64).to_le_bytes())?;
+        for child in children {
+            self.file.write_all(&child.to_le_bytes())?;
+        }
+        self.end += 8 * (1 + children.len() as u64);
+        Ok(offset)
+    }
+}
+
+fn push_sample(samples: &mut Samples, (data, dims, key): (u64, u64, [u8; 16])) {
+    samples.data.push(data);
+    samples.dims.push(dims);
+    samples.keys.extend_from_slice(&key);
+}
+
+/// array properties store the data and the dimensions of each sample one after the other
+fn interleave(samples: &Samples) -> Vec<u64> {
+    samples
+        .data
+        .iter()
+        .zip(&samples.dims)
+        .flat_map(|(&data, &dims)| [data, dims])
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+enum Property {
+    Compound,
+    Scalar {
+        pod: u32,
+        extent: u32,
+    },
+    Array {
+        pod: u32,
+        extent: u32,
+        homogeneous: bool,
+        /// every sample has exactly one value
+        scalar_like: bool,
+    },
+}
+
+/// appends the packed property header Alembic's Ogawa reader expects, the metadata is
+/// stored inline instead of in the indexed table
+fn property_header(
+    out: &mut Vec<u8>,
+    name: &str,
+    metadata: &str,
+    property: Property,
+    samples: u32,
+) {
+    const HAS_TIME_SAMPLING: u32 = 0x100;
+    const HOMOGENEOUS: u32 = 0x400;
+    const CONSTANT: u32 = 0x800;
+    const INLINE_METADATA: u32 = 0xff << 20;
+
+    let (value_property, homogeneous) = match property {
+        Property::Compound => (None, false),
+        Property::Scalar { pod, extent } => (Some((SCALAR, pod, extent)), false),
+        Property::Array {
+            pod,
+            extent,
+            homogeneous,
+            scalar_like,
+        } => (Some((ARRAY | scalar_like as u32, pod, extent)), homogeneous),
+    };
+    let largest = [
+        samples as usize,
+        TIME_SAMPLING as usize,
+        name.len(),
+        metadata.len(),
+    ]
+    .iter()
+    .copied()
+    .max()
+    .unwrap_or(0);
+    let size_hint = match largest {
+        0..=0xff => 0,
+        0x100..=0xffff => 1,
+        _ => 2,
+    };
+
+    let mut info = INLINE_METADATA | size_hint << 2;
+    if let Some((kind, pod, extent)) = value_property {
+        info |= kind | pod << 4 | HAS_TIME_SAMPLING | extent << 12;
+        if homogeneous {
+            info |= HOMOGENEOUS;
+        }
+        // changed samples are 1 to samples - 1 as every frame is stored, only a single
+        // sample has to be flagged as constant
+        if samples == 1 {
+            info |= CONSTANT;
+        }
+    } else {
+        info |= COMPOUND;
+    }
+    out.extend_from_slice(&info.to_le_bytes());
+
+    let push = |out: &mut Vec<u8>, value: usize| match size_hint {
+        0 => out.push(value as u8),
+        1 => out.extend_from_slice(&(value as u16).to_le_bytes()),
+        _ => out.extend_from_slice(&(value as u32).to_le_bytes()),
+    };
+    if value_property.is_some() {
+        push(out, samples as usize);
+        push(out, TIME_SAMPLING as usize);
+    }
+    push(out, name.len());
+    out.extend_from_slice(name.as_bytes());
+    push(out, metadata.len());
+    out.extend_from_slice(metadata.as_bytes());
+}
+
+fn object_header(out: &mut Vec<u8>, name: &str, metadata: &str) {
+    out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    out.extend_from_slice(name.as_bytes());
+    out.push(0xff);
+    out.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    out.extend_from_slice(metadata.as_bytes());
+}
+
+/// the default sampling at index 0 and the uniform frame sampling at index 1
+fn time_samplings(frames: u32, fps: f64) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (max_samples, time_per_cycle) in [(1, 1.0), (frames, 1.0 / fps)] {
+        out.extend_from_slice(&max_samples.to_le_bytes());
+        out.extend_from_slice(&time_per_cycle.to_le_bytes());
+        // one stored time, the start
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&0f64.to_le_bytes());
+    }
+    out
+}
+
+/// MurmurHash3 x64 128 with seed 0, what Alembic uses for sample keys
+fn murmur3(data: &[u8]) -> [u8; 16] {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+    fn mix1(k: u64) -> u64 {
+        k.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2)
+    }
+    fn mix2(k: u64) -> u64 {
+        k.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1)
+    }
+    fn fmix(mut k: u64) -> u64 {
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        k ^ (k >> 33)
+    }
+    let word = |bytes: &[u8]| {
+        let mut word = [0; 8];
+        word[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(word)
+    };
+
+    let (mut h1, mut h2) = (0u64, 0u64);
+    let blocks = data.chunks_exact(16);
+    let tail = blocks.remainder();
+    for block in blocks {
+        h1 ^= mix1(word(&block[..8]));
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+        h2 ^= mix2(word(&block[8..]));
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+    if tail.len() > 8 {
+        h2 ^= mix2(word(&tail[8..]));
+    }
+    if !tail.is_empty() {
+        h1 ^= mix1(word(&tail[..tail.len().min(8)]));
+    }
+
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix(h1);
+    h2 = fmix(h2);
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    let mut out = [0; 16];
+    out[..8].copy_from_slice(&h1.to_le_bytes());
+    out[8..].copy_from_slice(&h2.to_le_bytes());
+    out
+}
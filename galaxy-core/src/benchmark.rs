@@ -0,0 +1,183 @@
+//! Timings of the physics phases, for `--bench` and tracking performance between commits.
+//!
+//! A benchmark steps the stars like `step` and measures the wall clock time of its phases
+//! apart: the search for close encounters, the force sums and the rest of the integration.
+//! Hermite sums its forces inside the integrator, so for it the gravity is part of the
+//! integration. The results are one JSON object or one CSV row with a header, so a script
+//! can collect them for every commit.
+
+use crate::{gravity, Error, Particles, Result, SimulationParameters};
+use serde::Serialize;
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// physics steps of a benchmark without `steps=`
+pub const DEFAULT_STEPS: u64 = 1000;
+
+/// the run of `--bench`, `steps=1000 stars=10000`, a missing star count keeps the one of
+/// the parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Benchmark {
+    pub steps: u64,
+    pub stars: Option<usize>,
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Self {
+            steps: DEFAULT_STEPS,
+            stars: None,
+        }
+    }
+}
+
+impl FromStr for Benchmark {
+    type Err = Error;
+
+    /// `key=value` pairs separated by spaces or commas
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = |value: &str| Error::InvalidValue {
+            name: "--bench".to_string(),
+            value: value.to_string(),
+        };
+        let mut benchmark = Benchmark::default();
+        for pair in text.split(|c: char| c == ',' || c.is_whitespace()) {
+            if pair.is_empty() {
+                continue;
+            }
+            match pair.split_once('=') {
+                Some(("steps", n)) => benchmark.steps = n.parse().map_err(|_| invalid(pair))?,
+                Some(("stars", n)) => benchmark.stars = Some(n.parse().map_err(|_| invalid(pair))?),
+                _ => return Err(invalid(pair)),
+            }
+        }
+        if benchmark.steps == 0 {
+            return Err(invalid(text));
+        }
+        Ok(benchmark)
+    }
+}
+
+/// how the results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        match text {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(Error::InvalidValue {
+                name: "--bench-format".to_string(),
+                value: text.to_string(),
+            }),
+        }
+    }
+}
+
+/// wall clock time of the phases of one or more steps
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StepTimes {
+    pub encounters: Duration,
+    pub gravity: Duration,
+    pub integration: Duration,
+}
+
+impl std::ops::AddAssign for StepTimes {
+    fn add_assign(&mut self, other: Self) {
+        self.encounters += other.encounters;
+        self.gravity += other.gravity;
+        self.integration += other.integration;
+    }
+}
+
+/// `crate::step` with the time of each phase
+pub fn timed_step(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    close_encounter: impl FnMut(usize, usize),
+) -> StepTimes {
+    let started = Instant::now();
+    crate::report_close_encounters(particles, parameters, close_encounter);
+    let encounters = started.elapsed();
+
+    let mut gravity = Duration::default();
+    let started = Instant::now();
+    crate::advance_substeps(particles, parameters, |particles, parameters| {
+        let started = Instant::now();
+        gravity::accelerations(particles, parameters);
+        gravity += started.elapsed();
+    });
+    StepTimes {
+        encounters,
+        gravity,
+        integration: started.elapsed().saturating_sub(gravity),
+    }
+}
+
+/// the result of a benchmark, times in seconds
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Timings {
+    pub stars: usize,
+    pub steps: u64,
+    pub integrator: &'static str,
+    pub opening_angle: f64,
+    /// worker threads, 0 for all cores
+    pub threads: usize,
+    pub encounters: f64,
+    pub gravity: f64,
+    pub integration: f64,
+    pub total: f64,
+    pub steps_per_second: f64,
+}
+
+impl Timings {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("timings are plain numbers")
+    }
+
+    /// a header line and a row
+    pub fn to_csv(&self) -> String {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(self).expect("timings are plain numbers");
+        let bytes = writer.into_inner().expect("written to memory");
+        String::from_utf8(bytes).expect("csv of numbers and names")
+    }
+
+    pub fn format(&self, format: Format) -> String {
+        match format {
+            Format::Json => self.to_json(),
+            Format::Csv => self.to_csv(),
+        }
+    }
+}
+
+/// steps the particles `steps` times and sums the times of the phases
+pub fn run(particles: &mut Particles, parameters: &SimulationParameters, steps: u64) -> Timings {
+    let started = Instant::now();
+    let mut times = StepTimes::default();
+    for _ in 0..steps {
+        times += timed_step(particles, parameters, |_, _| {});
+    }
+    let total = started.elapsed().as_secs_f64();
+    Timings {
+        stars: particles.len(),
+        steps,
+        integrator: parameters.integrator.name(),
+        opening_angle: parameters.opening_angle,
+        threads: parameters.threads,
+        encounters: times.encounters.as_secs_f64(),
+        gravity: times.gravity.as_secs_f64(),
+        integration: times.integration.as_secs_f64(),
+        total,
+        steps_per_second: steps as f64 / total.max(f64::EPSILON),
+    }
+}
@@ -0,0 +1,276 @@
+//! Initial stars from real star catalogs, the HYG database or Gaia exports, as CSV files.
+//!
+//! The `[catalog]` table of the config file names the file and its format, the columns of
+//! the format can be renamed or replaced by others:
+//!
+//! ```toml
+//! [catalog]
+//! path = "hygdata_v41.csv"
+//! format = "hyg"
+//! max_distance = 50
+//! ```
+//!
+//! Positions come either from `x`, `y` and `z` in parsecs or from `ra` and `dec` in degrees
+//! with the distance in parsecs or the `parallax` in milliarcseconds. Velocities come from
+//! `vx`, `vy` and `vz` in parsecs per year, or from the proper motions `pmra` and `pmdec` in
+//! milliarcseconds per year with the `radial_velocity` in km/s, and are 0 without them.
+//! Masses are taken from `mass` in solar masses, estimated from `luminosity` in solar
+//! luminosities with the main sequence relation M = L^(1/3.5), or are `default_mass`.
+//!
+//! The Sun at rest in the origin is always the first star, it takes the place of the black
+//! hole. Rows without a usable position, or beyond `max_distance` parsecs, are skipped. HYG
+//! places stars without a known parallax at 100000 parsecs, a `max_distance` drops them.
+
+use crate::{
+    units::{PARSEC, SOLAR_MASS, YEAR},
+    DVec3, Error, Result, Star,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// km/s of a proper motion of one arcsecond per year at one parsec
+const KM_PER_S_PER_AU_PER_YEAR: f64 = 4.740_47;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogFormat {
+    /// the HYG database, Cartesian parsecs, parsecs per year and luminosities
+    #[default]
+    Hyg,
+    /// Gaia archive exports, sky positions, parallaxes and proper motions
+    Gaia,
+}
+
+/// the `[catalog]` table, the column names override those of the format
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: CatalogFormat,
+    /// parsecs, farther rows are skipped
+    pub max_distance: Option<f64>,
+    /// at most this many rows are read
+    pub limit: Option<usize>,
+    /// solar masses of rows without mass or luminosity, 1 by default
+    pub default_mass: Option<f64>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+    pub z: Option<String>,
+    pub vx: Option<String>,
+    pub vy: Option<String>,
+    pub vz: Option<String>,
+    pub ra: Option<String>,
+    pub dec: Option<String>,
+    pub distance: Option<String>,
+    pub parallax: Option<String>,
+    pub pmra: Option<String>,
+    pub pmdec: Option<String>,
+    pub radial_velocity: Option<String>,
+    pub mass: Option<String>,
+    pub luminosity: Option<String>,
+}
+
+/// column indices of one file
+#[derive(Default)]
+struct Columns {
+    cartesian: Option<[usize; 3]>,
+    sky: Option<(usize, usize)>,
+    distance: Option<usize>,
+    parallax: Option<usize>,
+    velocity: Option<[usize; 3]>,
+    proper_motion: Option<(usize, usize)>,
+    radial_velocity: Option<usize>,
+    mass: Option<usize>,
+    luminosity: Option<usize>,
+}
+
+impl CatalogConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+
+    /// the configured name of a column, or the one of the format
+    fn name<'a>(&self, field: &'a Option<String>, key: &'static str) -> Option<&'a str> {
+        if let Some(name) = field {
+            return Some(name);
+        }
+        match (self.format, key) {
+            (CatalogFormat::Hyg, "x" | "y" | "z" | "vx" | "vy" | "vz") => Some(key),
+            (CatalogFormat::Hyg, "distance") => Some("dist"),
+            (CatalogFormat::Hyg, "luminosity") => Some("lum"),
+            (
+                CatalogFormat::Gaia,
+                "ra" | "dec" | "parallax" | "pmra" | "pmdec" | "radial_velocity",
+            ) => Some(key),
+            _ => None,
+        }
+    }
+
+    fn columns(&self, header: &csv::StringRecord) -> Result<Columns> {
+        let index = |field: &Option<String>, key| -> Result<Option<usize>> {
+            match self.name(field, key) {
+                Some(name) => match header.iter().position(|column| column.trim() == name) {
+                    Some(index) => Ok(Some(index)),
+                    // only explicitly configured columns must exist
+                    None if field.is_some() => Err(self.error(format!("no column '{}'", name))),
+                    None => Ok(None),
+                },
+                None => Ok(None),
+            }
+        };
+        let triple = |a, b, c| match (a, b, c) {
+            (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+            _ => None,
+        };
+        let pair = |a: Option<usize>, b: Option<usize>| a.zip(b);
+        let columns = Columns {
+            cartesian: triple(
+                index(&self.x, "x")?,
+                index(&self.y, "y")?,
+                index(&self.z, "z")?,
+            ),
+            sky: pair(index(&self.ra, "ra")?, index(&self.dec, "dec")?),
+            distance: index(&self.distance, "distance")?,
+            parallax: index(&self.parallax, "parallax")?,
+            velocity: triple(
+                index(&self.vx, "vx")?,
+                index(&self.vy, "vy")?,
+                index(&self.vz, "vz")?,
+            ),
+            proper_motion: pair(index(&self.pmra, "pmra")?, index(&self.pmdec, "pmdec")?),
+            radial_velocity: index(&self.radial_velocity, "radial_velocity")?,
+            mass: index(&self.mass, "mass")?,
+            luminosity: index(&self.luminosity, "luminosity")?,
+        };
+        let sky_distance =
+            columns.sky.is_some() && (columns.distance.is_some() || columns.parallax.is_some());
+        if columns.cartesian.is_none() && !sky_distance {
+            return Err(self.error(
+                "needs the columns x, y and z or ra, dec and a distance or parallax".to_string(),
+            ));
+        }
+        Ok(columns)
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::Parse {
+            path: self.path.clone(),
+            message,
+        }
+    }
+
+    /// reads the stars of the catalog, the Sun first
+    pub fn load(&self) -> Result<Vec<Star>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(&self.path)
+            .map_err(|e| self.error(e.to_string()))?;
+        let header = reader
+            .headers()
+            .map_err(|e| self.error(e.to_string()))?
+            .clone();
+        let columns = self.columns(&header)?;
+        let default_mass = self.default_mass.unwrap_or(1.0);
+
+        let mut stars = vec![Star {
+            mass: SOLAR_MASS,
+            ..Default::default()
+        }];
+        for record in reader.records() {
+            if self.limit.is_some_and(|limit| stars.len() > limit) {
+                break;
+            }
+            let record = record.map_err(|e| self.error(e.to_string()))?;
+            if let Some(star) = columns.star(&record, default_mass) {
+                let distance = star.position.length() / PARSEC;
+                // the Sun is already there
+                if distance > 0.0 && self.max_distance.is_none_or(|max| distance <= max) {
+                    stars.push(star);
+                }
+            }
+        }
+        Ok(stars)
+    }
+}
+
+impl Columns {
+    /// the star of one row, `None` without a usable position
+    fn star(&self, record: &csv::StringRecord, default_mass: f64) -> Option<Star> {
+        let value = |index: usize| record.get(index)?.trim().parse::<f64>().ok();
+        let values = |[a, b, c]: [usize; 3]| Some(DVec3::new(value(a)?, value(b)?, value(c)?));
+
+        let (position, sky) = match self.cartesian.and_then(values) {
+            Some(position) => (position * PARSEC, None),
+            None => {
+                let (ra, dec) = self.sky?;
+                let (ra, dec) = (value(ra)?.to_radians(), value(dec)?.to_radians());
+                let parsecs = match self.distance.and_then(value) {
+                    Some(distance) => distance,
+                    None => 1000.0 / self.parallax.and_then(value).filter(|&p| p > 0.0)?,
+                };
+                let radial = DVec3::new(dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin());
+                (radial * parsecs * PARSEC, Some((ra, dec, parsecs)))
+            }
+        };
+        if !position.is_finite() {
+            return None;
+        }
+
+        let velocity = match (self.velocity.and_then(values), sky) {
+            (Some(velocity), _) => velocity * (PARSEC / YEAR),
+            (None, Some((ra, dec, parsecs))) => {
+                // the tangential velocities along increasing right ascension and declination
+                let tangential = |index: Option<usize>| {
+                    index.and_then(value).unwrap_or(0.0) / 1000.0
+                        * parsecs
+                        * KM_PER_S_PER_AU_PER_YEAR
+                };
+                let (pmra, pmdec) = self.proper_motion.unzip();
+                let east = DVec3::new(-ra.sin(), ra.cos(), 0.0);
+                let north = DVec3::new(-dec.sin() * ra.cos(), -dec.sin() * ra.sin(), dec.cos());
+                let radial = position.normalize();
+                (east * tangential(pmra)
+                    + north * tangential(pmdec)
+                    + radial * self.radial_velocity.and_then(value).unwrap_or(0.0))
+                    * 1000.0
+            }
+            (None, None) => DVec3::default(),
+        };
+
+        let mass = match self.mass.and_then(value).filter(|&m| m > 0.0) {
+            Some(mass) => mass,
+            None => self
+                .luminosity
+                .and_then(value)
+                .filter(|&l| l > 0.0)
+                .map_or(default_mass, |luminosity| luminosity.powf(1.0 / 3.5)),
+        };
+        Some(Star {
+            position,
+            velocity: if velocity.is_finite() {
+                velocity
+            } else {
+                DVec3::default()
+            },
+            acceleration: DVec3::default(),
+            mass: mass * SOLAR_MASS,
+        })
+    }
+}
+
+/// the distance of the farthest star from the origin, to size the galaxy to the catalog
+pub fn extent(stars: &[Star]) -> f64 {
+    stars
+        .iter()
+        .map(|star| star.position.length())
+        .fold(0.0, f64::max)
+}
+
+/// the catalog at `path` in its default format
+pub fn load(path: &Path) -> Result<Vec<Star>> {
+    CatalogConfig::new(path).load()
+}
@@ -0,0 +1,82 @@
+//! Friends-of-friends groups: two stars closer than the linking length are friends, and a
+//! clump is every star that can be reached from another through a chain of friends.
+
+use crate::{Particles, SpatialHash};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Clumps {
+    /// clump of each particle, `None` for stars in no clump of at least `min_members`
+    pub of_particle: Vec<Option<usize>>,
+    /// members of each clump, the largest clump first
+    pub sizes: Vec<usize>,
+}
+
+impl Clumps {
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+}
+
+/// the root of `i`, halving the path on the way
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// groups the unfrozen particles with the linking length, clumps with fewer than
+/// `min_members` stars are dropped
+///
+/// Clumps of equal size are ordered by their lowest particle index, so the numbering
+/// doesn't depend on the order the pairs are found in.
+pub fn friends_of_friends(
+    particles: &Particles,
+    linking_length: f64,
+    min_members: usize,
+) -> Clumps {
+    profiling::scope!("friends of friends");
+    let positions = &particles.positions;
+    let mut parents: Vec<usize> = (0..positions.len()).collect();
+    let mut hash = SpatialHash::new(linking_length);
+    hash.rebuild(positions, &particles.frozen);
+    hash.pairs(positions, linking_length, |i, j| {
+        let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+        // the lower index becomes the root, it is the lowest member of the clump
+        if a != b {
+            parents[a.max(b)] = a.min(b);
+        }
+    });
+
+    let mut members = vec![0; positions.len()];
+    for i in 0..positions.len() {
+        if !particles.frozen[i] {
+            members[find(&mut parents, i)] += 1;
+        }
+    }
+    let mut roots: Vec<usize> = (0..positions.len())
+        .filter(|&i| parents[i] == i && members[i] >= min_members.max(1))
+        .collect();
+    roots.sort_by_key(|&root| std::cmp::Reverse(members[root]));
+    let mut clump_of_root = vec![None; positions.len()];
+    for (clump, &root) in roots.iter().enumerate() {
+        clump_of_root[root] = Some(clump);
+    }
+    Clumps {
+        of_particle: (0..positions.len())
+            .map(|i| {
+                if particles.frozen[i] {
+                    None
+                } else {
+                    clump_of_root[find(&mut parents, i)]
+                }
+            })
+            .collect(),
+        sizes: roots.iter().map(|&root| members[root]).collect(),
+    }
+}
@@ -0,0 +1,323 @@
+//! Simulation constants from a TOML file, so runs can be tweaked without recompiling.
+//!
+//! Every key is optional and overrides the default of the `SimulationParameters` field of
+//! the same name, unknown keys are an error to catch typos. Lengths, masses and the time
+//! factor are SI numbers or texts with a unit, see `units`, the time factor is the
+//! simulated time per second:
+//!
+//! ```toml
+//! number_of_stars = 3000
+//! tracers = 100000
+//! star_mass_from = "0.05 Msun"
+//! star_mass_to = "50 Msun"
+//! galaxy_diameter = "67 au"
+//! time_factor = "0.06 yr"
+//! preset = "plummer"
+//! softening = 1e11
+//! integrator = "rk4"
+//! tick_rate = 120
+//! camera_speed = 0.1
+//! planar = true
+//! seed = 42
+//!
+//! [adaptive]
+//! accuracy = 0.05
+//!
+//! [spiral]
+//! arms = 3
+//! bulge_ratio = 0.1
+//!
+//! [[galaxies]]
+//! center = ["-20 kpc", 0, 0]
+//! velocity = ["100 km/s", 0, 0]
+//! stars = 2000
+//! radius = "10 kpc"
+//! tracers = 20000
+//!
+//! [[galaxies]]
+//! center = ["20 kpc", "5 kpc", 0]
+//! stars = 1000
+//! radius = "6 kpc"
+//! spin_axis = [0, 1, 1]
+//!
+//! [halo]
+//! profile = "nfw"
+//! scale_radius = 5e12
+//! mass = 1e33
+//!
+//! [catalog]
+//! path = "hygdata_v41.csv"
+//! ```
+
+use crate::{
+    catalog::CatalogConfig,
+    halo::Halo,
+    initial_conditions::GalaxyDescriptor,
+    units::{Length, Mass, Speed, TimeSpan},
+    AdaptiveTimestep, DVec3, Error, Integrator, Preset, Result, SimulationParameters,
+};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// the values of a config file, `None` for keys that are not set
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulationConfig {
+    pub g: Option<f64>,
+    pub number_of_stars: Option<usize>,
+    pub tracers: Option<usize>,
+    pub black_hole_mass: Option<Mass>,
+    pub star_mass_from: Option<Mass>,
+    pub star_mass_to: Option<Mass>,
+    pub galaxy_diameter: Option<Length>,
+    /// simulated time per second
+    pub time_factor: Option<TimeSpan>,
+    pub preset: Option<Preset>,
+    pub spin_factor: Option<f64>,
+    pub softening: Option<Length>,
+    pub min_gravity_distance: Option<Length>,
+    pub integrator: Option<Integrator>,
+    pub opening_angle: Option<f64>,
+    pub capture_radius: Option<Length>,
+    pub merge_density: Option<f64>,
+    pub escape_radius: Option<f64>,
+    pub freeze_escapers: Option<bool>,
+    pub spawn_pool_size: Option<usize>,
+    /// physics steps per second, sets `physics_dt`
+    pub tick_rate: Option<f64>,
+    pub render_rate: Option<f64>,
+    pub camera_speed: Option<f32>,
+    pub threads: Option<usize>,
+    pub planar: Option<bool>,
+    /// seed of the random initial stars and events, not a parameter of the solver
+    pub seed: Option<u64>,
+    pub adaptive: Option<AdaptiveConfig>,
+    pub spiral: Option<SpiralConfig>,
+    pub collision: Option<CollisionConfig>,
+    pub three_body: Option<ThreeBodyConfig>,
+    /// the `[[galaxies]]` tables, they replace the preset
+    pub galaxies: Option<Vec<GalaxyConfig>>,
+    pub halo: Option<Halo>,
+    /// initial stars from a star catalog, not a parameter of the solver
+    pub catalog: Option<CatalogConfig>,
+}
+
+/// the `[adaptive]` table, turns on the adaptive timestep
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveConfig {
+    pub accuracy: f64,
+    /// above 0, by default a hundredth of a physics step
+    pub min_dt: Option<f64>,
+    /// not below `min_dt`, by default a whole physics step
+    pub max_dt: Option<f64>,
+}
+
+/// the `[spiral]` table, the shape of the `spiral` preset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpiralConfig {
+    pub arms: Option<usize>,
+    pub pitch_angle: Option<f64>,
+    pub arm_width: Option<f64>,
+    pub bulge_ratio: Option<f64>,
+    pub disk_ratio: Option<f64>,
+    pub arm_ratio: Option<f64>,
+}
+
+/// the `[collision]` table, the encounter of the `collision` preset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CollisionConfig {
+    pub mass_ratio: Option<f64>,
+    pub separation: Option<f64>,
+    pub impact_parameter: Option<f64>,
+    pub relative_velocity: Option<f64>,
+    pub inclination: Option<f64>,
+}
+
+/// the `[three_body]` table, the bodies of the `three-body` preset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThreeBodyConfig {
+    pub primary_mass: Option<Mass>,
+    pub mass_ratio: Option<f64>,
+    pub separation: Option<f64>,
+}
+
+/// a `[[galaxies]]` table, one of the galaxies that replace the preset
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GalaxyConfig {
+    pub center: [Length; 3],
+    /// at rest by default
+    pub velocity: Option<[Speed; 3]>,
+    pub stars: usize,
+    pub radius: Length,
+    /// the z axis by default
+    pub spin_axis: Option<[f64; 3]>,
+    /// massless test particles in the disk, none by default
+    pub tracers: Option<usize>,
+    /// the `black_hole_mass` of the config by default
+    pub black_hole_mass: Option<Mass>,
+}
+
+impl GalaxyConfig {
+    fn descriptor(&self, black_hole_mass: f64) -> GalaxyDescriptor {
+        let velocity = self.velocity.map_or([0.0; 3], |v| v.map(|v| v.0));
+        GalaxyDescriptor {
+            center: DVec3::from(self.center.map(|x| x.0)),
+            velocity: DVec3::from(velocity),
+            stars: self.stars,
+            tracers: self.tracers.unwrap_or_default(),
+            radius: self.radius.0,
+            spin_axis: DVec3::from(self.spin_axis.unwrap_or([0.0, 0.0, 1.0])),
+            black_hole_mass: self.black_hole_mass.map_or(black_hole_mass, |m| m.0),
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        Self::parse(&text).map_err(|message| Error::Parse {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// reads the TOML text of a config file, with the error message when it is wrong
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let config: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        if config.tick_rate.is_some_and(|rate| rate <= 0.0) {
+            return Err("tick_rate must be above 0".to_string());
+        }
+        if let Some(adaptive) = config.adaptive {
+            // a substep of 0 never ends the step, bounds the wrong way round can't be met
+            if adaptive.accuracy.is_nan() || adaptive.accuracy <= 0.0 {
+                return Err("accuracy of [adaptive] must be above 0".to_string());
+            }
+            if [adaptive.min_dt, adaptive.max_dt]
+                .iter()
+                .flatten()
+                .any(|&dt| dt.is_nan() || dt <= 0.0)
+            {
+                return Err("min_dt and max_dt of [adaptive] must be above 0".to_string());
+            }
+            if let (Some(min), Some(max)) = (adaptive.min_dt, adaptive.max_dt) {
+                if min > max {
+                    return Err("min_dt of [adaptive] must not be above max_dt".to_string());
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// overrides the parameters with the keys that are set
+    pub fn apply(&self, parameters: &mut SimulationParameters) {
+        fn set<T: Copy>(value: Option<T>, field: &mut T) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        set(self.g, &mut parameters.g);
+        set(self.number_of_stars, &mut parameters.number_of_stars);
+        set(self.tracers, &mut parameters.tracers);
+        set(
+            self.black_hole_mass.map(|m| m.0),
+            &mut parameters.black_hole_mass,
+        );
+        set(
+            self.star_mass_from.map(|m| m.0),
+            &mut parameters.star_mass_from,
+        );
+        set(self.star_mass_to.map(|m| m.0), &mut parameters.star_mass_to);
+        set(
+            self.galaxy_diameter.map(|d| d.0),
+            &mut parameters.galaxy_diameter,
+        );
+        set(self.time_factor.map(|t| t.0), &mut parameters.time_factor);
+        set(self.preset, &mut parameters.preset);
+        set(self.spin_factor, &mut parameters.spin_factor);
+        set(self.softening.map(|s| s.0), &mut parameters.softening);
+        set(
+            self.min_gravity_distance.map(|d| d.0),
+            &mut parameters.min_gravity_distance,
+        );
+        set(self.integrator, &mut parameters.integrator);
+        set(self.opening_angle, &mut parameters.opening_angle);
+        set(
+            self.capture_radius.map(|r| r.0),
+            &mut parameters.capture_radius,
+        );
+        set(self.merge_density, &mut parameters.merge_density);
+        set(self.escape_radius, &mut parameters.escape_radius);
+        set(self.freeze_escapers, &mut parameters.freeze_escapers);
+        set(self.spawn_pool_size, &mut parameters.spawn_pool_size);
+        set(
+            self.tick_rate.map(|rate| 1.0 / rate),
+            &mut parameters.physics_dt,
+        );
+        set(self.render_rate, &mut parameters.render_rate);
+        set(self.camera_speed, &mut parameters.camera_speed);
+        set(self.threads, &mut parameters.threads);
+        set(self.planar, &mut parameters.planar);
+        if let Some(spiral) = self.spiral {
+            let shape = &mut parameters.spiral;
+            set(spiral.arms, &mut shape.arms);
+            set(spiral.pitch_angle, &mut shape.pitch_angle);
+            set(spiral.arm_width, &mut shape.arm_width);
+            set(spiral.bulge_ratio, &mut shape.bulge_ratio);
+            set(spiral.disk_ratio, &mut shape.disk_ratio);
+            set(spiral.arm_ratio, &mut shape.arm_ratio);
+        }
+        if let Some(collision) = self.collision {
+            let encounter = &mut parameters.collision;
+            set(collision.mass_ratio, &mut encounter.mass_ratio);
+            set(collision.separation, &mut encounter.separation);
+            set(collision.impact_parameter, &mut encounter.impact_parameter);
+            set(
+                collision.relative_velocity,
+                &mut encounter.relative_velocity,
+            );
+            set(collision.inclination, &mut encounter.inclination);
+        }
+        if let Some(three_body) = self.three_body {
+            let bodies = &mut parameters.three_body;
+            set(
+                three_body.primary_mass.map(|m| m.0),
+                &mut bodies.primary_mass,
+            );
+            set(three_body.mass_ratio, &mut bodies.mass_ratio);
+            set(three_body.separation, &mut bodies.separation);
+        }
+        if let Some(galaxies) = &self.galaxies {
+            parameters.galaxies = galaxies
+                .iter()
+                .map(|galaxy| galaxy.descriptor(parameters.black_hole_mass))
+                .collect();
+            // the massless star in the center and every galaxy with its black hole
+            parameters.number_of_stars = 1 + galaxies.iter().map(|g| g.stars + 1).sum::<usize>();
+            // the view and the escape radius follow the galaxy diameter, so make it fit them
+            if self.galaxy_diameter.is_none() {
+                parameters.galaxy_diameter = parameters
+                    .galaxies
+                    .iter()
+                    .map(|galaxy| galaxy.center.length() + galaxy.radius)
+                    .fold(0.0, f64::max);
+            }
+        }
+        if let Some(halo) = self.halo {
+            parameters.halo = Some(halo);
+        }
+        if let Some(adaptive) = self.adaptive {
+            let step = parameters.physics_dt * parameters.time_factor;
+            parameters.adaptive_timestep = Some(AdaptiveTimestep::with_defaults(
+                adaptive.accuracy,
+                adaptive.min_dt,
+                adaptive.max_dt,
+                step,
+            ));
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Quantities derived from the stars for monitoring a run, they don't influence the
+//! simulation.
+
+use crate::{DVec3, Particles, SimulationParameters};
+
+/// kinetic plus potential energy in joules, O(n²)
+///
+/// Like the forces it leaves out frozen particles and softens the potential of a pair to
+/// -G m₁ m₂ / √(r² + ε²). The stars in the `halo` add their potential energy in it.
+pub fn total_energy(particles: &Particles, parameters: &SimulationParameters) -> f64 {
+    conserved(particles, parameters).energy()
+}
+
+/// the quantities an isolated galaxy keeps, their drift shows the errors of the integrator
+///
+/// The momentum is left out, pinning the black hole to the center changes it. The pin and
+/// the halo pull towards the origin, so they don't change the angular momentum around it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Conserved {
+    /// kinetic energy in joules
+    pub kinetic: f64,
+    /// potential energy in joules, with the halo
+    pub potential: f64,
+    /// angular momentum around the origin in kg m²/s
+    pub angular_momentum: DVec3,
+}
+
+impl Conserved {
+    pub fn energy(&self) -> f64 {
+        self.kinetic + self.potential
+    }
+
+    /// relative change of the total energy since `initial`, `None` when that was 0
+    pub fn energy_drift(&self, initial: &Conserved) -> Option<f64> {
+        let initial = initial.energy();
+        (initial != 0.0).then(|| (self.energy() - initial) / initial.abs())
+    }
+
+    /// relative change of the angular momentum vector since `initial`, `None` when that
+    /// was 0
+    pub fn angular_momentum_drift(&self, initial: &Conserved) -> Option<f64> {
+        let length = initial.angular_momentum.length();
+        (length > 0.0).then(|| (self.angular_momentum - initial.angular_momentum).length() / length)
+    }
+}
+
+/// energies and angular momentum of the unfrozen particles, O(n²) like `total_energy`
+pub fn conserved(particles: &Particles, parameters: &SimulationParameters) -> Conserved {
+    profiling::scope!("conserved");
+    let positions = &particles.positions;
+    let masses = &particles.masses;
+    let frozen = &particles.frozen;
+    let mut conserved = Conserved::default();
+    for i in 0..positions.len() {
+        if frozen[i] {
+            continue;
+        }
+        let velocity = particles.velocities[i];
+        conserved.kinetic += 0.5 * masses[i] * velocity.length_squared();
+        conserved.angular_momentum += positions[i].cross(velocity) * masses[i];
+        if let Some(halo) = parameters.halo {
+            conserved.potential += masses[i] * halo.potential(positions[i], parameters.g);
+        }
+        for j in i + 1..positions.len() {
+            let squared = (positions[j] - positions[i]).length_squared()
+                + parameters.softening * parameters.softening;
+            if !frozen[j] && squared > 0.0 {
+                conserved.potential -= parameters.g * masses[i] * masses[j] / squared.sqrt();
+            }
+        }
+    }
+    conserved
+}
+
+/// stars per cubic meter within `radius` of the center
+pub fn core_density(particles: &Particles, radius: f64) -> f64 {
+    let inside = particles
+        .positions
+        .iter()
+        .filter(|p| p.length() <= radius)
+        .count();
+    inside as f64 / (4.0 / 3.0 * std::f64::consts::PI * radius.powi(3))
+}
+
+/// neighbors of the local density estimate, the sixth nearest as in Casertano & Hut (1985)
+const DENSITY_NEIGHBORS: usize = 6;
+
+/// mass fractions of `Structure::lagrangian_radii`
+pub const LAGRANGIAN_FRACTIONS: [f64; 3] = [0.1, 0.5, 0.9];
+
+/// the radial structure of the stars around their density center, all lengths in meters
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Structure {
+    pub density_center: crate::DVec3,
+    /// radius containing half of the mass, the same as the 50% Lagrangian radius
+    pub half_mass_radius: f64,
+    /// density weighted distance from the density center
+    pub core_radius: f64,
+    /// density weighted mean density in kg/m³
+    pub core_density: f64,
+    /// radii containing the `LAGRANGIAN_FRACTIONS` of the mass
+    pub lagrangian_radii: [f64; 3],
+}
+
+/// core and Lagrangian radii of the unfrozen stars with mass, O(n²)
+///
+/// The local density of every star comes from the mass of its nearest neighbors, the
+/// density center and the core follow Casertano & Hut (1985). Returns the default when
+/// there are not more stars than neighbors of the density estimate.
+pub fn structure(particles: &Particles) -> Structure {
+    profiling::scope!("structure");
+    let stars: Vec<(crate::DVec3, f64)> = particles
+        .positions
+        .iter()
+        .zip(&particles.masses)
+        .zip(&particles.frozen)
+        .filter(|&((_, &mass), &frozen)| !frozen && mass > 0.0)
+        .map(|((&position, &mass), _)| (position, mass))
+        .collect();
+    if stars.len() <= DENSITY_NEIGHBORS {
+        return Structure::default();
+    }
+
+    let mut neighbors = Vec::with_capacity(stars.len());
+    let densities: Vec<f64> = stars
+        .iter()
+        .enumerate()
+        .map(|(i, (position, _))| {
+            neighbors.clear();
+            neighbors.extend(
+                stars
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, (other, mass))| ((*other - *position).length(), *mass)),
+            );
+            let k = DENSITY_NEIGHBORS - 1;
+            neighbors.select_nth_unstable_by(k, |a, b| a.0.total_cmp(&b.0));
+            let radius = neighbors[k].0;
+            // the k-th neighbor marks the radius, only the closer ones count as inside
+            let mass: f64 = neighbors[..k].iter().map(|(_, mass)| mass).sum();
+            if radius > 0.0 {
+                mass / (4.0 / 3.0 * std::f64::consts::PI * radius.powi(3))
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    let weight: f64 = densities.iter().sum();
+    let weight_squared: f64 = densities.iter().map(|d| d * d).sum();
+    if weight <= 0.0 {
+        return Structure::default();
+    }
+    let center = stars
+        .iter()
+        .zip(&densities)
+        .fold(crate::DVec3::default(), |sum, ((position, _), density)| {
+            sum + *position * *density
+        })
+        / weight;
+    let core_radius = (stars
+        .iter()
+        .zip(&densities)
+        .map(|((position, _), density)| density * density * (*position - center).length_squared())
+        .sum::<f64>()
+        / weight_squared)
+        .sqrt();
+
+    let mut shells: Vec<(f64, f64)> = stars
+        .iter()
+        .map(|(position, mass)| ((*position - center).length(), *mass))
+        .collect();
+    shells.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+    let total: f64 = shells.iter().map(|(_, mass)| mass).sum();
+    let mut lagrangian_radii = [0.0; 3];
+    let mut enclosed = 0.0;
+    let mut next = 0;
+    for (radius, mass) in shells {
+        enclosed += mass;
+        while next < LAGRANGIAN_FRACTIONS.len() && enclosed >= LAGRANGIAN_FRACTIONS[next] * total {
+            lagrangian_radii[next] = radius;
+            next += 1;
+        }
+    }
+
+    Structure {
+        density_center: center,
+        half_mass_radius: lagrangian_radii[1],
+        core_radius,
+        core_density: weight_squared / weight,
+        lagrangian_radii,
+    }
+}
@@ -0,0 +1,537 @@
+//! One simulation split across several processes or machines.
+//!
+//! The black hole, the first particle, is on every node, the stars after it are split
+//! into contiguous blocks and every node only holds its own block, so a run can have more
+//! stars than fit into one machine. For every force sum, one for Euler and the leapfrog,
+//! four for RK4, the nodes send each other the bounding box of their stars. Every node
+//! answers with the sources its stars are to the receivers in that box: the octree cells
+//! that every receiver in it takes as a monopole, and the stars of the leaves one of them
+//! opens. With `opening_angle` 0 every cell is opened and all positions and masses are
+//! sent, but only for the sum, the block is all a node keeps. Every node adds those to
+//! the forces of its own stars, and the pulls of all blocks on the black hole are summed in
+//! the order of the ranks, so its copies move bitwise the same on all nodes. Hermite sums
+//! the jerks together with the accelerations and isn't supported.
+//!
+//! The nodes also agree at every step on what the others have to know: the changes to the
+//! mass, momentum and position of the black hole, e.g. by stars it swallowed, whether the
+//! accelerations of the last step can be kept, and the number of adaptive substeps.
+//! Everything else, spawning, freezing or removing stars, only changes the own block.
+//!
+//! The forces are summed in another order than in a single process, so the stars agree
+//! with `step` to rounding, not bitwise. Close encounters are only found between the stars
+//! of one node.
+
+use crate::{
+    gravity::{self, softened},
+    halo, octree, parallel, Error, Integrator, Particles, Result, SimulationParameters, Star,
+};
+use glam::DVec3;
+use rayon::prelude::*;
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    ops::Range,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how long to wait for the other nodes to start
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// f64 values per source a node sends for a force sum: position and mass
+const VALUES_PER_SOURCE: usize = 4;
+
+/// f64 values per star for the initial conditions: the above and the mass
+const VALUES_PER_STAR: usize = 10;
+
+/// f64 values a node sends at every step: the change of the mass, the momentum and the
+/// mass weighted position of the black hole and whether its accelerations are kept
+const AGREEMENT_VALUES: usize = 8;
+
+/// this process' part of a distributed simulation
+#[derive(Debug)]
+pub struct Node {
+    rank: usize,
+    /// connections indexed by rank, `None` for this node
+    peers: Vec<Option<TcpStream>>,
+    /// the black hole all nodes agreed on after the last step
+    black_hole: Option<BlackHole>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BlackHole {
+    mass: f64,
+    position: DVec3,
+    velocity: DVec3,
+}
+
+impl BlackHole {
+    fn of(particles: &Particles) -> Option<Self> {
+        (!particles.is_empty()).then(|| Self {
+            mass: particles.masses[0],
+            position: particles.positions[0],
+            velocity: particles.velocities[0],
+        })
+    }
+
+    /// what `local` changed: mass, momentum and mass weighted position
+    fn changes(&self, local: &BlackHole) -> [f64; 7] {
+        let momentum = local.velocity * local.mass - self.velocity * self.mass;
+        let moment = local.position * local.mass - self.position * self.mass;
+        [
+            local.mass - self.mass,
+            momentum.x,
+            momentum.y,
+            momentum.z,
+            moment.x,
+            moment.y,
+            moment.z,
+        ]
+    }
+
+    /// the black hole with the `changes` of all nodes, the mass weighted means of their
+    /// positions and velocities
+    fn with(&self, changes: &[f64; 7]) -> Self {
+        let mass = self.mass + changes[0];
+        if mass <= 0.0 {
+            // a massless black hole has no weight to average with, it stays
+            return Self { mass, ..*self };
+        }
+        let momentum = self.velocity * self.mass + vector(&changes[1..4]);
+        let moment = self.position * self.mass + vector(&changes[4..7]);
+        Self {
+            mass,
+            position: moment / mass,
+            velocity: momentum / mass,
+        }
+    }
+}
+
+/// the particles node `rank` of `nodes` owns
+pub fn owned_range(rank: usize, nodes: usize, len: usize) -> Range<usize> {
+    len * rank / nodes..len * (rank + 1) / nodes
+}
+
+fn network_error(peer: impl ToString) -> impl FnOnce(std::io::Error) -> Error {
+    move |source| Error::Network {
+        peer: peer.to_string(),
+        source,
+    }
+}
+
+fn push_vector(buffer: &mut Vec<u8>, v: DVec3) {
+    for x in [v.x, v.y, v.z].iter() {
+        buffer.extend_from_slice(&x.to_le_bytes());
+    }
+}
+
+fn push_values(buffer: &mut Vec<u8>, values: &[f64]) {
+    for x in values {
+        buffer.extend_from_slice(&x.to_le_bytes());
+    }
+}
+
+fn read_count(stream: &TcpStream) -> std::io::Result<usize> {
+    let mut bytes = [0; 8];
+    (&*stream).read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes) as usize)
+}
+
+fn read_values(stream: &TcpStream, count: usize) -> std::io::Result<Vec<f64>> {
+    let mut bytes = vec![0; count * 8];
+    (&*stream).read_exact(&mut bytes)?;
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+        .collect())
+}
+
+fn vector(values: &[f64]) -> DVec3 {
+    DVec3::new(values[0], values[1], values[2])
+}
+
+/// the stars of the own block, all particles but the black hole
+fn stars(particles: &Particles) -> Range<usize> {
+    1.min(particles.len())..particles.len()
+}
+
+impl Node {
+    /// connects to all other nodes, `addresses[rank]` is the address this node listens on
+    ///
+    /// Nodes connect to those with a lower rank and accept the higher ones, they can be
+    /// started in any order.
+    pub fn connect(rank: usize, addresses: &[SocketAddr]) -> Result<Self> {
+        let own = addresses[rank];
+        let listener = TcpListener::bind(own).map_err(network_error(own))?;
+        let mut peers: Vec<Option<TcpStream>> = (0..addresses.len()).map(|_| None).collect();
+
+        for (peer, &address) in addresses.iter().enumerate().take(rank) {
+            let started = Instant::now();
+            let stream = loop {
+                match TcpStream::connect(address) {
+                    Ok(stream) => break stream,
+                    Err(e) if started.elapsed() > CONNECT_TIMEOUT => {
+                        return Err(network_error(address)(e))
+                    }
+                    Err(_) => thread::sleep(Duration::from_millis(100)),
+                }
+            };
+            (&stream)
+                .write_all(&(rank as u64).to_le_bytes())
+                .map_err(network_error(address))?;
+            peers[peer] = Some(stream);
+        }
+
+        for _ in rank + 1..addresses.len() {
+            let (stream, address) = listener.accept().map_err(network_error(own))?;
+            let peer = read_count(&stream).map_err(network_error(address))?;
+            if peer <= rank || peer >= addresses.len() || peers[peer].is_some() {
+                return Err(Error::InvalidValue {
+                    name: format!("rank of node {}", address),
+                    value: peer.to_string(),
+                });
+            }
+            peers[peer] = Some(stream);
+        }
+
+        for stream in peers.iter().flatten() {
+            stream.set_nodelay(true).map_err(network_error(own))?;
+        }
+        Ok(Self {
+            rank,
+            peers,
+            black_hole: None,
+        })
+    }
+
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    /// number of nodes in the cluster, including this one
+    pub fn nodes(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// the stars of all `len` particles this node holds besides the black hole
+    pub fn owned(&self, len: usize) -> Range<usize> {
+        self.owned_by(self.rank, len)
+    }
+
+    fn owned_by(&self, rank: usize, len: usize) -> Range<usize> {
+        let stars = owned_range(rank, self.nodes(), len.saturating_sub(1));
+        stars.start + 1..stars.end + 1
+    }
+
+    /// node 0 sends every other node the black hole and its block of the stars, every node
+    /// returns its own
+    ///
+    /// `stars` are all stars on node 0, the others pass none.
+    pub fn share_stars(&self, stars: Vec<Star>) -> Result<Vec<Star>> {
+        let block = |rank| {
+            let black_hole = stars.first().copied();
+            black_hole
+                .into_iter()
+                .chain(stars[self.owned_by(rank, stars.len())].iter().copied())
+        };
+        if self.rank == 0 {
+            for (peer, stream) in self.peers.iter().enumerate() {
+                if let Some(stream) = stream {
+                    let block: Vec<Star> = block(peer).collect();
+                    let mut buffer = (block.len() as u64).to_le_bytes().to_vec();
+                    for star in &block {
+                        push_vector(&mut buffer, star.position);
+                        push_vector(&mut buffer, star.velocity);
+                        push_vector(&mut buffer, star.acceleration);
+                        buffer.extend_from_slice(&star.mass.to_le_bytes());
+                    }
+                    (&*stream).write_all(&buffer).map_err(network_error(peer))?;
+                }
+            }
+            return Ok(block(0).collect());
+        }
+
+        let stream = self.peers[0].as_ref().expect("node 0 is connected");
+        let count = read_count(stream).map_err(network_error(0))?;
+        let values = read_values(stream, count * VALUES_PER_STAR).map_err(network_error(0))?;
+        Ok(values
+            .chunks_exact(VALUES_PER_STAR)
+            .map(|v| Star {
+                position: vector(&v[0..3]),
+                velocity: vector(&v[3..6]),
+                acceleration: vector(&v[6..9]),
+                mass: v[9],
+            })
+            .collect())
+    }
+
+    /// the distributed version of `galaxy_core::step` for the black hole and the own block
+    /// of stars in `particles`, all nodes must call it together
+    pub fn step(
+        &mut self,
+        particles: &mut Particles,
+        parameters: &SimulationParameters,
+        close_encounter: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        profiling::scope!("distributed step");
+        if parameters.integrator == Integrator::Hermite {
+            return Err(Error::InvalidValue {
+                name: "integrator of a distributed simulation".to_string(),
+                value: parameters.integrator.name().to_string(),
+            });
+        }
+        // what the step does before its first sum, the agreement is about the result
+        if parameters.planar {
+            particles.flatten();
+        }
+        crate::pin_black_hole(particles, parameters);
+        self.agree_on_black_hole(particles, parameters)?;
+        crate::report_close_encounters(particles, parameters, close_encounter);
+
+        let node = &*self;
+        // the first failed exchange ends the step, the later sums are skipped
+        let mut result = Ok(());
+        let mut agreed = Ok(());
+        crate::advance_agreed_substeps(
+            particles,
+            parameters,
+            |particles, parameters| {
+                if result.is_ok() {
+                    result = node.accelerations(particles, parameters);
+                }
+            },
+            |substeps| match node.agree_on_substeps(substeps) {
+                Ok(substeps) => substeps,
+                Err(e) => {
+                    agreed = Err(e);
+                    substeps
+                }
+            },
+        );
+        result.and(agreed)?;
+        self.black_hole = BlackHole::of(particles);
+        Ok(())
+    }
+
+    /// sends `messages[peer]` to every other node and reads theirs with `receive`, all
+    /// nodes must call it together
+    fn all_to_all(
+        &self,
+        messages: &[Vec<u8>],
+        mut receive: impl FnMut(usize, &TcpStream) -> std::io::Result<()>,
+    ) -> Result<()> {
+        // all nodes send at the same time, so the sending side runs on extra threads to
+        // not block on full socket buffers
+        thread::scope(|scope| {
+            let senders: Vec<_> = self
+                .peers
+                .iter()
+                .enumerate()
+                .filter_map(|(peer, stream)| stream.as_ref().map(|stream| (peer, stream)))
+                .map(|(peer, stream)| {
+                    let message = &messages[peer];
+                    scope.spawn(move || {
+                        let mut stream: &TcpStream = stream;
+                        stream.write_all(message).map_err(network_error(peer))
+                    })
+                })
+                .collect();
+
+            for (peer, stream) in self.peers.iter().enumerate() {
+                if let Some(stream) = stream {
+                    receive(peer, stream).map_err(network_error(peer))?;
+                }
+            }
+            senders
+                .into_iter()
+                .try_for_each(|sender| sender.join().expect("sender thread panicked"))
+        })
+    }
+
+    /// `message` to every other node, the same for all
+    fn broadcast(&self, message: Vec<u8>) -> Vec<Vec<u8>> {
+        vec![message; self.nodes()]
+    }
+
+    /// applies the changes all nodes made to the black hole since the last step, and
+    /// forgets the accelerations unless every node kept its own
+    fn agree_on_black_hole(
+        &mut self,
+        particles: &mut Particles,
+        parameters: &SimulationParameters,
+    ) -> Result<()> {
+        let local = BlackHole::of(particles);
+        let agreed = *self
+            .black_hole
+            .get_or_insert_with(|| local.unwrap_or_default());
+        let kept = particles.accelerations_kept(parameters);
+        let mut own = [0.0; AGREEMENT_VALUES];
+        if let Some(local) = &local {
+            own[..7].copy_from_slice(&agreed.changes(local));
+        }
+        own[7] = kept as u8 as f64;
+        let mut message = Vec::with_capacity(AGREEMENT_VALUES * 8);
+        push_values(&mut message, &own);
+
+        let mut all = vec![own; self.nodes()];
+        self.all_to_all(&self.broadcast(message), |peer, stream| {
+            let values = read_values(stream, AGREEMENT_VALUES)?;
+            all[peer].copy_from_slice(&values);
+            Ok(())
+        })?;
+        // summed in the order of the ranks, the same on every node
+        let mut changes = [0.0; 7];
+        let mut changed = false;
+        let mut all_kept = true;
+        for values in &all {
+            for (sum, change) in changes.iter_mut().zip(&values[..7]) {
+                *sum += change;
+                changed |= *change != 0.0;
+            }
+            all_kept &= values[7] != 0.0;
+        }
+        if changed {
+            let black_hole = agreed.with(&changes);
+            if local.is_some() {
+                particles.masses[0] = black_hole.mass;
+                particles.positions[0] = black_hole.position;
+                particles.velocities[0] = black_hole.velocity;
+            }
+            self.black_hole = Some(black_hole);
+        }
+        if changed || !all_kept {
+            particles.forget_accelerations();
+        }
+        Ok(())
+    }
+
+    /// the most substeps any node needs
+    fn agree_on_substeps(&self, substeps: usize) -> Result<usize> {
+        let message = (substeps as u64).to_le_bytes().to_vec();
+        let mut most = substeps;
+        self.all_to_all(&self.broadcast(message), |_, stream| {
+            most = most.max(read_count(stream)?);
+            Ok(())
+        })?;
+        Ok(most)
+    }
+
+    /// sets the accelerations of the black hole and the own stars from the stars of all
+    /// nodes
+    fn accelerations(
+        &self,
+        particles: &mut Particles,
+        parameters: &SimulationParameters,
+    ) -> Result<()> {
+        let stars = stars(particles);
+        let nodes = self.nodes();
+
+        // the box around the own receivers, empty without any
+        let (min, max) = stars
+            .clone()
+            .filter(|&i| !particles.frozen[i])
+            .map(|i| particles.positions[i])
+            .fold(
+                (DVec3::splat(f64::INFINITY), DVec3::splat(f64::NEG_INFINITY)),
+                |(min, max), p| (min.min(p), max.max(p)),
+            );
+        let mut message = Vec::with_capacity(6 * 8);
+        push_vector(&mut message, min);
+        push_vector(&mut message, max);
+        let mut boxes = vec![(min, max); nodes];
+        self.all_to_all(&self.broadcast(message), |peer, stream| {
+            let values = read_values(stream, 6)?;
+            boxes[peer] = (vector(&values[0..3]), vector(&values[3..6]));
+            Ok(())
+        })?;
+
+        // the pull of the own stars on the black hole, exact, and the sources for the
+        // receivers of every other node
+        let own_pull = match particles.positions.first() {
+            Some(&black_hole) => stars
+                .clone()
+                .filter(|&i| !particles.frozen[i] && particles.masses[i] != 0.0)
+                .map(|i| {
+                    softened(
+                        particles.positions[i] - black_hole,
+                        particles.masses[i],
+                        parameters,
+                    )
+                })
+                .fold(DVec3::default(), |sum, pull| sum + pull),
+            None => DVec3::default(),
+        };
+        let tree = octree::Octree::of_range(particles, stars.clone(), parameters.threads);
+        let messages: Vec<Vec<u8>> = boxes
+            .iter()
+            .enumerate()
+            .map(|(peer, &(min, max))| {
+                if peer == self.rank {
+                    return Vec::new();
+                }
+                let sources = if min.x <= max.x {
+                    tree.summary(min, max, parameters.opening_angle)
+                } else {
+                    Vec::new()
+                };
+                let mut message = Vec::with_capacity((4 + sources.len() * VALUES_PER_SOURCE) * 8);
+                push_vector(&mut message, own_pull);
+                message.extend_from_slice(&(sources.len() as u64).to_le_bytes());
+                for (position, mass) in sources {
+                    push_vector(&mut message, position);
+                    message.extend_from_slice(&mass.to_le_bytes());
+                }
+                message
+            })
+            .collect();
+        let mut pulls = vec![own_pull; nodes];
+        let mut sources: Vec<(DVec3, f64)> = Vec::new();
+        self.all_to_all(&messages, |peer, stream| {
+            pulls[peer] = vector(&read_values(stream, 3)?);
+            let count = read_count(stream)?;
+            let values = read_values(stream, count * VALUES_PER_SOURCE)?;
+            sources.extend(
+                values
+                    .chunks_exact(VALUES_PER_SOURCE)
+                    .map(|v| (vector(v), v[3])),
+            );
+            Ok(())
+        })?;
+
+        gravity::accelerations_range(particles, parameters, stars.clone());
+        let mut accelerations = std::mem::take(&mut particles.accelerations);
+        let particles_ref = &*particles;
+        let start = stars.start;
+        let add = |(i, acceleration): (usize, &mut DVec3)| {
+            let i = start + i;
+            if !particles_ref.frozen[i] {
+                let position = particles_ref.positions[i];
+                for &(source, mass) in &sources {
+                    *acceleration += softened(source - position, mass, parameters);
+                }
+            }
+        };
+        let out = &mut accelerations[stars];
+        if parameters.threads == 1 {
+            out.iter_mut().enumerate().for_each(add);
+        } else {
+            parallel::install(parameters.threads, || {
+                out.par_iter_mut()
+                    .with_min_len(gravity::MIN_RECEIVERS_PER_TASK)
+                    .enumerate()
+                    .for_each(add)
+            });
+        }
+        particles.accelerations = accelerations;
+
+        if !particles.is_empty() {
+            particles.accelerations[0] = if particles.frozen[0] {
+                DVec3::default()
+            } else {
+                pulls.iter().fold(DVec3::default(), |sum, &pull| sum + pull)
+            };
+            halo::add_accelerations(particles, parameters, 0..1);
+        }
+        Ok(())
+    }
+}
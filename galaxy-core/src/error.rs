@@ -0,0 +1,57 @@
+use std::{fmt, fs, io, path::Path, path::PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    /// reading or writing a file failed
+    Io { path: PathBuf, source: io::Error },
+    /// a file could be read, but its content is wrong
+    Parse { path: PathBuf, message: String },
+    /// talking to another node of a distributed simulation failed
+    Network { peer: String, source: io::Error },
+    /// a setting from the command line, environment or config has an unusable value
+    InvalidValue { name: String, value: String },
+    /// no usable GPU, or the GPU force sum failed
+    Gpu { message: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::Network { peer, source } => write!(f, "node {}: {}", peer, source),
+            Error::InvalidValue { name, value } => {
+                write!(f, "invalid value '{}' for {}", value, name)
+            }
+            Error::Gpu { message } => write!(f, "gpu: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Network { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// creates `dir` if needed and checks that files can be written to it
+pub fn ensure_output_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+    let probe = dir.join(".galaxy-write-test");
+    fs::write(&probe, b"").map_err(|e| Error::io(dir, e))?;
+    fs::remove_file(&probe).map_err(|e| Error::io(&probe, e))
+}
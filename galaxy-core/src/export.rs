@@ -0,0 +1,71 @@
+//! Star tables as CSV files for analysis in Python, e.g. with `pandas.read_csv`.
+//!
+//! Every export is one file `stars-<step>.csv` in the output directory with a header line
+//! and one row per star: `id,time,x,y,z,vx,vy,vz,mass` in SI units. The numbers are
+//! written with full precision, the ids are the stable particle ids, so a star can be
+//! followed from file to file.
+
+use crate::{error::ensure_output_dir, Error, Particles, Result};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+pub const CSV_HEADER: &str = "id,time,x,y,z,vx,vy,vz,mass";
+
+/// periodic CSV exports into a directory
+#[derive(Debug, Clone)]
+pub struct CsvExport {
+    dir: PathBuf,
+    /// physics steps between the exports
+    every: u64,
+}
+
+impl CsvExport {
+    /// checks that the directory can be written, an export happens every `every` steps
+    pub fn new(dir: &Path, every: u64) -> Result<Self> {
+        ensure_output_dir(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            every: every.max(1),
+        })
+    }
+
+    /// whether step `step` is exported
+    pub fn is_due(&self, step: u64) -> bool {
+        step.is_multiple_of(self.every)
+    }
+
+    /// the file of step `step`
+    pub fn path(&self, step: u64) -> PathBuf {
+        self.dir.join(format!("stars-{:08}.csv", step))
+    }
+
+    /// writes all stars at step `step` and simulated time `time`, returns the file
+    pub fn write(&self, step: u64, time: f64, particles: &Particles) -> Result<PathBuf> {
+        let path = self.path(step);
+        write_csv(&path, time, particles)?;
+        Ok(path)
+    }
+}
+
+/// writes one table of all stars at simulated time `time`
+pub fn write_csv(path: &Path, time: f64, particles: &Particles) -> Result<()> {
+    let file = File::create(path).map_err(|e| Error::io(path, e))?;
+    let mut file = BufWriter::new(file);
+    let mut write = || -> std::io::Result<()> {
+        writeln!(file, "{}", CSV_HEADER)?;
+        for i in 0..particles.len() {
+            let p = particles.positions[i];
+            let v = particles.velocities[i];
+            writeln!(
+                file,
+                "{},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e}",
+                particles.ids[i].0, time, p.x, p.y, p.z, v.x, v.y, v.z, particles.masses[i]
+            )?;
+        }
+        file.flush()
+    };
+    write().map_err(|e| Error::io(path, e))
+}
@@ -0,0 +1,400 @@
+//! Writes snapshots in the HDF5 flavor of the GADGET-2 format, for yt, ParaView and other
+//! astronomy tools.
+//!
+//! The black hole, the first particle, is `PartType5` and all other stars are `PartType4`,
+//! each with the datasets `Coordinates`, `Velocities`, `Masses` and `ParticleIDs`. The
+//! `Header` group carries the usual attributes. Values are in the default internal units of
+//! GADGET, kpc, 10¹⁰ solar masses and km/s, with `HubbleParam` 1 and `Time` in kpc / (km/s).
+//! The galaxy is centered on the origin and not periodic, yt needs a `bounding_box` that
+//! encloses `BoxSize` around it.
+//!
+//! No HDF5 library is needed: the file is the subset of HDF5 1.8 a reader needs, a version 2
+//! superblock, version 2 object headers with compact links and contiguous datasets. The
+//! metadata checksums are Jenkins' lookup3 like in the HDF5 library.
+
+use crate::{Error, Particles, Result};
+use std::{fs, path::Path};
+
+/// GADGET's internal units in SI
+pub const UNIT_LENGTH: f64 = 3.085678e19;
+pub const UNIT_MASS: f64 = 1.989e40;
+pub const UNIT_VELOCITY: f64 = 1e3;
+
+const SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+const UNDEFINED: u64 = u64::MAX;
+const SUPERBLOCK_SIZE: usize = 48;
+
+/// object header message types
+const DATASPACE: u8 = 0x01;
+const LINK_INFO: u8 = 0x02;
+const DATATYPE: u8 = 0x03;
+const FILL_VALUE: u8 = 0x05;
+const LINK: u8 = 0x06;
+const LAYOUT: u8 = 0x08;
+const GROUP_INFO: u8 = 0x0a;
+const ATTRIBUTE: u8 = 0x0c;
+
+/// Jenkins' lookup3 hash with initial value 0, the checksum of HDF5 metadata
+pub fn lookup3(data: &[u8]) -> u32 {
+    fn mix(a: &mut u32, b: &mut u32, c: &mut u32) {
+        *a = a.wrapping_sub(*c) ^ c.rotate_left(4);
+        *c = c.wrapping_add(*b);
+        *b = b.wrapping_sub(*a) ^ a.rotate_left(6);
+        *a = a.wrapping_add(*c);
+        *c = c.wrapping_sub(*b) ^ b.rotate_left(8);
+        *b = b.wrapping_add(*a);
+        *a = a.wrapping_sub(*c) ^ c.rotate_left(16);
+        *c = c.wrapping_add(*b);
+        *b = b.wrapping_sub(*a) ^ a.rotate_left(19);
+        *a = a.wrapping_add(*c);
+        *c = c.wrapping_sub(*b) ^ b.rotate_left(4);
+        *b = b.wrapping_add(*a);
+    }
+    fn word(bytes: &[u8]) -> u32 {
+        bytes
+            .iter()
+            .enumerate()
+            .fold(0, |word, (i, &byte)| word | (byte as u32) << (8 * i))
+    }
+
+    let initial = 0xdead_beef_u32.wrapping_add(data.len() as u32);
+    let (mut a, mut b, mut c) = (initial, initial, initial);
+    let mut rest = data;
+    while rest.len() > 12 {
+        a = a.wrapping_add(word(&rest[0..4]));
+        b = b.wrapping_add(word(&rest[4..8]));
+        c = c.wrapping_add(word(&rest[8..12]));
+        mix(&mut a, &mut b, &mut c);
+        rest = &rest[12..];
+    }
+    if rest.is_empty() {
+        return c;
+    }
+    // the last block, zero padded
+    a = a.wrapping_add(word(&rest[..rest.len().min(4)]));
+    if rest.len() > 4 {
+        b = b.wrapping_add(word(&rest[4..rest.len().min(8)]));
+    }
+    if rest.len() > 8 {
+        c = c.wrapping_add(word(&rest[8..]));
+    }
+    c ^= b;
+    c = c.wrapping_sub(b.rotate_left(14));
+    a ^= c;
+    a = a.wrapping_sub(c.rotate_left(11));
+    b ^= a;
+    b = b.wrapping_sub(a.rotate_left(25));
+    c ^= b;
+    c = c.wrapping_sub(b.rotate_left(16));
+    a ^= c;
+    a = a.wrapping_sub(c.rotate_left(4));
+    b ^= a;
+    b = b.wrapping_sub(a.rotate_left(14));
+    c ^= b;
+    c = c.wrapping_sub(b.rotate_left(24));
+    c
+}
+
+/// element types of the datasets and attributes
+#[derive(Clone, Copy)]
+enum Type {
+    I32,
+    U32,
+    U64,
+    F64,
+}
+
+impl Type {
+    /// the datatype message
+    fn message(self) -> Vec<u8> {
+        // version 1 in the high nibble, the class in the low one
+        let fixed = |size: u32, signed: bool| {
+            let mut m = vec![0x10, if signed { 0x08 } else { 0x00 }, 0, 0];
+            m.extend_from_slice(&size.to_le_bytes());
+            // bit offset and precision
+            m.extend_from_slice(&0u16.to_le_bytes());
+            m.extend_from_slice(&(8 * size as u16).to_le_bytes());
+            m
+        };
+        match self {
+            Type::I32 => fixed(4, true),
+            Type::U32 => fixed(4, false),
+            Type::U64 => fixed(8, false),
+            Type::F64 => {
+                // IEEE little endian, implied leading mantissa bit, sign at bit 63
+                let mut m = vec![0x11, 0x20, 63, 0];
+                m.extend_from_slice(&8u32.to_le_bytes());
+                m.extend_from_slice(&0u16.to_le_bytes());
+                m.extend_from_slice(&64u16.to_le_bytes());
+                // exponent location and size, mantissa location and size, exponent bias
+                m.extend_from_slice(&[52, 11, 0, 52]);
+                m.extend_from_slice(&1023u32.to_le_bytes());
+                m
+            }
+        }
+    }
+}
+
+/// values of a dataset or an attribute, little endian
+struct Values {
+    kind: Type,
+    /// empty for a scalar
+    dims: Vec<u64>,
+    bytes: Vec<u8>,
+}
+
+impl Values {
+    fn f64s(values: impl IntoIterator<Item = f64>, dims: Vec<u64>) -> Self {
+        Self {
+            kind: Type::F64,
+            dims,
+            bytes: values.into_iter().flat_map(f64::to_le_bytes).collect(),
+        }
+    }
+
+    fn f64(value: f64) -> Self {
+        Self::f64s([value], Vec::new())
+    }
+
+    fn i32(value: i32) -> Self {
+        Self {
+            kind: Type::I32,
+            dims: Vec::new(),
+            bytes: value.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn i32s(values: [i32; 6]) -> Self {
+        Self {
+            kind: Type::I32,
+            dims: vec![6],
+            bytes: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    fn u32s(values: [u32; 6]) -> Self {
+        Self {
+            kind: Type::U32,
+            dims: vec![6],
+            bytes: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    fn u64s(values: impl IntoIterator<Item = u64>, dims: Vec<u64>) -> Self {
+        Self {
+            kind: Type::U64,
+            dims,
+            bytes: values.into_iter().flat_map(u64::to_le_bytes).collect(),
+        }
+    }
+
+    /// the dataspace message, version 2
+    fn dataspace(&self) -> Vec<u8> {
+        let simple = !self.dims.is_empty();
+        let mut m = vec![2, self.dims.len() as u8, 0, simple as u8];
+        for dim in &self.dims {
+            m.extend_from_slice(&dim.to_le_bytes());
+        }
+        m
+    }
+}
+
+/// an HDF5 file built in memory, objects are appended before the groups linking them
+struct File {
+    bytes: Vec<u8>,
+}
+
+impl File {
+    fn new() -> Self {
+        Self {
+            bytes: vec![0; SUPERBLOCK_SIZE],
+        }
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> u64 {
+        let address = self.bytes.len() as u64;
+        self.bytes.extend_from_slice(bytes);
+        address
+    }
+
+    /// a version 2 object header with the messages in one chunk
+    fn object(&mut self, messages: &[(u8, Vec<u8>)]) -> u64 {
+        let mut chunk = Vec::new();
+        for (kind, data) in messages {
+            chunk.push(*kind);
+            chunk.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            // message flags
+            chunk.push(0);
+            chunk.extend_from_slice(data);
+        }
+        let mut header = b"OHDR".to_vec();
+        // version 2, the chunk size takes 4 bytes
+        header.extend_from_slice(&[2, 0x02]);
+        header.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        header.extend_from_slice(&chunk);
+        let checksum = lookup3(&header);
+        header.extend_from_slice(&checksum.to_le_bytes());
+        self.append(&header)
+    }
+
+    fn dataset(&mut self, values: &Values) -> u64 {
+        let data = self.append(&values.bytes);
+        let mut layout = vec![3, 1];
+        layout.extend_from_slice(&data.to_le_bytes());
+        layout.extend_from_slice(&(values.bytes.len() as u64).to_le_bytes());
+        self.object(&[
+            (DATASPACE, values.dataspace()),
+            (DATATYPE, values.kind.message()),
+            // version 3, allocated early, written only when a fill value is set
+            (FILL_VALUE, vec![3, 0x09]),
+            (LAYOUT, layout),
+        ])
+    }
+
+    /// a group with compact links to the objects and attributes
+    fn group(&mut self, links: &[(&str, u64)], attributes: &[(&str, Values)]) -> u64 {
+        let mut link_info = vec![0, 0];
+        // no fractal heap and no name index, all links are in the header
+        link_info.extend_from_slice(&UNDEFINED.to_le_bytes());
+        link_info.extend_from_slice(&UNDEFINED.to_le_bytes());
+        let mut messages = vec![(LINK_INFO, link_info), (GROUP_INFO, vec![0, 0])];
+        for (name, address) in links {
+            // version 1, a hard link with a one byte name length
+            let mut link = vec![1, 0, name.len() as u8];
+            link.extend_from_slice(name.as_bytes());
+            link.extend_from_slice(&address.to_le_bytes());
+            messages.push((LINK, link));
+        }
+        for (name, values) in attributes {
+            let datatype = values.kind.message();
+            let dataspace = values.dataspace();
+            // version 3 with ASCII names, the name includes its terminating zero
+            let mut attribute = vec![3, 0];
+            attribute.extend_from_slice(&(name.len() as u16 + 1).to_le_bytes());
+            attribute.extend_from_slice(&(datatype.len() as u16).to_le_bytes());
+            attribute.extend_from_slice(&(dataspace.len() as u16).to_le_bytes());
+            attribute.push(0);
+            attribute.extend_from_slice(name.as_bytes());
+            attribute.push(0);
+            attribute.extend_from_slice(&datatype);
+            attribute.extend_from_slice(&dataspace);
+            attribute.extend_from_slice(&values.bytes);
+            messages.push((ATTRIBUTE, attribute));
+        }
+        self.object(&messages)
+    }
+
+    /// fills in the superblock for the root group and returns the file
+    fn finish(mut self, root: u64) -> Vec<u8> {
+        let end = self.bytes.len() as u64;
+        let mut superblock = SIGNATURE.to_vec();
+        // version 2, 8 byte offsets and lengths, no consistency flags
+        superblock.extend_from_slice(&[2, 8, 8, 0]);
+        // base address, no superblock extension
+        superblock.extend_from_slice(&0u64.to_le_bytes());
+        superblock.extend_from_slice(&UNDEFINED.to_le_bytes());
+        superblock.extend_from_slice(&end.to_le_bytes());
+        superblock.extend_from_slice(&root.to_le_bytes());
+        let checksum = lookup3(&superblock);
+        superblock.extend_from_slice(&checksum.to_le_bytes());
+        self.bytes[..SUPERBLOCK_SIZE].copy_from_slice(&superblock);
+        self.bytes
+    }
+}
+
+/// the datasets of the particles at `indices`
+fn particle_type(file: &mut File, particles: &Particles, indices: &[usize]) -> u64 {
+    let n = indices.len() as u64;
+    let vectors = |vectors: &[crate::DVec3], unit: f64| {
+        Values::f64s(
+            indices.iter().flat_map(|&i| {
+                let v = vectors[i] / unit;
+                [v.x, v.y, v.z]
+            }),
+            vec![n, 3],
+        )
+    };
+    let coordinates = file.dataset(&vectors(&particles.positions, UNIT_LENGTH));
+    let velocities = file.dataset(&vectors(&particles.velocities, UNIT_VELOCITY));
+    let masses = file.dataset(&Values::f64s(
+        indices.iter().map(|&i| particles.masses[i] / UNIT_MASS),
+        vec![n],
+    ));
+    let ids = file.dataset(&Values::u64s(
+        indices.iter().map(|&i| particles.ids[i].0),
+        vec![n],
+    ));
+    file.group(
+        &[
+            ("Coordinates", coordinates),
+            ("Velocities", velocities),
+            ("Masses", masses),
+            ("ParticleIDs", ids),
+        ],
+        &[],
+    )
+}
+
+/// the file contents of a snapshot at simulated time `time`
+pub fn encode(time: f64, particles: &Particles) -> Vec<u8> {
+    let mut file = File::new();
+    let black_hole: Vec<usize> = (0..particles.len().min(1)).collect();
+    let stars: Vec<usize> = (black_hole.len()..particles.len()).collect();
+    let mut counts = [0u32; 6];
+    counts[4] = stars.len() as u32;
+    counts[5] = black_hole.len() as u32;
+    let extent = particles
+        .positions
+        .iter()
+        .map(|p| p.abs().max_element())
+        .fold(0.0, f64::max);
+
+    let mut links = Vec::new();
+    if !stars.is_empty() {
+        links.push(("PartType4", particle_type(&mut file, particles, &stars)));
+    }
+    if !black_hole.is_empty() {
+        links.push((
+            "PartType5",
+            particle_type(&mut file, particles, &black_hole),
+        ));
+    }
+    let flag = |name| (name, Values::i32(0));
+    let header = file.group(
+        &[],
+        &[
+            ("NumPart_ThisFile", Values::i32s(counts.map(|c| c as i32))),
+            ("NumPart_Total", Values::u32s(counts)),
+            ("NumPart_Total_HighWord", Values::u32s([0; 6])),
+            ("MassTable", Values::f64s([0.0; 6], vec![6])),
+            ("Time", Values::f64(time * UNIT_VELOCITY / UNIT_LENGTH)),
+            ("Redshift", Values::f64(0.0)),
+            ("BoxSize", Values::f64(2.0 * extent / UNIT_LENGTH)),
+            ("NumFilesPerSnapshot", Values::i32(1)),
+            ("Omega0", Values::f64(0.0)),
+            ("OmegaLambda", Values::f64(0.0)),
+            ("HubbleParam", Values::f64(1.0)),
+            flag("Flag_Sfr"),
+            flag("Flag_Cooling"),
+            flag("Flag_StellarAge"),
+            flag("Flag_Metals"),
+            flag("Flag_Feedback"),
+            ("Flag_DoublePrecision", Values::i32(1)),
+        ],
+    );
+    links.insert(0, ("Header", header));
+    let root = file.group(&links, &[]);
+    file.finish(root)
+}
+
+/// writes the particles at simulated time `time` as a GADGET-2 HDF5 file
+pub fn save(path: &Path, time: f64, particles: &Particles) -> Result<()> {
+    fs::write(path, encode(time, particles)).map_err(|e| Error::io(path, e))
+}
+
+/// whether `path` names an HDF5 file, by its extension
+pub fn is_hdf5(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| matches!(extension, "hdf5" | "h5"))
+}
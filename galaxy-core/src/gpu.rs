@@ -0,0 +1,467 @@
+//! Direct sum of the accelerations in a wgpu compute shader, and the leapfrog on the GPU.
+//!
+//! `GpuGravity::accelerations` uploads the positions and masses to a storage buffer, runs
+//! one shader invocation per receiver and reads the accelerations back, `step` integrates
+//! with them on the CPU. `advance` keeps the particles on the GPU instead: it uploads them
+//! once, takes all its leapfrog steps there and reads positions, velocities and
+//! accelerations back at the end, so an app reads back once per rendered frame, not once
+//! per step. The shaders work in f32 with positions in units of the galaxy diameter and
+//! masses in units of the total mass, so the results agree with `gravity::direct_sum` to
+//! about 1e-6 relative, not bitwise.
+
+use crate::{Error, Integrator, Particles, Result, SimulationParameters};
+use futures_lite::future::block_on;
+use glam::DVec3;
+use std::borrow::Cow;
+
+/// invocations per workgroup, must match the shaders
+const WORKGROUP_SIZE: u32 = 64;
+/// bytes per body, per velocity and per acceleration, one vec4<f32>
+const VALUE_SIZE: wgpu::BufferAddress = 16;
+/// values per particle read back by `advance`: position, velocity and acceleration
+const READBACK_VALUES: wgpu::BufferAddress = 3;
+
+fn gpu_error(message: impl Into<String>) -> Error {
+    Error::Gpu {
+        message: message.into(),
+    }
+}
+
+/// the scale of the shader units, in which G = 1
+struct Units {
+    length: f64,
+    mass: f64,
+    /// sqrt(length³ / (G mass))
+    time: f64,
+}
+
+impl Units {
+    /// `None` when nothing pulls, without mass or without G
+    fn new(particles: &Particles, parameters: &SimulationParameters) -> Option<Self> {
+        let mass: f64 = (0..particles.len())
+            .filter(|&i| !particles.frozen[i])
+            .map(|i| particles.masses[i])
+            .sum();
+        let length = parameters.galaxy_diameter;
+        if parameters.g * mass <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            length,
+            mass,
+            time: (length.powi(3) / (parameters.g * mass)).sqrt(),
+        })
+    }
+
+    fn velocity(&self) -> f64 {
+        self.length / self.time
+    }
+
+    fn acceleration(&self) -> f64 {
+        self.length / (self.time * self.time)
+    }
+}
+
+/// buffers for up to `capacity` particles
+struct Buffers {
+    capacity: usize,
+    bodies: wgpu::Buffer,
+    velocities: wgpu::Buffer,
+    accelerations: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    leapfrog_bind_group: wgpu::BindGroup,
+}
+
+/// the device and compute pipelines of the GPU force sum and leapfrog
+pub struct GpuGravity {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+    parameters: wgpu::Buffer,
+    kick: wgpu::ComputePipeline,
+    drift: wgpu::ComputePipeline,
+    leapfrog_layout: wgpu::BindGroupLayout,
+    leapfrog_parameters: wgpu::Buffer,
+    buffers: Option<Buffers>,
+}
+
+impl GpuGravity {
+    /// opens the first high performance adapter, fails when there is none
+    pub fn new() -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+        }))
+        .ok_or_else(|| gpu_error("no GPU adapter found"))?;
+        let (device, queue) = block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("galaxy gravity"),
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        ))
+        .map_err(|e| gpu_error(e.to_string()))?;
+
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStage::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let uniform = wgpu::BufferBindingType::Uniform;
+        let read = wgpu::BufferBindingType::Storage { read_only: true };
+        let write = wgpu::BufferBindingType::Storage { read_only: false };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gravity"),
+            entries: &[entry(0, uniform), entry(1, read), entry(2, write)],
+        });
+        let leapfrog_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("leapfrog"),
+            entries: &[
+                entry(0, uniform),
+                entry(1, write),
+                entry(2, write),
+                entry(3, read),
+            ],
+        });
+        let pipeline = |label, layout, source, entry_point| {
+            let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+                flags: wgpu::ShaderFlags::all(),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point,
+            })
+        };
+        let gravity = include_str!("shaders/gravity.wgsl");
+        let leapfrog = include_str!("shaders/leapfrog.wgsl");
+        let uniform_buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: 16,
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        Ok(Self {
+            pipeline: pipeline("gravity", &layout, gravity, "main"),
+            kick: pipeline("kick", &leapfrog_layout, leapfrog, "kick"),
+            drift: pipeline("drift", &leapfrog_layout, leapfrog, "drift"),
+            parameters: uniform_buffer("gravity parameters"),
+            leapfrog_parameters: uniform_buffer("leapfrog parameters"),
+            device,
+            queue,
+            layout,
+            leapfrog_layout,
+            buffers: None,
+        })
+    }
+
+    /// whether `advance` can take the steps of `parameters`: the leapfrog in whole steps
+    /// without a halo, `step` takes the others
+    pub fn resident(parameters: &SimulationParameters) -> bool {
+        parameters.integrator == Integrator::Leapfrog
+            && parameters.adaptive_timestep.is_none()
+            && parameters.halo.is_none()
+    }
+
+    /// makes room for `count` particles, the buffers grow by doubling
+    fn reserve(&mut self, count: usize) {
+        if self.buffers.as_ref().is_none_or(|b| b.capacity < count) {
+            let capacity = count.next_power_of_two().max(WORKGROUP_SIZE as usize);
+            let size = capacity as wgpu::BufferAddress * VALUE_SIZE;
+            let buffer = |label, size, usage| {
+                self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage,
+                    mapped_at_creation: false,
+                })
+            };
+            let storage = wgpu::BufferUsage::STORAGE
+                | wgpu::BufferUsage::COPY_DST
+                | wgpu::BufferUsage::COPY_SRC;
+            let bodies = buffer("gravity bodies", size, storage);
+            let velocities = buffer("leapfrog velocities", size, storage);
+            let accelerations = buffer("gravity accelerations", size, storage);
+            let readback = buffer(
+                "gravity readback",
+                READBACK_VALUES * size,
+                wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            );
+            let bind_group = |label, layout, resources: &[&wgpu::Buffer]| {
+                let entries: Vec<wgpu::BindGroupEntry> = resources
+                    .iter()
+                    .enumerate()
+                    .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                        binding: binding as u32,
+                        resource: buffer.as_entire_binding(),
+                    })
+                    .collect();
+                self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(label),
+                    layout,
+                    entries: &entries,
+                })
+            };
+            self.buffers = Some(Buffers {
+                capacity,
+                bind_group: bind_group(
+                    "gravity",
+                    &self.layout,
+                    &[&self.parameters, &bodies, &accelerations],
+                ),
+                leapfrog_bind_group: bind_group(
+                    "leapfrog",
+                    &self.leapfrog_layout,
+                    &[
+                        &self.leapfrog_parameters,
+                        &bodies,
+                        &velocities,
+                        &accelerations,
+                    ],
+                ),
+                bodies,
+                velocities,
+                accelerations,
+                readback,
+            });
+        }
+    }
+
+    /// uploads the positions and masses of the particles and the parameters of the force
+    /// sum, in `units`
+    fn write_bodies(
+        &mut self,
+        particles: &Particles,
+        parameters: &SimulationParameters,
+        units: &Units,
+    ) {
+        let count = particles.len();
+        let bodies: Vec<[f32; 4]> = (0..count)
+            .map(|i| {
+                let p = particles.positions[i] / units.length;
+                let mass = if particles.frozen[i] {
+                    0.0
+                } else {
+                    particles.masses[i] / units.mass
+                };
+                [p.x as f32, p.y as f32, p.z as f32, mass as f32]
+            })
+            .collect();
+        let mut uniform = [0u8; 16];
+        uniform[0..4].copy_from_slice(&(count as u32).to_ne_bytes());
+        let softening = (parameters.softening / units.length) as f32;
+        uniform[4..8].copy_from_slice(&(softening * softening).to_ne_bytes());
+        self.queue.write_buffer(&self.parameters, 0, &uniform);
+
+        self.reserve(count);
+        let buffers = self.buffers.as_ref().expect("buffers were just reserved");
+        self.queue
+            .write_buffer(&buffers.bodies, 0, bytemuck::cast_slice(&bodies));
+    }
+
+    /// runs `pipeline` with `bind_group` once per particle
+    fn dispatch(
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::ComputePipeline,
+        bind_group: &wgpu::BindGroup,
+        count: usize,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch((count as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// submits `encoder` with copies of the first `count` values of `sources` and waits for
+    /// them, one after the other
+    fn read_back(
+        &self,
+        mut encoder: wgpu::CommandEncoder,
+        sources: &[&wgpu::Buffer],
+        count: usize,
+    ) -> Result<Vec<[f32; 4]>> {
+        let buffers = self.buffers.as_ref().expect("buffers are reserved");
+        let size = count as wgpu::BufferAddress * VALUE_SIZE;
+        for (k, source) in sources.iter().enumerate() {
+            let offset = k as wgpu::BufferAddress * size;
+            encoder.copy_buffer_to_buffer(source, 0, &buffers.readback, offset, size);
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffers
+            .readback
+            .slice(..sources.len() as wgpu::BufferAddress * size);
+        let mapped = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        block_on(mapped).map_err(|_| gpu_error("reading the results back failed"))?;
+        let data = slice.get_mapped_range();
+        let values = bytemuck::cast_slice(&data).to_vec();
+        drop(data);
+        buffers.readback.unmap();
+        Ok(values)
+    }
+
+    fn encoder(&self, label: &str) -> wgpu::CommandEncoder {
+        self.device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some(label) })
+    }
+
+    /// sets the acceleration of every particle like `gravity::direct_sum`, on the GPU
+    pub fn accelerations(
+        &mut self,
+        particles: &mut Particles,
+        parameters: &SimulationParameters,
+    ) -> Result<()> {
+        profiling::scope!("gpu force sum");
+        let count = particles.len();
+        if count == 0 {
+            return Ok(());
+        }
+        let units = match Units::new(particles, parameters) {
+            Some(units) => units,
+            None => {
+                particles.accelerations.fill(DVec3::default());
+                return Ok(());
+            }
+        };
+        self.write_bodies(particles, parameters, &units);
+        let buffers = self.buffers.as_ref().expect("buffers were just reserved");
+        let mut encoder = self.encoder("gravity");
+        Self::dispatch(&mut encoder, &self.pipeline, &buffers.bind_group, count);
+        let values = self.read_back(encoder, &[&buffers.accelerations], count)?;
+        let scale = units.acceleration();
+        for (i, (acceleration, value)) in
+            particles.accelerations.iter_mut().zip(&values).enumerate()
+        {
+            *acceleration = if particles.frozen[i] {
+                DVec3::default()
+            } else {
+                DVec3::new(value[0] as f64, value[1] as f64, value[2] as f64) * scale
+            };
+        }
+        Ok(())
+    }
+
+    /// `galaxy_core::step` with the force sums on the GPU, `Integrator::Hermite` still sums
+    /// on the CPU
+    pub fn step(
+        &mut self,
+        particles: &mut Particles,
+        parameters: &SimulationParameters,
+        close_encounter: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        profiling::scope!("gpu step");
+        crate::report_close_encounters(particles, parameters, close_encounter);
+        let mut result = Ok(());
+        crate::advance_substeps(particles, parameters, |particles, parameters| {
+            if result.is_ok() {
+                result = self.accelerations(particles, parameters);
+                crate::halo::add_accelerations(particles, parameters, 0..particles.len());
+            }
+        });
+        result
+    }
+
+    /// `steps` times `galaxy_core::step` with the leapfrog on the GPU, for the parameters
+    /// where `resident` holds
+    ///
+    /// The particles are uploaded once and read back once after the last step, close
+    /// encounters are only looked for at the final positions.
+    pub fn advance(
+        &mut self,
+        particles: &mut Particles,
+        parameters: &SimulationParameters,
+        steps: u64,
+        close_encounter: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        profiling::scope!("gpu advance");
+        debug_assert!(Self::resident(parameters));
+        let count = particles.len();
+        if parameters.planar {
+            particles.flatten();
+        }
+        crate::pin_black_hole(particles, parameters);
+        let units = match Units::new(particles, parameters) {
+            Some(units) if count > 0 => units,
+            // nothing pulls, the stars coast on the CPU
+            _ => {
+                for _ in 0..steps {
+                    crate::advance_substeps(particles, parameters, |particles, _| {
+                        particles.accelerations.fill(DVec3::default())
+                    });
+                }
+                crate::report_close_encounters(particles, parameters, close_encounter);
+                return Ok(());
+            }
+        };
+        self.write_bodies(particles, parameters, &units);
+        let velocity_scale = units.velocity();
+        let velocities: Vec<[f32; 4]> = (0..count)
+            .map(|i| {
+                let v = particles.velocities[i] / velocity_scale;
+                let frozen = if particles.frozen[i] { 1.0 } else { 0.0 };
+                [v.x as f32, v.y as f32, v.z as f32, frozen]
+            })
+            .collect();
+        let mut uniform = [0u8; 16];
+        uniform[0..4].copy_from_slice(&(count as u32).to_ne_bytes());
+        uniform[4..8].copy_from_slice(&(parameters.pin_black_hole as u32).to_ne_bytes());
+        let dt = parameters.physics_dt * parameters.time_factor / units.time;
+        uniform[8..12].copy_from_slice(&(dt as f32).to_ne_bytes());
+        self.queue
+            .write_buffer(&self.leapfrog_parameters, 0, &uniform);
+        let buffers = self.buffers.as_ref().expect("buffers were just reserved");
+        self.queue
+            .write_buffer(&buffers.velocities, 0, bytemuck::cast_slice(&velocities));
+
+        let mut encoder = self.encoder("leapfrog");
+        let forces = &buffers.bind_group;
+        let leapfrog = &buffers.leapfrog_bind_group;
+        Self::dispatch(&mut encoder, &self.pipeline, forces, count);
+        for _ in 0..steps {
+            Self::dispatch(&mut encoder, &self.kick, leapfrog, count);
+            Self::dispatch(&mut encoder, &self.drift, leapfrog, count);
+            Self::dispatch(&mut encoder, &self.pipeline, forces, count);
+            Self::dispatch(&mut encoder, &self.kick, leapfrog, count);
+        }
+        let sources = [&buffers.bodies, &buffers.velocities, &buffers.accelerations];
+        let values = self.read_back(encoder, &sources, count)?;
+        let (positions, rest) = values.split_at(count);
+        let (velocities, accelerations) = rest.split_at(count);
+        let vector = |value: &[f32; 4], scale: f64| {
+            DVec3::new(value[0] as f64, value[1] as f64, value[2] as f64) * scale
+        };
+        for i in 0..count {
+            // frozen particles keep their f64 values
+            if particles.frozen[i] {
+                particles.accelerations[i] = DVec3::default();
+                continue;
+            }
+            particles.positions[i] = vector(&positions[i], units.length);
+            particles.velocities[i] = vector(&velocities[i], velocity_scale);
+            particles.accelerations[i] = vector(&accelerations[i], units.acceleration());
+        }
+        particles.keep_accelerations(parameters);
+        crate::report_close_encounters(particles, parameters, close_encounter);
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{halo, octree, parallel, Particles, SimulationParameters};
+use glam::DVec3;
+use rayon::prelude::*;
+use std::ops::Range;
+
+/// receivers summed in one go by a thread, smaller tasks cost more than they save
+pub(crate) const MIN_RECEIVERS_PER_TASK: usize = 64;
+
+/// acceleration towards a source of `mass` at `offset` from the receiver, with the Plummer
+/// softened force law G m r / (r² + ε²)^(3/2) for the softening length ε
+///
+/// The softening keeps close pairs finite without clamping anything, like a star being a
+/// cloud of radius ε instead of a point. Coincident pairs without softening exert no force.
+pub fn softened(offset: DVec3, mass: f64, parameters: &SimulationParameters) -> DVec3 {
+    let squared = offset.length_squared() + parameters.softening * parameters.softening;
+    if squared > 0.0 {
+        offset * (parameters.g * mass / (squared * squared.sqrt()))
+    } else {
+        DVec3::default()
+    }
+}
+
+/// potential energy per kg at `offset` from a source of `mass`, -G m / √(r² + ε²) for the
+/// force of `softened`, 0 on top of it without softening
+pub fn softened_potential(offset: DVec3, mass: f64, parameters: &SimulationParameters) -> f64 {
+    let squared = offset.length_squared() + parameters.softening * parameters.softening;
+    if squared > 0.0 {
+        -parameters.g * mass / squared.sqrt()
+    } else {
+        0.0
+    }
+}
+
+/// sets the accelerations with the Barnes–Hut approximation when `opening_angle` is above
+/// 0, otherwise with the exact direct sum
+///
+/// The force of the `halo` is added to both.
+pub fn accelerations(particles: &mut Particles, parameters: &SimulationParameters) {
+    let all = 0..particles.len();
+    accelerations_range(particles, parameters, all);
+}
+
+/// like `accelerations`, but only sets those of the receivers in `receivers`
+pub fn accelerations_range(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    receivers: Range<usize>,
+) {
+    if parameters.opening_angle > 0.0 {
+        octree::barnes_hut_range(particles, parameters, receivers.clone());
+    } else {
+        direct_sum_range(particles, parameters, receivers.clone());
+    }
+    halo::add_accelerations(particles, parameters, receivers);
+}
+
+/// sets the acceleration of every particle from all the others, O(n²)
+///
+/// Gravity is softened by `softening`, see `softened`. Frozen particles are skipped both
+/// as sources and as receivers, massless tracers only as sources, so they cost a pass over
+/// the massive particles each instead of one over all.
+///
+/// Every acceleration is summed over the sources in index order, never through a
+/// shared accumulator. Splitting the receivers across threads in any way therefore
+/// gives bitwise the same result as the serial loop.
+pub fn direct_sum(particles: &mut Particles, parameters: &SimulationParameters) {
+    let all = 0..particles.len();
+    direct_sum_range(particles, parameters, all);
+}
+
+/// like `direct_sum`, but only sets the accelerations of the receivers in `receivers`
+///
+/// The receivers are split across `parameters.threads` threads, with 1 the loop runs on
+/// the calling thread.
+pub fn direct_sum_range(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    receivers: Range<usize>,
+) {
+    profiling::scope!("force sum");
+    let mut accelerations = std::mem::take(&mut particles.accelerations);
+    let particles_ref = &*particles;
+    let sources = sources(particles_ref);
+    let start = receivers.start;
+    let out = &mut accelerations[receivers];
+    let fill = |(i, acceleration): (usize, &mut DVec3)| {
+        *acceleration = receiver_acceleration(particles_ref, &sources, start + i, parameters);
+    };
+    if parameters.threads == 1 {
+        out.iter_mut().enumerate().for_each(fill);
+    } else {
+        parallel::install(parameters.threads, || {
+            out.par_iter_mut()
+                .with_min_len(MIN_RECEIVERS_PER_TASK)
+                .enumerate()
+                .for_each(fill)
+        });
+    }
+    particles.accelerations = accelerations;
+}
+
+/// the unfrozen particles with mass in index order, the only ones that pull
+fn sources(particles: &Particles) -> Vec<usize> {
+    (0..particles.len())
+        .filter(|&j| !particles.frozen[j] && particles.masses[j] != 0.0)
+        .collect()
+}
+
+/// acceleration of particle `i` from all other `sources`, 0 when it is frozen
+fn receiver_acceleration(
+    particles: &Particles,
+    sources: &[usize],
+    i: usize,
+    parameters: &SimulationParameters,
+) -> DVec3 {
+    // based on this code: https://rosettacode.org/wiki/N-body_problem#C.23
+    let positions = &particles.positions;
+    let masses = &particles.masses;
+    let frozen = &particles.frozen;
+    let mut acceleration = DVec3::default();
+    if frozen[i] {
+        return acceleration;
+    }
+    for &j in sources {
+        if i != j {
+            acceleration += softened(positions[j] - positions[i], masses[j], parameters);
+        }
+    }
+    acceleration
+}
+
+/// sets the accelerations like `direct_sum` and returns their time derivatives, the jerks,
+/// both with the `halo`
+pub fn accelerations_and_jerks(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+) -> Vec<DVec3> {
+    profiling::scope!("force and jerk sum");
+    let mut accelerations = std::mem::take(&mut particles.accelerations);
+    let mut jerks = vec![DVec3::default(); accelerations.len()];
+    let particles_ref = &*particles;
+    let sources = sources(particles_ref);
+    let fill = |(i, (acceleration, jerk)): (usize, (&mut DVec3, &mut DVec3))| {
+        let (a, j) = receiver_acceleration_and_jerk(particles_ref, &sources, i, parameters);
+        *acceleration = a;
+        *jerk = j;
+    };
+    if parameters.threads == 1 {
+        accelerations
+            .iter_mut()
+            .zip(jerks.iter_mut())
+            .enumerate()
+            .for_each(fill);
+    } else {
+        parallel::install(parameters.threads, || {
+            accelerations
+                .par_iter_mut()
+                .zip(jerks.par_iter_mut())
+                .with_min_len(MIN_RECEIVERS_PER_TASK)
+                .enumerate()
+                .for_each(fill)
+        });
+    }
+    particles.accelerations = accelerations;
+    jerks
+}
+
+fn receiver_acceleration_and_jerk(
+    particles: &Particles,
+    sources: &[usize],
+    i: usize,
+    parameters: &SimulationParameters,
+) -> (DVec3, DVec3) {
+    let mut acceleration = DVec3::default();
+    let mut jerk = DVec3::default();
+    if particles.frozen[i] {
+        return (acceleration, jerk);
+    }
+    let (position, velocity) = (particles.positions[i], particles.velocities[i]);
+    if let Some(halo) = parameters.halo {
+        (acceleration, jerk) = halo.acceleration_and_jerk(position, velocity, parameters.g);
+    }
+    for &j in sources {
+        if i != j {
+            let distance = particles.positions[j] - position;
+            let squared = distance.length_squared() + parameters.softening * parameters.softening;
+            if squared > 0.0 {
+                let relative_velocity = particles.velocities[j] - velocity;
+                let temp = parameters.g * particles.masses[j] / (squared * squared.sqrt());
+                let rate = 3.0 * distance.dot(relative_velocity) / squared;
+                acceleration += distance * temp;
+                jerk += (relative_velocity - distance * rate) * temp;
+            }
+        }
+    }
+    (acceleration, jerk)
+}
+
+/// acceleration a massless test particle at `position` gets from all unfrozen particles
+/// and the halo
+pub fn acceleration_at(
+    particles: &Particles,
+    position: DVec3,
+    parameters: &SimulationParameters,
+) -> DVec3 {
+    let mut acceleration = parameters.halo.map_or(DVec3::default(), |halo| {
+        halo.acceleration(position, parameters.g)
+    });
+    for ((other, mass), frozen) in particles
+        .positions
+        .iter()
+        .zip(&particles.masses)
+        .zip(&particles.frozen)
+    {
+        if *frozen {
+            continue;
+        }
+        acceleration += softened(*other - position, *mass, parameters);
+    }
+    acceleration
+}
+
+/// potential energy per kg at `position` from all unfrozen particles and the halo, the
+/// potential of `acceleration_at`
+pub fn potential_at(
+    particles: &Particles,
+    position: DVec3,
+    parameters: &SimulationParameters,
+) -> f64 {
+    let mut potential = parameters
+        .halo
+        .map_or(0.0, |halo| halo.potential(position, parameters.g));
+    for ((other, mass), frozen) in particles
+        .positions
+        .iter()
+        .zip(&particles.masses)
+        .zip(&particles.frozen)
+    {
+        if !*frozen {
+            potential += softened_potential(*other - position, *mass, parameters);
+        }
+    }
+    potential
+}
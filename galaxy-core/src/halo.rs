@@ -0,0 +1,121 @@
+//! Analytic dark matter halo around the center, an external potential every star feels on
+//! top of the gravity of the others.
+//!
+//! A few thousand stars can't hold up a flat rotation curve by themselves, real galaxies
+//! are dominated by dark matter far out. The halo adds its mass without particles, its
+//! force is part of every force sum, so all integrators and the energy see it. It is
+//! fixed in the center where the black hole is pinned.
+
+use crate::{Particles, SimulationParameters};
+use glam::DVec3;
+use serde::Deserialize;
+use std::ops::Range;
+
+/// density profile of the halo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HaloProfile {
+    /// Navarro–Frenk–White, ρ ∝ 1 / (x (1 + x)²) with x = r / r_s
+    Nfw,
+    /// cored isothermal sphere, ρ ∝ 1 / (1 + x²), its rotation curve is flat far out
+    Isothermal,
+}
+
+/// also the `[halo]` table of config files
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Halo {
+    pub profile: HaloProfile,
+    /// scale radius r_s of NFW or core radius of the isothermal sphere in meters
+    pub scale_radius: f64,
+    /// mass inside the scale radius in kg
+    pub mass: f64,
+}
+
+impl Halo {
+    /// the mass inside x = r / r_s in units of the mass scale of the profile, M(<r) / M_s,
+    /// and its derivative by x
+    fn inside(&self, x: f64) -> (f64, f64) {
+        match self.profile {
+            HaloProfile::Nfw => ((1.0 + x).ln() - x / (1.0 + x), x / ((1.0 + x) * (1.0 + x))),
+            HaloProfile::Isothermal => (x - x.atan(), x * x / (1.0 + x * x)),
+        }
+    }
+
+    /// G M_s, the mass scale from the mass inside the scale radius
+    fn gm(&self, g: f64) -> f64 {
+        g * self.mass / self.inside(1.0).0
+    }
+
+    /// f = G M(<r) / r³, the acceleration is -f times the position, and df / dr
+    fn strength(&self, r: f64, g: f64) -> (f64, f64) {
+        let (inside, slope) = self.inside(r / self.scale_radius);
+        let f = self.gm(g) * inside / (r * r * r);
+        let df = self.gm(g) * slope / (self.scale_radius * r * r * r) - 3.0 * f / r;
+        (f, df)
+    }
+
+    pub fn acceleration(&self, position: DVec3, g: f64) -> DVec3 {
+        let r = position.length();
+        if r > 0.0 {
+            -position * self.strength(r, g).0
+        } else {
+            DVec3::default()
+        }
+    }
+
+    /// the acceleration and its time derivative for a star moving with `velocity`
+    pub fn acceleration_and_jerk(
+        &self,
+        position: DVec3,
+        velocity: DVec3,
+        g: f64,
+    ) -> (DVec3, DVec3) {
+        let r = position.length();
+        if r > 0.0 {
+            let (f, df) = self.strength(r, g);
+            let radial_speed = position.dot(velocity) / r;
+            (
+                -position * f,
+                -velocity * f - position * (df * radial_speed),
+            )
+        } else {
+            (DVec3::default(), DVec3::default())
+        }
+    }
+
+    /// potential energy per kg at `position`
+    ///
+    /// NFW is 0 at infinity, the mass of the isothermal sphere grows without bound, so it
+    /// is 0 in the center instead.
+    pub fn potential(&self, position: DVec3, g: f64) -> f64 {
+        let x = position.length() / self.scale_radius;
+        let scale = self.gm(g) / self.scale_radius;
+        match self.profile {
+            HaloProfile::Nfw => {
+                let ln = if x > 0.0 { (1.0 + x).ln() / x } else { 1.0 };
+                -scale * ln
+            }
+            HaloProfile::Isothermal => {
+                let atan = if x > 0.0 { x.atan() / x } else { 1.0 };
+                scale * (atan - 1.0 + 0.5 * (1.0 + x * x).ln())
+            }
+        }
+    }
+}
+
+/// adds the halo acceleration to every unfrozen particle in `range`
+pub fn add_accelerations(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    range: Range<usize>,
+) {
+    if let Some(halo) = parameters.halo {
+        for i in range {
+            if !particles.frozen[i] {
+                particles.accelerations[i] +=
+                    halo.acceleration(particles.positions[i], parameters.g);
+            }
+        }
+    }
+}
@@ -0,0 +1,807 @@
+//! The first stars of a simulation, the first star is always the black hole at rest in the
+//! center.
+//!
+//! `random_disk` is the original flat box with a little spin, it isn't in equilibrium and
+//! collapses. The other presets sample standard equilibrium models, positions from the
+//! density and velocities from the distribution function, so they keep their shape:
+//!
+//! - `plummer`, the Plummer sphere with scale radius a quarter of `galaxy_diameter`
+//! - `exponential`, a rotating disk with surface density ∝ exp(-R / R_d) and scale length
+//!   R_d a quarter of `galaxy_diameter`, on circular orbits with a small dispersion
+//! - `king`, the King model of central potential `KING_W0` with that tidal radius
+//! - `spiral`, a bulge, an exponential disk and logarithmic spiral arms, the disk and arm
+//!   stars on circular orbits around the mass inside, see `SpiralGalaxy`
+//! - `collision`, two exponential disks on a collision course, see `Collision`
+//! - `three-body`, the circular restricted three-body problem of two bodies and massless
+//!   test particles around their orbit, see `ThreeBody`
+//!
+//! A list of `GalaxyDescriptor`s in `parameters.galaxies` replaces the preset with any
+//! number of exponential disks, each with its own place, motion, size and spin, see
+//! `galaxies`.
+//!
+//! `parameters.tracers` adds massless test particles after the stars of any of them, see
+//! `tracers`.
+//!
+//! All but `collision` and `three-body` are cut off at `galaxy_diameter`. The spheres ignore the black hole, their
+//! equilibrium holds as long as it is light compared to all stars.
+
+use crate::{units::SOLAR_MASS, SimulationParameters, Star};
+use glam::{DQuat, DVec3};
+use rand::Rng;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{f64::consts::PI, str::FromStr};
+
+/// dimensionless central potential W₀ = Ψ(0) / σ² of the King model, how concentrated it is
+pub const KING_W0: f64 = 6.0;
+/// velocity dispersion of the exponential disk relative to the circular velocity
+const DISK_DISPERSION: f64 = 0.1;
+/// scale height of the disks relative to their scale length
+const DISK_THICKNESS: f64 = 0.1;
+/// size of the disks of `Preset::Collision` relative to `galaxy_diameter`
+const COLLISION_DISK_SIZE: f64 = 1.0 / 3.0;
+/// Plummer radius of the bulge of `Preset::Spiral` relative to `galaxy_diameter`
+const BULGE_RADIUS: f64 = 0.05;
+
+/// shape of `Preset::Spiral`, the ratios split the stars into the populations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpiralGalaxy {
+    pub arms: usize,
+    /// angle between the arms and the circles around the center in degrees, small angles
+    /// wind the arms tightly
+    pub pitch_angle: f64,
+    /// spread of the arm stars around the spirals in radians
+    pub arm_width: f64,
+    pub bulge_ratio: f64,
+    pub disk_ratio: f64,
+    pub arm_ratio: f64,
+}
+
+/// the encounter of `Preset::Collision`, distances in galaxy diameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collision {
+    /// mass of the second galaxy over the first, in stars and black hole
+    pub mass_ratio: f64,
+    /// distance of the centers at the start
+    pub separation: f64,
+    /// closest approach of the centers if they passed on straight lines
+    pub impact_parameter: f64,
+    /// relative speed in m/s at the start, 0 for the parabolic speed of the two masses
+    pub relative_velocity: f64,
+    /// tilt of the second disk against the orbital plane in degrees
+    pub inclination: f64,
+}
+
+impl Default for Collision {
+    fn default() -> Self {
+        Self {
+            mass_ratio: 1.0,
+            separation: 1.0,
+            impact_parameter: 0.25,
+            relative_velocity: 0.0,
+            inclination: 45.0,
+        }
+    }
+}
+
+/// the two bodies of `Preset::ThreeBody`, the separation in galaxy diameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThreeBody {
+    /// mass of the heavier body in kg
+    pub primary_mass: f64,
+    /// mass of the lighter body over the heavier one, L4 and L5 are stable below 0.0385
+    pub mass_ratio: f64,
+    pub separation: f64,
+}
+
+impl Default for ThreeBody {
+    fn default() -> Self {
+        // the sun and a Jupiter
+        Self {
+            primary_mass: SOLAR_MASS,
+            mass_ratio: 1e-3,
+            separation: 0.25,
+        }
+    }
+}
+
+/// one galaxy of `parameters.galaxies`, an exponential disk with a black hole in its center
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GalaxyDescriptor {
+    /// position of the black hole in meters
+    pub center: DVec3,
+    /// velocity of the whole galaxy in m/s
+    pub velocity: DVec3,
+    /// stars without the black hole
+    pub stars: usize,
+    /// massless test particles after the stars
+    pub tracers: usize,
+    /// the disk is cut off at this radius in meters, its scale length is a quarter of it
+    pub radius: f64,
+    /// the stars turn counterclockwise around this direction
+    pub spin_axis: DVec3,
+    pub black_hole_mass: f64,
+}
+
+impl Default for SpiralGalaxy {
+    fn default() -> Self {
+        Self {
+            arms: 2,
+            pitch_angle: 15.0,
+            arm_width: 0.3,
+            bulge_ratio: 0.2,
+            disk_ratio: 0.3,
+            arm_ratio: 0.5,
+        }
+    }
+}
+
+/// the model `generate` samples the stars from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// the flat random box of `random_disk`
+    Box,
+    Plummer,
+    Exponential,
+    King,
+    Spiral,
+    Collision,
+    ThreeBody,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 7] = [
+        Preset::Box,
+        Preset::Plummer,
+        Preset::Exponential,
+        Preset::King,
+        Preset::Spiral,
+        Preset::Collision,
+        Preset::ThreeBody,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Box => "box",
+            Preset::Plummer => "plummer",
+            Preset::Exponential => "exponential",
+            Preset::King => "king",
+            Preset::Spiral => "spiral",
+            Preset::Collision => "collision",
+            Preset::ThreeBody => "three-body",
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|preset| preset.name() == s)
+            .ok_or(())
+    }
+}
+
+/// presets in config files are written by their `name`
+impl<'de> Deserialize<'de> for Preset {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|_| D::Error::custom(format!("unknown preset '{}'", name)))
+    }
+}
+
+/// the stars of `parameters.preset`
+pub fn generate(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    let mut stars = match parameters.preset {
+        _ if !parameters.galaxies.is_empty() => {
+            // like the collision the star in the center is massless
+            let mut stars = vec![Star::default()];
+            stars.extend(galaxies(rng, parameters).into_iter().flatten());
+            stars
+        }
+        Preset::Box => random_disk(rng, parameters),
+        Preset::Plummer => plummer(rng, parameters),
+        Preset::Exponential => exponential_disk(rng, parameters),
+        Preset::King => king(rng, parameters),
+        Preset::Spiral => spiral(rng, parameters),
+        Preset::Collision => collision(rng, parameters),
+        Preset::ThreeBody => three_body(rng, parameters),
+    };
+    // the galaxies and the collision put the tracers into their disks
+    if parameters.galaxies.is_empty() && parameters.preset != Preset::Collision {
+        let tracers = tracers(rng, &stars, parameters.tracers, parameters);
+        stars.extend(tracers);
+    }
+    flatten(&mut stars, parameters);
+    stars
+}
+
+/// a planar run keeps the stars of the model in the plane, spheres become disks
+fn flatten(stars: &mut [Star], parameters: &SimulationParameters) {
+    if parameters.planar {
+        for star in stars {
+            star.position.z = 0.0;
+            star.velocity.z = 0.0;
+        }
+    }
+}
+
+/// flat random box of stars with a little spin, the first star is the black hole
+pub fn random_disk(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    let diameter = parameters.galaxy_diameter;
+    (0..parameters.number_of_stars)
+        .map(|i| {
+            let px = rng.gen_range(-diameter..diameter);
+            let py = rng.gen_range(-diameter..diameter);
+            let pz = rng.gen_range(-diameter / 10.0..diameter / 10.0);
+            let mut star = Star {
+                position: if i == 0 {
+                    DVec3::default()
+                } else {
+                    DVec3::new(px, py, pz)
+                },
+                velocity: DVec3::default(),
+                acceleration: DVec3::default(),
+                mass: if i == 0 {
+                    parameters.black_hole_mass
+                } else {
+                    rng.gen_range(parameters.star_mass_from..parameters.star_mass_to)
+                },
+            };
+
+            // spin it
+            let angle: f64 = ang::atan2(px, py).in_radians();
+            let spin = parameters.spin_factor;
+            star.acceleration = DVec3::new(angle.cos() * spin, angle.sin() * spin, 0.0);
+            star.velocity = star.acceleration;
+            star
+        })
+        .collect()
+}
+
+/// Plummer sphere, sampled as by Aarseth, Hénon and Wielen (1974)
+pub fn plummer(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    let mut stars = black_hole_and_masses(rng, parameters);
+    let mass = stellar_mass(&stars);
+    let a = parameters.galaxy_diameter / 4.0;
+    for star in stars.iter_mut().skip(1) {
+        let r = loop {
+            let x: f64 = rng.gen_range(f64::EPSILON..1.0);
+            let r = a / (x.powf(-2.0 / 3.0) - 1.0).sqrt();
+            if r <= parameters.galaxy_diameter {
+                break r;
+            }
+        };
+        // the speed in units of the escape speed follows q² (1 - q²)^(7/2), below 0.1
+        let q = loop {
+            let q: f64 = rng.gen();
+            if rng.gen_range(0.0..0.1) < q * q * (1.0 - q * q).powf(3.5) {
+                break q;
+            }
+        };
+        let escape = (2.0 * parameters.g * mass / (r * r + a * a).sqrt()).sqrt();
+        star.position = direction(rng) * r;
+        star.velocity = direction(rng) * q * escape;
+    }
+    at_rest_in_the_center(&mut stars);
+    stars
+}
+
+/// a Plummer sphere of `count` stars without a black hole, of the total `mass` and cut off
+/// at `diameter`, at rest around the origin, to add a cluster or a dwarf galaxy to a run
+pub fn plummer_cluster(
+    rng: &mut impl Rng,
+    count: usize,
+    diameter: f64,
+    mass: f64,
+    parameters: &SimulationParameters,
+) -> Vec<Star> {
+    let cluster = SimulationParameters {
+        number_of_stars: count + 1,
+        galaxy_diameter: diameter,
+        black_hole_mass: 0.0,
+        ..parameters.clone()
+    };
+    let mut stars = plummer(rng, &cluster);
+    let sampled = stellar_mass(&stars);
+    stars.remove(0);
+    // the same orbits in a potential k times deeper are sqrt(k) times faster
+    if sampled > 0.0 {
+        let k = mass / sampled;
+        for star in &mut stars {
+            star.mass *= k;
+            star.velocity *= k.sqrt();
+        }
+    }
+    stars
+}
+
+/// rotating exponential disk on circular orbits around the mass inside
+pub fn exponential_disk(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    let mut stars = black_hole_and_masses(rng, parameters);
+    let scale = parameters.galaxy_diameter / 4.0;
+    for star in stars.iter_mut().skip(1) {
+        let r = disk_radius(rng, scale, parameters.galaxy_diameter);
+        let angle = rng.gen_range(0.0..2.0 * PI);
+        star.position = DVec3::new(r * angle.cos(), r * angle.sin(), disk_height(rng, scale));
+    }
+    rotate(rng, &mut stars, parameters, |_| true);
+    at_rest_in_the_center(&mut stars);
+    stars
+}
+
+/// spiral galaxy of a bulge, a disk and logarithmic spiral arms on circular orbits
+///
+/// The stars of the arms scatter around the spirals θ = θₖ + ln(R / R₀) / tan(pitch) of the
+/// arms k, R₀ is the radius of the bulge. `parameters.spiral` sets the shape.
+pub fn spiral(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    let mut stars = black_hole_and_masses(rng, parameters);
+    let spiral = parameters.spiral;
+    let diameter = parameters.galaxy_diameter;
+    let scale = diameter / 4.0;
+    let bulge = diameter * BULGE_RADIUS;
+    let total = spiral.bulge_ratio + spiral.disk_ratio + spiral.arm_ratio;
+    let (bulge_share, disk_share) = if total > 0.0 {
+        (spiral.bulge_ratio / total, spiral.disk_ratio / total)
+    } else {
+        (0.0, 1.0)
+    };
+    let winding = 1.0 / spiral.pitch_angle.to_radians().tan();
+    let mut in_bulge = vec![false; stars.len()];
+    for (star, in_bulge) in stars.iter_mut().zip(&mut in_bulge).skip(1) {
+        let population: f64 = rng.gen();
+        star.position = if population < bulge_share {
+            *in_bulge = true;
+            // Plummer sphere
+            let r = loop {
+                let x: f64 = rng.gen_range(f64::EPSILON..1.0);
+                let r = bulge / (x.powf(-2.0 / 3.0) - 1.0).sqrt();
+                if r <= 5.0 * bulge {
+                    break r;
+                }
+            };
+            direction(rng) * r
+        } else {
+            let r = disk_radius(rng, scale, diameter);
+            let angle = if population < bulge_share + disk_share || spiral.arms == 0 {
+                rng.gen_range(0.0..2.0 * PI)
+            } else {
+                let arm = rng.gen_range(0..spiral.arms) as f64;
+                let along = (r / bulge).max(1.0).ln() * winding;
+                2.0 * PI * arm / spiral.arms as f64 + along + gaussian(rng) * spiral.arm_width
+            };
+            DVec3::new(r * angle.cos(), r * angle.sin(), disk_height(rng, scale))
+        };
+    }
+
+    rotate(rng, &mut stars, parameters, |i| !in_bulge[i]);
+    // the bulge is hot, with the dispersion that holds it up against the mass inside
+    let speeds = circular_speeds(&stars, parameters);
+    for (i, star) in stars.iter_mut().enumerate().filter(|&(i, _)| in_bulge[i]) {
+        let random = DVec3::new(gaussian(rng), gaussian(rng), gaussian(rng));
+        star.velocity = random * speeds[i] / 3f64.sqrt();
+    }
+    at_rest_in_the_center(&mut stars);
+    stars
+}
+
+/// two exponential disks on a prograde collision course, like the classic merger runs that
+/// pull out tidal tails
+///
+/// The first star is massless and stays in the center, each galaxy has its own black hole,
+/// which moves with it, and its share of the `tracers` after its stars. The centers of mass of both galaxies start on the x axis apart by
+/// `separation`, at rest around the center. `parameters.collision` sets the encounter.
+pub fn collision(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    let encounter = parameters.collision;
+    let diameter = parameters.galaxy_diameter;
+    let ratio = encounter.mass_ratio.max(0.0);
+    let count = parameters.number_of_stars.saturating_sub(1);
+    let first_count = (count as f64 / (1.0 + ratio)).round() as usize;
+    let disk = |count, black_hole_mass| SimulationParameters {
+        number_of_stars: count,
+        galaxy_diameter: diameter * COLLISION_DISK_SIZE,
+        black_hole_mass,
+        // the halo is around the center, not around the galaxies
+        halo: None,
+        ..parameters.clone()
+    };
+    let first_tracers = (parameters.tracers as f64 / (1.0 + ratio)).round() as usize;
+    let mut galaxy = |count, black_hole_mass, tracer_count| {
+        let disk = disk(count, black_hole_mass);
+        let mut stars = exponential_disk(rng, &disk);
+        let tracers = tracers(rng, &stars, tracer_count, &disk);
+        stars.extend(tracers);
+        stars
+    };
+    let mut first = galaxy(first_count, parameters.black_hole_mass, first_tracers);
+    let mut second = galaxy(
+        count - first_count,
+        parameters.black_hole_mass * ratio,
+        parameters.tracers - first_tracers,
+    );
+    let tilt = encounter.inclination.to_radians();
+    let tilted = |v: DVec3| {
+        DVec3::new(
+            v.x,
+            v.y * tilt.cos() - v.z * tilt.sin(),
+            v.y * tilt.sin() + v.z * tilt.cos(),
+        )
+    };
+    for star in &mut second {
+        star.position = tilted(star.position);
+        star.velocity = tilted(star.velocity);
+    }
+
+    // the relative orbit of the second galaxy, it comes from -x below the first one and
+    // passes it counterclockwise like the stars rotate
+    let masses = [total_mass(&first), total_mass(&second)];
+    let total = masses[0] + masses[1];
+    let separation = encounter.separation * diameter;
+    let impact = encounter.impact_parameter * diameter;
+    let position = DVec3::new(
+        -(separation * separation - impact * impact).max(0.0).sqrt(),
+        -impact,
+        0.0,
+    );
+    let speed = if encounter.relative_velocity > 0.0 {
+        encounter.relative_velocity
+    } else {
+        (2.0 * parameters.g * total / separation).sqrt()
+    };
+    let velocity = DVec3::new(speed, 0.0, 0.0);
+    let share = if total > 0.0 { masses[1] / total } else { 0.5 };
+    for (galaxy, factor) in [(&mut first, -share), (&mut second, 1.0 - share)] {
+        for star in galaxy.iter_mut() {
+            star.position += position * factor;
+            star.velocity += velocity * factor;
+        }
+    }
+
+    let mut stars = Vec::with_capacity(parameters.number_of_stars);
+    if parameters.number_of_stars > 0 {
+        stars.push(Star::default());
+    }
+    stars.extend(first);
+    stars.extend(second);
+    stars
+}
+
+/// two bodies on a circular orbit around the center and massless test particles around it,
+/// the circular restricted three-body problem
+///
+/// The first star is massless and stays in the center of mass, like in `collision`, the
+/// primary and the secondary of `parameters.three_body` follow on the x axis, the
+/// secondary on the positive side. Both circle the center counterclockwise at the rate of
+/// their softened pull. The rest of the stars are test particles on circular orbits
+/// between half and one and a half times the separation, in the orbit plane, around the
+/// mass inside: near L4 and L5 they librate on tadpole orbits, along the orbit on
+/// horseshoes, further in and out they circulate. The Lagrange points of the second and
+/// the third star show with `lagrange::RotatingFrame`.
+pub fn three_body(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    let bodies = parameters.three_body;
+    let d = bodies.separation * parameters.galaxy_diameter;
+    let (m1, m2) = (
+        bodies.primary_mass,
+        bodies.primary_mass * bodies.mass_ratio.max(0.0),
+    );
+    let total = m1 + m2;
+    let x2 = if total > 0.0 { d * m1 / total } else { 0.0 };
+    let squared = d * d + parameters.softening * parameters.softening;
+    let omega = if squared > 0.0 {
+        (parameters.g * total / (squared * squared.sqrt())).sqrt()
+    } else {
+        0.0
+    };
+    let circling = |x: f64, mass| Star {
+        position: DVec3::new(x, 0.0, 0.0),
+        velocity: DVec3::new(0.0, omega * x, 0.0),
+        mass,
+        ..Default::default()
+    };
+    let mut stars = vec![Star::default(), circling(x2 - d, m1), circling(x2, m2)];
+    let count = parameters.number_of_stars.saturating_sub(stars.len());
+    stars.extend((0..count).map(|_| {
+        let r = d * rng.gen_range(0.5..1.5);
+        let angle = rng.gen_range(0.0..2.0 * PI);
+        Star {
+            position: DVec3::new(r * angle.cos(), r * angle.sin(), 0.0),
+            ..Default::default()
+        }
+    }));
+    let speeds = circular_speeds(&stars, parameters);
+    for (star, speed) in stars.iter_mut().zip(speeds).skip(3) {
+        let p = star.position;
+        star.velocity = DVec3::new(-p.y, p.x, 0.0).normalize_or_zero() * speed;
+    }
+    stars.truncate(parameters.number_of_stars);
+    stars
+}
+
+/// the stars of each of `parameters.galaxies`, its black hole first, without the massless
+/// star in the center that `generate` puts before them
+///
+/// Every galaxy is an exponential disk like `exponential_disk` in equilibrium on its own,
+/// with its `tracers` after its stars, turned from the z axis to its spin axis and moved to its center and velocity. The mass
+/// models, the softening and the integrator come from the parameters, the halo is left out.
+pub fn galaxies(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Vec<Star>> {
+    parameters
+        .galaxies
+        .iter()
+        .map(|galaxy| {
+            let disk = SimulationParameters {
+                number_of_stars: galaxy.stars + 1,
+                galaxy_diameter: galaxy.radius,
+                black_hole_mass: galaxy.black_hole_mass,
+                halo: None,
+                galaxies: Vec::new(),
+                ..parameters.clone()
+            };
+            let axis = galaxy.spin_axis.normalize_or_zero();
+            let turn = if axis == DVec3::ZERO {
+                DQuat::IDENTITY
+            } else {
+                DQuat::from_rotation_arc(DVec3::Z, axis)
+            };
+            let mut stars = exponential_disk(rng, &disk);
+            let tracers = tracers(rng, &stars, galaxy.tracers, &disk);
+            stars.extend(tracers);
+            for star in &mut stars {
+                star.position = galaxy.center + turn * star.position;
+                star.velocity = galaxy.velocity + turn * star.velocity;
+            }
+            flatten(&mut stars, parameters);
+            stars
+        })
+        .collect()
+}
+
+/// King (1966) model with central potential `KING_W0` and tidal radius `galaxy_diameter`
+pub fn king(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    let mut stars = black_hole_and_masses(rng, parameters);
+    let mass = stellar_mass(&stars);
+    let model = KingModel::solve(KING_W0);
+    // the model is in units of the King radius r₀ and the velocity dispersion σ
+    let (tidal, total) = model.table.last().map_or((1.0, 1.0), |&(x, _, m)| (x, m));
+    let r0 = parameters.galaxy_diameter / tidal;
+    let sigma = (parameters.g * mass / (9.0 * r0 * total)).sqrt();
+    for star in stars.iter_mut().skip(1) {
+        let (x, w) = model.radius(rng.gen_range(0.0..total));
+        star.position = direction(rng) * x * r0;
+        star.velocity = direction(rng) * king_speed(rng, w) * sigma;
+    }
+    at_rest_in_the_center(&mut stars);
+    stars
+}
+
+/// the potential and mass profile of a King model in units of r₀, σ and 4πρ₀r₀³
+struct KingModel {
+    /// radius, W and the mass inside, out to the tidal radius where W is 0
+    table: Vec<(f64, f64, f64)>,
+}
+
+impl KingModel {
+    fn solve(w0: f64) -> Self {
+        let central = king_density(w0);
+        // Poisson's equation (r² W')' = -9 r² ρ(W) / ρ₀ as W, W' and the mass inside
+        let derivatives = |x: f64, [w, dw, _]: [f64; 3]| {
+            let density = x * x * king_density(w.max(0.0)) / central;
+            [dw, -9.0 * density / (x * x) - 2.0 * dw / x, density]
+        };
+        let h = 1e-3;
+        // W starts as W₀ - 3/2 x², the center itself is singular
+        let mut x = h;
+        let mut y = [w0 - 1.5 * x * x, -3.0 * x, x * x * x / 3.0];
+        let mut table = vec![(0.0, w0, 0.0)];
+        while y[0] > 0.0 {
+            let add = |y: [f64; 3], k: [f64; 3], f: f64| {
+                [y[0] + k[0] * f, y[1] + k[1] * f, y[2] + k[2] * f]
+            };
+            let k1 = derivatives(x, y);
+            let k2 = derivatives(x + h / 2.0, add(y, k1, h / 2.0));
+            let k3 = derivatives(x + h / 2.0, add(y, k2, h / 2.0));
+            let k4 = derivatives(x + h, add(y, k3, h));
+            for i in 0..3 {
+                y[i] += h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]);
+            }
+            x += h;
+            table.push((x, y[0].max(0.0), y[2]));
+        }
+        Self { table }
+    }
+
+    /// the radius and W at which `mass` is inside
+    fn radius(&self, mass: f64) -> (f64, f64) {
+        let i = self
+            .table
+            .partition_point(|&(_, _, m)| m < mass)
+            .clamp(1, self.table.len() - 1);
+        let ((x0, w0, m0), (x1, w1, m1)) = (self.table[i - 1], self.table[i]);
+        let t = if m1 > m0 {
+            (mass - m0) / (m1 - m0)
+        } else {
+            0.0
+        };
+        (x0 + t * (x1 - x0), w0 + t * (w1 - w0))
+    }
+}
+
+/// density of the King distribution function at W, up to a constant factor
+fn king_density(w: f64) -> f64 {
+    // ∫ (exp(W - v²/2) - 1) v² dv up to the escape speed √(2W), by Simpson's rule
+    let n = 32;
+    let escape = (2.0 * w).sqrt();
+    let h = escape / n as f64;
+    let f = |v: f64| ((w - v * v / 2.0).exp() - 1.0) * v * v;
+    let inner: f64 = (1..n)
+        .map(|i| if i % 2 == 1 { 4.0 } else { 2.0 } * f(i as f64 * h))
+        .sum();
+    (f(0.0) + inner + f(escape)) * h / 3.0
+}
+
+/// a speed in units of σ from the King distribution function at W
+fn king_speed(rng: &mut impl Rng, w: f64) -> f64 {
+    let escape = (2.0 * w).sqrt();
+    let f = |v: f64| ((w - v * v / 2.0).exp() - 1.0) * v * v;
+    let max = (0..=64)
+        .map(|i| f(escape * i as f64 / 64.0))
+        .fold(0.0, f64::max)
+        * 1.1;
+    if max <= 0.0 {
+        return 0.0;
+    }
+    loop {
+        let v = rng.gen_range(0.0..escape);
+        if rng.gen_range(0.0..max) < f(v) {
+            return v;
+        }
+    }
+}
+
+/// a radius of an exponential surface density of scale length `scale`, up to `max`
+fn disk_radius(rng: &mut impl Rng, scale: f64, max: f64) -> f64 {
+    // Gamma(2) distributed
+    loop {
+        let (u, v): (f64, f64) = (rng.gen_range(f64::EPSILON..1.0), rng.gen());
+        let r = -scale * (u * (1.0 - v)).ln();
+        if r <= max {
+            return r;
+        }
+    }
+}
+
+/// `count` massless test particles around the center of `stars`, to be put after them
+///
+/// They are spread like the stars of `exponential_disk` and move on cold circular orbits,
+/// without dispersion, at the speed of the mass of `stars` inside them. As they pull
+/// nothing, many thousands of them cost a pass over the massive stars each and show where
+/// the tides of a few massive bodies take the disk.
+pub fn tracers(
+    rng: &mut impl Rng,
+    stars: &[Star],
+    count: usize,
+    parameters: &SimulationParameters,
+) -> Vec<Star> {
+    let scale = parameters.galaxy_diameter / 4.0;
+    let mut all = stars.to_vec();
+    all.extend((0..count).map(|_| {
+        let r = disk_radius(rng, scale, parameters.galaxy_diameter);
+        let angle = rng.gen_range(0.0..2.0 * PI);
+        Star {
+            position: DVec3::new(r * angle.cos(), r * angle.sin(), disk_height(rng, scale)),
+            ..Default::default()
+        }
+    }));
+    let speeds = circular_speeds(&all, parameters);
+    let mut tracers = all.split_off(stars.len());
+    for (star, speed) in tracers.iter_mut().zip(&speeds[stars.len()..]) {
+        let p = star.position;
+        star.velocity = DVec3::new(-p.y, p.x, 0.0).normalize_or_zero() * *speed;
+    }
+    tracers
+}
+
+/// a height of a sech² vertical profile for a disk of scale length `scale`
+fn disk_height(rng: &mut impl Rng, scale: f64) -> f64 {
+    scale * DISK_THICKNESS * rng.gen_range(-1.0f64..1.0).atanh()
+}
+
+/// the circular speed at each star from the mass inside, as if it were spherical, with the
+/// softened force of the solver, and the halo
+fn circular_speeds(stars: &[Star], parameters: &SimulationParameters) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..stars.len()).collect();
+    order.sort_by(|&i, &j| {
+        let radius = |k: usize| stars[k].position.length();
+        radius(i).total_cmp(&radius(j))
+    });
+    let softening = parameters.softening * parameters.softening;
+    let mut speeds = vec![0.0; stars.len()];
+    let mut inside = 0.0;
+    for i in order {
+        inside += stars[i].mass;
+        let r2 = stars[i].position.length_squared();
+        let halo = parameters.halo.map_or(0.0, |halo| {
+            let position = stars[i].position;
+            halo.acceleration(position, parameters.g).length() * position.length()
+        });
+        speeds[i] = (parameters.g * inside * r2 / (r2 + softening).powf(1.5) + halo).sqrt();
+    }
+    speeds
+}
+
+/// puts the stars for which `disk` is true on counterclockwise circular orbits around the
+/// z axis, with a small dispersion
+fn rotate(
+    rng: &mut impl Rng,
+    stars: &mut [Star],
+    parameters: &SimulationParameters,
+    disk: impl Fn(usize) -> bool,
+) {
+    let speeds = circular_speeds(stars, parameters);
+    for (i, star) in stars.iter_mut().enumerate().skip(1) {
+        if disk(i) {
+            let p = star.position;
+            let tangent = DVec3::new(-p.y, p.x, 0.0).normalize_or_zero();
+            let random = DVec3::new(gaussian(rng), gaussian(rng), gaussian(rng));
+            star.velocity = tangent * speeds[i] + random * speeds[i] * DISK_DISPERSION;
+        }
+    }
+}
+
+/// the black hole and stars with random masses, all still in the center
+fn black_hole_and_masses(rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+    (0..parameters.number_of_stars)
+        .map(|i| Star {
+            mass: if i == 0 {
+                parameters.black_hole_mass
+            } else {
+                rng.gen_range(parameters.star_mass_from..parameters.star_mass_to)
+            },
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn stellar_mass(stars: &[Star]) -> f64 {
+    stars.iter().skip(1).map(|star| star.mass).sum()
+}
+
+/// the mass of the stars and the black hole
+fn total_mass(stars: &[Star]) -> f64 {
+    stars.iter().map(|star| star.mass).sum()
+}
+
+/// moves the center of mass of the stars onto the black hole and stops their drift
+fn at_rest_in_the_center(stars: &mut [Star]) {
+    let mass = stellar_mass(stars);
+    if mass <= 0.0 {
+        return;
+    }
+    let (position, velocity) = stars
+        .iter()
+        .skip(1)
+        .fold((DVec3::default(), DVec3::default()), |(p, v), star| {
+            (p + star.position * star.mass, v + star.velocity * star.mass)
+        });
+    for star in stars.iter_mut().skip(1) {
+        star.position -= position / mass;
+        star.velocity -= velocity / mass;
+    }
+}
+
+/// a uniformly random unit vector
+fn direction(rng: &mut impl Rng) -> DVec3 {
+    let z: f64 = rng.gen_range(-1.0..1.0);
+    let angle = rng.gen_range(0.0..2.0 * PI);
+    let r = (1.0 - z * z).sqrt();
+    DVec3::new(r * angle.cos(), r * angle.sin(), z)
+}
+
+/// a standard normal random number by the Box–Muller transform
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
@@ -0,0 +1,264 @@
+use crate::{gravity, Particles, SimulationParameters};
+use glam::DVec3;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{ops::Range, str::FromStr};
+
+/// how a physics step advances the particles from the forces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrator {
+    /// velocity first, then position from the new velocity, one force sum per step
+    Euler,
+    /// kick, drift, kick: symplectic and time reversible, one force sum per step as the
+    /// accelerations at the end are kept for the first kick of the next one
+    Leapfrog,
+    /// classic fourth order Runge–Kutta, four force sums per step
+    Rk4,
+    /// fourth order Hermite predictor-corrector from the accelerations and their time
+    /// derivatives, two exact O(n²) sums per step, ignores `opening_angle`
+    Hermite,
+}
+
+impl Integrator {
+    /// all integrators in the order the app cycles through them
+    pub const ALL: [Integrator; 4] = [
+        Integrator::Euler,
+        Integrator::Leapfrog,
+        Integrator::Rk4,
+        Integrator::Hermite,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Integrator::Euler => "euler",
+            Integrator::Leapfrog => "leapfrog",
+            Integrator::Rk4 => "rk4",
+            Integrator::Hermite => "hermite",
+        }
+    }
+
+    /// the integrator after this one in `ALL`, the last is followed by the first
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&i| i == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+impl FromStr for Integrator {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|integrator| integrator.name() == s)
+            .ok_or(())
+    }
+}
+
+/// integrators in config files are written by their `name`
+impl<'de> Deserialize<'de> for Integrator {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|_| D::Error::custom(format!("unknown integrator '{}'", name)))
+    }
+}
+
+/// advances the particles by one physics step with `parameters.integrator`
+///
+/// The accelerations at the current positions must be set, except for `Hermite`, which
+/// sums them together with the jerks itself. `forces` sets the accelerations at the
+/// positions in between for the schemes with more than one force sum per step.
+pub fn advance(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    forces: impl FnMut(&mut Particles),
+) {
+    match parameters.integrator {
+        Integrator::Euler => integrate(particles, parameters),
+        Integrator::Leapfrog => leapfrog_with(particles, parameters, forces),
+        Integrator::Rk4 => rk4_with(particles, parameters, forces),
+        Integrator::Hermite => hermite(particles, parameters),
+    }
+}
+
+/// advances velocities and positions by one physics step from the current accelerations
+pub fn integrate(particles: &mut Particles, parameters: &SimulationParameters) {
+    let all = 0..particles.len();
+    integrate_range(particles, parameters, all);
+}
+
+/// like `integrate`, but only advances the particles in `range`
+pub fn integrate_range(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    range: Range<usize>,
+) {
+    profiling::scope!("integration");
+    let dt = parameters.physics_dt * parameters.time_factor;
+    for (((position, velocity), acceleration), frozen) in particles.positions[range.clone()]
+        .iter_mut()
+        .zip(particles.velocities[range.clone()].iter_mut())
+        .zip(&particles.accelerations[range.clone()])
+        .zip(&particles.frozen[range])
+    {
+        if *frozen {
+            continue;
+        }
+
+        // update velocities
+        *velocity += *acceleration * dt;
+
+        // update positions
+        let delta = *velocity + *acceleration * (0.5 * dt);
+        *position += delta * dt;
+    }
+}
+
+/// one leapfrog step: half a kick from the current accelerations, a full drift and half a
+/// kick from the accelerations at the new positions, which are left in `accelerations`
+///
+/// Both integrators advance by `physics_dt * time_factor` simulated seconds, so they are
+/// interchangeable for the same parameters.
+pub fn leapfrog(particles: &mut Particles, parameters: &SimulationParameters) {
+    leapfrog_with(particles, parameters, |particles| {
+        gravity::accelerations(particles, parameters)
+    });
+}
+
+/// `leapfrog` with the accelerations at the new positions set by `forces`, e.g. on the GPU
+pub fn leapfrog_with(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    forces: impl FnOnce(&mut Particles),
+) {
+    profiling::scope!("leapfrog");
+    half_kick(particles, parameters);
+    let dt = parameters.physics_dt * parameters.time_factor;
+    for ((position, velocity), frozen) in particles
+        .positions
+        .iter_mut()
+        .zip(&particles.velocities)
+        .zip(&particles.frozen)
+    {
+        if !*frozen {
+            *position += *velocity * dt;
+        }
+    }
+    forces(particles);
+    half_kick(particles, parameters);
+}
+
+fn half_kick(particles: &mut Particles, parameters: &SimulationParameters) {
+    let half_dt = 0.5 * parameters.physics_dt * parameters.time_factor;
+    for ((velocity, acceleration), frozen) in particles
+        .velocities
+        .iter_mut()
+        .zip(&particles.accelerations)
+        .zip(&particles.frozen)
+    {
+        if !*frozen {
+            *velocity += *acceleration * half_dt;
+        }
+    }
+}
+
+/// one classic Runge–Kutta step from the current accelerations, `forces` sets them at the
+/// three trial positions, the accelerations at the last one are left in `accelerations`
+pub fn rk4_with(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    mut forces: impl FnMut(&mut Particles),
+) {
+    profiling::scope!("rk4");
+    let dt = parameters.physics_dt * parameters.time_factor;
+    let positions = particles.positions.clone();
+    let velocities = particles.velocities.clone();
+    // sums of the weighted slopes of positions and velocities
+    let mut position_slope = velocities.clone();
+    let mut velocity_slope = particles.accelerations.clone();
+    // slopes of the previous stage
+    let mut slope_x = velocities.clone();
+    let mut slope_v = particles.accelerations.clone();
+    for (fraction, weight) in [(0.5, 2.0), (0.5, 2.0), (1.0, 1.0)] {
+        for i in 0..particles.len() {
+            if !particles.frozen[i] {
+                particles.positions[i] = positions[i] + slope_x[i] * (fraction * dt);
+            }
+        }
+        forces(particles);
+        for i in 0..particles.len() {
+            slope_x[i] = velocities[i] + slope_v[i] * (fraction * dt);
+            slope_v[i] = particles.accelerations[i];
+            position_slope[i] += slope_x[i] * weight;
+            velocity_slope[i] += slope_v[i] * weight;
+        }
+    }
+    for i in 0..particles.len() {
+        if particles.frozen[i] {
+            particles.positions[i] = positions[i];
+            continue;
+        }
+        particles.velocities[i] = velocities[i] + velocity_slope[i] * (dt / 6.0);
+        particles.positions[i] = positions[i] + position_slope[i] * (dt / 6.0);
+    }
+}
+
+/// one Hermite predictor-corrector step with its own exact sums of accelerations and jerks
+///
+/// The accelerations at the predicted positions are left in `accelerations`.
+pub fn hermite(particles: &mut Particles, parameters: &SimulationParameters) {
+    profiling::scope!("hermite");
+    let dt = parameters.physics_dt * parameters.time_factor;
+    let jerks = gravity::accelerations_and_jerks(particles, parameters);
+    let positions = particles.positions.clone();
+    let velocities = particles.velocities.clone();
+    let accelerations = particles.accelerations.clone();
+
+    // predict with the Taylor series up to the jerk
+    for i in 0..particles.len() {
+        if !particles.frozen[i] {
+            let (v, a, j) = (velocities[i], accelerations[i], jerks[i]);
+            particles.positions[i] =
+                positions[i] + v * dt + a * (dt * dt / 2.0) + j * (dt * dt * dt / 6.0);
+            particles.velocities[i] = v + a * dt + j * (dt * dt / 2.0);
+        }
+    }
+
+    // correct with the accelerations and jerks at the predicted state
+    let new_jerks = gravity::accelerations_and_jerks(particles, parameters);
+    for i in 0..particles.len() {
+        if particles.frozen[i] {
+            continue;
+        }
+        let (a0, a1) = (accelerations[i], particles.accelerations[i]);
+        let (j0, j1) = (jerks[i], new_jerks[i]);
+        let velocity = velocities[i] + (a0 + a1) * (dt / 2.0) + (j0 - j1) * (dt * dt / 12.0);
+        particles.positions[i] =
+            positions[i] + (velocities[i] + velocity) * (dt / 2.0) + (a0 - a1) * (dt * dt / 12.0);
+        particles.velocities[i] = velocity;
+    }
+}
+
+/// the positions a massless test particle starting at `position` with `velocity` passes
+/// in the next `steps` physics steps, with the particles held where they are
+///
+/// It moves like a particle of the simulation under `Integrator::Euler`, O(n) per step.
+pub fn trajectory(
+    particles: &Particles,
+    mut position: DVec3,
+    mut velocity: DVec3,
+    parameters: &SimulationParameters,
+    steps: usize,
+) -> Vec<DVec3> {
+    profiling::scope!("trajectory");
+    let dt = parameters.physics_dt * parameters.time_factor;
+    (0..steps)
+        .map(|_| {
+            let acceleration = gravity::acceleration_at(particles, position, parameters);
+            velocity += acceleration * dt;
+            position += (velocity + acceleration * (0.5 * dt)) * dt;
+            position
+        })
+        .collect()
+}
@@ -0,0 +1,233 @@
+//! Lagrange points and zero-velocity curves of two bodies in their rotating frame.
+//!
+//! The pair is treated as the circular restricted three-body problem: both bodies orbit
+//! their center of mass at the Kepler rate of their current separation, in the plane given
+//! by their relative position and velocity. A test particle at rest in that frame sees the
+//! effective potential of both masses plus the centrifugal term, the Lagrange points are
+//! where its gradient vanishes and the zero-velocity curves are its level lines.
+
+use crate::DVec3;
+
+/// bisection steps, enough to reach the f64 resolution of any interval
+const BISECTION_STEPS: usize = 200;
+/// steps of the outward search for the Roche lobe surface before the bisection
+const LOBE_STEPS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RotatingFrame {
+    /// the heavier body, `m1` at `primary`
+    pub primary: DVec3,
+    pub secondary: DVec3,
+    pub m1: f64,
+    pub m2: f64,
+    pub g: f64,
+    pub center_of_mass: DVec3,
+    /// unit vector from the primary to the secondary
+    pub x: DVec3,
+    /// unit vector in the orbit plane, ahead of the secondary
+    pub y: DVec3,
+    /// unit normal of the orbit plane
+    pub z: DVec3,
+    /// angular velocity of the frame in radians per second
+    pub omega: f64,
+}
+
+impl RotatingFrame {
+    /// the frame of two bodies given as (position, velocity, mass), in either order
+    ///
+    /// Returns `None` when the bodies are at the same place or have no mass.
+    pub fn new(a: (DVec3, DVec3, f64), b: (DVec3, DVec3, f64), g: f64) -> Option<Self> {
+        let ((r1, v1, m1), (r2, v2, m2)) = if a.2 >= b.2 { (a, b) } else { (b, a) };
+        let separation = r2 - r1;
+        let distance = separation.length();
+        let total = m1 + m2;
+        if distance == 0.0 || total <= 0.0 {
+            return None;
+        }
+        let x = separation / distance;
+        let normal = separation.cross(v2 - v1);
+        let z = if normal.length_squared() > 0.0 {
+            normal.normalize()
+        } else {
+            // no orbital motion, any plane through both bodies will do
+            let axis = if x.z.abs() < 0.9 { DVec3::Z } else { DVec3::X };
+            (axis - x * axis.dot(x)).normalize()
+        };
+        Some(Self {
+            primary: r1,
+            secondary: r2,
+            m1,
+            m2,
+            g,
+            center_of_mass: (r1 * m1 + r2 * m2) / total,
+            x,
+            y: z.cross(x),
+            z,
+            omega: (g * total / distance.powi(3)).sqrt(),
+        })
+    }
+
+    pub fn separation(&self) -> f64 {
+        (self.secondary - self.primary).length()
+    }
+
+    /// potential energy per unit mass of a particle at rest in the rotating frame
+    pub fn effective_potential(&self, position: DVec3) -> f64 {
+        let relative = position - self.center_of_mass;
+        let in_plane = relative - self.z * relative.dot(self.z);
+        -self.g * self.m1 / (position - self.primary).length()
+            - self.g * self.m2 / (position - self.secondary).length()
+            - 0.5 * self.omega * self.omega * in_plane.length_squared()
+    }
+
+    /// Jacobi constant of a particle moving with `velocity` relative to the rotating frame,
+    /// it can only reach places where it is at most `-2 * effective_potential`
+    pub fn jacobi_constant(&self, position: DVec3, velocity: DVec3) -> f64 {
+        -2.0 * self.effective_potential(position) - velocity.length_squared()
+    }
+
+    /// L1 to L5 in physics coordinates: L1 between the bodies, L2 beyond the secondary,
+    /// L3 beyond the primary, L4 leading and L5 trailing the secondary by 60 degrees
+    pub fn lagrange_points(&self) -> [DVec3; 5] {
+        let d = self.separation();
+        // positions of the bodies on the x axis, relative to the center of mass
+        let x1 = -d * self.m2 / (self.m1 + self.m2);
+        let x2 = x1 + d;
+        let along = |s: f64| self.center_of_mass + self.x * s;
+        // net force per unit mass along the axis, it rises from negative to positive
+        // between the bodies and on either side of them
+        let force = |s: f64| {
+            let pull = |m: f64, at: f64| -self.g * m * (s - at) / (s - at).abs().powi(3);
+            pull(self.m1, x1) + pull(self.m2, x2) + self.omega * self.omega * s
+        };
+        let root = |mut low: f64, mut high: f64| {
+            for _ in 0..BISECTION_STEPS {
+                let middle = 0.5 * (low + high);
+                if middle <= low || middle >= high {
+                    break;
+                }
+                if force(middle) < 0.0 {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            0.5 * (low + high)
+        };
+        let triangle = self.primary + self.x * (0.5 * d);
+        let height = self.y * (0.5 * 3f64.sqrt() * d);
+        [
+            along(root(x1, x2)),
+            along(root(x2, x2 + d)),
+            along(root(x1 - 2.0 * d, x1)),
+            triangle + height,
+            triangle - height,
+        ]
+    }
+
+    /// radius of the sphere around the secondary within which its own gravity dominates
+    /// the tide of the primary, the Hill approximation for a light secondary
+    pub fn tidal_radius(&self) -> f64 {
+        self.separation() * (self.m2 / (3.0 * self.m1)).cbrt()
+    }
+
+    /// distance from the secondary to the surface of its Roche lobe in `direction`
+    ///
+    /// The lobe is the region around the secondary below the effective potential of L1,
+    /// material beyond it is no longer bound to the secondary. It reaches furthest towards
+    /// L1, so the search goes out to that distance.
+    pub fn roche_lobe_radius(&self, direction: DVec3, l1: DVec3) -> f64 {
+        let direction = direction.normalize();
+        let level = self.effective_potential(l1);
+        let outside = |r: f64| self.effective_potential(self.secondary + direction * r) >= level;
+        let limit = (l1 - self.secondary).length();
+        let step = limit / LOBE_STEPS as f64;
+        let mut low = 0.0;
+        let mut high = match (1..=LOBE_STEPS)
+            .map(|i| i as f64 * step)
+            .find(|&r| outside(r))
+        {
+            Some(r) => r,
+            None => return limit,
+        };
+        if high > step {
+            low = high - step;
+        }
+        for _ in 0..BISECTION_STEPS {
+            let middle = 0.5 * (low + high);
+            if middle <= low || middle >= high {
+                break;
+            }
+            if outside(middle) {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+        0.5 * (low + high)
+    }
+
+    /// line segments where the effective potential equals `level`, sampled on a square
+    /// grid of `resolution` cells per side in the orbit plane, centered at `center`
+    pub fn zero_velocity_curve(
+        &self,
+        level: f64,
+        center: DVec3,
+        half_size: f64,
+        resolution: usize,
+    ) -> Vec<[DVec3; 2]> {
+        let cell = 2.0 * half_size / resolution as f64;
+        // the grid center projected into the orbit plane
+        let offset = center - self.center_of_mass;
+        let origin = self.center_of_mass + offset
+            - self.z * offset.dot(self.z)
+            - (self.x + self.y) * half_size;
+        let point =
+            |i: usize, j: usize| origin + self.x * (i as f64 * cell) + self.y * (j as f64 * cell);
+        let values: Vec<f64> = (0..=resolution)
+            .flat_map(|j| (0..=resolution).map(move |i| (i, j)))
+            // the bodies themselves are at minus infinity, a finite floor keeps the
+            // interpolation below working
+            .map(|(i, j)| self.effective_potential(point(i, j)).max(f64::MIN / 4.0) - level)
+            .collect();
+        let value = |i: usize, j: usize| values[j * (resolution + 1) + i];
+
+        let mut segments = Vec::new();
+        for j in 0..resolution {
+            for i in 0..resolution {
+                // corners counter clockwise, the edge k runs from corner k to corner k + 1
+                let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+                let v = corners.map(|(i, j)| value(i, j));
+                let crossing = |k: usize| {
+                    let (a, b) = (k, (k + 1) % 4);
+                    if (v[a] < 0.0) == (v[b] < 0.0) {
+                        return None;
+                    }
+                    let t = v[a] / (v[a] - v[b]);
+                    let (pa, pb) = (
+                        point(corners[a].0, corners[a].1),
+                        point(corners[b].0, corners[b].1),
+                    );
+                    Some(pa.lerp(pb, t))
+                };
+                let p: Vec<DVec3> = (0..4).filter_map(crossing).collect();
+                match p.len() {
+                    2 => segments.push([p[0], p[1]]),
+                    4 => {
+                        // a saddle, the value in the middle decides which corners connect
+                        let middle = v.iter().sum::<f64>() / 4.0;
+                        if (middle < 0.0) == (v[0] < 0.0) {
+                            segments.push([p[0], p[1]]);
+                            segments.push([p[2], p[3]]);
+                        } else {
+                            segments.push([p[3], p[0]]);
+                            segments.push([p[1], p[2]]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        segments
+    }
+}
@@ -0,0 +1,193 @@
+//! Renderer independent part of the galaxy simulator: star state, gravity and integration.
+
+pub mod accretion;
+pub mod alembic;
+pub mod benchmark;
+pub mod catalog;
+pub mod clumps;
+pub mod config;
+pub mod diagnostics;
+pub mod distributed;
+pub mod error;
+pub mod export;
+pub mod gadget;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod gravity;
+pub mod halo;
+pub mod initial_conditions;
+pub mod integrator;
+pub mod lagrange;
+pub mod merging;
+pub mod octree;
+mod parallel;
+pub mod parameters;
+pub mod particles;
+pub mod potential;
+pub mod reference;
+pub mod simulation;
+pub mod snapshot;
+pub mod spatial_hash;
+pub mod trajectory;
+pub mod units;
+pub mod validation;
+
+pub use config::SimulationConfig;
+pub use error::{Error, Result};
+pub use glam::DVec3;
+pub use initial_conditions::Preset;
+pub use integrator::Integrator;
+pub use parameters::{AdaptiveTimestep, SimulationParameters};
+pub use particles::{ParticleId, Particles};
+use serde::{Deserialize, Serialize};
+pub use simulation::{Simulation, SimulationBuilder};
+pub use snapshot::Snapshot;
+pub use spatial_hash::SpatialHash;
+
+/// a single particle, the solver itself keeps them in `Particles`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Star {
+    pub position: DVec3,
+    pub velocity: DVec3,
+    pub acceleration: DVec3,
+    pub mass: f64,
+}
+
+/// one full physics step: gravity, integration and pinning the black hole to the center
+/// unless `pin_black_hole` is off
+///
+/// Pairs closer than `min_gravity_distance` are reported to `close_encounter` first, the
+/// forces are softened by `softening`.
+pub fn step(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    close_encounter: impl FnMut(usize, usize),
+) {
+    profiling::scope!("physics step");
+    report_close_encounters(particles, parameters, close_encounter);
+    advance_substeps(particles, parameters, gravity::accelerations);
+}
+
+/// integrates one physics step in the substeps of `parameters.adaptive_timestep`, with
+/// `forces` setting the accelerations
+///
+/// Every substep has the `time_factor` reduced to its share of the step.
+pub(crate) fn advance_substeps(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    forces: impl FnMut(&mut Particles, &SimulationParameters),
+) {
+    advance_agreed_substeps(particles, parameters, forces, |count| count);
+}
+
+/// `advance_substeps` with the number of substeps passed through `agree`, which gives all
+/// nodes of a distributed simulation the same
+pub(crate) fn advance_agreed_substeps(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    mut forces: impl FnMut(&mut Particles, &SimulationParameters),
+    agree: impl FnOnce(usize) -> usize,
+) {
+    // stars added during the run, e.g. by the launcher, join the plane
+    if parameters.planar {
+        particles.flatten();
+    }
+    // the black hole is pinned before every sum, so the leapfrog ends a step with the
+    // accelerations at its final positions and the next step starts from them
+    let mut forces = |particles: &mut Particles, parameters: &SimulationParameters| {
+        pin_black_hole(particles, parameters);
+        forces(particles, parameters);
+    };
+    // Hermite sums the accelerations itself, for it they are only needed to count the
+    // substeps
+    let kept =
+        parameters.integrator == Integrator::Leapfrog && particles.accelerations_kept(parameters);
+    let count = match parameters.adaptive_timestep {
+        Some(adaptive) => {
+            if !kept {
+                forces(particles, parameters);
+            }
+            agree(adaptive.substeps(particles, parameters.physics_dt * parameters.time_factor))
+        }
+        None => {
+            if parameters.integrator != Integrator::Hermite && !kept {
+                forces(particles, parameters);
+            }
+            1
+        }
+    };
+    let substep = SimulationParameters {
+        time_factor: parameters.time_factor / count as f64,
+        ..parameters.clone()
+    };
+    for k in 0..count {
+        // the first substep uses the accelerations summed above, the leapfrog those at
+        // the end of the last substep
+        let summed = matches!(
+            substep.integrator,
+            Integrator::Hermite | Integrator::Leapfrog
+        );
+        if k > 0 && !summed {
+            forces(particles, &substep);
+        }
+        integrator::advance(particles, &substep, |particles| forces(particles, &substep));
+        pin_black_hole(particles, parameters);
+    }
+    if parameters.integrator == Integrator::Leapfrog {
+        particles.keep_accelerations(parameters);
+    }
+}
+
+/// the particles that crossed the escape radius outwards since they were at `previous`,
+/// they are frozen when `freeze_escapers` is on
+pub fn escape(
+    particles: &mut Particles,
+    previous: &[DVec3],
+    parameters: &SimulationParameters,
+) -> Vec<usize> {
+    let escape_radius = parameters.escape_radius * parameters.galaxy_diameter;
+    let escaped: Vec<usize> = previous
+        .iter()
+        .zip(&particles.positions)
+        .enumerate()
+        .filter(|(_, (previous, position))| {
+            previous.length() < escape_radius && position.length() >= escape_radius
+        })
+        .map(|(i, _)| i)
+        .collect();
+    if parameters.freeze_escapers {
+        for &i in &escaped {
+            particles.frozen[i] = true;
+        }
+    }
+    escaped
+}
+
+pub(crate) fn report_close_encounters(
+    particles: &Particles,
+    parameters: &SimulationParameters,
+    close_encounter: impl FnMut(usize, usize),
+) {
+    if parameters.min_gravity_distance > 0.0 {
+        // tracers pass through everything
+        let skip: Vec<bool> = (0..particles.len())
+            .map(|i| particles.frozen[i] || particles.is_tracer(i))
+            .collect();
+        let mut hash = SpatialHash::new(parameters.min_gravity_distance);
+        hash.rebuild(&particles.positions, &skip);
+        hash.pairs(
+            &particles.positions,
+            parameters.min_gravity_distance,
+            close_encounter,
+        );
+    }
+}
+
+pub(crate) fn pin_black_hole(particles: &mut Particles, parameters: &SimulationParameters) {
+    if !parameters.pin_black_hole {
+        return;
+    }
+    if let Some(black_hole) = particles.positions.first_mut() {
+        *black_hole = DVec3::default();
+    }
+}
@@ -0,0 +1,121 @@
+//! Stars that touch merge into one, for runaway mergers in dense clusters.
+//!
+//! Every star is a ball of `merge_density`, so its radius grows with the cube root of its
+//! mass. A spatial hash finds the candidates, two stars closer than the sum of their
+//! radii merge, conserving mass and momentum.
+
+use crate::{spatial_hash::SpatialHash, Particles, SimulationParameters, Star};
+use std::f64::consts::PI;
+
+/// radius in meters of a star of `mass` kg with `density` kg/m³
+pub fn radius(mass: f64, density: f64) -> f64 {
+    (3.0 * mass / (4.0 * PI * density)).cbrt()
+}
+
+/// the touching pairs of unfrozen stars as survivor and absorbed index, each star is in
+/// at most one pair
+///
+/// The heavier star survives, the black hole always. Tracers touch nothing and nothing
+/// touches while `merge_density` is 0.
+pub fn touching(particles: &Particles, parameters: &SimulationParameters) -> Vec<(usize, usize)> {
+    let density = parameters.merge_density;
+    if density <= 0.0 {
+        return Vec::new();
+    }
+    let radii: Vec<f64> = particles
+        .masses
+        .iter()
+        .map(|&mass| radius(mass.max(0.0), density))
+        .collect();
+    let largest = radii
+        .iter()
+        .zip(&particles.frozen)
+        .filter(|(_, &frozen)| !frozen)
+        .map(|(&r, _)| r)
+        .fold(0.0, f64::max);
+    if largest <= 0.0 {
+        return Vec::new();
+    }
+
+    let skip: Vec<bool> = (0..particles.len())
+        .map(|i| particles.frozen[i] || particles.is_tracer(i))
+        .collect();
+    let mut hash = SpatialHash::new(2.0 * largest);
+    hash.rebuild(&particles.positions, &skip);
+    let positions = &particles.positions;
+    let mut candidates = Vec::new();
+    hash.pairs(positions, 2.0 * largest, |i, j| {
+        if positions[i].distance(positions[j]) < radii[i] + radii[j] {
+            candidates.push((i, j));
+        }
+    });
+
+    let mut taken = vec![false; particles.len()];
+    let mut pairs = Vec::new();
+    for (i, j) in candidates {
+        if taken[i] || taken[j] {
+            continue;
+        }
+        taken[i] = true;
+        taken[j] = true;
+        // i is the lower index, so the black hole is always i
+        let pair = if i == 0 || particles.masses[i] >= particles.masses[j] {
+            (i, j)
+        } else {
+            (j, i)
+        };
+        pairs.push(pair);
+    }
+    pairs
+}
+
+/// merges `absorbed` into `survivor` at their center of mass with their total momentum,
+/// the absorbed particle is left as it is and must be removed
+///
+/// A black hole held by `pin_black_hole` stays at its position, an unpinned one moves to
+/// the center of mass like any other survivor.
+pub fn merge_into(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    survivor: usize,
+    absorbed: usize,
+) {
+    let (a, b) = (particles.star(survivor), particles.star(absorbed));
+    let mass = a.mass + b.mass;
+    if mass <= 0.0 {
+        return;
+    }
+    let position = if survivor == 0 && parameters.pin_black_hole {
+        a.position
+    } else {
+        (a.position * a.mass + b.position * b.mass) / mass
+    };
+    particles.set_star(
+        survivor,
+        Star {
+            position,
+            velocity: (a.velocity * a.mass + b.velocity * b.mass) / mass,
+            acceleration: a.acceleration,
+            mass,
+        },
+    );
+}
+
+/// merges all touching pairs and removes the absorbed particles, returns the number of
+/// mergers
+pub fn merge_all(particles: &mut Particles, parameters: &SimulationParameters) -> usize {
+    let pairs = touching(particles, parameters);
+    let mut absorbed: Vec<usize> = pairs
+        .iter()
+        .map(|&(survivor, absorbed)| {
+            merge_into(particles, parameters, survivor, absorbed);
+            absorbed
+        })
+        .collect();
+    // from the back, so the swapped in particles are never ones still to remove
+    absorbed.sort_unstable_by(|a, b| b.cmp(a));
+    for &i in &absorbed {
+        particles.swap_remove(i);
+    }
+    pairs.len()
+}
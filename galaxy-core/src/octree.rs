@@ -0,0 +1,467 @@
+//! Barnes–Hut forces: a distant group of particles pulls like one particle with their mass
+//! at their center of mass, which makes the force sum O(n log n).
+//!
+//! The particles are sorted into an octree. A receiver takes the monopole of a cell when
+//! the cell is smaller than `opening_angle` times its distance to the center of mass,
+//! otherwise it looks at the children. The cell containing the receiver itself is always
+//! opened, so a particle never pulls on itself. An opening angle of 0 opens every cell and
+//! gives the direct sum.
+//!
+//! The tree is built top down, each cell sorting its particles into its octants, and the
+//! octants of cells with many particles are built on threads of their own.
+
+use crate::{
+    gravity::{softened, softened_potential, MIN_RECEIVERS_PER_TASK},
+    parallel, DVec3, Particles, SimulationParameters,
+};
+use rayon::prelude::*;
+use std::ops::Range;
+
+/// particles a leaf holds before it is split
+const LEAF_SIZE: usize = 8;
+/// leaves this deep are never split, for particles at the same place
+const MAX_DEPTH: usize = 32;
+const NONE: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    /// center of the cube
+    center: DVec3,
+    half_size: f64,
+    mass: f64,
+    center_of_mass: DVec3,
+    /// index of each octant's child, `NONE` for empty octants, all `NONE` for a leaf
+    children: [u32; 8],
+    /// the start of the particles of a leaf in `Octree::bodies`
+    first: u32,
+    count: usize,
+}
+
+impl Node {
+    fn new(center: DVec3, half_size: f64) -> Self {
+        Self {
+            center,
+            half_size,
+            mass: 0.0,
+            center_of_mass: DVec3::default(),
+            children: [NONE; 8],
+            first: NONE,
+            count: 0,
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children == [NONE; 8]
+    }
+
+    fn contains(&self, p: DVec3) -> bool {
+        let d = (p - self.center).abs();
+        d.x <= self.half_size && d.y <= self.half_size && d.z <= self.half_size
+    }
+}
+
+/// a particle in a leaf: position, mass and particle index
+type Body = (DVec3, f64, u32);
+
+/// the octree of the unfrozen particles with mass, with their masses and centers of mass
+#[derive(Debug, Clone, Default)]
+pub struct Octree {
+    nodes: Vec<Node>,
+    /// the particles of every leaf one after the other, for fast leaf sums
+    bodies: Vec<Body>,
+}
+
+/// whether particle `i` pulls on the others, massless tracers only feel the tree
+fn source(particles: &Particles, i: usize) -> bool {
+    !particles.frozen[i] && particles.masses[i] != 0.0
+}
+
+/// the octant of `p` around `center`, bit 0 for x, 1 for y and 2 for z
+fn octant(p: DVec3, center: DVec3) -> usize {
+    (p.x > center.x) as usize | ((p.y > center.y) as usize) << 1 | ((p.z > center.z) as usize) << 2
+}
+
+impl Octree {
+    /// builds the tree on this thread
+    pub fn new(particles: &Particles) -> Self {
+        Self::with_threads(particles, 1)
+    }
+
+    /// builds the tree with `threads` like the force sums, 0 for all cores
+    ///
+    /// The cells are laid out depth first, every cell followed by the cells of its
+    /// children in octant order, so the tree is the same with any number of threads.
+    pub fn with_threads(particles: &Particles, threads: usize) -> Self {
+        Self::of_range(particles, 0..particles.len(), threads)
+    }
+
+    /// builds the tree of only the particles in `range`
+    pub(crate) fn of_range(particles: &Particles, range: Range<usize>, threads: usize) -> Self {
+        profiling::scope!("octree build");
+        let mut indices: Vec<u32> = range
+            .filter(|&i| source(particles, i))
+            .map(|i| i as u32)
+            .collect();
+        let positions = &particles.positions;
+        let (min, max) = indices.iter().map(|&i| positions[i as usize]).fold(
+            (DVec3::splat(f64::INFINITY), DVec3::splat(f64::NEG_INFINITY)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        );
+        if indices.is_empty() {
+            return Self::default();
+        }
+        let half_size = 0.5 * (max - min).max_element();
+        // a little larger, so the particles on the faces are inside for sure
+        let root = Node::new(0.5 * (min + max), half_size * 1.001 + f64::MIN_POSITIVE);
+        let mut nodes = Vec::new();
+        let threaded = threads != 1;
+        let mut build = || subtree(&mut nodes, positions, &mut indices, 0, root, 0, threaded);
+        if threaded {
+            parallel::install(threads, build);
+        } else {
+            build();
+        }
+        // the leaves partitioned the indices into their ranges
+        let bodies = indices
+            .iter()
+            .map(|&i| (positions[i as usize], particles.masses[i as usize], i))
+            .collect();
+        let mut tree = Self { nodes, bodies };
+
+        // children always follow their parents, backwards every child is done before its
+        // parent
+        for n in (0..tree.nodes.len()).rev() {
+            let node = tree.nodes[n];
+            let (mut mass, mut moment) = (0.0, DVec3::default());
+            if node.is_leaf() {
+                for &(position, m, _) in tree.leaf(&node) {
+                    mass += m;
+                    moment += position * m;
+                }
+            } else {
+                for &child in node.children.iter().filter(|&&c| c != NONE) {
+                    let child = &tree.nodes[child as usize];
+                    mass += child.mass;
+                    moment += child.center_of_mass * child.mass;
+                }
+            }
+            let node = &mut tree.nodes[n];
+            node.mass = mass;
+            node.center_of_mass = if mass > 0.0 {
+                moment / mass
+            } else {
+                node.center
+            };
+        }
+        tree
+    }
+
+    fn leaf(&self, node: &Node) -> &[Body] {
+        let start = node.first as usize;
+        &self.bodies[start..start + node.count]
+    }
+
+    /// number of cells, for tests and statistics
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// every cell of the tree, the root first
+    pub fn cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.nodes.iter().map(Cell::from)
+    }
+
+    /// walks the tree for a receiver at `position`, `visit` gets the cells pulling on it
+    /// as a monopole with `true` and the leaves summed particle by particle with `false`
+    fn traverse(
+        &self,
+        position: DVec3,
+        parameters: &SimulationParameters,
+        mut visit: impl FnMut(&Node, bool),
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0u32);
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n as usize];
+            if node.mass == 0.0 {
+                continue;
+            }
+            let distance = (node.center_of_mass - position).length();
+            if !node.contains(position)
+                && 2.0 * node.half_size < parameters.opening_angle * distance
+            {
+                visit(node, true);
+            } else if node.is_leaf() {
+                visit(node, false);
+            } else {
+                stack.extend(node.children.iter().filter(|&&c| c != NONE));
+            }
+        }
+    }
+
+    /// the pull of the tree on every receiver in the box from `min` to `max` as sources of
+    /// position and mass: the cells that every receiver in the box takes as a monopole, and
+    /// the particles of the leaves some receiver opens
+    pub(crate) fn summary(&self, min: DVec3, max: DVec3, opening_angle: f64) -> Vec<(DVec3, f64)> {
+        let mut sources = Vec::new();
+        if self.nodes.is_empty() {
+            return sources;
+        }
+        let mut stack = vec![0u32];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n as usize];
+            if node.mass == 0.0 {
+                continue;
+            }
+            // the receiver in the box closest to the center of mass
+            let closest = node.center_of_mass.max(min).min(max);
+            let distance = (node.center_of_mass - closest).length();
+            if 2.0 * node.half_size < opening_angle * distance {
+                sources.push((node.center_of_mass, node.mass));
+            } else if node.is_leaf() {
+                sources.extend(
+                    self.leaf(node)
+                        .iter()
+                        .map(|&(position, mass, _)| (position, mass)),
+                );
+            } else {
+                stack.extend(node.children.iter().filter(|&&c| c != NONE));
+            }
+        }
+        sources
+    }
+
+    /// acceleration of particle `i` with the softened force law of `gravity::softened`
+    pub fn acceleration(
+        &self,
+        particles: &Particles,
+        i: usize,
+        parameters: &SimulationParameters,
+    ) -> DVec3 {
+        let position = particles.positions[i];
+        let pull = |source: DVec3, mass: f64| softened(source - position, mass, parameters);
+        let mut acceleration = DVec3::default();
+        self.traverse(position, parameters, |node, monopole| {
+            if monopole {
+                acceleration += pull(node.center_of_mass, node.mass);
+            } else {
+                for &(source, mass, j) in self.leaf(node) {
+                    if j as usize != i {
+                        acceleration += pull(source, mass);
+                    }
+                }
+            }
+        });
+        acceleration
+    }
+
+    /// potential energy per kg at `position` of `gravity::softened_potential`, without the
+    /// halo, the monopoles approximated like the forces
+    pub fn potential(&self, position: DVec3, parameters: &SimulationParameters) -> f64 {
+        let mut potential = 0.0;
+        self.traverse(position, parameters, |node, monopole| {
+            if monopole {
+                potential +=
+                    softened_potential(node.center_of_mass - position, node.mass, parameters);
+            } else {
+                for &(source, mass, _) in self.leaf(node) {
+                    potential += softened_potential(source - position, mass, parameters);
+                }
+            }
+        });
+        potential
+    }
+
+    /// the cells whose monopole pulls on particle `i`, and the leaves whose particles pull
+    /// on it one by one
+    pub fn interactions(
+        &self,
+        particles: &Particles,
+        i: usize,
+        parameters: &SimulationParameters,
+    ) -> Interactions {
+        let mut interactions = Interactions::default();
+        self.traverse(particles.positions[i], parameters, |node, monopole| {
+            let cells = if monopole {
+                &mut interactions.approximated
+            } else {
+                &mut interactions.direct
+            };
+            cells.push(Cell::from(node));
+        });
+        interactions
+    }
+}
+
+/// particles of a cell above which its children are built in parallel
+const MIN_PARALLEL_PARTICLES: usize = 4096;
+
+/// appends `node` holding the particles `indices`, which start at `first` of the bodies,
+/// and the cells below it to `nodes`, sorting `indices` into the ranges of the leaves
+fn subtree(
+    nodes: &mut Vec<Node>,
+    positions: &[DVec3],
+    indices: &mut [u32],
+    first: usize,
+    mut node: Node,
+    depth: usize,
+    parallel: bool,
+) {
+    let (n, count) = (nodes.len(), indices.len());
+    if count <= LEAF_SIZE || depth >= MAX_DEPTH {
+        node.first = first as u32;
+        node.count = count;
+        nodes.push(node);
+        return;
+    }
+    nodes.push(node);
+
+    // a stable partition by octant keeps the particles of a leaf in index order
+    let octants: Vec<usize> = indices
+        .iter()
+        .map(|&i| octant(positions[i as usize], node.center))
+        .collect();
+    let mut counts = [0; 8];
+    for &o in &octants {
+        counts[o] += 1;
+    }
+    let mut starts = [0; 8];
+    for o in 1..8 {
+        starts[o] = starts[o - 1] + counts[o - 1];
+    }
+    let mut sorted = vec![0; indices.len()];
+    let mut next = starts;
+    for (&i, &o) in indices.iter().zip(&octants) {
+        sorted[next[o]] = i;
+        next[o] += 1;
+    }
+    indices.copy_from_slice(&sorted);
+
+    let half = 0.5 * node.half_size;
+    let mut children = Vec::with_capacity(8);
+    let mut rest = indices;
+    for (o, &size) in counts.iter().enumerate() {
+        let (mine, others) = rest.split_at_mut(size);
+        rest = others;
+        if size > 0 {
+            let sign = |bit: usize| if o & bit != 0 { half } else { -half };
+            let center = node.center + DVec3::new(sign(1), sign(2), sign(4));
+            children.push((o, first + starts[o], mine, Node::new(center, half)));
+        }
+    }
+
+    if parallel && count >= MIN_PARALLEL_PARTICLES {
+        // every child builds its cells on its own, numbered from 0, and they are appended
+        // in octant order with their numbers moved behind the cells before them
+        let subtrees: Vec<(usize, Vec<Node>)> = children
+            .into_par_iter()
+            .map(|(o, first, indices, child)| {
+                let mut cells = Vec::new();
+                subtree(
+                    &mut cells,
+                    positions,
+                    indices,
+                    first,
+                    child,
+                    depth + 1,
+                    true,
+                );
+                (o, cells)
+            })
+            .collect();
+        for (o, cells) in subtrees {
+            let base = nodes.len() as u32;
+            nodes[n].children[o] = base;
+            nodes.extend(cells.into_iter().map(|mut cell| {
+                for c in cell.children.iter_mut().filter(|c| **c != NONE) {
+                    *c += base;
+                }
+                cell
+            }));
+        }
+    } else {
+        for (o, first, indices, child) in children {
+            nodes[n].children[o] = nodes.len() as u32;
+            subtree(nodes, positions, indices, first, child, depth + 1, parallel);
+        }
+    }
+}
+
+/// a cube of the octree with the mass of its particles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub center: DVec3,
+    pub half_size: f64,
+    pub mass: f64,
+    pub center_of_mass: DVec3,
+}
+
+impl From<&Node> for Cell {
+    fn from(node: &Node) -> Self {
+        Self {
+            center: node.center,
+            half_size: node.half_size,
+            mass: node.mass,
+            center_of_mass: node.center_of_mass,
+        }
+    }
+}
+
+/// how the force on one particle was summed, see `Octree::interactions`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Interactions {
+    /// cells taken as one particle at their center of mass
+    pub approximated: Vec<Cell>,
+    /// leaves opened down to their particles
+    pub direct: Vec<Cell>,
+}
+
+/// sets the acceleration of every particle with the Barnes–Hut approximation
+///
+/// Frozen particles are skipped both as sources and as receivers. Every receiver is
+/// independent of the others, so the receivers are split across `parameters.threads`
+/// threads without changing the result.
+pub fn barnes_hut(particles: &mut Particles, parameters: &SimulationParameters) {
+    let all = 0..particles.len();
+    barnes_hut_range(particles, parameters, all);
+}
+
+/// like `barnes_hut`, but only sets the accelerations of the receivers in `receivers`
+pub fn barnes_hut_range(
+    particles: &mut Particles,
+    parameters: &SimulationParameters,
+    receivers: Range<usize>,
+) {
+    profiling::scope!("barnes hut");
+    let tree = Octree::with_threads(particles, parameters.threads);
+    let mut accelerations = std::mem::take(&mut particles.accelerations);
+    let particles_ref = &*particles;
+    let start = receivers.start;
+    let out = &mut accelerations[receivers];
+    let fill = |(i, acceleration): (usize, &mut DVec3)| {
+        let i = start + i;
+        *acceleration = if particles_ref.frozen[i] {
+            DVec3::default()
+        } else {
+            tree.acceleration(particles_ref, i, parameters)
+        };
+    };
+    if parameters.threads == 1 {
+        out.iter_mut().enumerate().for_each(fill);
+    } else {
+        parallel::install(parameters.threads, || {
+            out.par_iter_mut()
+                .with_min_len(MIN_RECEIVERS_PER_TASK)
+                .enumerate()
+                .for_each(fill)
+        });
+    }
+    particles.accelerations = accelerations;
+}
@@ -0,0 +1,38 @@
+//! Thread pools for the parallel force sums.
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::{Arc, Mutex};
+
+/// pools already built, by number of threads
+static POOLS: Mutex<Vec<(usize, Arc<ThreadPool>)>> = Mutex::new(Vec::new());
+
+/// runs `f` on a pool with `threads` worker threads, 0 uses rayon's global pool with all cores
+///
+/// The pools are built once and kept, so calling this every physics step is cheap.
+pub(crate) fn install<R: Send>(threads: usize, f: impl FnOnce() -> R + Send) -> R {
+    if threads == 0 {
+        return f();
+    }
+    let pool = {
+        let mut pools = POOLS.lock().unwrap_or_else(|e| e.into_inner());
+        match pools.iter().find(|(n, _)| *n == threads) {
+            Some((_, pool)) => pool.clone(),
+            None => {
+                let pool = ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|i| format!("galaxy force {}", i))
+                    .build()
+                    .map(Arc::new);
+                match pool {
+                    Ok(pool) => {
+                        pools.push((threads, pool.clone()));
+                        pool
+                    }
+                    // without threads the work still gets done on this one
+                    Err(_) => return f(),
+                }
+            }
+        }
+    };
+    pool.install(f)
+}
@@ -0,0 +1,160 @@
+use crate::{
+    halo::Halo,
+    initial_conditions::{Collision, GalaxyDescriptor, Preset, SpiralGalaxy, ThreeBody},
+    integrator::Integrator,
+    Particles,
+};
+
+/// all tunable values of the simulation, read by the systems every frame
+#[derive(Debug, Clone)]
+pub struct SimulationParameters {
+    /// gravitational constant in m³/(kg s²)
+    pub g: f64,
+    pub number_of_stars: usize,
+    /// massless test particles after the stars, on cold circular orbits in a disk of
+    /// `galaxy_diameter`, they feel gravity but exert none, see `initial_conditions::tracers`
+    pub tracers: usize,
+    pub black_hole_mass: f64,
+    pub star_mass_from: f64,
+    pub star_mass_to: f64,
+    pub galaxy_diameter: f64,
+    /// simulated seconds per physics second
+    pub time_factor: f64,
+    /// the model of the initial stars
+    pub preset: Preset,
+    /// shape of `Preset::Spiral`
+    pub spiral: SpiralGalaxy,
+    /// encounter of `Preset::Collision`
+    pub collision: Collision,
+    /// bodies of `Preset::ThreeBody`
+    pub three_body: ThreeBody,
+    /// galaxies that replace the preset when there are any
+    pub galaxies: Vec<GalaxyDescriptor>,
+    /// initial speed of the stars of `Preset::Box` in m/s
+    pub spin_factor: f64,
+    /// Plummer softening length ε in meters, gravity follows G m r / (r² + ε²)^(3/2)
+    pub softening: f64,
+    /// pairs closer than this in meters are reported as close encounters, e.g. for
+    /// mergers, it doesn't change the forces
+    pub min_gravity_distance: f64,
+    /// how a step advances the stars, distributed nodes support all but `Integrator::Hermite`
+    pub integrator: Integrator,
+    /// Barnes–Hut cells smaller than this times their distance act as one particle, 0 is
+    /// the exact O(n²) sum, 0.5 is a common choice for many stars
+    pub opening_angle: f64,
+    /// stars closer to the black hole than this in meters are swallowed by it, 0 for none
+    pub capture_radius: f64,
+    /// density of the stars in kg/m³ that sets their radii, touching stars merge, 0 for
+    /// no merging
+    pub merge_density: f64,
+    /// radius at which a star counts as escaped, in galaxy diameters
+    pub escape_radius: f64,
+    /// escaped stars are frozen and cost nothing afterwards
+    pub freeze_escapers: bool,
+    /// hidden star entities created at startup for stars spawned later
+    pub spawn_pool_size: usize,
+    /// physics step in seconds, one over the tick rate, only read when the app is built
+    pub physics_dt: f64,
+    /// target rendered frames per second independent of the tick rate, 0 is unlimited
+    pub render_rate: f64,
+    /// camera orbit speed in radians per second
+    pub camera_speed: f32,
+    /// worker threads for the parallel parts, 0 uses all cores and 1 is fully deterministic
+    pub threads: usize,
+    /// the stars start and stay in the z = 0 plane, where the forces between them have no
+    /// z component either, and the app looks at them from above
+    pub planar: bool,
+    /// external dark matter potential around the center, `None` for only the stars
+    pub halo: Option<Halo>,
+    /// the first particle is the black hole and held at the origin after every step,
+    /// otherwise it moves like the others
+    pub pin_black_hole: bool,
+    /// splits the physics steps into substeps while stars pass each other closely, `None`
+    /// always takes whole steps
+    pub adaptive_timestep: Option<AdaptiveTimestep>,
+}
+
+/// substep sizes for the adaptive timestep
+///
+/// A physics step still advances `physics_dt * time_factor` simulated seconds, in as many
+/// equal substeps as needed for every star to take at most `accuracy` of its time scale
+/// |v| / |a|, which is about an orbit over 2π far out and the crossing time of a close
+/// fly-by. Quiet steps take one substep, so they cost no more than without adaptation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveTimestep {
+    pub accuracy: f64,
+    /// shortest substep in simulated seconds, limits the substeps per physics step
+    pub min_dt: f64,
+    /// longest substep in simulated seconds
+    pub max_dt: f64,
+}
+
+impl AdaptiveTimestep {
+    /// the bounds that aren't given are a hundredth of a physics step of `step` simulated
+    /// seconds and a whole one, moved to the given bound where they would cross it
+    pub fn with_defaults(
+        accuracy: f64,
+        min_dt: Option<f64>,
+        max_dt: Option<f64>,
+        step: f64,
+    ) -> Self {
+        let min_dt =
+            min_dt.unwrap_or_else(|| max_dt.map_or(step / 100.0, |max| max.min(step / 100.0)));
+        Self {
+            accuracy,
+            min_dt,
+            max_dt: max_dt.unwrap_or_else(|| step.max(min_dt)),
+        }
+    }
+
+    /// number of substeps for a physics step of `dt` simulated seconds
+    pub fn substeps(&self, particles: &Particles, dt: f64) -> usize {
+        // the pinned black hole doesn't move
+        let shortest = (1..particles.len())
+            .filter(|&i| !particles.frozen[i])
+            .map(|i| particles.velocities[i].length() / particles.accelerations[i].length())
+            .filter(|t| !t.is_nan())
+            .fold(f64::INFINITY, f64::min);
+        let substep = (self.accuracy * shortest).clamp(self.min_dt, self.max_dt);
+        let max = (dt / self.min_dt).ceil().max(1.0);
+        (dt / substep).ceil().clamp(1.0, max) as usize
+    }
+}
+
+impl Default for SimulationParameters {
+    fn default() -> Self {
+        Self {
+            g: 6.674e-11,
+            number_of_stars: 1000,
+            tracers: 0,
+            black_hole_mass: 0.0,
+            star_mass_from: 1.0e29,
+            star_mass_to: 1.0e32,
+            galaxy_diameter: 1.0e13,
+            time_factor: 2.0e6,
+            preset: Preset::Box,
+            spiral: SpiralGalaxy::default(),
+            collision: Collision::default(),
+            three_body: ThreeBody::default(),
+            galaxies: Vec::new(),
+            spin_factor: 13.0,
+            softening: 1.0e11,
+            min_gravity_distance: 1.0e1,
+            integrator: Integrator::Leapfrog,
+            opening_angle: 0.0,
+            capture_radius: 0.0,
+            merge_density: 0.0,
+            escape_radius: 1.5,
+            freeze_escapers: true,
+            spawn_pool_size: 64,
+            physics_dt: 1.0 / 60.0,
+            render_rate: 0.0,
+            camera_speed: 0.0,
+            threads: 0,
+            planar: false,
+            halo: None,
+            pin_black_hole: true,
+            adaptive_timestep: None,
+        }
+    }
+}
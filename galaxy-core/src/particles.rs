@@ -0,0 +1,156 @@
+use crate::{halo::Halo, SimulationParameters, Star};
+use glam::DVec3;
+use std::iter::FromIterator;
+
+/// stable identity of a particle, survives reordering of the arrays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ParticleId(pub u64);
+
+/// all particles as structure of arrays, index i is the same particle in every array
+#[derive(Debug, Clone, Default)]
+pub struct Particles {
+    pub ids: Vec<ParticleId>,
+    pub positions: Vec<DVec3>,
+    pub velocities: Vec<DVec3>,
+    pub accelerations: Vec<DVec3>,
+    pub masses: Vec<f64>,
+    /// frozen particles neither move nor attract others, e.g. escaped stars
+    pub frozen: Vec<bool>,
+    next_id: u64,
+    /// what the accelerations were last summed from, while it matches they are reused
+    summed: Option<ForceInputs>,
+}
+
+/// everything the accelerations depend on
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ForceInputs {
+    positions: Vec<DVec3>,
+    masses: Vec<f64>,
+    frozen: Vec<bool>,
+    /// g, softening and opening angle
+    constants: [f64; 3],
+    halo: Option<Halo>,
+}
+
+fn constants(parameters: &SimulationParameters) -> [f64; 3] {
+    [parameters.g, parameters.softening, parameters.opening_angle]
+}
+
+impl Particles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// appends a particle and returns its new id
+    pub fn push(&mut self, star: Star) -> ParticleId {
+        let id = ParticleId(self.next_id);
+        self.next_id += 1;
+        self.ids.push(id);
+        self.positions.push(star.position);
+        self.velocities.push(star.velocity);
+        self.accelerations.push(star.acceleration);
+        self.masses.push(star.mass);
+        self.frozen.push(false);
+        id
+    }
+
+    /// removes the particle at `index`, the last particle takes its place
+    pub fn swap_remove(&mut self, index: usize) -> Star {
+        self.ids.swap_remove(index);
+        self.frozen.swap_remove(index);
+        Star {
+            position: self.positions.swap_remove(index),
+            velocity: self.velocities.swap_remove(index),
+            acceleration: self.accelerations.swap_remove(index),
+            mass: self.masses.swap_remove(index),
+        }
+    }
+
+    /// a massless test particle, which feels gravity but exerts none and meets no other
+    /// particle, the black hole in the first place isn't one even without mass
+    pub fn is_tracer(&self, index: usize) -> bool {
+        index > 0 && self.masses[index] == 0.0
+    }
+
+    /// moves every particle into the z = 0 plane, with its velocity and acceleration along
+    /// it, for `SimulationParameters::planar`
+    pub fn flatten(&mut self) {
+        for v in self
+            .positions
+            .iter_mut()
+            .chain(&mut self.velocities)
+            .chain(&mut self.accelerations)
+        {
+            v.z = 0.0;
+        }
+    }
+
+    pub fn star(&self, index: usize) -> Star {
+        Star {
+            position: self.positions[index],
+            velocity: self.velocities[index],
+            acceleration: self.accelerations[index],
+            mass: self.masses[index],
+        }
+    }
+
+    pub fn set_star(&mut self, index: usize, star: Star) {
+        self.positions[index] = star.position;
+        self.velocities[index] = star.velocity;
+        self.accelerations[index] = star.acceleration;
+        self.masses[index] = star.mass;
+    }
+
+    /// current array index of a particle, linear search
+    pub fn index_of(&self, id: ParticleId) -> Option<usize> {
+        self.ids.iter().position(|&i| i == id)
+    }
+
+    pub fn stars(&self) -> impl Iterator<Item = Star> + '_ {
+        (0..self.len()).map(move |i| self.star(i))
+    }
+
+    /// remembers that the accelerations were just summed with `parameters`
+    pub(crate) fn keep_accelerations(&mut self, parameters: &SimulationParameters) {
+        let summed = self.summed.get_or_insert_with(ForceInputs::default);
+        summed.positions.clone_from(&self.positions);
+        summed.masses.clone_from(&self.masses);
+        summed.frozen.clone_from(&self.frozen);
+        summed.constants = constants(parameters);
+        summed.halo = parameters.halo;
+    }
+
+    /// makes the next step sum the accelerations again, e.g. when other particles changed
+    pub(crate) fn forget_accelerations(&mut self) {
+        self.summed = None;
+    }
+
+    /// whether nothing changed since `keep_accelerations`, so they need no new sum
+    pub(crate) fn accelerations_kept(&self, parameters: &SimulationParameters) -> bool {
+        self.summed.as_ref().is_some_and(|summed| {
+            summed.constants == constants(parameters)
+                && summed.halo == parameters.halo
+                && summed.positions == self.positions
+                && summed.masses == self.masses
+                && summed.frozen == self.frozen
+        })
+    }
+}
+
+impl FromIterator<Star> for Particles {
+    fn from_iter<T: IntoIterator<Item = Star>>(iter: T) -> Self {
+        let mut particles = Particles::new();
+        for star in iter {
+            particles.push(star);
+        }
+        particles
+    }
+}
@@ -0,0 +1,97 @@
+//! The gravitational potential sampled on a square grid in a plane, to see the wells of
+//! the black hole and the clumps.
+//!
+//! The grid lies in the z = 0 plane shifted to the height of its center, with
+//! `resolution` samples along x and y. The potential per kg is the softened one of the
+//! forces with the halo, summed exactly while the opening angle is 0 and over the
+//! Barnes–Hut octree of the stars above it, like `gravity::accelerations`.
+
+use crate::{
+    gravity::{potential_at, MIN_RECEIVERS_PER_TASK},
+    octree::Octree,
+    parallel, DVec3, Particles, SimulationParameters,
+};
+use rayon::prelude::*;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PotentialMap {
+    pub center: DVec3,
+    /// half the side of the square in meters
+    pub half_size: f64,
+    /// samples along each side, at least 2
+    pub resolution: usize,
+    /// potential per kg in J/kg row by row from -y to +y, each row from -x to +x
+    pub values: Vec<f64>,
+}
+
+impl PotentialMap {
+    /// samples the potential of the particles on the square of `half_size` around `center`
+    pub fn sample(
+        particles: &Particles,
+        parameters: &SimulationParameters,
+        center: DVec3,
+        half_size: f64,
+        resolution: usize,
+    ) -> Self {
+        profiling::scope!("potential map");
+        let mut map = Self {
+            center,
+            half_size,
+            resolution: resolution.max(2),
+            values: Vec::new(),
+        };
+        let tree = (parameters.opening_angle > 0.0)
+            .then(|| Octree::with_threads(particles, parameters.threads));
+        let potential = |position| match &tree {
+            Some(tree) => {
+                let halo = parameters
+                    .halo
+                    .map_or(0.0, |halo| halo.potential(position, parameters.g));
+                tree.potential(position, parameters) + halo
+            }
+            None => potential_at(particles, position, parameters),
+        };
+        let mut values = vec![0.0; map.resolution * map.resolution];
+        let fill = |(i, value): (usize, &mut f64)| {
+            *value = potential(map.point(i % map.resolution, i / map.resolution));
+        };
+        if parameters.threads == 1 {
+            values.iter_mut().enumerate().for_each(fill);
+        } else {
+            parallel::install(parameters.threads, || {
+                values
+                    .par_iter_mut()
+                    .with_min_len(MIN_RECEIVERS_PER_TASK)
+                    .enumerate()
+                    .for_each(fill)
+            });
+        }
+        map.values = values;
+        map
+    }
+
+    /// the position of the sample in `column` from -x and `row` from -y
+    pub fn point(&self, column: usize, row: usize) -> DVec3 {
+        let step = 2.0 * self.half_size / (self.resolution - 1) as f64;
+        self.center
+            + DVec3::new(
+                column as f64 * step - self.half_size,
+                row as f64 * step - self.half_size,
+                0.0,
+            )
+    }
+
+    /// the potential at the sample in `column` and `row`
+    pub fn value(&self, column: usize, row: usize) -> f64 {
+        self.values[row * self.resolution + column]
+    }
+
+    /// the deepest and the highest sampled potential
+    pub fn range(&self) -> (f64, f64) {
+        self.values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            })
+    }
+}
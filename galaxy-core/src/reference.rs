@@ -0,0 +1,137 @@
+//! Comparison with a reference trajectory from another N-body code.
+//!
+//! The reference is a CSV file with the columns time, star, x, y, z, vx, vy, vz in SI
+//! units, one row per star every `INTERVAL` simulated seconds up to `DURATION`. The
+//! stars are integrated with the `step` of the simulation in the integrator and the
+//! opening angle of the parameters, one simulated second per physics step, without
+//! clamping, softening and the pinned black hole, and compared at every sample.
+//! `examples/validate.rs` exports `moons` and compares any file, `tests/reference` holds
+//! one for the tests.
+
+use crate::{diagnostics::total_energy, DVec3, Error, Particles, SimulationParameters, Star};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// simulated seconds, must match `examples/rebound_reference.py`
+pub const DURATION: u64 = 20_000;
+/// simulated seconds between the samples
+pub const INTERVAL: u64 = 1_000;
+
+/// positions and velocities of all stars per sample time
+pub type Trajectory = BTreeMap<u64, Vec<(DVec3, DVec3)>>;
+
+/// how far the integrated stars are from the reference at one sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub time: u64,
+    /// largest distance of a star from its reference position in meters
+    pub error: f64,
+    /// the largest distance relative to the distance of the star from the origin
+    pub relative_error: f64,
+    pub energy_drift: f64,
+    pub reference_energy_drift: f64,
+}
+
+/// the integrator and the opening angle of `parameters` in the steps of the reference
+pub fn parameters(parameters: &SimulationParameters) -> SimulationParameters {
+    SimulationParameters {
+        time_factor: 1.0,
+        physics_dt: 1.0,
+        softening: 0.0,
+        galaxy_diameter: f64::INFINITY,
+        min_gravity_distance: 0.0,
+        halo: None,
+        adaptive_timestep: None,
+        pin_black_hole: false,
+        ..parameters.clone()
+    }
+}
+
+/// an Earth mass planet with three moons on slightly eccentric, inclined orbits
+pub fn moons(parameters: &SimulationParameters) -> Vec<Star> {
+    let central = 6.0e24;
+    let mut stars = vec![Star {
+        mass: central,
+        ..Default::default()
+    }];
+    for (i, &(radius, mass)) in [(1.0e7, 1.0e20), (1.6e7, 5.0e20), (2.5e7, 2.0e20)]
+        .iter()
+        .enumerate()
+    {
+        let speed = (parameters.g * central / radius).sqrt() * 1.05;
+        let tilt = 0.1 * i as f64;
+        stars.push(Star {
+            position: DVec3::new(radius, 0.0, 0.0),
+            velocity: DVec3::new(0.0, speed * tilt.cos(), speed * tilt.sin()),
+            mass,
+            ..Default::default()
+        });
+    }
+    stars
+}
+
+pub fn read(path: &Path) -> crate::Result<Trajectory> {
+    let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    let parse_error = |line: usize| Error::Parse {
+        path: path.to_path_buf(),
+        message: format!("line {}: expected time, star and six numbers", line + 1),
+    };
+    let mut trajectory = Trajectory::new();
+    for (line, row) in text.lines().enumerate().skip(1) {
+        let values: Vec<f64> = row
+            .split(',')
+            .map(|v| v.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| parse_error(line))?;
+        if values.len() != 8 {
+            return Err(parse_error(line));
+        }
+        trajectory
+            .entry(values[0].round() as u64)
+            .or_default()
+            .push((
+                DVec3::new(values[2], values[3], values[4]),
+                DVec3::new(values[5], values[6], values[7]),
+            ));
+    }
+    Ok(trajectory)
+}
+
+/// integrates the particles for `DURATION` simulated seconds, one sample per reference
+/// time
+pub fn compare(
+    mut particles: Particles,
+    parameters: &SimulationParameters,
+    reference: &Trajectory,
+) -> Vec<Sample> {
+    let parameters = self::parameters(parameters);
+    let mut reference_particles = particles.clone();
+    let initial_energy = total_energy(&particles, &parameters);
+    let drift = |particles: &Particles| {
+        ((total_energy(particles, &parameters) - initial_energy) / initial_energy).abs()
+    };
+    let mut samples = Vec::new();
+    for time in 0..=DURATION {
+        if let Some(states) = reference.get(&time) {
+            let mut error: f64 = 0.0;
+            let mut relative_error: f64 = 0.0;
+            for (i, &(position, velocity)) in states.iter().enumerate() {
+                let distance = (particles.positions[i] - position).length();
+                error = error.max(distance);
+                relative_error = relative_error.max(distance / position.length().max(1.0));
+                reference_particles.positions[i] = position;
+                reference_particles.velocities[i] = velocity;
+            }
+            samples.push(Sample {
+                time,
+                error,
+                relative_error,
+                energy_drift: drift(&particles),
+                reference_energy_drift: drift(&reference_particles),
+            });
+        }
+        if time < DURATION {
+            crate::step(&mut particles, &parameters, |_, _| {});
+        }
+    }
+    samples
+}
@@ -0,0 +1,55 @@
+// direct sum of the accelerations, one invocation per receiver
+//
+// Positions are in units of the galaxy diameter and masses in units of the total mass,
+// so the values stay in the f32 range. The sums are scaled back on the CPU in f64.
+
+[[block]]
+struct Parameters {
+  count : u32;
+  // squared softening length
+  softening : f32;
+};
+
+// xyz is the position, w the mass, 0 for frozen particles
+[[block]]
+struct Bodies {
+  bodies : [[stride(16)]] array<vec4<f32> >;
+};
+
+[[block]]
+struct Accelerations {
+  accelerations : [[stride(16)]] array<vec4<f32> >;
+};
+
+[[group(0), binding(0)]] var<uniform> parameters : Parameters;
+[[group(0), binding(1)]] var<storage> input : [[access(read)]] Bodies;
+[[group(0), binding(2)]] var<storage> output : [[access(read_write)]] Accelerations;
+
+[[builtin(global_invocation_id)]] var global_id : vec3<u32>;
+
+[[stage(compute), workgroup_size(64)]]
+fn main() {
+  const i : u32 = global_id.x;
+  if (i >= parameters.count) {
+    return;
+  }
+  const position : vec3<f32> = input.bodies[i].xyz;
+  var acceleration : vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+  var j : u32 = 0u;
+  loop {
+    if (j >= parameters.count) {
+      break;
+    }
+    const body : vec4<f32> = input.bodies[j];
+    const offset : vec3<f32> = body.xyz - position;
+    const squared : f32 = dot(offset, offset) + parameters.softening;
+    // the receiver itself pulls with a zero offset, but without softening it must be skipped
+    if (squared > 0.0) {
+      acceleration = acceleration + offset * (body.w / (squared * sqrt(squared)));
+    }
+    continuing {
+      j = j + 1u;
+    }
+  }
+  output.accelerations[i] = vec4<f32>(acceleration, 0.0);
+}
@@ -0,0 +1,71 @@
+// the kicks and the drift of the leapfrog, one invocation per particle
+//
+// The units are those of gravity.wgsl with G = 1, so the time is in units of
+// sqrt(L³ / (G M)) and the velocities in units of L over that time.
+
+[[block]]
+struct Parameters {
+  count : u32;
+  // 1 when the first particle, the black hole, is held at the origin
+  pinned : u32;
+  // the whole step
+  dt : f32;
+};
+
+// xyz is the position, w the mass, 0 for frozen particles
+[[block]]
+struct Bodies {
+  bodies : [[stride(16)]] array<vec4<f32> >;
+};
+
+// xyz is the velocity, w is 1 for frozen particles
+[[block]]
+struct Velocities {
+  velocities : [[stride(16)]] array<vec4<f32> >;
+};
+
+[[block]]
+struct Accelerations {
+  accelerations : [[stride(16)]] array<vec4<f32> >;
+};
+
+[[group(0), binding(0)]] var<uniform> parameters : Parameters;
+[[group(0), binding(1)]] var<storage> bodies : [[access(read_write)]] Bodies;
+[[group(0), binding(2)]] var<storage> velocities : [[access(read_write)]] Velocities;
+[[group(0), binding(3)]] var<storage> input : [[access(read)]] Accelerations;
+
+[[builtin(global_invocation_id)]] var global_id : vec3<u32>;
+
+// half a kick from the accelerations
+[[stage(compute), workgroup_size(64)]]
+fn kick() {
+  const i : u32 = global_id.x;
+  if (i >= parameters.count) {
+    return;
+  }
+  const velocity : vec4<f32> = velocities.velocities[i];
+  if (velocity.w > 0.0) {
+    return;
+  }
+  const delta : vec3<f32> = input.accelerations[i].xyz * (0.5 * parameters.dt);
+  velocities.velocities[i] = vec4<f32>(velocity.xyz + delta, 0.0);
+}
+
+// a full drift with the velocities, the pinned black hole returns to the origin
+[[stage(compute), workgroup_size(64)]]
+fn drift() {
+  const i : u32 = global_id.x;
+  if (i >= parameters.count) {
+    return;
+  }
+  const velocity : vec4<f32> = velocities.velocities[i];
+  if (velocity.w > 0.0) {
+    return;
+  }
+  const body : vec4<f32> = bodies.bodies[i];
+  if (i == 0u && parameters.pinned == 1u) {
+    bodies.bodies[i] = vec4<f32>(0.0, 0.0, 0.0, body.w);
+    return;
+  }
+  bodies.bodies[i] = vec4<f32>(body.xyz + velocity.xyz * parameters.dt, body.w);
+}
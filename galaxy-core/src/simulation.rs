@@ -0,0 +1,243 @@
+//! Stars, parameters and clock in one value, convenient for tools and notebooks.
+//!
+//! ```
+//! use galaxy_core::Simulation;
+//!
+//! let mut simulation = Simulation::builder().number_of_stars(200).seed(42).build();
+//! simulation.step_n(10);
+//! assert_eq!(simulation.positions_flat().len(), 3 * 200);
+//! ```
+
+use crate::{
+    accretion, initial_conditions, merging, DVec3, Particles, Preset, SimulationParameters,
+    Snapshot, Star,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Default)]
+pub struct Simulation {
+    pub particles: Particles,
+    pub parameters: SimulationParameters,
+    /// simulated seconds since the start
+    pub time: f64,
+    /// physics steps since the start
+    pub steps: u64,
+}
+
+/// views the vectors as x, y, z of one after the other
+fn flat(vectors: &[DVec3]) -> &[f64] {
+    // DVec3 is three f64 with C layout
+    assert_eq!(std::mem::size_of::<DVec3>(), 3 * std::mem::size_of::<f64>());
+    unsafe { std::slice::from_raw_parts(vectors.as_ptr() as *const f64, 3 * vectors.len()) }
+}
+
+impl Simulation {
+    pub fn builder() -> SimulationBuilder {
+        SimulationBuilder::default()
+    }
+
+    pub fn new(particles: Particles, parameters: SimulationParameters) -> Self {
+        Self {
+            particles,
+            parameters,
+            time: 0.0,
+            steps: 0,
+        }
+    }
+
+    /// continues a saved run with the given parameters
+    pub fn from_snapshot(snapshot: &Snapshot, parameters: SimulationParameters) -> Self {
+        Self {
+            time: snapshot.time,
+            ..Self::new(snapshot.particles(), parameters)
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self.time, &self.particles)
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// one physics step, close encounters are ignored, escaped stars are frozen like in the
+    /// app, stars within the capture radius are swallowed by the black hole and touching
+    /// stars merge
+    pub fn step(&mut self) {
+        let previous = self.particles.positions.clone();
+        crate::step(&mut self.particles, &self.parameters, |_, _| {});
+        crate::escape(&mut self.particles, &previous, &self.parameters);
+        accretion::accrete(&mut self.particles, &self.parameters);
+        merging::merge_all(&mut self.particles, &self.parameters);
+        self.steps += 1;
+        self.time += self.parameters.physics_dt * self.parameters.time_factor;
+    }
+
+    pub fn step_n(&mut self, steps: u64) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    pub fn positions(&self) -> &[DVec3] {
+        &self.particles.positions
+    }
+
+    pub fn velocities(&self) -> &[DVec3] {
+        &self.particles.velocities
+    }
+
+    pub fn masses(&self) -> &[f64] {
+        &self.particles.masses
+    }
+
+    /// positions as one slice of x, y, z per star, the memory layout of an (n, 3) array
+    pub fn positions_flat(&self) -> &[f64] {
+        flat(&self.particles.positions)
+    }
+
+    /// velocities in the layout of `positions_flat`
+    pub fn velocities_flat(&self) -> &[f64] {
+        flat(&self.particles.velocities)
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn positions_view(&self) -> ndarray::ArrayView2<'_, f64> {
+        ndarray::ArrayView2::from_shape((self.len(), 3), self.positions_flat())
+            .expect("three values per star")
+    }
+
+    #[cfg(feature = "ndarray")]
+    pub fn velocities_view(&self) -> ndarray::ArrayView2<'_, f64> {
+        ndarray::ArrayView2::from_shape((self.len(), 3), self.velocities_flat())
+            .expect("three values per star")
+    }
+
+    /// the stars seen from above as white dots on black, `size` pixels wide and high
+    ///
+    /// The view spans twice the galaxy diameter, the black hole is red.
+    pub fn svg(&self, size: u32) -> Svg {
+        let size = size as f64;
+        let scale = size / (2.0 * self.parameters.galaxy_diameter);
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\">\
+             <rect width=\"100%\" height=\"100%\" fill=\"black\"/>",
+            size
+        );
+        for (i, p) in self.positions().iter().enumerate() {
+            let x = size / 2.0 + p.x * scale;
+            let y = size / 2.0 - p.y * scale;
+            let color = if i == 0 { "red" } else { "white" };
+            let _ = write!(
+                svg,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"1\" fill=\"{}\"/>",
+                x, y, color
+            );
+        }
+        svg.push_str("</svg>");
+        Svg(svg)
+    }
+}
+
+/// SVG image text, shown as an image when it is the last value of an evcxr notebook cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct Svg(pub String);
+
+impl Svg {
+    /// called by evcxr to display the value
+    pub fn evcxr_display(&self) {
+        println!(
+            "EVCXR_BEGIN_CONTENT image/svg+xml\n{}\nEVCXR_END_CONTENT",
+            self.0
+        );
+    }
+}
+
+/// set up a `Simulation` step by step, everything not set keeps its default
+#[derive(Debug, Clone, Default)]
+pub struct SimulationBuilder {
+    parameters: SimulationParameters,
+    seed: Option<u64>,
+    stars: Option<Vec<Star>>,
+}
+
+impl SimulationBuilder {
+    pub fn parameters(mut self, parameters: SimulationParameters) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// size of the generated galaxy, including the black hole
+    pub fn number_of_stars(mut self, count: usize) -> Self {
+        self.parameters.number_of_stars = count;
+        self
+    }
+
+    /// model of the generated galaxy
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.parameters.preset = preset;
+        self
+    }
+
+    /// seed of the generated galaxy, without one it is different every time
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// starts from these stars instead of a generated galaxy, the first one is the black hole
+    pub fn stars(mut self, stars: Vec<Star>) -> Self {
+        self.stars = Some(stars);
+        self
+    }
+
+    pub fn g(mut self, g: f64) -> Self {
+        self.parameters.g = g;
+        self
+    }
+
+    pub fn time_factor(mut self, time_factor: f64) -> Self {
+        self.parameters.time_factor = time_factor;
+        self
+    }
+
+    pub fn physics_dt(mut self, physics_dt: f64) -> Self {
+        self.parameters.physics_dt = physics_dt;
+        self
+    }
+
+    pub fn galaxy_diameter(mut self, diameter: f64) -> Self {
+        self.parameters.galaxy_diameter = diameter;
+        self
+    }
+
+    pub fn softening(mut self, softening: f64) -> Self {
+        self.parameters.softening = softening;
+        self
+    }
+
+    pub fn min_gravity_distance(mut self, distance: f64) -> Self {
+        self.parameters.min_gravity_distance = distance;
+        self
+    }
+
+    pub fn build(self) -> Simulation {
+        let stars = match self.stars {
+            Some(stars) => stars,
+            None => {
+                let mut rng = match self.seed {
+                    Some(seed) => StdRng::seed_from_u64(seed),
+                    None => StdRng::from_entropy(),
+                };
+                initial_conditions::generate(&mut rng, &self.parameters)
+            }
+        };
+        Simulation::new(stars.into_iter().collect(), self.parameters)
+    }
+}
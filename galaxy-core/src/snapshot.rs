@@ -0,0 +1,42 @@
+//! Full simulation state as JSON, for saving and resuming runs.
+
+use crate::{Error, Particles, Result, Star};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// simulated seconds since the start
+    pub time: f64,
+    /// all stars in particle order, the first one is the black hole
+    pub stars: Vec<Star>,
+}
+
+impl Snapshot {
+    pub fn new(time: f64, particles: &Particles) -> Self {
+        Self {
+            time,
+            stars: particles.stars().collect(),
+        }
+    }
+
+    pub fn particles(&self) -> Particles {
+        self.stars.iter().copied().collect()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string(self).map_err(|e| Error::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        fs::write(path, json).map_err(|e| Error::io(path, e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        serde_json::from_str(&json).map_err(|e| Error::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+    }
+}
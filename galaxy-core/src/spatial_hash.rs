@@ -0,0 +1,99 @@
+use glam::DVec3;
+use std::collections::HashMap;
+
+/// largest cell coordinate, far from the ends of i64 so the query ranges can't overflow
+const MAX_CELL: f64 = (1u64 << 62) as f64;
+
+/// uniform grid of particle indices for neighbor queries, rebuilt every step
+///
+/// Queries are exact: candidates from the overlapping cells are filtered by distance.
+/// Results come in an order that doesn't depend on the hash map iteration order, so
+/// runs with the same input report the same pairs in the same order.
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    cell_size: f64,
+    cells: HashMap<[i64; 3], Vec<usize>>,
+    /// inserted indices in ascending order
+    members: Vec<usize>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f64) -> Self {
+        assert!(cell_size > 0.0, "cell size must be positive");
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            members: Vec::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    /// the cell of `p`, far out the cells are clamped to the outermost ones
+    ///
+    /// Clamping never moves two positions further apart in cells, so the queries still find
+    /// every neighbor, the far cells just hold more candidates.
+    fn cell(&self, p: DVec3) -> [i64; 3] {
+        let c = p / self.cell_size;
+        [c.x, c.y, c.z].map(|c| c.floor().clamp(-MAX_CELL, MAX_CELL) as i64)
+    }
+
+    /// fills the grid with all positions whose `skip` flag is false, keeping the allocations
+    pub fn rebuild(&mut self, positions: &[DVec3], skip: &[bool]) {
+        profiling::scope!("spatial hash build");
+        for indices in self.cells.values_mut() {
+            indices.clear();
+        }
+        self.members.clear();
+        for (i, p) in positions.iter().enumerate() {
+            if !skip[i] {
+                self.members.push(i);
+                let cell = self.cell(*p);
+                self.cells.entry(cell).or_default().push(i);
+            }
+        }
+        self.cells.retain(|_, indices| !indices.is_empty());
+    }
+
+    /// calls `f` for every particle within `radius` of `center`
+    pub fn query(&self, positions: &[DVec3], center: DVec3, radius: f64, mut f: impl FnMut(usize)) {
+        let reach = (radius / self.cell_size).ceil() as i64;
+        let [cx, cy, cz] = self.cell(center);
+        let radius2 = radius * radius;
+        let range = |c: i64| c.saturating_sub(reach)..=c.saturating_add(reach);
+        for x in range(cx) {
+            for y in range(cy) {
+                for z in range(cz) {
+                    if let Some(indices) = self.cells.get(&[x, y, z]) {
+                        for &i in indices {
+                            if (positions[i] - center).length_squared() <= radius2 {
+                                f(i);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// calls `f` once for every pair closer than `radius`, lower index first
+    pub fn pairs(&self, positions: &[DVec3], radius: f64, mut f: impl FnMut(usize, usize)) {
+        profiling::scope!("spatial hash pairs");
+        for &i in &self.members {
+            self.query(positions, positions[i], radius, |j| {
+                if j > i {
+                    f(i, j);
+                }
+            });
+        }
+    }
+
+    /// number of particles within `radius` of `center`, for local density estimates
+    pub fn count_within(&self, positions: &[DVec3], center: DVec3, radius: f64) -> usize {
+        let mut count = 0;
+        self.query(positions, center, radius, |_| count += 1);
+        count
+    }
+}
@@ -0,0 +1,151 @@
+//! Compact binary trajectory files: the star positions of every physics step, for replays.
+//!
+//! The file starts with the 8 byte magic `GXTRAJ1\0`, followed by one frame per step: the
+//! simulated time as little endian `f64`, the star count as `u32` and the positions in
+//! meters as three `f32` per star, in particle order. Single precision keeps the file at 12
+//! bytes per star and frame, plenty for drawing them. A frame cut off at the end, e.g. by a
+//! crash while recording, is ignored when reading.
+
+use crate::{DVec3, Error, Particles, Result};
+use std::{
+    convert::TryInto,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 8] = b"GXTRAJ1\0";
+/// time and star count
+const FRAME_HEADER: u64 = 12;
+const BYTES_PER_STAR: u64 = 12;
+
+/// streams recorded frames to a trajectory file, call `finish` to flush the last ones
+pub struct TrajectoryWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    frames: u64,
+}
+
+impl TrajectoryWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path).map_err(|e| Error::io(path, e))?);
+        file.write_all(MAGIC).map_err(|e| Error::io(path, e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            frames: 0,
+        })
+    }
+
+    /// appends the positions of all particles at simulated time `time`
+    pub fn write_frame(&mut self, time: f64, particles: &Particles) -> Result<()> {
+        let mut frame =
+            Vec::with_capacity((FRAME_HEADER + BYTES_PER_STAR * particles.len() as u64) as usize);
+        frame.extend_from_slice(&time.to_le_bytes());
+        frame.extend_from_slice(&(particles.len() as u32).to_le_bytes());
+        for position in &particles.positions {
+            for coordinate in [position.x, position.y, position.z] {
+                frame.extend_from_slice(&(coordinate as f32).to_le_bytes());
+            }
+        }
+        self.file
+            .write_all(&frame)
+            .map_err(|e| Error::io(&self.path, e))?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// flushes the file and returns the number of frames
+    pub fn finish(mut self) -> Result<u64> {
+        self.file.flush().map_err(|e| Error::io(&self.path, e))?;
+        Ok(self.frames)
+    }
+}
+
+/// reads the frames of a trajectory file on demand, only their offsets and times are kept
+pub struct TrajectoryReader {
+    path: PathBuf,
+    file: BufReader<File>,
+    /// file offset and star count of every complete frame
+    frames: Vec<(u64, u32)>,
+    times: Vec<f64>,
+}
+
+impl TrajectoryReader {
+    /// opens a file and indexes its frames
+    pub fn open(path: &Path) -> Result<Self> {
+        let io_error = |e| Error::io(path, e);
+        let mut file = BufReader::new(File::open(path).map_err(io_error)?);
+        let size = file.get_ref().metadata().map_err(io_error)?.len();
+        let mut magic = [0; 8];
+        if file.read_exact(&mut magic).is_err() || &magic != MAGIC {
+            return Err(Error::Parse {
+                path: path.to_path_buf(),
+                message: "not a trajectory file".to_string(),
+            });
+        }
+
+        let mut frames = Vec::new();
+        let mut times = Vec::new();
+        let mut offset = MAGIC.len() as u64;
+        let mut header = [0; FRAME_HEADER as usize];
+        while offset + FRAME_HEADER <= size {
+            file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+            file.read_exact(&mut header).map_err(io_error)?;
+            let time = f64::from_le_bytes(header[..8].try_into().unwrap());
+            let count = u32::from_le_bytes(header[8..].try_into().unwrap());
+            let end = offset + FRAME_HEADER + BYTES_PER_STAR * count as u64;
+            if end > size {
+                break;
+            }
+            frames.push((offset, count));
+            times.push(time);
+            offset = end;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            frames,
+            times,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// simulated time of every frame
+    pub fn times(&self) -> &[f64] {
+        &self.times
+    }
+
+    /// the last frame at or before `time`, the first one for earlier times
+    pub fn index_at(&self, time: f64) -> usize {
+        self.times.partition_point(|&t| t <= time).saturating_sub(1)
+    }
+
+    /// positions of frame `index` in meters, in the particle order of the recording
+    pub fn frame(&mut self, index: usize) -> Result<Vec<DVec3>> {
+        let (offset, count) = self.frames[index];
+        let mut bytes = vec![0; (BYTES_PER_STAR * count as u64) as usize];
+        self.file
+            .seek(SeekFrom::Start(offset + FRAME_HEADER))
+            .and_then(|_| self.file.read_exact(&mut bytes))
+            .map_err(|e: io::Error| Error::io(&self.path, e))?;
+        let coordinate = |bytes: &[u8]| f32::from_le_bytes(bytes.try_into().unwrap()) as f64;
+        Ok(bytes
+            .chunks_exact(BYTES_PER_STAR as usize)
+            .map(|star| {
+                DVec3::new(
+                    coordinate(&star[0..4]),
+                    coordinate(&star[4..8]),
+                    coordinate(&star[8..12]),
+                )
+            })
+            .collect())
+    }
+}
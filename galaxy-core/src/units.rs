@@ -0,0 +1,162 @@
+//! Astronomical units and their conversion to the SI units of the solver.
+//!
+//! The solver works in meters, kilograms and seconds. The config file and the flags take
+//! lengths, masses and times either as plain SI numbers or as text with a unit, like
+//! `galaxy_diameter = "30 kpc"`, `black_hole_mass = "4e6 Msun"` or
+//! `time_factor = "0.5 Myr"`, and the UI shows them in the unit that fits their size.
+//!
+//! `Length`, `Mass`, `TimeSpan` and `Speed` hold the SI value, they parse with `FromStr`
+//! and serde and display with `{}` and an optional precision, 3 digits by default.
+
+use serde::Deserialize;
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// meters per astronomical unit
+pub const AU: f64 = 1.495_978_707e11;
+/// meters per parsec
+pub const PARSEC: f64 = 3.085_677_581e16;
+pub const KPC: f64 = 1e3 * PARSEC;
+pub const MPC: f64 = 1e6 * PARSEC;
+pub const SOLAR_MASS: f64 = 1.988_47e30;
+/// seconds of a Julian year
+pub const YEAR: f64 = 3.155_76e7;
+pub const MYR: f64 = 1e6 * YEAR;
+pub const GYR: f64 = 1e9 * YEAR;
+/// meters per second of a km/s
+pub const KM_PER_S: f64 = 1e3;
+
+/// the names of the units of a quantity and their SI values, the display picks the largest
+/// unit at most as large as the value, so they are sorted by size and the first one is SI
+type Units = &'static [(&'static str, f64)];
+
+const LENGTH_UNITS: Units = &[
+    ("m", 1.0),
+    ("km", 1e3),
+    ("au", AU),
+    ("pc", PARSEC),
+    ("kpc", KPC),
+    ("Mpc", MPC),
+];
+const MASS_UNITS: Units = &[("kg", 1.0), ("Msun", SOLAR_MASS)];
+const TIME_UNITS: Units = &[("s", 1.0), ("yr", YEAR), ("Myr", MYR), ("Gyr", GYR)];
+const SPEED_UNITS: Units = &[("m/s", 1.0), ("km/s", KM_PER_S)];
+
+/// other spellings of the units, for reading only
+const ALIASES: &[(&str, &str)] = &[("AU", "au"), ("M☉", "Msun"), ("Msol", "Msun")];
+
+/// the SI value of a number in SI or a number followed by one of `units`
+fn parse(text: &str, units: Units) -> Result<f64, String> {
+    let text = text.trim();
+    let split = text
+        .char_indices()
+        .find(|&(i, c)| {
+            // the exponent of 1e6 is part of the number
+            let exponent = matches!(c, 'e' | 'E')
+                && text[i + 1..].starts_with(|n: char| n.is_ascii_digit() || n == '-' || n == '+');
+            c.is_alphabetic() && !exponent
+        })
+        .map_or(text.len(), |(i, _)| i);
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' isn't a number with a unit", text))?;
+    let unit = unit.trim();
+    if unit.is_empty() {
+        return Ok(number);
+    }
+    let unit = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == unit)
+        .map_or(unit, |(_, name)| name);
+    units
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, factor)| number * factor)
+        .ok_or_else(|| {
+            let names: Vec<_> = units.iter().map(|(name, _)| *name).collect();
+            format!(
+                "unknown unit '{}', expected one of {}",
+                unit,
+                names.join(", ")
+            )
+        })
+}
+
+fn display(value: f64, units: Units, f: &mut fmt::Formatter) -> fmt::Result {
+    let (name, factor) = units
+        .iter()
+        .rev()
+        .find(|(_, factor)| value.abs() >= *factor)
+        .unwrap_or(&units[0]);
+    let scaled = value / factor;
+    let precision = f.precision().unwrap_or(3);
+    if scaled == 0.0 || (1e-2..1e4).contains(&scaled.abs()) {
+        write!(f, "{:.*} {}", precision, scaled, name)
+    } else {
+        write!(f, "{:.*e} {}", precision, scaled, name)
+    }
+}
+
+/// a number in SI or a text with a unit, as a config value
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+macro_rules! quantity {
+    ($(#[$meta:meta])* $name:ident, $units:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Deserialize)]
+        #[serde(try_from = "Value")]
+        pub struct $name(pub f64);
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(text: &str) -> Result<Self, String> {
+                parse(text, $units).map($name)
+            }
+        }
+
+        impl TryFrom<Value> for $name {
+            type Error = String;
+
+            fn try_from(value: Value) -> Result<Self, String> {
+                match value {
+                    Value::Number(value) => Ok($name(value)),
+                    Value::Text(text) => text.parse(),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                display(self.0, $units, f)
+            }
+        }
+    };
+}
+
+quantity!(
+    /// meters
+    Length,
+    LENGTH_UNITS
+);
+quantity!(
+    /// kilograms
+    Mass,
+    MASS_UNITS
+);
+quantity!(
+    /// seconds, also of the simulated time per second of `time_factor`
+    TimeSpan,
+    TIME_UNITS
+);
+quantity!(
+    /// meters per second
+    Speed,
+    SPEED_UNITS
+);
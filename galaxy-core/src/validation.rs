@@ -0,0 +1,120 @@
+//! Kepler validation: a circular two-body orbit integrated by the solver and compared with
+//! the analytic solution.
+//!
+//! A light body circles a star of one solar mass at one astronomical unit, the star in
+//! the place of the black hole, pinned to the center like it. One revolution is
+//! integrated with the `step` of the simulation, in the integrator, the opening angle and
+//! the adaptive timestep of the parameters, softening and the halo left out. The period
+//! is measured from the swept angle and compared with 2π √(r³ / GM), and the energy and
+//! the radius have to stay where they started.
+
+use crate::{
+    diagnostics::total_energy,
+    units::{AU, SOLAR_MASS},
+    DVec3, Particles, SimulationParameters, Star,
+};
+use std::f64::consts::PI;
+
+/// physics steps per revolution of `circular_orbit` without another count
+pub const DEFAULT_STEPS_PER_ORBIT: u64 = 1000;
+
+const RADIUS: f64 = AU;
+/// light enough that the pull on the pinned star doesn't matter
+const ORBITING_MASS: f64 = 1.0e3;
+
+/// how far the integrated orbit is from the Kepler solution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeplerError {
+    /// simulated seconds of one revolution
+    pub period: f64,
+    pub expected_period: f64,
+    /// relative change of the total energy after the revolution
+    pub energy_drift: f64,
+    /// largest relative deviation from the initial radius during the revolution
+    pub radius_drift: f64,
+}
+
+impl KeplerError {
+    /// relative error of the period
+    pub fn period_error(&self) -> f64 {
+        (self.period - self.expected_period).abs() / self.expected_period
+    }
+}
+
+/// the parameters of the orbit, one physics step per `1 / steps_per_orbit` revolution
+fn orbit_parameters(
+    parameters: &SimulationParameters,
+    steps_per_orbit: u64,
+) -> SimulationParameters {
+    let mu = parameters.g * SOLAR_MASS;
+    let step = 2.0 * PI * (RADIUS.powi(3) / mu).sqrt() / steps_per_orbit.max(1) as f64;
+    SimulationParameters {
+        number_of_stars: 2,
+        galaxy_diameter: f64::INFINITY,
+        physics_dt: 1.0,
+        time_factor: step,
+        softening: 0.0,
+        halo: None,
+        adaptive_timestep: parameters
+            .adaptive_timestep
+            .map(|adaptive| crate::AdaptiveTimestep {
+                accuracy: adaptive.accuracy,
+                min_dt: step / 100.0,
+                max_dt: step,
+            }),
+        ..parameters.clone()
+    }
+}
+
+/// integrates one revolution of the circular orbit in `steps_per_orbit` physics steps
+pub fn circular_orbit(parameters: &SimulationParameters, steps_per_orbit: u64) -> KeplerError {
+    let parameters = orbit_parameters(parameters, steps_per_orbit);
+    let mu = parameters.g * SOLAR_MASS;
+    let expected_period = 2.0 * PI * (RADIUS.powi(3) / mu).sqrt();
+    let mut particles: Particles = vec![
+        Star {
+            mass: SOLAR_MASS,
+            ..Default::default()
+        },
+        Star {
+            position: DVec3::new(RADIUS, 0.0, 0.0),
+            velocity: DVec3::new(0.0, (mu / RADIUS).sqrt(), 0.0),
+            mass: ORBITING_MASS,
+            ..Default::default()
+        },
+    ]
+    .into_iter()
+    .collect();
+    let initial_energy = total_energy(&particles, &parameters);
+    let angle = |p: &Particles| p.positions[1].y.atan2(p.positions[1].x);
+
+    let dt = parameters.physics_dt * parameters.time_factor;
+    let mut swept = 0.0;
+    let mut time = 0.0;
+    let mut radius_drift: f64 = 0.0;
+    // a bad integrator may never close the circle
+    while time < 2.0 * expected_period {
+        let before = angle(&particles);
+        crate::step(&mut particles, &parameters, |_, _| {});
+        time += dt;
+        radius_drift = radius_drift.max((particles.positions[1].length() - RADIUS).abs() / RADIUS);
+        let mut delta = angle(&particles) - before;
+        if delta < -PI {
+            delta += 2.0 * PI;
+        }
+        if swept + delta >= 2.0 * PI {
+            // the moment the circle was closed, between the last two steps
+            time -= (1.0 - (2.0 * PI - swept) / delta) * dt;
+            break;
+        }
+        swept += delta;
+    }
+
+    KeplerError {
+        period: time,
+        expected_period,
+        energy_drift: ((total_energy(&particles, &parameters) - initial_energy) / initial_energy)
+            .abs(),
+        radius_drift,
+    }
+}
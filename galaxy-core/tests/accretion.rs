@@ -0,0 +1,59 @@
+use galaxy_core::{accretion, DVec3, Particles, Simulation, SimulationParameters, Star};
+
+fn star(x: f64, mass: f64) -> Star {
+    Star {
+        position: DVec3::new(x, 0.0, 0.0),
+        mass,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn stars_inside_the_capture_radius_join_the_black_hole() {
+    let parameters = SimulationParameters {
+        capture_radius: 1.0e11,
+        ..Default::default()
+    };
+    let mut particles: Particles = vec![
+        star(0.0, 1.0e33),
+        star(5.0e10, 1.0e30),
+        star(2.0e11, 2.0e30),
+        star(-9.0e10, 3.0e30),
+    ]
+    .into_iter()
+    .collect();
+    particles.frozen[3] = true;
+    assert_eq!(accretion::captured(&particles, &parameters), [1]);
+
+    particles.frozen[3] = false;
+    assert_eq!(accretion::captured(&particles, &parameters), [3, 1]);
+    let far = particles.ids[2];
+    let swallowed = accretion::accrete(&mut particles, &parameters);
+    assert_eq!(swallowed.len(), 2);
+    assert_eq!(particles.len(), 2);
+    assert_eq!(particles.ids[1], far);
+    assert_eq!(particles.masses[0], 1.0e33 + 4.0e30);
+
+    let off = SimulationParameters::default();
+    assert!(accretion::captured(&particles, &off).is_empty());
+}
+
+#[test]
+fn simulation_steps_swallow_falling_stars() {
+    let parameters = SimulationParameters {
+        capture_radius: 1.0e11,
+        ..Default::default()
+    };
+    let mut falling = star(3.0e11, 1.0e30);
+    falling.velocity = DVec3::new(-1.0e5, 0.0, 0.0);
+    let mut simulation = Simulation::new(
+        vec![star(0.0, 1.0e33), falling, star(8.0e12, 1.0e30)]
+            .into_iter()
+            .collect(),
+        parameters,
+    );
+    simulation.step_n(200);
+    assert_eq!(simulation.len(), 2);
+    let mass: f64 = simulation.masses().iter().sum();
+    assert_eq!(mass, 1.0e33 + 2.0e30);
+}
@@ -0,0 +1,86 @@
+use galaxy_core::{alembic::PointCache, initial_conditions, SimulationParameters};
+use rand::{rngs::StdRng, SeedableRng};
+use std::convert::TryInto;
+
+const DATA: u64 = 1 << 63;
+
+/// just enough of an Ogawa reader to walk the groups
+struct Ogawa(Vec<u8>);
+
+impl Ogawa {
+    fn u64_at(&self, offset: u64) -> u64 {
+        let offset = offset as usize;
+        u64::from_le_bytes(self.0[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn children(&self, group: u64) -> Vec<u64> {
+        assert_eq!(group & DATA, 0, "expected a group");
+        let count = self.u64_at(group);
+        (0..count)
+            .map(|i| self.u64_at(group + 8 * (1 + i)))
+            .collect()
+    }
+
+    fn data(&self, child: u64) -> &[u8] {
+        assert_ne!(child & DATA, 0, "expected data");
+        let offset = child & !DATA;
+        if offset == 0 {
+            return &[];
+        }
+        let size = self.u64_at(offset) as usize;
+        let start = offset as usize + 8;
+        &self.0[start..start + size]
+    }
+}
+
+#[test]
+fn positions_can_be_read_back() {
+    let parameters = SimulationParameters {
+        number_of_stars: 30,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    let scale = 1000.0 / parameters.galaxy_diameter;
+    let path = std::env::temp_dir().join(format!("galaxy-{}.abc", std::process::id()));
+    let mut cache = PointCache::create(&path, 24.0, scale).unwrap();
+    let mut frames = Vec::new();
+    for _ in 0..3 {
+        cache.write_frame(&particles).unwrap();
+        frames.push(particles.positions.clone());
+        galaxy_core::step(&mut particles, &parameters, |_, _| {});
+    }
+    assert_eq!(cache.finish().unwrap(), 3);
+    let file = Ogawa(std::fs::read(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(&file.0[..8], b"Ogawa\xff\x00\x01");
+    let root = file.children(file.u64_at(8));
+    assert_eq!(root.len(), 6);
+    let top = file.children(root[2]);
+    let headers = file.data(*top.last().unwrap());
+    assert_eq!(&headers[4..10], b"galaxy");
+
+    let points = file.children(top[1]);
+    let properties = file.children(points[0]);
+    let geom = file.children(properties[0]);
+    let headers = String::from_utf8_lossy(file.data(geom[3]));
+    assert!(headers.contains(".selfBnds") && headers.contains("id"));
+    let positions = file.children(geom[1]);
+    assert_eq!(positions.len(), 2 * frames.len());
+    for (i, expected) in frames.iter().enumerate() {
+        // 16 bytes of key before the floats
+        let values: Vec<f32> = file.data(positions[2 * i])[16..]
+            .chunks_exact(4)
+            .map(|v| f32::from_le_bytes(v.try_into().unwrap()))
+            .collect();
+        let expected: Vec<f32> = expected
+            .iter()
+            .flat_map(|p| [p.x, p.y, p.z])
+            .map(|v| (v * scale) as f32)
+            .collect();
+        assert_eq!(values, expected);
+    }
+}
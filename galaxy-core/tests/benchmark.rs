@@ -0,0 +1,74 @@
+use galaxy_core::{
+    benchmark::{self, timed_step, Benchmark, Format},
+    initial_conditions, Particles, SimulationParameters,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+fn galaxy() -> (Particles, SimulationParameters) {
+    let parameters = SimulationParameters {
+        number_of_stars: 200,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(5);
+    let particles = initial_conditions::generate(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    (particles, parameters)
+}
+
+#[test]
+fn parses_the_bench_settings() {
+    assert_eq!(
+        "steps=50 stars=10000".parse::<Benchmark>().unwrap(),
+        Benchmark {
+            steps: 50,
+            stars: Some(10000)
+        }
+    );
+    assert_eq!("".parse::<Benchmark>().unwrap(), Benchmark::default());
+    assert_eq!(
+        "stars=3,steps=7".parse::<Benchmark>().unwrap(),
+        Benchmark {
+            steps: 7,
+            stars: Some(3)
+        }
+    );
+    assert!("steps=0".parse::<Benchmark>().is_err());
+    assert!("steps=many".parse::<Benchmark>().is_err());
+    assert!("speed=2".parse::<Benchmark>().is_err());
+    assert_eq!("csv".parse::<Format>().unwrap(), Format::Csv);
+    assert!("xml".parse::<Format>().is_err());
+}
+
+#[test]
+fn timed_steps_step_like_the_solver() {
+    let (mut timed, parameters) = galaxy();
+    let mut plain = timed.clone();
+    for _ in 0..3 {
+        timed_step(&mut timed, &parameters, |_, _| {});
+        galaxy_core::step(&mut plain, &parameters, |_, _| {});
+    }
+    assert_eq!(timed.positions, plain.positions);
+    assert_eq!(timed.velocities, plain.velocities);
+}
+
+#[test]
+fn prints_json_and_csv() {
+    let (mut particles, parameters) = galaxy();
+    let timings = benchmark::run(&mut particles, &parameters, 4);
+    assert_eq!(timings.steps, 4);
+    assert_eq!(timings.stars, particles.len());
+    assert!(timings.gravity > 0.0 && timings.gravity + timings.integration <= timings.total);
+
+    let json: serde_json::Value = serde_json::from_str(&timings.format(Format::Json)).unwrap();
+    assert_eq!(json["integrator"], "leapfrog");
+    assert_eq!(json["steps"], 4);
+    let csv = timings.format(Format::Csv);
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(
+        lines[0].starts_with("stars,steps,integrator,"),
+        "{}",
+        lines[0]
+    );
+}
@@ -0,0 +1,82 @@
+use galaxy_core::{
+    catalog::{CatalogConfig, CatalogFormat},
+    units::{PARSEC, SOLAR_MASS, YEAR},
+};
+
+fn write(name: &str, text: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("galaxy-{}-{}.csv", name, std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * a.abs().max(b.abs())
+}
+
+#[test]
+fn hyg_rows_become_stars_after_the_sun() {
+    let path = write(
+        "hyg",
+        "\"id\",\"proper\",\"dist\",\"lum\",\"x\",\"y\",\"z\",\"vx\",\"vy\",\"vz\"\n\
+         0,\"Sol\",0,1,0,0,0,0,0,0\n\
+         1,\"Far, far away\",3,16,1,2,2,1e-6,0,0\n\
+         2,,,,,,,,,\n\
+         3,,100000,2,100000,0,0,0,0,0\n",
+    );
+    let catalog = CatalogConfig {
+        max_distance: Some(1000.0),
+        ..CatalogConfig::new(&path)
+    };
+    let stars = catalog.load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // the Sun of the file is replaced by the one in front, the empty and far rows are dropped
+    assert_eq!(stars.len(), 2);
+    assert_eq!(stars[0].mass, SOLAR_MASS);
+    assert_eq!(stars[0].position.length(), 0.0);
+    let star = stars[1];
+    assert_eq!(star.position.x, PARSEC);
+    assert_eq!(star.position.z, 2.0 * PARSEC);
+    assert!(close(star.velocity.x, 1e-6 * PARSEC / YEAR));
+    // 16 solar luminosities are 2.2 solar masses
+    assert!(close(star.mass, 16f64.powf(1.0 / 3.5) * SOLAR_MASS));
+}
+
+#[test]
+fn gaia_rows_are_converted_from_the_sky() {
+    let path = write(
+        "gaia",
+        "source_id,ra,dec,parallax,pmra,pmdec,radial_velocity,mass_flame\n\
+         1,90,0,100,1000,0,5,0.5\n\
+         2,0,0,-1,0,0,0,1\n",
+    );
+    let catalog = CatalogConfig {
+        format: CatalogFormat::Gaia,
+        mass: Some("mass_flame".to_string()),
+        ..CatalogConfig::new(&path)
+    };
+    let stars = catalog.load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // the negative parallax has no distance
+    assert_eq!(stars.len(), 2);
+    let star = stars[1];
+    // 100 mas are 10 parsecs, along y at a right ascension of 90 degrees
+    assert!(close(star.position.y, 10.0 * PARSEC));
+    assert!(star.position.x.abs() < 1e-6 * PARSEC);
+    // 1 arcsecond per year at 10 parsecs is 47.4 km/s towards the east, -x here
+    assert!(close(star.velocity.x, -47_404.7));
+    assert!(close(star.velocity.y, 5_000.0));
+    assert_eq!(star.mass, 0.5 * SOLAR_MASS);
+
+    // a configured column must exist
+    let path = write("gaia-missing", "ra,dec,parallax\n1,2,3\n");
+    let error = CatalogConfig {
+        mass: Some("mass".to_string()),
+        ..CatalogConfig::new(&path)
+    }
+    .load()
+    .unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(error.to_string().contains("no column 'mass'"));
+}
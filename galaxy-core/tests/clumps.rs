@@ -0,0 +1,40 @@
+use galaxy_core::{clumps::friends_of_friends, DVec3, Particles, Star};
+
+fn particles(positions: &[[f64; 3]]) -> Particles {
+    positions
+        .iter()
+        .map(|&[x, y, z]| Star {
+            position: DVec3::new(x, y, z),
+            mass: 1.0,
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[test]
+fn chains_of_friends_form_one_clump() {
+    // a chain of three, a pair far away and a loner
+    let p = particles(&[
+        [0.0, 0.0, 0.0],
+        [100.0, 0.0, 0.0],
+        [0.9, 0.0, 0.0],
+        [1.8, 0.0, 0.0],
+        [100.5, 0.0, 0.0],
+        [50.0, 50.0, 0.0],
+    ]);
+    let clumps = friends_of_friends(&p, 1.0, 2);
+    assert_eq!(clumps.sizes, vec![3, 2]);
+    assert_eq!(
+        clumps.of_particle,
+        vec![Some(0), Some(1), Some(0), Some(0), Some(1), None]
+    );
+}
+
+#[test]
+fn frozen_stars_link_nothing() {
+    let mut p = particles(&[[0.0, 0.0, 0.0], [0.9, 0.0, 0.0], [1.8, 0.0, 0.0]]);
+    p.frozen[1] = true;
+    let clumps = friends_of_friends(&p, 1.0, 1);
+    assert_eq!(clumps.sizes, vec![1, 1]);
+    assert_eq!(clumps.of_particle, vec![Some(0), None, Some(1)]);
+}
@@ -0,0 +1,156 @@
+//! Reads config files onto the simulation parameters.
+
+use galaxy_core::{
+    units::{KM_PER_S, KPC, SOLAR_MASS},
+    AdaptiveTimestep, Integrator, Simulation, SimulationConfig, SimulationParameters,
+};
+
+#[test]
+fn keys_override_the_defaults() {
+    let config = SimulationConfig::parse(
+        r#"
+        number_of_stars = 3000
+        softening = 5e10
+        integrator = "rk4"
+        tick_rate = 120
+        seed = 7
+
+        [adaptive]
+        accuracy = 0.05
+
+        [three_body]
+        primary_mass = "2 Msun"
+        mass_ratio = 0.01
+        "#,
+    )
+    .unwrap();
+    let mut parameters = SimulationParameters::default();
+    config.apply(&mut parameters);
+    let defaults = SimulationParameters::default();
+    assert_eq!(parameters.number_of_stars, 3000);
+    assert_eq!(parameters.softening, 5e10);
+    assert_eq!(parameters.integrator, Integrator::Rk4);
+    assert_eq!(parameters.physics_dt, 1.0 / 120.0);
+    assert_eq!(config.seed, Some(7));
+    assert_eq!(parameters.time_factor, defaults.time_factor);
+    let adaptive = parameters.adaptive_timestep.unwrap();
+    let step = parameters.physics_dt * parameters.time_factor;
+    assert_eq!(adaptive.accuracy, 0.05);
+    assert_eq!(adaptive.max_dt, step);
+    assert_eq!(parameters.three_body.primary_mass, 2.0 * SOLAR_MASS);
+    assert_eq!(parameters.three_body.mass_ratio, 0.01);
+    assert_eq!(
+        parameters.three_body.separation,
+        defaults.three_body.separation
+    );
+}
+
+#[test]
+fn an_empty_file_keeps_the_defaults() {
+    let mut parameters = SimulationParameters::default();
+    SimulationConfig::parse("").unwrap().apply(&mut parameters);
+    assert_eq!(
+        format!("{:?}", parameters),
+        format!("{:?}", SimulationParameters::default())
+    );
+}
+
+#[test]
+fn mistakes_are_errors() {
+    assert!(SimulationConfig::parse("number_of_starz = 10").is_err());
+    assert!(SimulationConfig::parse("integrator = \"verlet\"").is_err());
+    assert!(SimulationConfig::parse("tick_rate = 0.0").is_err());
+    assert!(SimulationConfig::parse("time_factor = \"fast\"").is_err());
+    for adaptive in [
+        "accuracy = 0.0",
+        "accuracy = 0.1\nmin_dt = 0.0",
+        "accuracy = 0.1\nmax_dt = -1.0",
+        "accuracy = 0.1\nmin_dt = 2.0\nmax_dt = 1.0",
+    ] {
+        let text = format!("[adaptive]\n{}", adaptive);
+        assert!(SimulationConfig::parse(&text).is_err(), "{}", text);
+    }
+}
+
+#[test]
+fn adaptive_defaults_keep_the_bounds_in_order() {
+    let step = 100.0;
+    let only_min = AdaptiveTimestep::with_defaults(0.1, Some(500.0), None, step);
+    assert_eq!((only_min.min_dt, only_min.max_dt), (500.0, 500.0));
+    let only_max = AdaptiveTimestep::with_defaults(0.1, None, Some(0.5), step);
+    assert_eq!((only_max.min_dt, only_max.max_dt), (0.5, 0.5));
+    let neither = AdaptiveTimestep::with_defaults(0.1, None, None, step);
+    assert_eq!((neither.min_dt, neither.max_dt), (1.0, 100.0));
+}
+
+#[test]
+fn galaxies_are_tables_with_units() {
+    let config = SimulationConfig::parse(
+        r#"
+        black_hole_mass = "1e6 Msun"
+
+        [[galaxies]]
+        center = ["-20 kpc", 0, 0]
+        velocity = ["100 km/s", 0, 0]
+        stars = 200
+        radius = "10 kpc"
+
+        [[galaxies]]
+        center = ["20 kpc", "5 kpc", 0]
+        stars = 100
+        radius = "6 kpc"
+        spin_axis = [0, 1, 1]
+        black_hole_mass = 0
+        "#,
+    )
+    .unwrap();
+    let mut parameters = SimulationParameters::default();
+    config.apply(&mut parameters);
+    let galaxies = &parameters.galaxies;
+    assert_eq!(galaxies.len(), 2);
+    assert_eq!(galaxies[0].center.x, -20.0 * KPC);
+    assert_eq!(galaxies[0].velocity.x, 100.0 * KM_PER_S);
+    assert_eq!(galaxies[0].black_hole_mass, 1e6 * SOLAR_MASS);
+    assert_eq!(galaxies[0].spin_axis, galaxy_core::DVec3::Z);
+    assert_eq!(galaxies[1].radius, 6.0 * KPC);
+    assert_eq!(galaxies[1].black_hole_mass, 0.0);
+    assert_eq!(parameters.number_of_stars, 1 + 201 + 101);
+    // the first disk reaches farthest, 20 kpc out and 10 kpc wide
+    assert_eq!(parameters.galaxy_diameter, 20.0 * KPC + 10.0 * KPC);
+}
+
+#[test]
+fn galaxies_at_kpc_stay_apart_while_stepping() {
+    let config = SimulationConfig::parse(
+        r#"
+        black_hole_mass = "1e8 Msun"
+        star_mass_from = "1e5 Msun"
+        star_mass_to = "2e5 Msun"
+        time_factor = "1 Myr"
+
+        [[galaxies]]
+        center = ["-20 kpc", 0, 0]
+        stars = 50
+        radius = "5 kpc"
+
+        [[galaxies]]
+        center = ["20 kpc", 0, 0]
+        stars = 50
+        radius = "5 kpc"
+        "#,
+    )
+    .unwrap();
+    let mut parameters = SimulationParameters::default();
+    config.apply(&mut parameters);
+    let mut simulation = Simulation::builder().parameters(parameters).seed(3).build();
+    simulation.step_n(20);
+    let positions = simulation.positions();
+    let (first, second) = (positions[1], positions[52]);
+    assert!(
+        first.distance(second) > 35.0 * KPC,
+        "{} kpc apart",
+        first.distance(second) / KPC
+    );
+    let escape_radius = simulation.parameters.escape_radius * simulation.parameters.galaxy_diameter;
+    assert!(positions.iter().all(|p| p.length() < escape_radius));
+}
@@ -0,0 +1,166 @@
+use galaxy_core::{
+    distributed::Node, initial_conditions, AdaptiveTimestep, Integrator, Particles,
+    SimulationParameters,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    net::{SocketAddr, TcpListener},
+    thread,
+};
+
+const NODES: usize = 3;
+const STEPS: usize = 20;
+
+fn free_addresses(count: usize) -> Vec<SocketAddr> {
+    let listeners: Vec<_> = (0..count)
+        .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+        .collect();
+    listeners
+        .iter()
+        .map(|listener| listener.local_addr().unwrap())
+        .collect()
+}
+
+/// runs the same stars on `NODES` nodes and in one process, the nodes return their
+/// particles or the error of their first step
+fn run(parameters: SimulationParameters) -> (Particles, Vec<galaxy_core::Result<Particles>>) {
+    let mut rng = StdRng::seed_from_u64(3);
+    let stars = initial_conditions::random_disk(&mut rng, &parameters);
+
+    let mut expected: Particles = stars.iter().copied().collect();
+    for _ in 0..STEPS {
+        galaxy_core::step(&mut expected, &parameters, |_, _| {});
+    }
+
+    let addresses = free_addresses(NODES);
+    let nodes: Vec<_> = (0..NODES)
+        .map(|rank| {
+            let addresses = addresses.clone();
+            let parameters = parameters.clone();
+            // only node 0 knows the initial conditions
+            let stars = if rank == 0 { stars.clone() } else { Vec::new() };
+            thread::spawn(move || {
+                let mut node = Node::connect(rank, &addresses)?;
+                let mut particles: Particles = node.share_stars(stars)?.into_iter().collect();
+                for _ in 0..STEPS {
+                    node.step(&mut particles, &parameters, |_, _| {})?;
+                }
+                Ok(particles)
+            })
+        })
+        .collect();
+    let nodes = nodes.into_iter().map(|node| node.join().unwrap()).collect();
+    (expected, nodes)
+}
+
+/// every node holds the black hole and its own block of the stars, together they are the
+/// stars of the single process
+fn assert_nodes_match(parameters: SimulationParameters) {
+    let (expected, nodes) = run(parameters.clone());
+    let nodes: Vec<Particles> = nodes.into_iter().map(|node| node.unwrap()).collect();
+    let mut positions = vec![nodes[0].positions[0]];
+    let mut velocities = vec![nodes[0].velocities[0]];
+    for particles in &nodes {
+        assert!(particles.len() < expected.len() / 2, "{}", particles.len());
+        // the copies of the black hole move bitwise the same
+        assert_eq!(particles.positions[0], nodes[0].positions[0]);
+        assert_eq!(particles.velocities[0], nodes[0].velocities[0]);
+        positions.extend_from_slice(&particles.positions[1..]);
+        velocities.extend_from_slice(&particles.velocities[1..]);
+    }
+    assert_eq!(positions.len(), expected.len());
+    // the forces are summed in another order, to rounding and to the approximation of
+    // the tree
+    let tolerance = if parameters.opening_angle > 0.0 {
+        1e-3
+    } else {
+        1e-9
+    };
+    let length = parameters.galaxy_diameter;
+    for (p, e) in positions.iter().zip(&expected.positions) {
+        assert!((*p - *e).length() <= tolerance * length, "{} {}", p, e);
+    }
+    let speed = expected
+        .velocities
+        .iter()
+        .map(|v| v.length())
+        .fold(0.0, f64::max);
+    for (v, e) in velocities.iter().zip(&expected.velocities) {
+        assert!((*v - *e).length() <= tolerance * speed, "{} {}", v, e);
+    }
+}
+
+#[test]
+fn nodes_match_single_process() {
+    for integrator in [Integrator::Euler, Integrator::Leapfrog, Integrator::Rk4] {
+        assert_nodes_match(SimulationParameters {
+            number_of_stars: 50,
+            integrator,
+            ..Default::default()
+        });
+    }
+}
+
+#[test]
+fn nodes_match_single_process_over_the_tree_in_substeps() {
+    let step =
+        SimulationParameters::default().physics_dt * SimulationParameters::default().time_factor;
+    assert_nodes_match(SimulationParameters {
+        number_of_stars: 200,
+        opening_angle: 0.5,
+        adaptive_timestep: Some(AdaptiveTimestep {
+            accuracy: 0.001,
+            min_dt: step / 8.0,
+            max_dt: step,
+        }),
+        ..Default::default()
+    });
+}
+
+#[test]
+fn nodes_refuse_hermite() {
+    let (_, nodes) = run(SimulationParameters {
+        number_of_stars: 20,
+        integrator: Integrator::Hermite,
+        ..Default::default()
+    });
+    for particles in nodes {
+        let error = particles.unwrap_err().to_string();
+        assert!(error.contains("hermite"), "{}", error);
+    }
+}
+
+#[test]
+fn black_hole_changes_reach_all_nodes() {
+    let parameters = SimulationParameters {
+        number_of_stars: 30,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(4);
+    let stars = initial_conditions::random_disk(&mut rng, &parameters);
+    let swallowed = 1.0e30;
+    let expected = stars[0].mass + swallowed;
+    let addresses = free_addresses(NODES);
+    let nodes: Vec<_> = (0..NODES)
+        .map(|rank| {
+            let addresses = addresses.clone();
+            let parameters = parameters.clone();
+            let stars = if rank == 0 { stars.clone() } else { Vec::new() };
+            thread::spawn(move || -> galaxy_core::Result<Particles> {
+                let mut node = Node::connect(rank, &addresses)?;
+                let mut particles: Particles = node.share_stars(stars)?.into_iter().collect();
+                node.step(&mut particles, &parameters, |_, _| {})?;
+                // only node 1 sees the black hole swallow a star
+                if rank == 1 {
+                    particles.masses[0] += swallowed;
+                }
+                node.step(&mut particles, &parameters, |_, _| {})?;
+                Ok(particles)
+            })
+        })
+        .collect();
+    for node in nodes {
+        let particles = node.join().unwrap().unwrap();
+        assert_eq!(particles.masses[0], expected);
+    }
+}
@@ -0,0 +1,47 @@
+use galaxy_core::{
+    export::{CsvExport, CSV_HEADER},
+    initial_conditions, Particles, SimulationParameters,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn csv_rows_read_back_exactly() {
+    let parameters = SimulationParameters {
+        number_of_stars: 15,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(5);
+    let particles: Particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    let dir = std::env::temp_dir().join(format!("galaxy-export-{}", std::process::id()));
+    let export = CsvExport::new(&dir, 30).unwrap();
+    assert!(export.is_due(0) && export.is_due(60) && !export.is_due(45));
+
+    let path = export.write(60, 1.5e12, &particles).unwrap();
+    assert_eq!(path.file_name().unwrap(), "stars-00000060.csv");
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some(CSV_HEADER));
+    let rows: Vec<Vec<f64>> = lines
+        .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+        .collect();
+    assert_eq!(rows.len(), particles.len());
+    for (i, row) in rows.iter().enumerate() {
+        let (p, v) = (particles.positions[i], particles.velocities[i]);
+        let expected = [
+            particles.ids[i].0 as f64,
+            1.5e12,
+            p.x,
+            p.y,
+            p.z,
+            v.x,
+            v.y,
+            v.z,
+            particles.masses[i],
+        ];
+        assert_eq!(row.as_slice(), &expected);
+    }
+}
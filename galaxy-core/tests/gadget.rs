@@ -0,0 +1,156 @@
+use galaxy_core::{
+    gadget::{self, lookup3, UNIT_LENGTH, UNIT_MASS},
+    initial_conditions, Particles, SimulationParameters,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{collections::HashMap, convert::TryInto};
+
+#[test]
+fn lookup3_matches_the_reference() {
+    // test vectors from Bob Jenkins' lookup3.c
+    assert_eq!(lookup3(b""), 0xdeadbeef);
+    assert_eq!(lookup3(b"Four score and seven years ago"), 0x17770551);
+}
+
+/// just enough of an HDF5 reader to walk the objects the writer produces
+struct Hdf5(Vec<u8>);
+
+/// the messages of one object header by type, in order
+type Messages = Vec<(u8, Vec<u8>)>;
+
+impl Hdf5 {
+    fn u64_at(&self, offset: usize) -> u64 {
+        u64::from_le_bytes(self.0[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn root(&self) -> u64 {
+        assert_eq!(&self.0[..8], b"\x89HDF\r\n\x1a\n");
+        assert_eq!(self.0[8], 2, "superblock version");
+        let checksum = u32::from_le_bytes(self.0[44..48].try_into().unwrap());
+        assert_eq!(lookup3(&self.0[..44]), checksum, "superblock checksum");
+        assert_eq!(self.u64_at(28), self.0.len() as u64, "end of file address");
+        self.u64_at(36)
+    }
+
+    fn messages(&self, address: u64) -> Messages {
+        let start = address as usize;
+        assert_eq!(&self.0[start..start + 4], b"OHDR");
+        assert_eq!(self.0[start + 4], 2);
+        assert_eq!(self.0[start + 5], 0x02);
+        let size = u32::from_le_bytes(self.0[start + 6..start + 10].try_into().unwrap());
+        let end = start + 10 + size as usize;
+        let checksum = u32::from_le_bytes(self.0[end..end + 4].try_into().unwrap());
+        assert_eq!(
+            lookup3(&self.0[start..end]),
+            checksum,
+            "object header checksum"
+        );
+        let mut messages = Vec::new();
+        let mut offset = start + 10;
+        while offset < end {
+            let kind = self.0[offset];
+            let length = u16::from_le_bytes([self.0[offset + 1], self.0[offset + 2]]) as usize;
+            messages.push((kind, self.0[offset + 4..offset + 4 + length].to_vec()));
+            offset += 4 + length;
+        }
+        messages
+    }
+
+    /// the hard links of a group by name
+    fn links(&self, address: u64) -> HashMap<String, u64> {
+        let messages = self.messages(address);
+        assert!(messages.iter().any(|(kind, _)| *kind == 0x02), "link info");
+        messages
+            .iter()
+            .filter(|(kind, _)| *kind == 0x06)
+            .map(|(_, link)| {
+                let length = link[2] as usize;
+                let name = String::from_utf8(link[3..3 + length].to_vec()).unwrap();
+                let target = u64::from_le_bytes(link[3 + length..].try_into().unwrap());
+                (name, target)
+            })
+            .collect()
+    }
+
+    /// the dimensions and the raw bytes of a contiguous dataset
+    fn dataset(&self, address: u64) -> (Vec<u64>, &[u8]) {
+        let messages = self.messages(address);
+        let find = |kind| &messages.iter().find(|(k, _)| *k == kind).unwrap().1;
+        let space = find(0x01);
+        let dims = (0..space[1] as usize)
+            .map(|i| u64::from_le_bytes(space[4 + 8 * i..12 + 8 * i].try_into().unwrap()))
+            .collect();
+        let layout = find(0x08);
+        assert_eq!(&layout[..2], &[3, 1], "contiguous layout");
+        let data = u64::from_le_bytes(layout[2..10].try_into().unwrap()) as usize;
+        let size = u64::from_le_bytes(layout[10..18].try_into().unwrap()) as usize;
+        (dims, &self.0[data..data + size])
+    }
+
+    /// the values of the attributes of an object by name
+    fn attributes(&self, address: u64) -> HashMap<String, Vec<u8>> {
+        self.messages(address)
+            .iter()
+            .filter(|(kind, _)| *kind == 0x0c)
+            .map(|(_, a)| {
+                let name = u16::from_le_bytes([a[2], a[3]]) as usize;
+                let datatype = u16::from_le_bytes([a[4], a[5]]) as usize;
+                let dataspace = u16::from_le_bytes([a[6], a[7]]) as usize;
+                let name_text = String::from_utf8(a[9..9 + name - 1].to_vec()).unwrap();
+                (name_text, a[9 + name + datatype + dataspace..].to_vec())
+            })
+            .collect()
+    }
+}
+
+fn f64s(bytes: &[u8]) -> Vec<f64> {
+    bytes
+        .chunks_exact(8)
+        .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+        .collect()
+}
+
+#[test]
+fn snapshot_has_the_gadget_layout() {
+    let parameters = SimulationParameters {
+        number_of_stars: 12,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(9);
+    let particles: Particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    let file = Hdf5(gadget::encode(2.0e12, &particles));
+
+    let root = file.links(file.root());
+    let header = file.attributes(root["Header"]);
+    let counts: Vec<i32> = header["NumPart_ThisFile"]
+        .chunks_exact(4)
+        .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(counts, [0, 0, 0, 0, 11, 1]);
+    assert_eq!(f64s(&header["HubbleParam"]), [1.0]);
+
+    let stars = file.links(root["PartType4"]);
+    let (dims, coordinates) = file.dataset(stars["Coordinates"]);
+    assert_eq!(dims, [11, 3]);
+    for (read, position) in f64s(coordinates)
+        .chunks_exact(3)
+        .zip(&particles.positions[1..])
+    {
+        assert_eq!(
+            read,
+            [position.x, position.y, position.z].map(|c| c / UNIT_LENGTH)
+        );
+    }
+    let (dims, ids) = file.dataset(stars["ParticleIDs"]);
+    assert_eq!(dims, [11]);
+    assert_eq!(
+        u64::from_le_bytes(ids[..8].try_into().unwrap()),
+        particles.ids[1].0
+    );
+
+    let black_hole = file.links(root["PartType5"]);
+    let (_, masses) = file.dataset(black_hole["Masses"]);
+    assert_eq!(f64s(masses), [particles.masses[0] / UNIT_MASS]);
+}
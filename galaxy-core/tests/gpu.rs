@@ -0,0 +1,69 @@
+#![cfg(feature = "gpu")]
+
+use galaxy_core::{
+    gpu::GpuGravity, gravity, initial_conditions, Integrator, Particles, SimulationParameters,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+/// the GPU, or none on machines without one, where there is nothing to test
+fn gpu() -> Option<GpuGravity> {
+    match GpuGravity::new() {
+        Ok(gpu) => Some(gpu),
+        Err(e) => {
+            eprintln!("skipped: {}", e);
+            None
+        }
+    }
+}
+
+#[test]
+fn gpu_matches_the_direct_sum() {
+    let mut gpu = match gpu() {
+        Some(gpu) => gpu,
+        None => return,
+    };
+    let parameters = SimulationParameters {
+        number_of_stars: 1000,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut particles: Particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    particles.frozen[7] = true;
+    let mut exact = particles.clone();
+    gravity::direct_sum(&mut exact, &parameters);
+    gpu.accelerations(&mut particles, &parameters).unwrap();
+    assert_eq!(particles.accelerations[7], exact.accelerations[7]);
+    for (e, a) in exact.accelerations.iter().zip(&particles.accelerations) {
+        assert!((*e - *a).length() <= 1e-4 * e.length(), "{} {}", e, a);
+    }
+}
+
+#[test]
+fn gpu_leapfrog_matches_the_cpu() {
+    let mut gpu = match gpu() {
+        Some(gpu) => gpu,
+        None => return,
+    };
+    let parameters = SimulationParameters {
+        number_of_stars: 500,
+        integrator: Integrator::Leapfrog,
+        ..Default::default()
+    };
+    assert!(GpuGravity::resident(&parameters));
+    let mut rng = StdRng::seed_from_u64(6);
+    let mut particles: Particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    let mut exact = particles.clone();
+    for _ in 0..10 {
+        galaxy_core::step(&mut exact, &parameters, |_, _| {});
+    }
+    gpu.advance(&mut particles, &parameters, 10, |_, _| {})
+        .unwrap();
+    let tolerance = 1e-5 * parameters.galaxy_diameter;
+    for (e, a) in exact.positions.iter().zip(&particles.positions) {
+        assert!((*e - *a).length() <= tolerance, "{} {}", e, a);
+    }
+}
@@ -0,0 +1,123 @@
+use galaxy_core::{
+    diagnostics, gravity,
+    halo::{Halo, HaloProfile},
+    initial_conditions, DVec3, Integrator, Particles, Preset, SimulationParameters, Star,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+const G: f64 = 6.674e-11;
+
+fn halos() -> [Halo; 2] {
+    [HaloProfile::Nfw, HaloProfile::Isothermal].map(|profile| Halo {
+        profile,
+        scale_radius: 5.0e12,
+        mass: 1.0e33,
+    })
+}
+
+#[test]
+fn acceleration_is_the_gradient_of_the_potential() {
+    let position = DVec3::new(3.0e12, -4.0e12, 1.0e12);
+    let velocity = DVec3::new(2.0e3, 1.0e3, -5.0e2);
+    for halo in halos() {
+        let h = 1.0e6;
+        let gradient = DVec3::new(
+            halo.potential(position + DVec3::X * h, G) - halo.potential(position - DVec3::X * h, G),
+            halo.potential(position + DVec3::Y * h, G) - halo.potential(position - DVec3::Y * h, G),
+            halo.potential(position + DVec3::Z * h, G) - halo.potential(position - DVec3::Z * h, G),
+        ) / (2.0 * h);
+        let acceleration = halo.acceleration(position, G);
+        assert!((acceleration + gradient).length() < 1e-6 * acceleration.length());
+
+        // the jerk is the change of the acceleration along the velocity
+        let dt = 1.0e3;
+        let change = (halo.acceleration(position + velocity * dt, G)
+            - halo.acceleration(position - velocity * dt, G))
+            / (2.0 * dt);
+        let (same, jerk) = halo.acceleration_and_jerk(position, velocity, G);
+        assert_eq!(same, acceleration);
+        assert!((jerk - change).length() < 1e-6 * jerk.length());
+    }
+}
+
+#[test]
+fn mass_inside_the_scale_radius_is_the_halo_mass() {
+    for halo in halos() {
+        let position = DVec3::new(halo.scale_radius, 0.0, 0.0);
+        let inside = halo.acceleration(position, G).length() * halo.scale_radius.powi(2) / G;
+        assert!((inside / halo.mass - 1.0).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn isothermal_rotation_curve_is_flat() {
+    let halo = halos()[1];
+    let speed = |r: f64| (halo.acceleration(DVec3::X * r, G).length() * r).sqrt();
+    let far = speed(100.0 * halo.scale_radius);
+    assert!((speed(200.0 * halo.scale_radius) / far - 1.0).abs() < 0.01);
+}
+
+#[test]
+fn orbits_in_the_halo_conserve_energy() {
+    // Euler drifts by itself
+    for integrator in [Integrator::Leapfrog, Integrator::Rk4, Integrator::Hermite] {
+        for halo in halos() {
+            let parameters = SimulationParameters {
+                halo: Some(halo),
+                integrator,
+                // a quarter of an orbit in the 500 steps
+                time_factor: 2.5e8,
+                ..Default::default()
+            };
+            // a massless center and a light star on an eccentric orbit
+            let position = DVec3::new(halo.scale_radius, 0.0, 0.0);
+            let circular = (halo.acceleration(position, G).length() * position.x).sqrt();
+            let mut particles: Particles = vec![
+                Star::default(),
+                Star {
+                    position,
+                    velocity: DVec3::new(0.0, 0.7 * circular, 0.0),
+                    mass: 1.0,
+                    ..Default::default()
+                },
+            ]
+            .into_iter()
+            .collect();
+            gravity::accelerations(&mut particles, &parameters);
+            let start = diagnostics::total_energy(&particles, &parameters);
+            for _ in 0..500 {
+                galaxy_core::step(&mut particles, &parameters, |_, _| {});
+            }
+            let end = diagnostics::total_energy(&particles, &parameters);
+            assert!(
+                ((end - start) / start).abs() < 1e-3,
+                "{} {:?}: {} {}",
+                integrator.name(),
+                halo.profile,
+                start,
+                end
+            );
+        }
+    }
+}
+
+#[test]
+fn disks_rotate_with_the_halo() {
+    let halo = halos()[1];
+    let parameters = SimulationParameters {
+        number_of_stars: 500,
+        preset: Preset::Exponential,
+        halo: Some(halo),
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(31);
+    let stars = initial_conditions::generate(&mut rng, &parameters);
+    // far out the halo alone sets the speed
+    let outer = stars[1..]
+        .iter()
+        .max_by(|a, b| a.position.length().total_cmp(&b.position.length()))
+        .unwrap();
+    let r = outer.position.length();
+    let halo_speed = (halo.acceleration(outer.position, G).length() * r).sqrt();
+    assert!(outer.velocity.length() > 0.8 * halo_speed);
+}
@@ -0,0 +1,279 @@
+use galaxy_core::{
+    initial_conditions::{self, GalaxyDescriptor},
+    DVec3, Preset, SimulationParameters, Star,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::f64::consts::PI;
+
+/// 2 K / |W| of the stars, 1 in equilibrium
+fn virial_ratio(stars: &[Star], g: f64) -> f64 {
+    let kinetic: f64 = stars
+        .iter()
+        .map(|star| 0.5 * star.mass * star.velocity.length_squared())
+        .sum();
+    let mut potential = 0.0;
+    for (i, a) in stars.iter().enumerate() {
+        for b in &stars[i + 1..] {
+            potential -= g * a.mass * b.mass / (a.position - b.position).length();
+        }
+    }
+    2.0 * kinetic / -potential
+}
+
+fn stars(preset: Preset) -> (Vec<Star>, SimulationParameters) {
+    let parameters = SimulationParameters {
+        number_of_stars: 2000,
+        preset,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(17);
+    (
+        initial_conditions::generate(&mut rng, &parameters),
+        parameters,
+    )
+}
+
+#[test]
+fn presets_are_named() {
+    for preset in Preset::ALL {
+        assert_eq!(preset.name().parse(), Ok(preset));
+    }
+    assert!("ring".parse::<Preset>().is_err());
+}
+
+#[test]
+fn spheres_start_in_virial_equilibrium() {
+    for preset in [Preset::Plummer, Preset::King] {
+        let (stars, parameters) = stars(preset);
+        assert_eq!(stars.len(), parameters.number_of_stars);
+        assert_eq!(stars[0].position.length(), 0.0);
+        let ratio = virial_ratio(&stars, parameters.g);
+        assert!((ratio - 1.0).abs() < 0.1, "{}: {}", preset.name(), ratio);
+        // cut off at the diameter, up to the shift onto the center of mass
+        let radius = stars
+            .iter()
+            .map(|s| s.position.length())
+            .fold(0.0, f64::max);
+        assert!(radius < 1.1 * parameters.galaxy_diameter);
+    }
+}
+
+#[test]
+fn plummer_cluster_has_the_mass_and_stays_in_equilibrium() {
+    let parameters = SimulationParameters::default();
+    let mut rng = StdRng::seed_from_u64(5);
+    let mass = 1.0e39;
+    let stars = initial_conditions::plummer_cluster(&mut rng, 1000, 1.0e19, mass, &parameters);
+    assert_eq!(stars.len(), 1000);
+    let total: f64 = stars.iter().map(|star| star.mass).sum();
+    assert!((total / mass - 1.0).abs() < 1e-9);
+    let center = stars
+        .iter()
+        .fold(DVec3::ZERO, |sum, star| sum + star.position * star.mass)
+        / total;
+    assert!(center.length() < 1.0e16);
+    let ratio = virial_ratio(&stars, parameters.g);
+    assert!((ratio - 1.0).abs() < 0.15, "{}", ratio);
+}
+
+#[test]
+fn exponential_disk_rotates_on_circular_orbits() {
+    let (stars, parameters) = stars(Preset::Exponential);
+    let diameter = parameters.galaxy_diameter;
+    assert!(stars[1..]
+        .iter()
+        .all(|star| star.position.z.abs() < diameter / 4.0));
+    // counterclockwise seen from +z, apart from a few slow stars in the center
+    let rotating = stars[1..]
+        .iter()
+        .filter(|s| s.position.x * s.velocity.y - s.position.y * s.velocity.x > 0.0)
+        .count();
+    assert!(rotating > stars.len() * 95 / 100);
+    // a cold rotating disk has far more kinetic energy in rotation than a sphere needs
+    assert!(virial_ratio(&stars, parameters.g) > 0.5);
+}
+
+#[test]
+fn spiral_arms_follow_logarithmic_spirals() {
+    let mut parameters = SimulationParameters {
+        number_of_stars: 2000,
+        preset: Preset::Spiral,
+        ..Default::default()
+    };
+    parameters.spiral.bulge_ratio = 0.0;
+    parameters.spiral.disk_ratio = 0.0;
+    parameters.spiral.arm_width = 0.1;
+    let mut rng = StdRng::seed_from_u64(23);
+    let stars = initial_conditions::generate(&mut rng, &parameters);
+
+    // both arms are half a turn apart, so the phase along the spiral is a multiple of π
+    let spiral = parameters.spiral;
+    let bulge = parameters.galaxy_diameter * 0.05;
+    let winding = 1.0 / spiral.pitch_angle.to_radians().tan();
+    // far enough out that the shift onto the center of mass doesn't change the angles much
+    let outside: Vec<&Star> = stars[1..]
+        .iter()
+        .filter(|s| s.position.truncate().length() > 4.0 * bulge)
+        .collect();
+    let on_arm = outside
+        .iter()
+        .filter(|s| {
+            let p = s.position;
+            let along = (p.truncate().length() / bulge).ln() * winding;
+            let phase = (p.y.atan2(p.x) - along).rem_euclid(PI);
+            phase.min(PI - phase) < 0.3
+        })
+        .count();
+    assert!(
+        on_arm > outside.len() * 9 / 10,
+        "{} of {}",
+        on_arm,
+        outside.len()
+    );
+
+    let rotating = stars[1..]
+        .iter()
+        .filter(|s| s.position.x * s.velocity.y - s.position.y * s.velocity.x > 0.0)
+        .count();
+    assert!(rotating > stars.len() * 95 / 100);
+}
+
+#[test]
+fn collision_sets_two_disks_on_course() {
+    let mut parameters = SimulationParameters {
+        number_of_stars: 901,
+        preset: Preset::Collision,
+        black_hole_mass: 1e33,
+        ..Default::default()
+    };
+    parameters.collision.mass_ratio = 0.5;
+    let mut rng = StdRng::seed_from_u64(29);
+    let stars = initial_conditions::generate(&mut rng, &parameters);
+    assert_eq!(stars.len(), 901);
+    // the pinned center is massless, the galaxies bring their own black holes
+    assert_eq!(stars[0].mass, 0.0);
+    let (first, second) = (stars[1], stars[601]);
+    assert_eq!(first.mass, 1e33);
+    assert_eq!(second.mass, 0.5e33);
+
+    let encounter = parameters.collision;
+    let diameter = parameters.galaxy_diameter;
+    let offset = second.position - first.position;
+    assert!((offset.length() / diameter - encounter.separation).abs() < 0.05);
+    assert!((offset.y.abs() / diameter - encounter.impact_parameter).abs() < 0.05);
+    // heading for each other, at rest around the center
+    assert!(offset.dot(second.velocity - first.velocity) < 0.0);
+    let momentum = stars
+        .iter()
+        .fold(DVec3::default(), |p, star| p + star.velocity * star.mass);
+    let scale: f64 = stars.iter().map(|s| s.mass * s.velocity.length()).sum();
+    assert!(momentum.length() < 1e-9 * scale);
+}
+
+#[test]
+fn galaxies_start_at_their_centers_and_turn_around_their_axes() {
+    let descriptor = |center: DVec3, velocity, spin_axis| GalaxyDescriptor {
+        center,
+        velocity,
+        stars: 500,
+        tracers: 0,
+        radius: 1e13,
+        spin_axis,
+        black_hole_mass: 1e34,
+    };
+    let parameters = SimulationParameters {
+        galaxies: vec![
+            descriptor(
+                DVec3::new(-5e13, 0.0, 0.0),
+                DVec3::new(1e4, 0.0, 0.0),
+                DVec3::Z,
+            ),
+            descriptor(DVec3::new(5e13, 0.0, 0.0), DVec3::ZERO, DVec3::X),
+        ],
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(2);
+    let galaxies = initial_conditions::galaxies(&mut rng, &parameters);
+    assert_eq!(
+        galaxies.iter().map(Vec::len).collect::<Vec<_>>(),
+        [501, 501]
+    );
+    for (stars, galaxy) in galaxies.iter().zip(&parameters.galaxies) {
+        // the black hole rests in the center of its galaxy, the stars move around it
+        assert_eq!(stars[0].position, galaxy.center);
+        assert_eq!(stars[0].velocity, galaxy.velocity);
+        let spin: DVec3 = stars[1..]
+            .iter()
+            .map(|star| (star.position - galaxy.center).cross(star.velocity - galaxy.velocity))
+            .fold(DVec3::ZERO, |sum, l| sum + l);
+        assert!(spin.normalize().dot(galaxy.spin_axis) > 0.99, "{:?}", spin);
+    }
+
+    let stars = initial_conditions::generate(&mut rng, &parameters);
+    assert_eq!(stars.len(), 1 + 2 * 501);
+    assert_eq!(stars[0].mass, 0.0);
+}
+
+#[test]
+fn tracers_are_massless_and_circle_the_stars() {
+    let parameters = SimulationParameters {
+        number_of_stars: 300,
+        tracers: 200,
+        preset: Preset::Exponential,
+        black_hole_mass: 1e34,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(5);
+    let stars = initial_conditions::generate(&mut rng, &parameters);
+    assert_eq!(stars.len(), 500);
+    assert!(stars[1..300].iter().all(|star| star.mass > 0.0));
+    for tracer in &stars[300..] {
+        assert_eq!(tracer.mass, 0.0);
+        assert!(tracer.position.length() <= parameters.galaxy_diameter);
+        // cold, along the circle around the z axis
+        assert_eq!(tracer.velocity.z, 0.0);
+        let radial = tracer
+            .position
+            .truncate()
+            .normalize()
+            .dot(tracer.velocity.truncate());
+        assert!(radial.abs() < 1e-6 * tracer.velocity.length());
+        assert!(tracer.velocity.length() > 0.0);
+    }
+
+    let collision = SimulationParameters {
+        preset: Preset::Collision,
+        ..parameters
+    };
+    let stars = initial_conditions::generate(&mut rng, &collision);
+    assert_eq!(stars.len(), 500);
+    assert_eq!(
+        stars.iter().skip(1).filter(|star| star.mass == 0.0).count(),
+        200
+    );
+}
+
+#[test]
+fn three_body_test_particles_circle_around_the_orbit() {
+    let parameters = SimulationParameters {
+        number_of_stars: 503,
+        preset: Preset::ThreeBody,
+        ..Default::default()
+    };
+    let stars = initial_conditions::generate(&mut StdRng::seed_from_u64(6), &parameters);
+    assert_eq!(stars.len(), 503);
+    let bodies = parameters.three_body;
+    assert_eq!(stars[0].mass, 0.0);
+    assert_eq!(stars[1].mass, bodies.primary_mass);
+    assert_eq!(stars[2].mass, bodies.primary_mass * bodies.mass_ratio);
+    let momentum = stars[1].velocity * stars[1].mass + stars[2].velocity * stars[2].mass;
+    assert!(momentum.length() < 1e-9 * stars[1].mass * stars[1].velocity.length());
+    let d = bodies.separation * parameters.galaxy_diameter;
+    for star in &stars[3..] {
+        assert_eq!(star.mass, 0.0);
+        let r = star.position.length();
+        assert!((0.5 * d..1.5 * d).contains(&r));
+        assert_eq!(star.position.z, 0.0);
+        assert!(star.velocity.dot(star.position).abs() < 1e-6 * r * star.velocity.length());
+    }
+}
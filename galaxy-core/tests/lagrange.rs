@@ -0,0 +1,133 @@
+use galaxy_core::{
+    initial_conditions::{self, ThreeBody},
+    lagrange::RotatingFrame,
+    DVec3, Particles, Preset, SimulationParameters, Star,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::f64::consts::PI;
+
+/// Earth and Moon in units where G, the separation and the total mass are 1
+fn earth_moon() -> RotatingFrame {
+    let mu = 0.012_150_585;
+    let earth = (
+        DVec3::new(-mu, 0.0, 0.0),
+        DVec3::new(0.0, -mu, 0.0),
+        1.0 - mu,
+    );
+    let moon = (
+        DVec3::new(1.0 - mu, 0.0, 0.0),
+        DVec3::new(0.0, 1.0 - mu, 0.0),
+        mu,
+    );
+    RotatingFrame::new(moon, earth, 1.0).unwrap()
+}
+
+#[test]
+fn collinear_points_match_the_known_positions() {
+    let frame = earth_moon();
+    assert!((frame.omega - 1.0).abs() < 1e-12);
+    let points = frame.lagrange_points();
+    for (point, expected) in points.iter().zip(&[0.836_915, 1.155_682, -1.005_063]) {
+        assert!(
+            (point.x - expected).abs() < 1e-5,
+            "{} vs {}",
+            point.x,
+            expected
+        );
+        assert_eq!(point.y, 0.0);
+    }
+    let l4 = points[3];
+    let distance = |a: DVec3, b: DVec3| (a - b).length();
+    assert!((distance(l4, frame.primary) - 1.0).abs() < 1e-12);
+    assert!((distance(l4, frame.secondary) - 1.0).abs() < 1e-12);
+    assert!(l4.y > 0.0 && points[4].y < 0.0);
+}
+
+#[test]
+fn effective_potential_is_flat_at_the_lagrange_points() {
+    let frame = earth_moon();
+    let h = 1e-6;
+    for point in &frame.lagrange_points() {
+        for axis in &[DVec3::X, DVec3::Y] {
+            let slope = (frame.effective_potential(*point + *axis * h)
+                - frame.effective_potential(*point - *axis * h))
+                / (2.0 * h);
+            assert!(slope.abs() < 1e-6, "slope {} at {}", slope, point);
+        }
+    }
+}
+
+#[test]
+fn zero_velocity_curve_follows_its_level() {
+    let frame = earth_moon();
+    let level = frame.effective_potential(frame.lagrange_points()[0]) - 0.01;
+    let segments = frame.zero_velocity_curve(level, frame.center_of_mass, 1.5, 300);
+    assert!(!segments.is_empty());
+    for point in segments.iter().flatten() {
+        let error = frame.effective_potential(*point) - level;
+        assert!(error.abs() < 1e-2, "{} off at {}", error, point);
+    }
+}
+
+#[test]
+fn roche_lobe_touches_l1_and_is_close_to_the_tidal_radius() {
+    let frame = earth_moon();
+    let l1 = frame.lagrange_points()[0];
+    let towards_l1 = frame.roche_lobe_radius(l1 - frame.secondary, l1);
+    assert!((towards_l1 - (l1 - frame.secondary).length()).abs() < 1e-6);
+    // the lobe is flattened across the axis and a bit smaller than the Hill sphere there
+    let sideways = frame.roche_lobe_radius(DVec3::Y, l1);
+    let up = frame.roche_lobe_radius(DVec3::Z, l1);
+    let hill = frame.tidal_radius();
+    assert!(up < sideways && sideways < towards_l1);
+    assert!(sideways > 0.6 * hill && sideways < hill);
+}
+
+#[test]
+fn a_test_particle_at_l4_of_the_three_body_preset_stays_there() {
+    let mut parameters = SimulationParameters {
+        number_of_stars: 3,
+        preset: Preset::ThreeBody,
+        three_body: ThreeBody {
+            mass_ratio: 0.01,
+            ..Default::default()
+        },
+        softening: 0.0,
+        physics_dt: 1.0,
+        ..Default::default()
+    };
+    let stars = initial_conditions::generate(&mut StdRng::seed_from_u64(1), &parameters);
+    assert_eq!(stars.len(), 3);
+    let body = |star: &Star| (star.position, star.velocity, star.mass);
+    let frame = RotatingFrame::new(body(&stars[1]), body(&stars[2]), parameters.g).unwrap();
+    let d = parameters.three_body.separation * parameters.galaxy_diameter;
+    assert!((frame.separation() - d).abs() < 1e-9 * d);
+    assert!(frame.center_of_mass.length() < 1e-9 * d);
+    // the bodies circle at the rate of the frame
+    assert!((stars[2].velocity.y - frame.omega * stars[2].position.x).abs() < 1e-9);
+
+    let l4 = frame.lagrange_points()[3];
+    let mut particles: Particles = stars.into_iter().collect();
+    particles.push(Star {
+        position: l4,
+        velocity: frame.z.cross(l4 - frame.center_of_mass) * frame.omega,
+        ..Default::default()
+    });
+    let period = 2.0 * PI / frame.omega;
+    let steps = 2000;
+    parameters.time_factor = period / steps as f64;
+    for _ in 0..steps {
+        galaxy_core::step(&mut particles, &parameters, |_, _| {});
+    }
+    let body = |i: usize| {
+        (
+            particles.positions[i],
+            particles.velocities[i],
+            particles.masses[i],
+        )
+    };
+    let frame = RotatingFrame::new(body(1), body(2), parameters.g).unwrap();
+    assert!((frame.separation() - d).abs() < 1e-3 * d);
+    let drift = (particles.positions[3] - frame.lagrange_points()[3]).length();
+    assert!(drift < 0.01 * d, "{} of {}", drift, d);
+}
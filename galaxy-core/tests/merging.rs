@@ -0,0 +1,119 @@
+use galaxy_core::{merging, DVec3, Particles, Simulation, SimulationParameters, Star};
+
+fn star(x: f64, vx: f64, mass: f64) -> Star {
+    Star {
+        position: DVec3::new(x, 0.0, 0.0),
+        velocity: DVec3::new(vx, 0.0, 0.0),
+        mass,
+        ..Default::default()
+    }
+}
+
+/// 1 kg/m³ gives a star of 1e30 kg a radius of about 6.2e9 m
+const DENSITY: f64 = 1.0;
+
+#[test]
+fn radius_grows_with_the_cube_root_of_the_mass() {
+    let r = merging::radius(1.0e30, DENSITY);
+    assert!((r - 6.2035e9).abs() < 1.0e6, "{}", r);
+    assert!((merging::radius(8.0e30, DENSITY) / r - 2.0).abs() < 1e-12);
+}
+
+#[test]
+fn touching_stars_merge_conserving_mass_and_momentum() {
+    let parameters = SimulationParameters {
+        merge_density: DENSITY,
+        ..Default::default()
+    };
+    let mut particles: Particles = vec![
+        star(0.0, 0.0, 1.0e33),
+        star(1.0e12, 1.0e4, 1.0e30),
+        star(1.0e12 + 5.0e9, -2.0e4, 3.0e30),
+        star(5.0e12, 0.0, 1.0e30),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(merging::touching(&particles, &parameters), [(2, 1)]);
+
+    let far = particles.ids[3];
+    let heavier = particles.ids[2];
+    assert_eq!(merging::merge_all(&mut particles, &parameters), 1);
+    assert_eq!(particles.len(), 3);
+    let survivor = particles.index_of(heavier).unwrap();
+    assert_eq!(particles.masses[survivor], 4.0e30);
+    assert!((particles.velocities[survivor].x - -1.25e4).abs() < 1e-9);
+    // at the center of mass
+    assert!((particles.positions[survivor].x - (1.0e12 + 3.75e9)).abs() < 1.0);
+    assert!(particles.index_of(far).is_some());
+
+    let off = SimulationParameters::default();
+    assert!(merging::touching(&particles, &off).is_empty());
+}
+
+#[test]
+fn every_star_merges_once_per_step_and_the_black_hole_survives() {
+    let parameters = SimulationParameters {
+        merge_density: DENSITY,
+        ..Default::default()
+    };
+    // a light black hole touching a star and three stars in a row
+    let mut particles: Particles = vec![
+        star(0.0, 0.0, 1.0e29),
+        star(1.0e9, 0.0, 1.0e31),
+        star(1.0e12, 0.0, 1.0e30),
+        star(1.0e12 + 6.0e9, 0.0, 1.0e30),
+        star(1.0e12 + 1.2e10, 0.0, 1.0e30),
+    ]
+    .into_iter()
+    .collect();
+    let pairs = merging::touching(&particles, &parameters);
+    assert_eq!(pairs.len(), 2);
+    assert!(pairs.contains(&(0, 1)));
+    let mut seen: Vec<usize> = pairs.iter().flat_map(|&(a, b)| [a, b]).collect();
+    seen.sort_unstable();
+    seen.dedup();
+    assert_eq!(seen.len(), 4);
+
+    merging::merge_all(&mut particles, &parameters);
+    assert_eq!(particles.len(), 3);
+    assert_eq!(particles.positions[0], DVec3::default());
+    assert_eq!(particles.masses[0], 1.0e29 + 1.0e31);
+}
+
+#[test]
+fn simulation_steps_merge_colliding_stars() {
+    let parameters = SimulationParameters {
+        merge_density: DENSITY,
+        ..Default::default()
+    };
+    let mut simulation = Simulation::new(
+        vec![
+            star(0.0, 0.0, 1.0e33),
+            star(4.0e12, 1.0e5, 1.0e30),
+            star(4.1e12, -1.0e5, 1.0e30),
+        ]
+        .into_iter()
+        .collect(),
+        parameters,
+    );
+    simulation.step_n(200);
+    assert_eq!(simulation.len(), 2);
+    let mass: f64 = simulation.masses().iter().sum();
+    assert_eq!(mass, 1.0e33 + 2.0e30);
+}
+
+#[test]
+fn an_unpinned_black_hole_merges_at_the_center_of_mass() {
+    let parameters = SimulationParameters {
+        merge_density: DENSITY,
+        pin_black_hole: false,
+        ..Default::default()
+    };
+    let mut particles: Particles = vec![star(1.0e9, 0.0, 3.0e30), star(1.0e9 + 4.0e9, 0.0, 1.0e30)]
+        .into_iter()
+        .collect();
+    assert_eq!(merging::touching(&particles, &parameters), [(0, 1)]);
+    merging::merge_all(&mut particles, &parameters);
+    assert_eq!(particles.len(), 1);
+    assert!((particles.positions[0].x - 2.0e9).abs() < 1.0);
+}
@@ -0,0 +1,145 @@
+use galaxy_core::{
+    gravity, initial_conditions,
+    octree::{barnes_hut, Octree},
+    DVec3, Particles, SimulationParameters,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+fn galaxy(count: usize) -> (Particles, SimulationParameters) {
+    let parameters = SimulationParameters {
+        number_of_stars: count,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(3);
+    let particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    (particles, parameters)
+}
+
+/// relative errors of `approximate` against the direct sum, sorted
+fn errors(particles: &Particles, parameters: &SimulationParameters) -> Vec<f64> {
+    let mut exact = particles.clone();
+    gravity::direct_sum(&mut exact, parameters);
+    let mut approximate = particles.clone();
+    barnes_hut(&mut approximate, parameters);
+    let mut errors: Vec<f64> = exact
+        .accelerations
+        .iter()
+        .zip(&approximate.accelerations)
+        .map(|(e, a)| (*e - *a).length() / e.length())
+        .collect();
+    errors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    errors
+}
+
+#[test]
+fn zero_opening_angle_is_the_direct_sum() {
+    let (particles, parameters) = galaxy(1000);
+    let errors = errors(&particles, &parameters);
+    assert!(errors[errors.len() - 1] < 1e-10, "{:?}", errors.last());
+}
+
+#[test]
+fn smaller_opening_angles_are_more_accurate() {
+    let (particles, mut parameters) = galaxy(2000);
+    let mut medians = Vec::new();
+    for &angle in &[0.8, 0.5, 0.3] {
+        parameters.opening_angle = angle;
+        let errors = errors(&particles, &parameters);
+        let median = errors[errors.len() / 2];
+        let p99 = errors[errors.len() * 99 / 100];
+        assert!(median < 0.05 && p99 < 0.5, "{}: {} {}", angle, median, p99);
+        medians.push(median);
+    }
+    assert!(
+        medians[0] > medians[1] && medians[1] > medians[2],
+        "{:?}",
+        medians
+    );
+    assert!(medians[1] < 1e-2, "{:?}", medians);
+}
+
+#[test]
+fn threads_do_not_change_the_result() {
+    let (particles, mut parameters) = galaxy(10_000);
+    parameters.opening_angle = 0.7;
+    parameters.threads = 1;
+    let mut serial = particles.clone();
+    barnes_hut(&mut serial, &parameters);
+    parameters.threads = 4;
+    let mut parallel = particles;
+    barnes_hut(&mut parallel, &parameters);
+    assert_eq!(serial.accelerations, parallel.accelerations);
+}
+
+#[test]
+fn threads_build_the_same_tree() {
+    let (particles, _) = galaxy(20_000);
+    let serial = Octree::new(&particles);
+    for &threads in &[0, 3] {
+        let parallel = Octree::with_threads(&particles, threads);
+        assert!(serial.cells().eq(parallel.cells()));
+    }
+}
+
+#[test]
+fn threads_do_not_change_the_direct_sum() {
+    let (particles, mut parameters) = galaxy(2000);
+    parameters.threads = 1;
+    let mut serial = particles.clone();
+    gravity::direct_sum(&mut serial, &parameters);
+    for &threads in &[0, 3] {
+        parameters.threads = threads;
+        let mut parallel = particles.clone();
+        gravity::direct_sum(&mut parallel, &parameters);
+        assert_eq!(serial.accelerations, parallel.accelerations);
+    }
+}
+
+#[test]
+fn frozen_particles_neither_pull_nor_move() {
+    let (mut particles, mut parameters) = galaxy(500);
+    parameters.opening_angle = 0.5;
+    particles.frozen[10] = true;
+    particles.masses[10] *= 1e6;
+    let mut with_frozen = particles.clone();
+    barnes_hut(&mut with_frozen, &parameters);
+    particles.masses[10] = 0.0;
+    barnes_hut(&mut particles, &parameters);
+    assert_eq!(with_frozen.accelerations[10], DVec3::default());
+    assert_eq!(with_frozen.accelerations, particles.accelerations);
+}
+
+#[test]
+fn cells_hold_the_mass_of_the_particles() {
+    let (particles, mut parameters) = galaxy(500);
+    parameters.opening_angle = 0.5;
+    let tree = Octree::new(&particles);
+    let total: f64 = particles.masses.iter().sum();
+    let root = tree.cells().next().unwrap();
+    assert!((root.mass - total).abs() < 1e-9 * total);
+    assert_eq!(tree.cells().count(), tree.len());
+    // the pull of every particle comes from exactly one approximated cell or opened leaf
+    let interactions = tree.interactions(&particles, 10, &parameters);
+    let mass: f64 = interactions
+        .approximated
+        .iter()
+        .chain(&interactions.direct)
+        .map(|cell| cell.mass)
+        .sum();
+    assert!((mass - total).abs() < 1e-9 * total);
+    assert!(!interactions.approximated.is_empty());
+}
+
+#[test]
+fn zero_opening_angle_approximates_no_cell() {
+    let (particles, mut parameters) = galaxy(500);
+    parameters.opening_angle = 0.0;
+    let tree = Octree::new(&particles);
+    let interactions = tree.interactions(&particles, 10, &parameters);
+    assert!(interactions.approximated.is_empty());
+    let total: f64 = particles.masses.iter().sum();
+    let mass: f64 = interactions.direct.iter().map(|cell| cell.mass).sum();
+    assert!((mass - total).abs() < 1e-9 * total);
+}
@@ -0,0 +1,58 @@
+use galaxy_core::{
+    gravity, initial_conditions, potential::PotentialMap, DVec3, Particles, Preset,
+    SimulationParameters, Star,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn a_single_mass_has_the_softened_point_potential() {
+    let parameters = SimulationParameters::default();
+    let mass = 1e30;
+    let particles: Particles = vec![Star {
+        position: DVec3::new(1e12, 0.0, 0.0),
+        mass,
+        ..Default::default()
+    }]
+    .into_iter()
+    .collect();
+    let map = PotentialMap::sample(&particles, &parameters, DVec3::ZERO, 2e12, 5);
+    assert_eq!(map.values.len(), 25);
+    assert_eq!(map.point(0, 0), DVec3::new(-2e12, -2e12, 0.0));
+    assert_eq!(map.point(3, 2), DVec3::new(1e12, 0.0, 0.0));
+    let epsilon = parameters.softening;
+    assert_eq!(map.value(3, 2), -parameters.g * mass / epsilon);
+    // the deepest sample is on top of the mass
+    let (min, max) = map.range();
+    assert_eq!(min, map.value(3, 2));
+    let far = map.point(0, 4).distance(particles.positions[0]);
+    assert_eq!(
+        max,
+        -parameters.g * mass / (far * far + epsilon * epsilon).sqrt()
+    );
+}
+
+#[test]
+fn the_tree_potential_is_close_to_the_exact_one() {
+    let exact = SimulationParameters {
+        number_of_stars: 2000,
+        preset: Preset::Plummer,
+        ..Default::default()
+    };
+    let particles: Particles = initial_conditions::generate(&mut StdRng::seed_from_u64(8), &exact)
+        .into_iter()
+        .collect();
+    let tree = SimulationParameters {
+        opening_angle: 0.5,
+        ..exact.clone()
+    };
+    let half_size = exact.galaxy_diameter;
+    let a = PotentialMap::sample(&particles, &exact, DVec3::ZERO, half_size, 8);
+    let b = PotentialMap::sample(&particles, &tree, DVec3::ZERO, half_size, 8);
+    for (exact, approximate) in a.values.iter().zip(&b.values) {
+        assert!((approximate - exact).abs() < 0.01 * exact.abs());
+    }
+    assert_eq!(
+        a.value(1, 5),
+        gravity::potential_at(&particles, a.point(1, 5), &exact)
+    );
+}
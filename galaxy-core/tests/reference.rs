@@ -0,0 +1,42 @@
+//! The moons of the validate example against the committed reference trajectory.
+
+use galaxy_core::{reference, Integrator, Particles, SimulationParameters};
+use std::path::Path;
+
+#[test]
+fn the_integrators_follow_the_reference() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reference/moons.csv");
+    let trajectory = reference::read(&path).unwrap();
+    assert_eq!(
+        trajectory.len() as u64,
+        reference::DURATION / reference::INTERVAL + 1
+    );
+    for (integrator, tolerance) in [
+        (Integrator::Euler, 1e-1),
+        (Integrator::Leapfrog, 1e-5),
+        (Integrator::Rk4, 1e-10),
+        (Integrator::Hermite, 1e-10),
+    ] {
+        let parameters = SimulationParameters {
+            integrator,
+            ..Default::default()
+        };
+        let particles: Particles = reference::moons(&parameters).into_iter().collect();
+        let samples = reference::compare(particles, &parameters, &trajectory);
+        assert_eq!(samples.len(), trajectory.len());
+        for sample in samples {
+            assert!(
+                sample.relative_error < tolerance,
+                "{} at {} s: {:e}",
+                integrator.name(),
+                sample.time,
+                sample.relative_error
+            );
+            assert!(
+                sample.energy_drift < tolerance,
+                "{} energy",
+                integrator.name()
+            );
+        }
+    }
+}
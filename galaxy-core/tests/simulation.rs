@@ -0,0 +1,160 @@
+use galaxy_core::{
+    gravity, initial_conditions, integrator, DVec3, Integrator, Particles, Simulation,
+    SimulationParameters,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn same_seed_same_run() {
+    let run = || {
+        let mut simulation = Simulation::builder().number_of_stars(50).seed(7).build();
+        simulation.step_n(5);
+        simulation
+    };
+    let (a, b) = (run(), run());
+    assert_eq!(a.steps, 5);
+    assert_eq!(a.positions(), b.positions());
+    assert_eq!(a.velocities(), b.velocities());
+}
+
+#[test]
+fn flat_positions_are_x_y_z_per_star() {
+    let simulation = Simulation::builder().number_of_stars(10).seed(1).build();
+    let flat = simulation.positions_flat();
+    for (i, p) in simulation.positions().iter().enumerate() {
+        assert_eq!(&flat[3 * i..3 * i + 3], &[p.x, p.y, p.z]);
+    }
+}
+
+#[test]
+fn svg_has_a_dot_per_star() {
+    let simulation = Simulation::builder().number_of_stars(10).seed(1).build();
+    let svg = simulation.svg(200).0;
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("<circle").count(), 10);
+}
+
+#[test]
+fn planar_runs_stay_in_the_plane() {
+    let parameters = galaxy_core::SimulationParameters {
+        planar: true,
+        preset: galaxy_core::Preset::Plummer,
+        opening_angle: 0.5,
+        ..Default::default()
+    };
+    let mut simulation = Simulation::builder()
+        .parameters(parameters)
+        .number_of_stars(300)
+        .seed(4)
+        .build();
+    assert!(simulation.positions().iter().all(|p| p.z == 0.0));
+    // a star added out of the plane joins it
+    simulation.particles.push(galaxy_core::Star {
+        position: galaxy_core::DVec3::new(1e12, 0.0, 1e12),
+        velocity: galaxy_core::DVec3::new(0.0, 1e3, 1e3),
+        ..Default::default()
+    });
+    simulation.step_n(10);
+    assert!(simulation.positions().iter().all(|p| p.z == 0.0));
+    assert!(simulation.velocities().iter().all(|v| v.z == 0.0));
+    assert!(simulation.positions().iter().any(|p| p.x != 0.0));
+}
+
+#[test]
+fn tracers_feel_gravity_but_exert_none() {
+    for opening_angle in [0.0, 0.5] {
+        let parameters = galaxy_core::SimulationParameters {
+            preset: galaxy_core::Preset::Plummer,
+            opening_angle,
+            ..Default::default()
+        };
+        let run = |tracers| {
+            let mut simulation = Simulation::builder()
+                .parameters(galaxy_core::SimulationParameters {
+                    tracers,
+                    ..parameters.clone()
+                })
+                .number_of_stars(100)
+                .seed(3)
+                .build();
+            let start = simulation.positions()[100..].to_vec();
+            simulation.step_n(10);
+            (simulation, start)
+        };
+        let (without, _) = run(0);
+        let (with, start) = run(50);
+        // the massive stars move exactly as without the tracers
+        assert_eq!(with.positions()[..100], without.positions()[..100]);
+        assert_eq!(with.particles.len(), 150);
+        let moved = with.positions()[100..]
+            .iter()
+            .zip(&start)
+            .all(|(position, start)| position != start);
+        assert!(moved);
+    }
+}
+
+#[test]
+fn leapfrog_steps_start_from_the_forces_of_the_last_one() {
+    let parameters = SimulationParameters {
+        number_of_stars: 100,
+        integrator: Integrator::Leapfrog,
+        ..Default::default()
+    };
+    let stars = initial_conditions::random_disk(&mut StdRng::seed_from_u64(5), &parameters);
+    let mut reused: Particles = stars.iter().copied().collect();
+    let mut summed = reused.clone();
+    for _ in 0..20 {
+        galaxy_core::step(&mut reused, &parameters, |_, _| {});
+        // the black hole of the random disk is massless, pinning it before the sums only
+        // changes its own velocity
+        gravity::accelerations(&mut summed, &parameters);
+        integrator::leapfrog(&mut summed, &parameters);
+        summed.positions[0] = DVec3::default();
+    }
+    assert_eq!(reused.positions, summed.positions);
+    assert_eq!(reused.velocities[1..], summed.velocities[1..]);
+
+    // a change between the steps is summed again
+    reused.masses[7] *= 2.0;
+    summed.masses[7] *= 2.0;
+    galaxy_core::step(&mut reused, &parameters, |_, _| {});
+    gravity::accelerations(&mut summed, &parameters);
+    integrator::leapfrog(&mut summed, &parameters);
+    summed.positions[0] = DVec3::default();
+    assert_eq!(reused.velocities[1..], summed.velocities[1..]);
+}
+
+#[test]
+fn escaping_stars_are_frozen() {
+    let parameters = SimulationParameters::default();
+    let escape_radius = parameters.escape_radius * parameters.galaxy_diameter;
+    // the first star is the pinned black hole, the second one is about to escape
+    let stars = [0.0, 0.99, 0.5].map(|fraction| galaxy_core::Star {
+        position: DVec3::new(fraction * escape_radius, 0.0, 0.0),
+        velocity: DVec3::new(1e7, 0.0, 0.0),
+        mass: 1e30,
+        ..Default::default()
+    });
+    let mut simulation = Simulation::new(stars.iter().cloned().collect(), parameters.clone());
+    for _ in 0..10 {
+        simulation.step();
+    }
+    assert!(simulation.particles.positions[1].length() > escape_radius);
+    assert!(simulation.particles.frozen[1]);
+    assert!(!simulation.particles.frozen[2]);
+    let frozen = simulation.particles.positions[1];
+    simulation.step();
+    assert_eq!(simulation.particles.positions[1], frozen);
+
+    // without freezing the star is only reported
+    let mut particles: Particles = stars.iter().cloned().collect();
+    let previous = particles.positions.clone();
+    particles.positions[1].x = escape_radius;
+    let keep = SimulationParameters {
+        freeze_escapers: false,
+        ..parameters
+    };
+    assert_eq!(galaxy_core::escape(&mut particles, &previous, &keep), [1]);
+    assert!(!particles.frozen[1]);
+}
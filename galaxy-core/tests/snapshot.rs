@@ -0,0 +1,86 @@
+//! Runs a small seeded galaxy and compares the final state with a committed reference.
+//!
+//! After an intended change of the results, regenerate the reference with
+//! `UPDATE_SNAPSHOTS=1 cargo test -p galaxy-core --test snapshot`.
+
+use galaxy_core::{initial_conditions, Particles, SimulationParameters};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{fs, path::PathBuf};
+
+const STEPS: usize = 200;
+
+/// relative tolerance, loose enough for libm differences between platforms
+const TOLERANCE: f64 = 1e-9;
+
+fn reference_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("reference")
+        .join(format!("{}.txt", name))
+}
+
+fn format_state(particles: &Particles) -> String {
+    particles
+        .stars()
+        .map(|s| {
+            let p = s.position;
+            let v = s.velocity;
+            format!(
+                "{:e} {:e} {:e} {:e} {:e} {:e} {:e}\n",
+                p.x, p.y, p.z, v.x, v.y, v.z, s.mass
+            )
+        })
+        .collect()
+}
+
+fn parse_state(text: &str) -> Vec<Vec<f64>> {
+    text.lines()
+        .map(|line| line.split(' ').map(|x| x.parse().unwrap()).collect())
+        .collect()
+}
+
+fn close(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() <= TOLERANCE * a.abs().max(b.abs())
+}
+
+fn check_snapshot(name: &str, particles: &Particles) {
+    let path = reference_path(name);
+    let actual = format_state(particles);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!("can't read {}: {}", path.display(), e);
+    });
+    let expected = parse_state(&expected);
+    let actual = parse_state(&actual);
+    assert_eq!(expected.len(), actual.len(), "number of stars changed");
+    for (i, (e, a)) in expected.iter().zip(&actual).enumerate() {
+        assert!(
+            e.iter().zip(a).all(|(e, a)| close(*e, *a)),
+            "star {} differs from the reference:\nexpected {:?}\nactual   {:?}",
+            i,
+            e,
+            a
+        );
+    }
+}
+
+#[test]
+fn random_disk() {
+    let parameters = SimulationParameters {
+        number_of_stars: 100,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut particles: Particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    for _ in 0..STEPS {
+        galaxy_core::step(&mut particles, &parameters, |_, _| {});
+    }
+    check_snapshot("random_disk", &particles);
+}
@@ -0,0 +1,20 @@
+use galaxy_core::{initial_conditions, SimulationParameters, Snapshot};
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn save_and_load_round_trip() {
+    let parameters = SimulationParameters {
+        number_of_stars: 20,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(1);
+    let snapshot = Snapshot {
+        time: 1.25e15,
+        stars: initial_conditions::random_disk(&mut rng, &parameters),
+    };
+    let path = std::env::temp_dir().join(format!("galaxy-snapshot-{}.json", std::process::id()));
+    snapshot.save(&path).unwrap();
+    let loaded = Snapshot::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(snapshot, loaded);
+}
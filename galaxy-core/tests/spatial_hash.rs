@@ -0,0 +1,88 @@
+//! Compares the spatial hash queries with brute force searches.
+
+use galaxy_core::{DVec3, SpatialHash};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn positions() -> Vec<DVec3> {
+    let mut rng = StdRng::seed_from_u64(7);
+    (0..500)
+        .map(|_| {
+            DVec3::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn pairs_match_brute_force() {
+    let positions = positions();
+    let radius = 1.5;
+    let mut hash = SpatialHash::new(1.0);
+    hash.rebuild(&positions, &vec![false; positions.len()]);
+
+    let mut found = Vec::new();
+    hash.pairs(&positions, radius, |i, j| found.push((i, j)));
+    found.sort_unstable();
+
+    let mut expected = Vec::new();
+    for i in 0..positions.len() {
+        for j in i + 1..positions.len() {
+            if (positions[i] - positions[j]).length() <= radius {
+                expected.push((i, j));
+            }
+        }
+    }
+    assert!(!expected.is_empty());
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn skipped_particles_are_not_found() {
+    let positions = positions();
+    let mut skip = vec![false; positions.len()];
+    skip[0] = true;
+    let mut hash = SpatialHash::new(2.0);
+    hash.rebuild(&positions, &skip);
+
+    let all = positions
+        .iter()
+        .filter(|p| (**p - positions[0]).length() <= 3.0)
+        .count();
+    assert_eq!(hash.count_within(&positions, positions[0], 3.0), all - 1);
+}
+
+#[test]
+fn pair_order_is_deterministic() {
+    let positions = positions();
+    let pairs = || {
+        let mut hash = SpatialHash::new(1.0);
+        hash.rebuild(&positions, &vec![false; positions.len()]);
+        let mut found = Vec::new();
+        hash.pairs(&positions, 1.5, |i, j| found.push((i, j)));
+        found
+    };
+    // every hash map gets its own random seed, so this would differ without the fixed order
+    assert_eq!(pairs(), pairs());
+}
+
+#[test]
+fn positions_at_kpc_scale_find_their_neighbors() {
+    let kpc = galaxy_core::units::KPC;
+    // beyond the range of i64 cells of 10 m
+    let positions = vec![
+        DVec3::new(20.0 * kpc, 0.0, 0.0),
+        DVec3::new(20.0 * kpc, 0.0, 0.0),
+        DVec3::new(-20.0 * kpc, 5.0 * kpc, 0.0),
+        DVec3::new(-20.0 * kpc, 5.0 * kpc, -5.0),
+        DVec3::new(0.0, 0.0, 0.0),
+    ];
+    let mut hash = SpatialHash::new(10.0);
+    hash.rebuild(&positions, &vec![false; positions.len()]);
+    let mut found = Vec::new();
+    hash.pairs(&positions, 10.0, |i, j| found.push((i, j)));
+    found.sort_unstable();
+    assert_eq!(found, [(0, 1), (2, 3)]);
+}
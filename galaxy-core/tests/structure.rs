@@ -0,0 +1,74 @@
+use galaxy_core::{diagnostics::structure, DVec3, Particles, Star};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// equal mass stars spread uniformly in a sphere of radius 1 around `center`
+fn uniform_sphere(count: usize, center: DVec3) -> Particles {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut particles = Particles::new();
+    while particles.len() < count {
+        let p = DVec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        if p.length() <= 1.0 {
+            particles.push(Star {
+                position: center + p,
+                mass: 1.0,
+                ..Default::default()
+            });
+        }
+    }
+    particles
+}
+
+#[test]
+fn lagrangian_radii_of_a_uniform_sphere() {
+    let center = DVec3::new(5.0, -3.0, 2.0);
+    let s = structure(&uniform_sphere(2000, center));
+    assert!(
+        (s.density_center - center).length() < 0.1,
+        "{:?}",
+        s.density_center
+    );
+    for (radius, fraction) in s.lagrangian_radii.iter().zip(&[0.1f64, 0.5, 0.9]) {
+        let expected = fraction.cbrt();
+        assert!(
+            (radius - expected).abs() < 0.05,
+            "{} vs {}",
+            radius,
+            expected
+        );
+    }
+    assert_eq!(s.half_mass_radius, s.lagrangian_radii[1]);
+    // a flat profile has no distinct core, it spans a good part of the sphere
+    assert!(s.core_radius > 0.2 && s.core_radius < s.half_mass_radius);
+    let mean_density = 2000.0 / (4.0 / 3.0 * std::f64::consts::PI);
+    assert!((s.core_density / mean_density - 1.0).abs() < 0.5);
+}
+
+#[test]
+fn frozen_and_massless_stars_are_left_out() {
+    let mut particles = uniform_sphere(200, DVec3::default());
+    let before = structure(&particles);
+    particles.push(Star {
+        position: DVec3::new(100.0, 0.0, 0.0),
+        mass: 1.0,
+        ..Default::default()
+    });
+    let last = particles.len() - 1;
+    particles.frozen[last] = true;
+    particles.push(Star {
+        position: DVec3::new(-100.0, 0.0, 0.0),
+        ..Default::default()
+    });
+    assert_eq!(structure(&particles), before);
+}
+
+#[test]
+fn too_few_stars_give_the_default() {
+    assert_eq!(
+        structure(&uniform_sphere(3, DVec3::default())),
+        Default::default()
+    );
+}
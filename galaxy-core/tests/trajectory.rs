@@ -0,0 +1,57 @@
+use galaxy_core::{
+    initial_conditions,
+    trajectory::{TrajectoryReader, TrajectoryWriter},
+    Particles, SimulationParameters,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn frames_can_be_read_back_and_seeked() {
+    let parameters = SimulationParameters {
+        number_of_stars: 25,
+        ..Default::default()
+    };
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut particles: Particles = initial_conditions::random_disk(&mut rng, &parameters)
+        .into_iter()
+        .collect();
+    let path = std::env::temp_dir().join(format!("galaxy-trajectory-{}.bin", std::process::id()));
+
+    let mut writer = TrajectoryWriter::create(&path).unwrap();
+    let mut recorded = Vec::new();
+    for frame in 0..3 {
+        // the star count changes between frames, like after a merger
+        if frame == 2 {
+            particles.swap_remove(5);
+        }
+        writer.write_frame(frame as f64 * 10.0, &particles).unwrap();
+        recorded.push(particles.positions.clone());
+        for position in &mut particles.positions {
+            *position *= 1.01;
+        }
+    }
+    assert_eq!(writer.finish().unwrap(), 3);
+    // a frame cut off while recording is ignored
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    std::io::Write::write_all(&mut file, &[1, 2, 3, 4, 5, 6, 7, 8, 25, 0, 0, 0, 9]).unwrap();
+    drop(file);
+
+    let mut reader = TrajectoryReader::open(&path).unwrap();
+    assert_eq!(reader.len(), 3);
+    assert_eq!(reader.times(), &[0.0, 10.0, 20.0]);
+    assert_eq!(reader.index_at(-1.0), 0);
+    assert_eq!(reader.index_at(15.0), 1);
+    assert_eq!(reader.index_at(1e9), 2);
+    for (index, positions) in recorded.iter().enumerate().rev() {
+        let read = reader.frame(index).unwrap();
+        assert_eq!(read.len(), positions.len());
+        for (read, position) in read.iter().zip(positions) {
+            // single precision
+            assert!((*read - *position).length() <= position.length() * 1e-6);
+        }
+    }
+    std::fs::remove_file(&path).unwrap();
+}
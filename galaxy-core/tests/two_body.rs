@@ -0,0 +1,293 @@
+//! Integrates an eccentric two-body orbit and compares it with the Kepler solution.
+
+use galaxy_core::{
+    diagnostics::conserved, gravity, integrator, AdaptiveTimestep, DVec3, Integrator, Particles,
+    SimulationParameters, Star,
+};
+use std::f64::consts::PI;
+
+const CENTRAL_MASS: f64 = 6.0e24;
+const ORBITING_MASS: f64 = 1.0e3;
+const SEMI_MAJOR_AXIS: f64 = 1.0e7;
+const ECCENTRICITY: f64 = 0.5;
+
+/// one simulated second per step and no softening, so the integrator sees plain Newtonian gravity
+fn parameters() -> SimulationParameters {
+    SimulationParameters {
+        number_of_stars: 2,
+        galaxy_diameter: f64::INFINITY,
+        time_factor: 1.0,
+        physics_dt: 1.0,
+        softening: 0.0,
+        ..Default::default()
+    }
+}
+
+fn mu(parameters: &SimulationParameters) -> f64 {
+    parameters.g * (CENTRAL_MASS + ORBITING_MASS)
+}
+
+fn analytic_period(parameters: &SimulationParameters) -> f64 {
+    2.0 * PI * (SEMI_MAJOR_AXIS.powi(3) / mu(parameters)).sqrt()
+}
+
+/// both bodies at periapsis in the center of mass frame
+fn initial_particles(parameters: &SimulationParameters) -> Particles {
+    let total = CENTRAL_MASS + ORBITING_MASS;
+    let r = SEMI_MAJOR_AXIS * (1.0 - ECCENTRICITY);
+    let v = (mu(parameters) * (1.0 + ECCENTRICITY) / r).sqrt();
+    let body = |sign: f64, mass: f64, share: f64| Star {
+        position: DVec3::new(sign * r * share, 0.0, 0.0),
+        velocity: DVec3::new(0.0, sign * v * share, 0.0),
+        acceleration: DVec3::default(),
+        mass,
+    };
+    vec![
+        body(-1.0, CENTRAL_MASS, ORBITING_MASS / total),
+        body(1.0, ORBITING_MASS, CENTRAL_MASS / total),
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn energy(p: &Particles, parameters: &SimulationParameters) -> f64 {
+    let kinetic: f64 = p
+        .stars()
+        .map(|s| 0.5 * s.mass * s.velocity.length_squared())
+        .sum();
+    let r = (p.positions[1] - p.positions[0]).length();
+    kinetic - parameters.g * p.masses[0] * p.masses[1] / r
+}
+
+fn eccentricity(p: &Particles, parameters: &SimulationParameters) -> f64 {
+    let r = p.positions[1] - p.positions[0];
+    let v = p.velocities[1] - p.velocities[0];
+    let mu = mu(parameters);
+    let e = (r * (v.length_squared() - mu / r.length()) - v * r.dot(v)) / mu;
+    e.length()
+}
+
+struct Orbit {
+    period: f64,
+    eccentricity: f64,
+    energy_drift: f64,
+}
+
+/// integrates one full revolution, measuring the period from the swept angle
+fn orbit(
+    parameters: &SimulationParameters,
+    mut step: impl FnMut(&mut Particles, &SimulationParameters),
+) -> Orbit {
+    let parameters = parameters.clone();
+    let mut particles = initial_particles(&parameters);
+    let initial_energy = energy(&particles, &parameters);
+    let angle = |p: &Particles| {
+        let r = p.positions[1] - p.positions[0];
+        r.y.atan2(r.x)
+    };
+
+    let mut swept = 0.0;
+    let mut time = 0.0;
+    let max_time = 2.0 * analytic_period(&parameters);
+    while time < max_time {
+        let before = angle(&particles);
+        step(&mut particles, &parameters);
+        time += parameters.physics_dt * parameters.time_factor;
+        let mut delta = angle(&particles) - before;
+        if delta < -PI {
+            delta += 2.0 * PI;
+        }
+        if swept + delta >= 2.0 * PI {
+            // interpolate the moment the full circle was completed
+            let fraction = (2.0 * PI - swept) / delta;
+            time -= (1.0 - fraction) * parameters.physics_dt * parameters.time_factor;
+            break;
+        }
+        swept += delta;
+    }
+
+    Orbit {
+        period: time,
+        eccentricity: eccentricity(&particles, &parameters),
+        energy_drift: ((energy(&particles, &parameters) - initial_energy) / initial_energy).abs(),
+    }
+}
+
+fn assert_kepler(orbit: Orbit, tolerance: f64) {
+    let expected = analytic_period(&parameters());
+    let period_error = (orbit.period - expected).abs() / expected;
+    assert!(
+        period_error < tolerance,
+        "period {} s, expected {} s",
+        orbit.period,
+        expected
+    );
+    assert!(
+        (orbit.eccentricity - ECCENTRICITY).abs() < tolerance,
+        "eccentricity {}, expected {}",
+        orbit.eccentricity,
+        ECCENTRICITY
+    );
+    assert!(
+        orbit.energy_drift < tolerance,
+        "relative energy drift {}",
+        orbit.energy_drift
+    );
+}
+
+#[test]
+fn euler_integrator() {
+    let orbit = orbit(&parameters(), |particles, parameters| {
+        gravity::direct_sum(particles, parameters);
+        integrator::integrate(particles, parameters);
+    });
+    assert_kepler(orbit, 1e-2);
+}
+
+#[test]
+fn leapfrog_integrator() {
+    let orbit = orbit(&parameters(), |particles, parameters| {
+        gravity::direct_sum(particles, parameters);
+        integrator::leapfrog(particles, parameters);
+    });
+    assert_kepler(orbit, 1e-4);
+}
+
+#[test]
+fn rk4_integrator() {
+    let parameters = SimulationParameters {
+        integrator: Integrator::Rk4,
+        ..parameters()
+    };
+    let orbit = orbit(&parameters, |particles, parameters| {
+        gravity::direct_sum(particles, parameters);
+        integrator::advance(particles, parameters, |particles| {
+            gravity::direct_sum(particles, parameters)
+        });
+    });
+    assert_kepler(orbit, 1e-5);
+}
+
+#[test]
+fn hermite_integrator() {
+    let parameters = SimulationParameters {
+        integrator: Integrator::Hermite,
+        ..parameters()
+    };
+    let orbit = orbit(&parameters, |particles, parameters| {
+        integrator::advance(particles, parameters, |_| {});
+    });
+    assert_kepler(orbit, 1e-5);
+}
+
+#[test]
+fn time_factor_scales_the_step() {
+    // four simulated seconds per step, the orbit must not depend on how they are split
+    let parameters = SimulationParameters {
+        time_factor: 4.0,
+        ..parameters()
+    };
+    let orbit = orbit(&parameters, |particles, parameters| {
+        gravity::direct_sum(particles, parameters);
+        integrator::leapfrog(particles, parameters);
+    });
+    assert_kepler(orbit, 1e-3);
+}
+
+#[test]
+fn trajectory_follows_a_massless_orbiter() {
+    let parameters = parameters();
+    let mut particles = initial_particles(&parameters);
+    // the central body stays where it is, like the sources of a trajectory
+    particles.masses[1] = 0.0;
+    particles.velocities[0] = DVec3::default();
+    let central: Particles = std::iter::once(particles.star(0)).collect();
+    let predicted = integrator::trajectory(
+        &central,
+        particles.positions[1],
+        particles.velocities[1],
+        &parameters,
+        1000,
+    );
+    for expected in predicted {
+        gravity::direct_sum(&mut particles, &parameters);
+        integrator::integrate(&mut particles, &parameters);
+        assert_eq!(particles.positions[1], expected);
+    }
+}
+
+#[test]
+fn adaptive_timestep_resolves_the_periapsis() {
+    // about fifty steps per revolution, too coarse for the periapsis passage
+    let coarse = SimulationParameters {
+        time_factor: 200.0,
+        ..parameters()
+    };
+    let adaptive = SimulationParameters {
+        adaptive_timestep: Some(AdaptiveTimestep {
+            accuracy: 0.02,
+            min_dt: 0.1,
+            max_dt: 200.0,
+        }),
+        ..coarse.clone()
+    };
+    let step = |particles: &mut Particles, parameters: &SimulationParameters| {
+        galaxy_core::step(particles, parameters, |_, _| {})
+    };
+    let fixed = orbit(&coarse, step);
+    let adapted = orbit(&adaptive, step);
+    assert!(
+        adapted.energy_drift < fixed.energy_drift / 10.0,
+        "fixed {} adaptive {}",
+        fixed.energy_drift,
+        adapted.energy_drift
+    );
+    assert_kepler(adapted, 1e-3);
+}
+
+#[test]
+fn softening_keeps_close_pairs_finite() {
+    let parameters = SimulationParameters {
+        softening: 1.0e5,
+        ..parameters()
+    };
+    let pair = |distance: f64| {
+        let mut particles: Particles = initial_particles(&parameters);
+        particles.positions[0] = DVec3::default();
+        particles.positions[1] = DVec3::new(distance, 0.0, 0.0);
+        gravity::direct_sum(&mut particles, &parameters);
+        particles.accelerations[1].length()
+    };
+    // a coincident pair pulls with nothing, the pull peaks at ε / √2 instead of diverging
+    assert_eq!(pair(0.0), 0.0);
+    let peak = parameters.g * CENTRAL_MASS / (2f64.sqrt() * 1.5f64.powf(1.5) * 1.0e10);
+    assert!((pair(1.0e5 / 2f64.sqrt()) - peak).abs() < 1e-9 * peak);
+    assert!(pair(1.0e3) < peak);
+    // far away it is plain Newtonian gravity
+    let newtonian = parameters.g * CENTRAL_MASS / 1.0e16;
+    assert!((pair(1.0e8) - newtonian).abs() < 1e-5 * newtonian);
+}
+
+#[test]
+fn conserved_quantities_of_the_orbit() {
+    let parameters = parameters();
+    let mut particles = initial_particles(&parameters);
+    let initial = conserved(&particles, &parameters);
+    // the vis-viva energy and the specific angular momentum of the reduced mass
+    let reduced = CENTRAL_MASS * ORBITING_MASS / (CENTRAL_MASS + ORBITING_MASS);
+    let energy = -parameters.g * CENTRAL_MASS * ORBITING_MASS / (2.0 * SEMI_MAJOR_AXIS);
+    let momentum =
+        reduced * (mu(&parameters) * SEMI_MAJOR_AXIS * (1.0 - ECCENTRICITY * ECCENTRICITY)).sqrt();
+    assert!((initial.energy() - energy).abs() < 1e-9 * energy.abs());
+    assert!((initial.angular_momentum.z - momentum).abs() < 1e-9 * momentum);
+    assert_eq!(initial.energy_drift(&initial), Some(0.0));
+
+    for _ in 0..1000 {
+        gravity::direct_sum(&mut particles, &parameters);
+        integrator::leapfrog(&mut particles, &parameters);
+    }
+    let later = conserved(&particles, &parameters);
+    assert!(later.angular_momentum_drift(&initial).unwrap() < 1e-9);
+    assert!(later.energy_drift(&initial).unwrap().abs() < 1e-3);
+    assert_eq!(later.angular_momentum_drift(&Default::default()), None);
+}
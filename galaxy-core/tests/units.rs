@@ -0,0 +1,56 @@
+use galaxy_core::{
+    units::{Length, Mass, Speed, TimeSpan, KPC, MYR, PARSEC, SOLAR_MASS},
+    SimulationConfig, SimulationParameters,
+};
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-12 * b.abs()
+}
+
+#[test]
+fn parses_numbers_with_units() {
+    assert_eq!("30 kpc".parse(), Ok(Length(30.0 * KPC)));
+    assert_eq!("0.1pc".parse(), Ok(Length(0.1 * PARSEC)));
+    assert_eq!("1e13".parse(), Ok(Length(1e13)));
+    assert!(close(
+        "4e6 Msun".parse::<Mass>().unwrap().0,
+        4e6 * SOLAR_MASS
+    ));
+    assert_eq!("2 M☉".parse(), Ok(Mass(2.0 * SOLAR_MASS)));
+    assert_eq!("1.5e-3 Myr".parse(), Ok(TimeSpan(1.5e-3 * MYR)));
+    assert_eq!("220 km/s".parse(), Ok(Speed(2.2e5)));
+    assert!("30 parsecs".parse::<Length>().is_err());
+    assert!("30 Msun".parse::<Length>().is_err());
+    assert!("kpc".parse::<Length>().is_err());
+}
+
+#[test]
+fn displays_in_the_unit_that_fits() {
+    assert_eq!(Length(30.0 * KPC).to_string(), "30.000 kpc");
+    assert_eq!(format!("{:.1}", Length(1e13)), "66.8 au");
+    assert_eq!(Length(5.0).to_string(), "5.000 m");
+    assert_eq!(format!("{:.2}", Mass(4e6 * SOLAR_MASS)), "4.00e6 Msun");
+    assert_eq!(format!("{:.1}", TimeSpan(2.5 * MYR)), "2.5 Myr");
+    assert_eq!(format!("{:.0}", Speed(2.2e5)), "220 km/s");
+    assert_eq!(Mass(0.0).to_string(), "0.000 kg");
+}
+
+#[test]
+fn config_values_take_units() {
+    let config = SimulationConfig::parse(
+        r#"
+        galaxy_diameter = "30 kpc"
+        black_hole_mass = "4e6 Msun"
+        time_factor = "1 Myr"
+        softening = 1e11
+        "#,
+    )
+    .unwrap();
+    let mut parameters = SimulationParameters::default();
+    config.apply(&mut parameters);
+    assert_eq!(parameters.galaxy_diameter, 30.0 * KPC);
+    assert!(close(parameters.black_hole_mass, 4e6 * SOLAR_MASS));
+    assert_eq!(parameters.time_factor, MYR);
+    assert_eq!(parameters.softening, 1e11);
+    assert!(SimulationConfig::parse("galaxy_diameter = \"30 Myr\"").is_err());
+}
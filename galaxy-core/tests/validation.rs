@@ -0,0 +1,67 @@
+//! The circular Kepler orbit of `--validate` with every integrator.
+
+use galaxy_core::{
+    validation::{circular_orbit, DEFAULT_STEPS_PER_ORBIT},
+    AdaptiveTimestep, Integrator, SimulationParameters,
+};
+
+fn validate(integrator: Integrator, steps: u64) -> galaxy_core::validation::KeplerError {
+    let parameters = SimulationParameters {
+        integrator,
+        ..Default::default()
+    };
+    circular_orbit(&parameters, steps)
+}
+
+#[test]
+fn the_integrators_keep_the_period_and_the_energy() {
+    for (integrator, tolerance) in [
+        (Integrator::Euler, 1e-1),
+        (Integrator::Leapfrog, 1e-4),
+        (Integrator::Rk4, 1e-8),
+        (Integrator::Hermite, 1e-8),
+    ] {
+        let error = validate(integrator, DEFAULT_STEPS_PER_ORBIT);
+        assert!(
+            error.period_error() < tolerance,
+            "{} period: {:?}",
+            integrator.name(),
+            error
+        );
+        assert!(
+            error.energy_drift < tolerance,
+            "{} energy: {:?}",
+            integrator.name(),
+            error
+        );
+        assert!(
+            error.radius_drift < tolerance,
+            "{} radius: {:?}",
+            integrator.name(),
+            error
+        );
+    }
+}
+
+#[test]
+fn more_steps_make_leapfrog_more_accurate() {
+    let coarse = validate(Integrator::Leapfrog, 100);
+    let fine = validate(Integrator::Leapfrog, 1000);
+    // second order, ten times the steps are about a hundred times better
+    assert!(fine.energy_drift < coarse.energy_drift / 50.0);
+    assert!(fine.radius_drift < coarse.radius_drift / 50.0);
+}
+
+#[test]
+fn the_adaptive_timestep_is_scaled_to_the_orbit() {
+    let parameters = SimulationParameters {
+        adaptive_timestep: Some(AdaptiveTimestep {
+            accuracy: 0.01,
+            min_dt: 1.0,
+            max_dt: 1.0,
+        }),
+        ..Default::default()
+    };
+    let error = circular_orbit(&parameters, 100);
+    assert!(error.period_error() < 1e-3);
+}
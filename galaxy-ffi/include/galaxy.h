@@ -0,0 +1,74 @@
+/* C API of the galaxy simulator core, link against the galaxy_ffi library built by galaxy-ffi.
+ *
+ * All vectors are passed as flat arrays of doubles, x, y, z for every star, in SI units.
+ * The first star is the black hole, it stays pinned to the origin.
+ */
+
+#ifndef GALAXY_H
+#define GALAXY_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define GALAXY_OK 0
+#define GALAXY_NULL_POINTER -1
+#define GALAXY_WRONG_COUNT -2
+#define GALAXY_INVALID_VALUE -3
+
+/* integrators for galaxy_set_integrator */
+#define GALAXY_EULER 0
+#define GALAXY_LEAPFROG 1
+#define GALAXY_RK4 2
+#define GALAXY_HERMITE 3
+
+typedef struct GalaxySimulation GalaxySimulation;
+
+/* empty simulation with the default parameters, free it with galaxy_destroy */
+GalaxySimulation *galaxy_create(void);
+
+/* random disk galaxy like the one the viewer starts with */
+GalaxySimulation *galaxy_create_random(size_t stars, uint64_t seed);
+
+void galaxy_destroy(GalaxySimulation *simulation);
+
+/* replaces all stars, positions and velocities have 3 * count entries, masses count,
+ * GALAXY_WRONG_COUNT if 3 * count overflows */
+int galaxy_set_particles(GalaxySimulation *simulation, size_t count, const double *positions,
+                         const double *velocities, const double *masses);
+
+/* advances by the given number of physics steps */
+int galaxy_step(GalaxySimulation *simulation, size_t steps);
+
+size_t galaxy_count(const GalaxySimulation *simulation);
+
+/* simulated seconds since the start */
+double galaxy_time(const GalaxySimulation *simulation);
+
+/* copy out the current state, count must be galaxy_count() */
+int galaxy_positions(const GalaxySimulation *simulation, double *positions, size_t count);
+int galaxy_velocities(const GalaxySimulation *simulation, double *velocities, size_t count);
+
+/* simulated seconds per physics second */
+int galaxy_set_time_factor(GalaxySimulation *simulation, double time_factor);
+
+/* physics seconds per step */
+int galaxy_set_physics_dt(GalaxySimulation *simulation, double physics_dt);
+
+/* size of the galaxy in meters, stars beyond 1.5 times it count as escaped */
+int galaxy_set_galaxy_diameter(GalaxySimulation *simulation, double galaxy_diameter);
+
+/* Plummer softening length of the forces in meters */
+int galaxy_set_softening(GalaxySimulation *simulation, double softening);
+
+/* one of the GALAXY_EULER ... GALAXY_HERMITE integrators, GALAXY_INVALID_VALUE otherwise */
+int galaxy_set_integrator(GalaxySimulation *simulation, int integrator);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,258 @@
+//! C API of `galaxy-core`, declared in `include/galaxy.h`.
+//!
+//! Every function returns `GALAXY_OK` or a negative error code, or a plain value when it
+//! can't fail. A simulation is only ever used from one thread at a time.
+
+use galaxy_core::{DVec3, Integrator, Simulation, Star};
+use std::{convert::TryFrom, os::raw::c_int, slice};
+
+pub const GALAXY_OK: c_int = 0;
+pub const GALAXY_NULL_POINTER: c_int = -1;
+pub const GALAXY_WRONG_COUNT: c_int = -2;
+pub const GALAXY_INVALID_VALUE: c_int = -3;
+
+/// the integrators by their index in `Integrator::ALL`
+pub const GALAXY_EULER: c_int = 0;
+pub const GALAXY_LEAPFROG: c_int = 1;
+pub const GALAXY_RK4: c_int = 2;
+pub const GALAXY_HERMITE: c_int = 3;
+
+/// opaque to C
+pub struct GalaxySimulation(Simulation);
+
+fn vectors(values: &[f64]) -> impl Iterator<Item = DVec3> + '_ {
+    values.chunks_exact(3).map(|v| DVec3::new(v[0], v[1], v[2]))
+}
+
+fn copy_vectors(vectors: &[DVec3], out: *mut f64, count: usize) -> c_int {
+    if out.is_null() {
+        return GALAXY_NULL_POINTER;
+    }
+    if count != vectors.len() {
+        return GALAXY_WRONG_COUNT;
+    }
+    let values = match count.checked_mul(3) {
+        Some(values) => values,
+        None => return GALAXY_WRONG_COUNT,
+    };
+    let out = unsafe { slice::from_raw_parts_mut(out, values) };
+    for (out, v) in out.chunks_exact_mut(3).zip(vectors) {
+        out.copy_from_slice(&[v.x, v.y, v.z]);
+    }
+    GALAXY_OK
+}
+
+#[no_mangle]
+pub extern "C" fn galaxy_create() -> *mut GalaxySimulation {
+    Box::into_raw(Box::new(GalaxySimulation(Simulation::default())))
+}
+
+#[no_mangle]
+pub extern "C" fn galaxy_create_random(stars: usize, seed: u64) -> *mut GalaxySimulation {
+    let simulation = Simulation::builder()
+        .number_of_stars(stars)
+        .seed(seed)
+        .build();
+    Box::into_raw(Box::new(GalaxySimulation(simulation)))
+}
+
+/// # Safety
+///
+/// `simulation` must come from `galaxy_create` or `galaxy_create_random` and must not be
+/// used afterwards. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_destroy(simulation: *mut GalaxySimulation) {
+    if !simulation.is_null() {
+        drop(Box::from_raw(simulation));
+    }
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation, `positions` and `velocities` must point to
+/// `3 * count` doubles and `masses` to `count` doubles.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_set_particles(
+    simulation: *mut GalaxySimulation,
+    count: usize,
+    positions: *const f64,
+    velocities: *const f64,
+    masses: *const f64,
+) -> c_int {
+    let simulation = match simulation.as_mut() {
+        Some(simulation) => simulation,
+        None => return GALAXY_NULL_POINTER,
+    };
+    if positions.is_null() || velocities.is_null() || masses.is_null() {
+        return GALAXY_NULL_POINTER;
+    }
+    let values = match count.checked_mul(3) {
+        Some(values) => values,
+        None => return GALAXY_WRONG_COUNT,
+    };
+    let positions = slice::from_raw_parts(positions, values);
+    let velocities = slice::from_raw_parts(velocities, values);
+    let masses = slice::from_raw_parts(masses, count);
+    simulation.0.particles = vectors(positions)
+        .zip(vectors(velocities))
+        .zip(masses)
+        .map(|((position, velocity), &mass)| Star {
+            position,
+            velocity,
+            acceleration: DVec3::default(),
+            mass,
+        })
+        .collect();
+    GALAXY_OK
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_step(simulation: *mut GalaxySimulation, steps: usize) -> c_int {
+    let simulation = match simulation.as_mut() {
+        Some(simulation) => simulation,
+        None => return GALAXY_NULL_POINTER,
+    };
+    simulation.0.step_n(steps as u64);
+    GALAXY_OK
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation or null, which has no stars.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_count(simulation: *const GalaxySimulation) -> usize {
+    simulation.as_ref().map_or(0, |s| s.0.len())
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation or null, which is at time 0.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_time(simulation: *const GalaxySimulation) -> f64 {
+    simulation.as_ref().map_or(0.0, |s| s.0.time)
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation and `positions` must point to `3 * count` doubles.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_positions(
+    simulation: *const GalaxySimulation,
+    positions: *mut f64,
+    count: usize,
+) -> c_int {
+    match simulation.as_ref() {
+        Some(simulation) => copy_vectors(&simulation.0.particles.positions, positions, count),
+        None => GALAXY_NULL_POINTER,
+    }
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation and `velocities` must point to `3 * count` doubles.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_velocities(
+    simulation: *const GalaxySimulation,
+    velocities: *mut f64,
+    count: usize,
+) -> c_int {
+    match simulation.as_ref() {
+        Some(simulation) => copy_vectors(&simulation.0.particles.velocities, velocities, count),
+        None => GALAXY_NULL_POINTER,
+    }
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_set_time_factor(
+    simulation: *mut GalaxySimulation,
+    time_factor: f64,
+) -> c_int {
+    match simulation.as_mut() {
+        Some(simulation) => {
+            simulation.0.parameters.time_factor = time_factor;
+            GALAXY_OK
+        }
+        None => GALAXY_NULL_POINTER,
+    }
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_set_physics_dt(
+    simulation: *mut GalaxySimulation,
+    physics_dt: f64,
+) -> c_int {
+    match simulation.as_mut() {
+        Some(simulation) => {
+            simulation.0.parameters.physics_dt = physics_dt;
+            GALAXY_OK
+        }
+        None => GALAXY_NULL_POINTER,
+    }
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_set_galaxy_diameter(
+    simulation: *mut GalaxySimulation,
+    galaxy_diameter: f64,
+) -> c_int {
+    match simulation.as_mut() {
+        Some(simulation) => {
+            simulation.0.parameters.galaxy_diameter = galaxy_diameter;
+            GALAXY_OK
+        }
+        None => GALAXY_NULL_POINTER,
+    }
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_set_softening(
+    simulation: *mut GalaxySimulation,
+    softening: f64,
+) -> c_int {
+    match simulation.as_mut() {
+        Some(simulation) => {
+            simulation.0.parameters.softening = softening;
+            GALAXY_OK
+        }
+        None => GALAXY_NULL_POINTER,
+    }
+}
+
+/// # Safety
+///
+/// `simulation` must be a live simulation.
+#[no_mangle]
+pub unsafe extern "C" fn galaxy_set_integrator(
+    simulation: *mut GalaxySimulation,
+    integrator: c_int,
+) -> c_int {
+    let simulation = match simulation.as_mut() {
+        Some(simulation) => simulation,
+        None => return GALAXY_NULL_POINTER,
+    };
+    match usize::try_from(integrator)
+        .ok()
+        .and_then(|i| Integrator::ALL.get(i))
+    {
+        Some(&integrator) => {
+            simulation.0.parameters.integrator = integrator;
+            GALAXY_OK
+        }
+        None => GALAXY_INVALID_VALUE,
+    }
+}
@@ -0,0 +1,98 @@
+//! Drives the C API the way a C program would, through raw pointers.
+
+use galaxy_ffi::*;
+use std::ptr;
+
+#[test]
+fn step_moves_the_stars() {
+    let simulation = galaxy_create_random(10, 7);
+    unsafe {
+        let count = galaxy_count(simulation);
+        assert_eq!(count, 10);
+        let mut before = vec![0.0; 3 * count];
+        assert_eq!(
+            galaxy_positions(simulation, before.as_mut_ptr(), count),
+            GALAXY_OK
+        );
+        assert_eq!(galaxy_step(simulation, 5), GALAXY_OK);
+        let mut after = vec![0.0; 3 * count];
+        assert_eq!(
+            galaxy_positions(simulation, after.as_mut_ptr(), count),
+            GALAXY_OK
+        );
+        assert_ne!(before, after);
+        assert!(galaxy_time(simulation) > 0.0);
+        galaxy_destroy(simulation);
+    }
+}
+
+#[test]
+fn set_particles_replaces_the_stars() {
+    let simulation = galaxy_create();
+    let positions = [0.0, 0.0, 0.0, 1e10, 0.0, 0.0];
+    let velocities = [0.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+    let masses = [1e30, 1e20];
+    unsafe {
+        assert_eq!(
+            galaxy_set_particles(
+                simulation,
+                2,
+                positions.as_ptr(),
+                velocities.as_ptr(),
+                masses.as_ptr()
+            ),
+            GALAXY_OK
+        );
+        assert_eq!(galaxy_count(simulation), 2);
+        let mut out = [0.0; 6];
+        assert_eq!(
+            galaxy_velocities(simulation, out.as_mut_ptr(), 2),
+            GALAXY_OK
+        );
+        assert_eq!(out, velocities);
+        assert_eq!(
+            galaxy_velocities(simulation, out.as_mut_ptr(), 3),
+            GALAXY_WRONG_COUNT
+        );
+        galaxy_destroy(simulation);
+    }
+}
+
+#[test]
+fn parameters_are_set_or_rejected() {
+    let simulation = galaxy_create_random(10, 7);
+    unsafe {
+        assert_eq!(galaxy_set_galaxy_diameter(simulation, 2e13), GALAXY_OK);
+        assert_eq!(galaxy_set_softening(simulation, 1e10), GALAXY_OK);
+        assert_eq!(galaxy_set_integrator(simulation, GALAXY_RK4), GALAXY_OK);
+        assert_eq!(galaxy_set_integrator(simulation, 4), GALAXY_INVALID_VALUE);
+        assert_eq!(galaxy_set_integrator(simulation, -1), GALAXY_INVALID_VALUE);
+        assert_eq!(galaxy_step(simulation, 2), GALAXY_OK);
+        let one = [0.0; 3];
+        assert_eq!(
+            galaxy_set_particles(
+                simulation,
+                usize::MAX,
+                one.as_ptr(),
+                one.as_ptr(),
+                one.as_ptr()
+            ),
+            GALAXY_WRONG_COUNT
+        );
+        assert_eq!(galaxy_count(simulation), 10);
+        assert_eq!(
+            galaxy_set_softening(ptr::null_mut(), 1.0),
+            GALAXY_NULL_POINTER
+        );
+        galaxy_destroy(simulation);
+    }
+}
+
+#[test]
+fn null_pointers_are_rejected() {
+    unsafe {
+        assert_eq!(galaxy_step(ptr::null_mut(), 1), GALAXY_NULL_POINTER);
+        assert_eq!(galaxy_count(ptr::null()), 0);
+        galaxy_destroy(ptr::null_mut());
+    }
+}
@@ -0,0 +1,380 @@
+//! Python bindings for `galaxy-core`, build them with `maturin develop` and use them with
+//! `import galaxy`.
+//!
+//! Arrays go in and out as NumPy arrays of float64: positions, velocities and accelerations
+//! with shape (n, 3), masses with shape (n,), all in SI units.
+
+use galaxy_core::{
+    catalog, DVec3, Error, Integrator, Particles, SimulationParameters, Snapshot, Star,
+};
+use numpy::{
+    ndarray::ArrayView2, PyArray1, PyArray2, PyArrayMethods, PyReadonlyArray1, PyReadonlyArray2,
+};
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+};
+use std::path::PathBuf;
+
+fn to_py_err(e: Error) -> PyErr {
+    match e {
+        Error::Io { .. } => PyIOError::new_err(e.to_string()),
+        _ => PyValueError::new_err(e.to_string()),
+    }
+}
+
+fn to_vectors(name: &str, array: ArrayView2<f64>) -> PyResult<Vec<DVec3>> {
+    if array.ncols() != 3 {
+        return Err(PyValueError::new_err(format!(
+            "{} must have shape (n, 3)",
+            name
+        )));
+    }
+    Ok(array
+        .rows()
+        .into_iter()
+        .map(|row| DVec3::new(row[0], row[1], row[2]))
+        .collect())
+}
+
+fn to_array<'py>(py: Python<'py>, vectors: &[DVec3]) -> Bound<'py, PyArray2<f64>> {
+    let flat = vectors.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
+    PyArray1::from_vec(py, flat)
+        .reshape([vectors.len(), 3])
+        .expect("length is a multiple of 3")
+}
+
+fn check_len(name: &str, len: usize, expected: usize) -> PyResult<()> {
+    if len == expected {
+        Ok(())
+    } else {
+        Err(PyValueError::new_err(format!(
+            "{} has {} entries, but there are {} stars",
+            name, len, expected
+        )))
+    }
+}
+
+/// the stars and the parameters of one simulation
+#[pyclass]
+struct Simulation {
+    inner: galaxy_core::Simulation,
+}
+
+fn to_integrator(name: &str) -> PyResult<Integrator> {
+    name.parse().map_err(|_| {
+        let names: Vec<_> = Integrator::ALL.iter().map(|i| i.name()).collect();
+        PyValueError::new_err(format!(
+            "unknown integrator '{}', expected one of {}",
+            name,
+            names.join(", ")
+        ))
+    })
+}
+
+impl Simulation {
+    /// the stars with the default parameters except for the keyword arguments of
+    /// `from_arrays` and `load`
+    fn with_particles(
+        particles: Particles,
+        time: f64,
+        galaxy_diameter: Option<f64>,
+        integrator: Option<&str>,
+        opening_angle: Option<f64>,
+        pin_black_hole: bool,
+    ) -> PyResult<Self> {
+        let mut parameters = SimulationParameters {
+            pin_black_hole,
+            ..Default::default()
+        };
+        // like the viewer does for catalogs, the escape radius follows the diameter
+        let extent = catalog::extent(&particles.stars().collect::<Vec<_>>());
+        if extent > 0.0 {
+            parameters.galaxy_diameter = extent;
+        }
+        if let Some(galaxy_diameter) = galaxy_diameter {
+            parameters.galaxy_diameter = galaxy_diameter;
+        }
+        if let Some(integrator) = integrator {
+            parameters.integrator = to_integrator(integrator)?;
+        }
+        if let Some(opening_angle) = opening_angle {
+            parameters.opening_angle = opening_angle;
+        }
+        let mut inner = galaxy_core::Simulation::new(particles, parameters);
+        inner.time = time;
+        Ok(Self { inner })
+    }
+}
+
+#[pymethods]
+impl Simulation {
+    /// a random disk galaxy like the one the viewer starts with, the first star is the black hole
+    #[new]
+    #[pyo3(signature = (number_of_stars = 1000, seed = None))]
+    fn new(number_of_stars: usize, seed: Option<u64>) -> Self {
+        let mut builder = galaxy_core::Simulation::builder().number_of_stars(number_of_stars);
+        if let Some(seed) = seed {
+            builder = builder.seed(seed);
+        }
+        Self {
+            inner: builder.build(),
+        }
+    }
+
+    /// stars from arrays, all of them moving unless `pin_black_hole` holds the first one at
+    /// the origin as the black hole
+    ///
+    /// `galaxy_diameter` is the distance of the farthest star by default, `integrator` one
+    /// of "euler", "leapfrog", "rk4" and "hermite" and `opening_angle` 0 for the exact sum.
+    #[staticmethod]
+    #[pyo3(signature = (
+        positions,
+        velocities,
+        masses,
+        *,
+        galaxy_diameter = None,
+        integrator = None,
+        opening_angle = None,
+        pin_black_hole = false,
+    ))]
+    fn from_arrays(
+        positions: PyReadonlyArray2<f64>,
+        velocities: PyReadonlyArray2<f64>,
+        masses: PyReadonlyArray1<f64>,
+        galaxy_diameter: Option<f64>,
+        integrator: Option<&str>,
+        opening_angle: Option<f64>,
+        pin_black_hole: bool,
+    ) -> PyResult<Self> {
+        let positions = to_vectors("positions", positions.as_array())?;
+        let velocities = to_vectors("velocities", velocities.as_array())?;
+        let masses = masses.as_array();
+        check_len("velocities", velocities.len(), positions.len())?;
+        check_len("masses", masses.len(), positions.len())?;
+        let particles = positions
+            .iter()
+            .zip(&velocities)
+            .zip(masses.iter())
+            .map(|((&position, &velocity), &mass)| Star {
+                position,
+                velocity,
+                acceleration: DVec3::default(),
+                mass,
+            })
+            .collect();
+        Self::with_particles(
+            particles,
+            0.0,
+            galaxy_diameter,
+            integrator,
+            opening_angle,
+            pin_black_hole,
+        )
+    }
+
+    /// reads a snapshot written by `save` or by the viewer, with the keyword arguments of
+    /// `from_arrays`
+    #[staticmethod]
+    #[pyo3(signature = (
+        path,
+        *,
+        galaxy_diameter = None,
+        integrator = None,
+        opening_angle = None,
+        pin_black_hole = false,
+    ))]
+    fn load(
+        path: PathBuf,
+        galaxy_diameter: Option<f64>,
+        integrator: Option<&str>,
+        opening_angle: Option<f64>,
+        pin_black_hole: bool,
+    ) -> PyResult<Self> {
+        let snapshot = Snapshot::load(&path).map_err(to_py_err)?;
+        Self::with_particles(
+            snapshot.particles(),
+            snapshot.time,
+            galaxy_diameter,
+            integrator,
+            opening_angle,
+            pin_black_hole,
+        )
+    }
+
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        self.inner.snapshot().save(&path).map_err(to_py_err)
+    }
+
+    /// advances the simulation by `steps` physics steps
+    #[pyo3(signature = (steps = 1))]
+    fn step(&mut self, py: Python, steps: u64) {
+        let inner = &mut self.inner;
+        py.allow_threads(|| inner.step_n(steps));
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.particles.len()
+    }
+
+    /// simulated seconds since the start
+    #[getter]
+    fn time(&self) -> f64 {
+        self.inner.time
+    }
+
+    /// physics steps since this object was created
+    #[getter]
+    fn steps(&self) -> u64 {
+        self.inner.steps
+    }
+
+    #[getter]
+    fn positions<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        to_array(py, &self.inner.particles.positions)
+    }
+
+    #[setter]
+    fn set_positions(&mut self, positions: PyReadonlyArray2<f64>) -> PyResult<()> {
+        let positions = to_vectors("positions", positions.as_array())?;
+        check_len("positions", positions.len(), self.inner.particles.len())?;
+        self.inner.particles.positions = positions;
+        Ok(())
+    }
+
+    #[getter]
+    fn velocities<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        to_array(py, &self.inner.particles.velocities)
+    }
+
+    #[setter]
+    fn set_velocities(&mut self, velocities: PyReadonlyArray2<f64>) -> PyResult<()> {
+        let velocities = to_vectors("velocities", velocities.as_array())?;
+        check_len("velocities", velocities.len(), self.inner.particles.len())?;
+        self.inner.particles.velocities = velocities;
+        Ok(())
+    }
+
+    #[getter]
+    fn accelerations<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        to_array(py, &self.inner.particles.accelerations)
+    }
+
+    #[getter]
+    fn masses<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        PyArray1::from_slice(py, &self.inner.particles.masses)
+    }
+
+    #[setter]
+    fn set_masses(&mut self, masses: PyReadonlyArray1<f64>) -> PyResult<()> {
+        let masses = masses.as_array();
+        check_len("masses", masses.len(), self.inner.particles.len())?;
+        self.inner.particles.masses = masses.to_vec();
+        Ok(())
+    }
+
+    /// gravitational constant
+    #[getter]
+    fn g(&self) -> f64 {
+        self.inner.parameters.g
+    }
+
+    #[setter]
+    fn set_g(&mut self, g: f64) {
+        self.inner.parameters.g = g;
+    }
+
+    /// simulated seconds per physics second
+    #[getter]
+    fn time_factor(&self) -> f64 {
+        self.inner.parameters.time_factor
+    }
+
+    #[setter]
+    fn set_time_factor(&mut self, time_factor: f64) {
+        self.inner.parameters.time_factor = time_factor;
+    }
+
+    /// physics seconds per step
+    #[getter]
+    fn physics_dt(&self) -> f64 {
+        self.inner.parameters.physics_dt
+    }
+
+    #[setter]
+    fn set_physics_dt(&mut self, physics_dt: f64) {
+        self.inner.parameters.physics_dt = physics_dt;
+    }
+
+    /// Plummer softening length of the forces in meters
+    #[getter]
+    fn softening(&self) -> f64 {
+        self.inner.parameters.softening
+    }
+
+    #[setter]
+    fn set_softening(&mut self, softening: f64) {
+        self.inner.parameters.softening = softening;
+    }
+
+    /// size of the galaxy in meters, stars beyond 1.5 times it count as escaped
+    #[getter]
+    fn galaxy_diameter(&self) -> f64 {
+        self.inner.parameters.galaxy_diameter
+    }
+
+    #[setter]
+    fn set_galaxy_diameter(&mut self, galaxy_diameter: f64) {
+        self.inner.parameters.galaxy_diameter = galaxy_diameter;
+    }
+
+    /// name of the integrator, "euler", "leapfrog", "rk4" or "hermite"
+    #[getter]
+    fn integrator(&self) -> &'static str {
+        self.inner.parameters.integrator.name()
+    }
+
+    #[setter]
+    fn set_integrator(&mut self, integrator: &str) -> PyResult<()> {
+        self.inner.parameters.integrator = to_integrator(integrator)?;
+        Ok(())
+    }
+
+    /// Barnes–Hut opening angle, 0 for the exact O(n²) sum
+    #[getter]
+    fn opening_angle(&self) -> f64 {
+        self.inner.parameters.opening_angle
+    }
+
+    #[setter]
+    fn set_opening_angle(&mut self, opening_angle: f64) {
+        self.inner.parameters.opening_angle = opening_angle;
+    }
+
+    /// whether the first star is held at the origin as the black hole
+    #[getter]
+    fn pin_black_hole(&self) -> bool {
+        self.inner.parameters.pin_black_hole
+    }
+
+    #[setter]
+    fn set_pin_black_hole(&mut self, pin: bool) {
+        self.inner.parameters.pin_black_hole = pin;
+    }
+
+    /// pairs closer than this are reported as close encounters
+    #[getter]
+    fn min_gravity_distance(&self) -> f64 {
+        self.inner.parameters.min_gravity_distance
+    }
+
+    #[setter]
+    fn set_min_gravity_distance(&mut self, distance: f64) {
+        self.inner.parameters.min_gravity_distance = distance;
+    }
+}
+
+#[pymodule]
+fn galaxy(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Simulation>()
+}
@@ -0,0 +1,490 @@
+//! The whole `Galaxy` app: the window, the simulation, the camera, the UI and the optional
+//! subsystems, wired up the way the binary runs them.
+//!
+//! `GalaxyApp` holds what a run is started with: the parameters, the initial stars, the
+//! files to record to and which subsystems are on. Everything in it is already opened and
+//! checked, so `build` can't fail, it only adds the resources and plugins in the order they
+//! need: the resources the simulation plugin reads while it is added come before it, the
+//! plugins adding systems to the physics stage after it. The binary fills it from the
+//! command line, other frontends can fill it in code.
+//!
+//! ```no_run
+//! use galaxy::{app::GalaxyApp, SimulationParameters};
+//! use rand::{rngs::StdRng, SeedableRng};
+//!
+//! let mut app = GalaxyApp::new(SimulationParameters::default(), StdRng::seed_from_u64(1));
+//! app.trails = Some(100);
+//! app.build().run();
+//! ```
+
+#[cfg(feature = "audio")]
+use crate::audio;
+#[cfg(feature = "chat")]
+use crate::chat;
+#[cfg(feature = "grpc")]
+use crate::grpc;
+#[cfg(feature = "http")]
+use crate::http;
+#[cfg(feature = "ipc")]
+use crate::ipc;
+#[cfg(feature = "midi")]
+use crate::midi;
+#[cfg(feature = "osc")]
+use crate::osc;
+#[cfg(feature = "scripting")]
+use crate::scripting;
+#[cfg(feature = "websocket")]
+use crate::websocket;
+use crate::{
+    attract, black_hole, camera, capture, clumps, coloring, compare, director, energy, force_tree,
+    inspector, lagrange, launcher, merging, panel, potential, recording, replay, roche, selection,
+    snapshot, sprites,
+    state::{GalaxyRng, GalaxyState, InitialStars, InitialTime, SimulationState},
+    stats, structure, timeline, trails, video, GalaxySimulationPlugin, PHYSICS_STAGE,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::utils::Instant;
+use bevy::{app::AppExit, core::DefaultTaskPoolOptions, prelude::*};
+use galaxy_core::{
+    alembic::PointCache, distributed::Node, export::CsvExport, trajectory::TrajectoryWriter,
+    units::TimeSpan, ParticleId, SimulationParameters, Star,
+};
+use rand::rngs::StdRng;
+use std::path::PathBuf;
+
+/// the canvas of `web/index.html` the browser app draws into
+#[cfg(target_arch = "wasm32")]
+const WEB_CANVAS: &str = "#galaxy";
+
+/// factor of a `+` or `-` press on the time factor
+const TIME_SCALE_STEP: f64 = 2.0;
+
+/// a run of the app, `new` starts with every optional subsystem off
+pub struct GalaxyApp {
+    pub parameters: SimulationParameters,
+    pub rng: StdRng,
+    /// stars to start from instead of a new galaxy
+    pub initial_stars: Option<Vec<Star>>,
+    /// simulated seconds to start at
+    pub initial_time: Option<f64>,
+    /// the distributed simulation this process is a node of
+    pub node: Option<Node>,
+    pub stop_after: Option<StopAfter>,
+    pub jets: bool,
+    pub sprites: bool,
+    pub bloom: Option<f32>,
+    /// file `F5` and `F9` save to and load from
+    pub snapshot_file: Option<PathBuf>,
+    pub colormap: coloring::Colormap,
+    /// the director has the camera from the start
+    pub director: bool,
+    pub video: Option<video::VideoSettings>,
+    pub capture: Option<capture::CaptureSettings>,
+    pub replay: Option<replay::Replay>,
+    pub alembic: Option<PointCache>,
+    pub export: Option<CsvExport>,
+    pub trajectory: Option<TrajectoryWriter>,
+    pub lagrange: Option<(ParticleId, ParticleId)>,
+    pub roche: Option<(ParticleId, ParticleId)>,
+    /// the parameters of the A/B copy
+    pub compare: Option<SimulationParameters>,
+    /// linking length of the clump finder in galaxy diameters
+    pub clumps: Option<f64>,
+    /// seconds without input until the attract mode starts
+    pub attract: Option<f64>,
+    pub timeline: bool,
+    /// where the timeline is written to or read from
+    pub timeline_file: Option<timeline::TimelineFile>,
+    pub panel: bool,
+    pub structure: Option<structure::StructureLog>,
+    /// physics positions per trail
+    pub trails: Option<usize>,
+    pub energy: Option<energy::EnergySettings>,
+    pub potential: Option<potential::PotentialSettings>,
+    #[cfg(feature = "gpu")]
+    pub gpu: Option<galaxy_core::gpu::GpuGravity>,
+    #[cfg(feature = "ipc")]
+    pub publisher: Option<ipc::Publisher>,
+    #[cfg(feature = "ipc")]
+    pub viewer: Option<ipc::Viewer>,
+    #[cfg(feature = "scripting")]
+    pub script: Option<scripting::Script>,
+}
+
+impl GalaxyApp {
+    pub fn new(parameters: SimulationParameters, rng: StdRng) -> Self {
+        Self {
+            parameters,
+            rng,
+            initial_stars: None,
+            initial_time: None,
+            node: None,
+            stop_after: None,
+            jets: false,
+            sprites: false,
+            bloom: None,
+            snapshot_file: None,
+            colormap: Default::default(),
+            director: false,
+            video: None,
+            capture: None,
+            replay: None,
+            alembic: None,
+            export: None,
+            trajectory: None,
+            lagrange: None,
+            roche: None,
+            compare: None,
+            clumps: None,
+            attract: None,
+            timeline: false,
+            timeline_file: None,
+            panel: false,
+            structure: None,
+            trails: None,
+            energy: None,
+            potential: None,
+            #[cfg(feature = "gpu")]
+            gpu: None,
+            #[cfg(feature = "ipc")]
+            publisher: None,
+            #[cfg(feature = "ipc")]
+            viewer: None,
+            #[cfg(feature = "scripting")]
+            script: None,
+        }
+    }
+
+    /// the Bevy app of the run, ready to `run`
+    pub fn build(self) -> AppBuilder {
+        let mut parameters = self.parameters;
+        // nodes must keep the same stars, replays and viewers show the stars of another run
+        let following = self.replay.is_some();
+        #[cfg(feature = "ipc")]
+        let following = following || self.viewer.is_some();
+        if self.node.is_some() || following {
+            parameters.capture_radius = 0.0;
+            parameters.merge_density = 0.0;
+        }
+
+        let mut app = App::build();
+        if parameters.threads > 0 {
+            app.insert_resource(DefaultTaskPoolOptions::with_num_threads(parameters.threads));
+        }
+        if let Some(stars) = self.initial_stars {
+            app.insert_resource(InitialStars(stars));
+        }
+        if let Some(time) = self.initial_time {
+            app.insert_resource(InitialTime(time));
+        }
+        if self.jets {
+            app.insert_resource(black_hole::Jets);
+        }
+        if self.sprites {
+            // before the star assets, which leave the drawing to the sprites then
+            app.insert_resource(sprites::Sprites);
+        }
+        if let Some(path) = self.snapshot_file {
+            app.insert_resource(snapshot::SnapshotFile(path));
+        }
+        if let Some(node) = self.node {
+            app.insert_resource(node);
+        }
+        app.insert_resource(GalaxyRng(self.rng));
+        if let Some(stop) = self.stop_after {
+            app.insert_resource(stop).add_system(stop_after.system());
+        }
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = self.gpu {
+            app.insert_resource(gpu);
+        }
+        if self.director {
+            app.insert_resource(director::DirectorSettings {
+                active: true,
+                ..Default::default()
+            });
+        }
+
+        // the simulation plugin takes its steps from the video, the replay or the viewer
+        if let Some(settings) = self.video {
+            app.insert_resource(video::Video::new(settings))
+                .add_system_to_stage(CoreStage::Last, video::finish.system());
+        }
+        if let Some(replay) = self.replay {
+            app.insert_resource(replay);
+        }
+        #[cfg(feature = "ipc")]
+        if let Some(viewer) = self.viewer {
+            app.insert_resource(viewer);
+        }
+
+        app.insert_resource(Msaa { samples: 4 })
+            .insert_resource(WindowDescriptor {
+                vsync: false, // Disabled for this demo to remove vsync as a source of input latency
+                #[cfg(target_arch = "wasm32")]
+                canvas: Some(WEB_CANVAS.to_string()),
+                ..Default::default()
+            })
+            .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
+            .insert_resource(parameters.clone())
+            .add_plugins(DefaultPlugins);
+        // the browser has no wgpu, WebGL2 renders instead
+        #[cfg(feature = "web")]
+        app.add_plugin(bevy_webgl2::WebGL2Plugin);
+        app.add_plugin(GalaxySimulationPlugin)
+            .add_plugin(director::DirectorPlugin)
+            .add_plugin(camera::CameraPlugin)
+            // the picking: a click selects the star under the cursor
+            .add_plugin(selection::SelectionPlugin)
+            .add_plugin(inspector::InspectorPlugin)
+            .add_plugin(snapshot::SnapshotPlugin)
+            .add_plugin(black_hole::BlackHolePlugin)
+            .add_plugin(merging::MergingPlugin)
+            .insert_resource(self.colormap)
+            .add_plugin(coloring::ColoringPlugin)
+            .add_plugin(launcher::LauncherPlugin)
+            .add_plugin(stats::StatsPlugin)
+            .add_plugin(force_tree::ForceTreePlugin)
+            .add_plugin(lagrange::LagrangePlugin)
+            .add_startup_system(setup.system())
+            .add_startup_system(watch_assets.system())
+            .add_system(switch_integrator.system())
+            .add_system(time_control.system())
+            .add_system_to_stage(CoreStage::Last, finish_profiling_frame.system());
+        // the browser paces the frames and can't block, nor read frames back from WebGL2
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugin(capture::CapturePlugin)
+            .add_system_to_stage(CoreStage::Last, limit_frame_rate.system());
+        if let Some(cache) = self.alembic {
+            app.insert_resource(recording::Recording(Some(cache)))
+                .add_system_to_stage(CoreStage::Last, recording::finish.system())
+                .add_system_to_stage(PHYSICS_STAGE, recording::record.system().after("moving"));
+        }
+        if let Some(export) = self.export {
+            app.insert_resource(recording::Export(Some(export)))
+                .add_system_to_stage(
+                    PHYSICS_STAGE,
+                    recording::export_csv.system().after("moving"),
+                );
+        }
+        if let Some(writer) = self.trajectory {
+            app.insert_resource(recording::TrajectoryRecording(Some(writer)))
+                .add_system_to_stage(CoreStage::Last, recording::finish_trajectory.system())
+                .add_system_to_stage(
+                    PHYSICS_STAGE,
+                    recording::record_trajectory.system().after("moving"),
+                );
+        }
+        if let Some((a, b)) = self.lagrange {
+            app.insert_resource(lagrange::LagrangePair::new(a, b));
+        }
+        if let Some((a, b)) = self.roche {
+            app.insert_resource(roche::RochePair(a, b))
+                .add_plugin(roche::RochePlugin);
+        }
+        if let Some(parameters) = self.compare {
+            app.insert_resource(compare::CompareParameters(parameters))
+                .add_plugin(compare::ComparePlugin);
+        }
+        if let Some(linking_length) = self.clumps {
+            app.insert_resource(clumps::ClumpSettings::new(linking_length, &parameters))
+                .add_plugin(clumps::ClumpPlugin);
+        }
+        if let Some(idle) = self.attract {
+            app.insert_resource(attract::AttractSettings { idle })
+                .add_plugin(attract::AttractPlugin);
+        }
+        if self.timeline {
+            if let Some(file) = self.timeline_file {
+                app.insert_resource(file);
+            }
+            app.add_plugin(timeline::TimelinePlugin);
+        }
+        if self.panel {
+            app.add_plugin(panel::PanelPlugin);
+        }
+        if let Some(log) = self.structure {
+            app.insert_resource(log)
+                .add_plugin(structure::StructurePlugin);
+        }
+        if let Some(bloom) = self.bloom {
+            app.insert_resource(sprites::Bloom(bloom));
+        }
+        if self.sprites || self.bloom.is_some() {
+            app.add_plugin(sprites::SpritesPlugin);
+        }
+        if let Some(length) = self.trails {
+            app.insert_resource(trails::Trails::new(length))
+                .add_plugin(trails::TrailsPlugin);
+        }
+        if let Some(settings) = self.capture {
+            app.insert_resource(settings);
+        }
+        if let Some(settings) = self.energy {
+            app.insert_resource(settings)
+                .add_plugin(energy::EnergyPlugin);
+        }
+        if let Some(settings) = self.potential {
+            app.insert_resource(settings)
+                .add_plugin(potential::PotentialPlugin);
+        }
+        #[cfg(feature = "websocket")]
+        app.add_plugin(websocket::WebSocketPlugin);
+        #[cfg(feature = "http")]
+        app.add_plugin(http::HttpPlugin);
+        #[cfg(feature = "ipc")]
+        if let Some(publisher) = self.publisher {
+            app.insert_resource(publisher)
+                .add_system(ipc::publish.system());
+        }
+        #[cfg(feature = "osc")]
+        app.add_plugin(osc::OscPlugin);
+        #[cfg(feature = "midi")]
+        app.add_plugin(midi::MidiPlugin);
+        #[cfg(feature = "chat")]
+        app.add_plugin(chat::ChatPlugin);
+        #[cfg(feature = "grpc")]
+        app.add_plugin(grpc::GrpcPlugin);
+        #[cfg(feature = "audio")]
+        app.add_plugin(audio::AudioPlugin);
+        #[cfg(feature = "scripting")]
+        app.add_plugin(scripting::ScriptingPlugin(std::sync::Mutex::new(
+            self.script,
+        )));
+        app
+    }
+}
+
+/// physics steps until the app quits
+pub struct StopAfter(pub u64);
+
+impl StopAfter {
+    /// the steps of `duration` physics seconds
+    pub fn new(duration: f64, parameters: &SimulationParameters) -> Self {
+        Self((duration / parameters.physics_dt).round().max(1.0) as u64)
+    }
+}
+
+/// quits once the physics steps of `StopAfter` are done
+fn stop_after(
+    stop: Res<StopAfter>,
+    state: Option<Res<GalaxyState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if state.is_some_and(|state| state.steps >= stop.0) {
+        exit.send(AppExit);
+    }
+}
+
+/// set up a simple 3D scene around the stars of the simulation plugin
+fn setup(mut commands: Commands, parameters: Res<SimulationParameters>) {
+    // light
+    commands.spawn_bundle(LightBundle {
+        transform: Transform::from_xyz(-2000.0, 2000.0, 1000.0),
+        ..Default::default()
+    });
+
+    // camera
+    let transform = Transform::from_xyz(0.0, 0.0, 2500.0).looking_at(Vec3::ZERO, Vec3::Y);
+    if parameters.planar {
+        let mut camera = OrthographicCameraBundle::new_3d();
+        camera.transform = transform;
+        camera.orthographic_projection.near = 1.0;
+        camera.orthographic_projection.far = 10000.0;
+        commands
+            .spawn_bundle(camera)
+            .insert(camera::OrbitCamera::default());
+    } else {
+        let mut camera = PerspectiveCameraBundle {
+            transform,
+            ..Default::default()
+        };
+        camera.perspective_projection.near = 1.0;
+        camera.perspective_projection.far = 10000.0;
+        commands
+            .spawn_bundle(camera)
+            .insert(camera::OrbitCamera::default());
+    }
+    // for the captions and panels
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+/// with the dev feature changed assets and shaders are reloaded while running
+fn watch_assets(asset_server: Res<AssetServer>) {
+    #[cfg(feature = "dev")]
+    if let Err(e) = asset_server.watch_for_changes() {
+        warn!("can't watch the assets for changes: {:?}", e);
+    }
+    #[cfg(not(feature = "dev"))]
+    let _ = asset_server;
+}
+
+/// `I` cycles through the integrators, distributed nodes must stay in lockstep and ignore it
+fn switch_integrator(
+    keys: Res<Input<KeyCode>>,
+    node: Option<Res<Node>>,
+    mut parameters: ResMut<SimulationParameters>,
+) {
+    if keys.just_pressed(KeyCode::I) && node.is_none() {
+        parameters.integrator = parameters.integrator.next();
+        info!("integrator {}", parameters.integrator.name());
+    }
+}
+
+/// `Space` pauses and resumes, `N` takes a single step while paused and `+` and `-` double
+/// and halve the time factor, distributed nodes must stay in lockstep and ignore them
+fn time_control(
+    keys: Res<Input<KeyCode>>,
+    node: Option<Res<Node>>,
+    mut simulation: ResMut<SimulationState>,
+    mut parameters: ResMut<SimulationParameters>,
+) {
+    if node.is_some() {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Space) {
+        simulation.paused = !simulation.paused;
+        simulation.single_steps = 0;
+        info!(
+            "{}",
+            if simulation.paused {
+                "paused"
+            } else {
+                "resumed"
+            }
+        );
+    }
+    if keys.just_pressed(KeyCode::N) && simulation.paused {
+        simulation.single_steps += 1;
+    }
+    let faster = keys.just_pressed(KeyCode::Equals) || keys.just_pressed(KeyCode::NumpadAdd);
+    let slower = keys.just_pressed(KeyCode::Minus) || keys.just_pressed(KeyCode::NumpadSubtract);
+    if faster || slower {
+        let factor = if faster {
+            TIME_SCALE_STEP
+        } else {
+            1.0 / TIME_SCALE_STEP
+        };
+        parameters.time_factor *= factor;
+        info!("time factor {:.2}/s", TimeSpan(parameters.time_factor));
+    }
+}
+
+fn finish_profiling_frame() {
+    profiling::finish_frame!();
+}
+
+/// sleeps at the end of the frame to hold the target render rate, physics catches up
+/// by running as many fixed steps as needed in the next frame
+#[cfg(not(target_arch = "wasm32"))]
+fn limit_frame_rate(mut last: Local<Option<Instant>>, parameters: Res<SimulationParameters>) {
+    if parameters.render_rate > 0.0 {
+        let frame = std::time::Duration::from_secs_f64(1.0 / parameters.render_rate);
+        if let Some(elapsed) = last.map(|last| last.elapsed()) {
+            if elapsed < frame {
+                std::thread::sleep(frame - elapsed);
+            }
+        }
+    }
+    *last = Some(Instant::now());
+}
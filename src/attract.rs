@@ -0,0 +1,145 @@
+//! Attract mode: after a while without input the app runs itself like a screensaver.
+//!
+//! `--attract <seconds>` is the idle time until it starts. Every scenario is a new
+//! random galaxy with a random number of stars and spin, shown alternately by the camera
+//! orbit and by the director, and each scenario colors the stars by the next `ColorMode`,
+//! leaving out the galaxy a star started in, a scenario is one galaxy. While uniform the
+//! clump colors show, when clumps are searched. Any key, mouse button or mouse movement
+//! hands the app back with the camera and colors as they were before.
+
+use crate::{
+    clumps::ClumpSettings,
+    coloring::ColorMode,
+    director::DirectorSettings,
+    spawning::SpawnRequests,
+    state::{GalaxyRng, GalaxyState},
+};
+use bevy::{input::mouse::MouseMotion, prelude::*};
+use galaxy_core::{initial_conditions, SimulationParameters};
+use rand::Rng;
+
+/// seconds per scenario
+const SCENARIO_LENGTH: f64 = 60.0;
+/// camera orbit speed in radians per second while attracting
+const ORBIT_SPEED: f32 = 0.1;
+
+pub struct AttractSettings {
+    /// seconds without input until the attract mode starts
+    pub idle: f64,
+}
+
+/// the settings to restore when the user is back
+struct Saved {
+    director: bool,
+    camera_speed: f32,
+    color_mode: ColorMode,
+    clumps_colored: Option<bool>,
+}
+
+#[derive(Default)]
+struct Attract {
+    idle: f64,
+    /// in the current scenario, it starts when this reaches the scenario length
+    elapsed: f64,
+    scenarios: u64,
+    saved: Option<Saved>,
+}
+
+pub struct AttractPlugin;
+
+impl Plugin for AttractPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Attract>().add_system(attract.system());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn attract(
+    time: Res<Time>,
+    settings: Res<AttractSettings>,
+    mut attract: ResMut<Attract>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut director: ResMut<DirectorSettings>,
+    mut parameters: ResMut<SimulationParameters>,
+    mut color_mode: ResMut<ColorMode>,
+    mut clumps: Option<ResMut<ClumpSettings>>,
+    mut requests: ResMut<SpawnRequests>,
+    state: Res<GalaxyState>,
+    mut rng: ResMut<GalaxyRng>,
+) {
+    let input = keys.get_just_pressed().next().is_some()
+        || buttons.get_just_pressed().next().is_some()
+        || motion.iter().next().is_some();
+    if input {
+        attract.idle = 0.0;
+        if let Some(saved) = attract.saved.take() {
+            info!("attract mode off");
+            director.active = saved.director;
+            parameters.camera_speed = saved.camera_speed;
+            *color_mode = saved.color_mode;
+            if let (Some(clumps), Some(colored)) = (clumps.as_mut(), saved.clumps_colored) {
+                clumps.colored = colored;
+            }
+        }
+        return;
+    }
+
+    let delta = time.delta_seconds_f64();
+    attract.idle += delta;
+    if attract.saved.is_none() {
+        if attract.idle < settings.idle {
+            return;
+        }
+        info!("attract mode on");
+        attract.saved = Some(Saved {
+            director: director.active,
+            camera_speed: parameters.camera_speed,
+            color_mode: *color_mode,
+            clumps_colored: clumps.as_ref().map(|clumps| clumps.colored),
+        });
+        // the first scenario starts right away
+        attract.elapsed = SCENARIO_LENGTH;
+    }
+    attract.elapsed += delta;
+    if attract.elapsed < SCENARIO_LENGTH {
+        return;
+    }
+    attract.elapsed = 0.0;
+    attract.scenarios += 1;
+
+    // everything but the black hole is replaced by a new galaxy
+    let rng = &mut rng.0;
+    let scenario = SimulationParameters {
+        number_of_stars: rng.gen_range(parameters.number_of_stars / 2..=parameters.number_of_stars),
+        spin_factor: parameters.spin_factor * rng.gen_range(0.5..2.0),
+        ..parameters.clone()
+    };
+    requests
+        .despawn
+        .extend(state.particles.ids.iter().skip(1).copied());
+    requests.spawn.extend(
+        initial_conditions::generate(rng, &scenario)
+            .into_iter()
+            .skip(1),
+    );
+
+    let directed = attract.scenarios.is_multiple_of(2);
+    director.active = directed;
+    parameters.camera_speed = ORBIT_SPEED;
+    let mut next = color_mode.next();
+    if next == ColorMode::Galaxy {
+        next = next.next();
+    }
+    *color_mode = next;
+    if let Some(clumps) = clumps.as_mut() {
+        clumps.colored = true;
+    }
+    info!(
+        "attract mode: {} stars, spin factor {:.2e}, colored by {}",
+        scenario.number_of_stars,
+        scenario.spin_factor,
+        color_mode.name()
+    );
+}
@@ -0,0 +1,327 @@
+//! Turns the simulation into sound, for installations and for listening instead of watching.
+//!
+//! * a low rumble that grows with the density of the core
+//! * a chime for every merger
+//! * a tone whose pitch follows the orbital speed of the selected star
+//!
+//! `M` mutes and unmutes, `Page Up` and `Page Down` change the volume. `GALAXY_VOLUME` sets the volume at
+//! the start, from 0 to 1. The sound is synthesized on the audio thread, the app only
+//! updates a few shared controls.
+
+use crate::{events::MergeEvent, state::GalaxyState};
+use bevy::prelude::*;
+use galaxy_core::{diagnostics, ParticleId, SimulationParameters};
+use rodio::{OutputStream, Sink, Source};
+use std::{
+    f32::consts::TAU,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+const SAMPLE_RATE: u32 = 44100;
+/// samples between reads of the controls
+const CONTROL_PERIOD: u32 = 256;
+/// pitch of the selected star when it moves at its initial speed
+const BASE_PITCH: f32 = 220.0;
+
+pub struct AudioSettings {
+    /// from 0 to 1
+    pub volume: f32,
+    /// radius of the core for the density, in galaxy diameters
+    pub core_radius: f64,
+    /// star whose speed sets the pitch
+    pub selected: ParticleId,
+    /// control updates per second
+    pub rate: f64,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        let volume = std::env::var("GALAXY_VOLUME")
+            .ok()
+            .and_then(|volume| volume.parse().ok())
+            .unwrap_or(0.5f32);
+        Self {
+            volume: volume.clamp(0.0, 1.0),
+            core_radius: 0.1,
+            selected: ParticleId(1),
+            rate: 20.0,
+        }
+    }
+}
+
+/// f32 values shared with the audio thread
+#[derive(Default)]
+struct Controls {
+    volume: AtomicU32,
+    muted: AtomicBool,
+    /// loudness of the rumble from 0 to 1
+    rumble: AtomicU32,
+    /// frequency of the tone in Hz, 0 while there is no selected star
+    pitch: AtomicU32,
+    /// mergers so far, every increment rings a chime
+    chimes: AtomicU32,
+}
+
+fn store(value: &AtomicU32, f: f32) {
+    value.store(f.to_bits(), Ordering::Relaxed);
+}
+
+fn load(value: &AtomicU32) -> f32 {
+    f32::from_bits(value.load(Ordering::Relaxed))
+}
+
+struct AudioControls(Arc<Controls>);
+
+/// the values the quantities are compared to, taken when they are first seen
+#[derive(Default)]
+struct Baseline {
+    density: Option<f64>,
+    speed: Option<f64>,
+}
+
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<AudioSettings>()
+            .add_startup_system(start_audio.system())
+            .add_system(update_controls.system())
+            .add_system(ring_chimes.system())
+            .add_system(keyboard_controls.system());
+    }
+}
+
+fn start_audio(mut commands: Commands, settings: Res<AudioSettings>) {
+    let controls = Arc::new(Controls::default());
+    store(&controls.volume, settings.volume);
+    let synth = Synth::new(controls.clone());
+    // the output stream can't leave the thread it was created on
+    thread::spawn(move || {
+        let (_stream, handle) = match OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                error!("no audio output: {}", e);
+                return;
+            }
+        };
+        match Sink::try_new(&handle) {
+            Ok(sink) => {
+                sink.append(synth);
+                sink.sleep_until_end();
+            }
+            Err(e) => error!("can't play audio: {}", e),
+        }
+    });
+    commands.insert_resource(AudioControls(controls));
+}
+
+fn update_controls(
+    time: Res<Time>,
+    mut since_last: Local<f64>,
+    mut baseline: Local<Baseline>,
+    controls: Res<AudioControls>,
+    settings: Res<AudioSettings>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+) {
+    *since_last += time.delta_seconds_f64();
+    if *since_last < 1.0 / settings.rate {
+        return;
+    }
+    *since_last = 0.0;
+
+    let particles = &state.particles;
+    let density =
+        diagnostics::core_density(particles, settings.core_radius * parameters.galaxy_diameter);
+    let initial = *baseline.density.get_or_insert(density);
+    let rumble = if initial > 0.0 {
+        0.5 * density / initial
+    } else {
+        0.0
+    };
+    store(&controls.0.rumble, rumble.min(1.0) as f32);
+
+    let pitch = match particles.index_of(settings.selected) {
+        Some(i) => {
+            let speed = particles.velocities[i].length();
+            let initial = *baseline.speed.get_or_insert(speed);
+            if initial > 0.0 {
+                (BASE_PITCH * (speed / initial) as f32).clamp(55.0, 1760.0)
+            } else {
+                BASE_PITCH
+            }
+        }
+        None => 0.0,
+    };
+    store(&controls.0.pitch, pitch);
+}
+
+fn ring_chimes(controls: Res<AudioControls>, mut merges: EventReader<MergeEvent>) {
+    let count = merges.iter().count() as u32;
+    if count > 0 {
+        controls.0.chimes.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+fn keyboard_controls(
+    keys: Res<Input<KeyCode>>,
+    controls: Res<AudioControls>,
+    mut settings: ResMut<AudioSettings>,
+) {
+    if keys.just_pressed(KeyCode::M) {
+        let muted = !controls.0.muted.load(Ordering::Relaxed);
+        controls.0.muted.store(muted, Ordering::Relaxed);
+        info!("audio {}", if muted { "muted" } else { "on" });
+    }
+    // `+` and `-` belong to the time factor
+    let louder = keys.just_pressed(KeyCode::PageUp);
+    let quieter = keys.just_pressed(KeyCode::PageDown);
+    if louder || quieter {
+        let step = if louder { 0.1 } else { -0.1 };
+        settings.volume = (settings.volume + step).clamp(0.0, 1.0);
+        store(&controls.0.volume, settings.volume);
+        info!("audio volume {:.1}", settings.volume);
+    }
+}
+
+/// a struck bell, its partials decay at different rates
+struct Chime {
+    age: u32,
+}
+
+impl Chime {
+    const PARTIALS: [(f32, f32, f32); 3] =
+        [(880.0, 1.0, 1.5), (2429.0, 0.5, 2.5), (4752.0, 0.25, 4.0)];
+    const LENGTH: u32 = 4 * SAMPLE_RATE;
+
+    fn sample(&self) -> f32 {
+        let t = self.age as f32 / SAMPLE_RATE as f32;
+        Self::PARTIALS
+            .iter()
+            .map(|&(frequency, amplitude, decay)| {
+                amplitude * (-decay * t).exp() * (TAU * frequency * t).sin()
+            })
+            .sum()
+    }
+}
+
+/// endless mono source reading its parameters from the controls
+struct Synth {
+    controls: Arc<Controls>,
+    until_control: u32,
+    volume: f32,
+    /// current and target value
+    rumble: (f32, f32),
+    /// current and target value
+    pitch: (f32, f32),
+    chimes_seen: u32,
+    chimes: Vec<Chime>,
+    /// xorshift state of the noise
+    noise: u32,
+    brown: f32,
+    rumble_phase: f32,
+    tone_phase: f32,
+}
+
+impl Synth {
+    fn new(controls: Arc<Controls>) -> Self {
+        Self {
+            controls,
+            until_control: 0,
+            volume: 0.0,
+            rumble: (0.0, 0.0),
+            pitch: (0.0, 0.0),
+            chimes_seen: 0,
+            chimes: Vec::new(),
+            noise: 0x1234_5678,
+            brown: 0.0,
+            rumble_phase: 0.0,
+            tone_phase: 0.0,
+        }
+    }
+
+    fn read_controls(&mut self) {
+        let controls = &self.controls;
+        self.volume = if controls.muted.load(Ordering::Relaxed) {
+            0.0
+        } else {
+            load(&controls.volume)
+        };
+        self.rumble.1 = load(&controls.rumble);
+        self.pitch.1 = load(&controls.pitch);
+        let chimes = controls.chimes.load(Ordering::Relaxed);
+        // a burst of mergers rings a few chimes, not hundreds
+        for _ in 0..chimes.wrapping_sub(self.chimes_seen).min(4) {
+            self.chimes.push(Chime { age: 0 });
+        }
+        self.chimes_seen = chimes;
+    }
+
+    fn white_noise(&mut self) -> f32 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+impl Iterator for Synth {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.until_control == 0 {
+            self.read_controls();
+            self.until_control = CONTROL_PERIOD;
+        }
+        self.until_control -= 1;
+
+        // glide towards the targets instead of jumping, jumps click
+        let glide = 0.0005;
+        self.rumble.0 += (self.rumble.1 - self.rumble.0) * glide;
+        self.pitch.0 += (self.pitch.1 - self.pitch.0) * glide;
+        let dt = 1.0 / SAMPLE_RATE as f32;
+
+        let white = self.white_noise();
+        self.brown = (self.brown + 0.02 * white) / 1.02;
+        self.rumble_phase = (self.rumble_phase + 38.0 * dt) % 1.0;
+        let rumble = self.rumble.0 * (2.0 * self.brown + 0.3 * (TAU * self.rumble_phase).sin());
+
+        let mut tone = 0.0;
+        if self.pitch.1 > 0.0 {
+            self.tone_phase = (self.tone_phase + self.pitch.0 * dt) % 1.0;
+            tone = 0.15 * (TAU * self.tone_phase).sin();
+        }
+
+        let mut chimes = 0.0;
+        for chime in &mut self.chimes {
+            chimes += 0.2 * chime.sample();
+            chime.age += 1;
+        }
+        self.chimes.retain(|chime| chime.age < Chime::LENGTH);
+
+        Some(((rumble + tone + chimes) * self.volume).clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for Synth {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
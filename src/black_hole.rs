@@ -0,0 +1,181 @@
+//! The black hole in the center swallows stars that come too close and shows it.
+//!
+//! With a `capture_radius` in the config, stars closer to the black hole are removed and
+//! their mass is added to it, each capture raises an `AccretionEvent` and lights up a flash
+//! around the hole. `--jets` adds two flickering jets along the rotation axis.
+
+use crate::{
+    events::AccretionEvent,
+    spawning::SpawnRequests,
+    state::{GalaxyState, SimulationState},
+};
+use bevy::prelude::*;
+use galaxy_core::{accretion, SimulationParameters};
+
+/// seconds a capture flash lasts
+const FLASH_TIME: f32 = 0.6;
+/// size of a flash at its largest, in star cubes
+const FLASH_SCALE: f32 = 8.0;
+/// length of a jet in render units
+const JET_LENGTH: f32 = 150.0;
+
+/// the jets are only shown when this resource exists
+pub struct Jets;
+
+struct BlackHoleAssets {
+    flash_mesh: Handle<Mesh>,
+    flash_material: Handle<StandardMaterial>,
+}
+
+/// a growing and fading flash around the black hole, seconds since the capture
+struct Flash(f32);
+
+/// one of the jets, 1 pointing up and -1 down
+struct Jet(f32);
+
+pub struct BlackHolePlugin;
+
+impl Plugin for BlackHolePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system_to_stage(
+                crate::PHYSICS_STAGE,
+                accrete
+                    .system()
+                    .label("accretion")
+                    .after("moving")
+                    .before("spawn"),
+            )
+            .add_system(flash.system().after("sync"))
+            .add_system(jets.system().after("sync"));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    jets: Option<Res<Jets>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let flash_mesh = meshes.add(Mesh::from(shape::Icosphere {
+        radius: 1.5,
+        subdivisions: 2,
+    }));
+    let flash_material = materials.add(StandardMaterial {
+        base_color: Color::BLACK,
+        emissive: Color::rgb(8.0, 6.0, 3.0),
+        unlit: true,
+        ..Default::default()
+    });
+    if jets.is_some() {
+        let mesh = meshes.add(Mesh::from(shape::Box::new(1.5, JET_LENGTH, 1.5)));
+        let material = materials.add(StandardMaterial {
+            base_color: Color::BLACK,
+            emissive: Color::rgb(1.5, 2.5, 8.0),
+            unlit: true,
+            ..Default::default()
+        });
+        for direction in [1.0, -1.0] {
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    ..Default::default()
+                })
+                .insert(Jet(direction));
+        }
+    }
+    commands.insert_resource(BlackHoleAssets {
+        flash_mesh,
+        flash_material,
+    });
+}
+
+/// swallows the stars within the capture radius after a physics step
+fn accrete(
+    mut commands: Commands,
+    mut state: ResMut<GalaxyState>,
+    mut requests: ResMut<SpawnRequests>,
+    mut accretions: EventWriter<AccretionEvent>,
+    parameters: Res<SimulationParameters>,
+    simulation: Res<SimulationState>,
+    assets: Res<BlackHoleAssets>,
+) {
+    if !simulation.stepped {
+        return;
+    }
+    let captured = accretion::captured(&state.particles, &parameters);
+    if captured.is_empty() {
+        return;
+    }
+    let state = &mut *state;
+    for i in captured {
+        let mass = state.particles.masses[i];
+        state.particles.masses[0] += mass;
+        requests.despawn.push(state.particles.ids[i]);
+        accretions.send(AccretionEvent {
+            black_hole: state.entities[0],
+            star: state.entities[i],
+            mass,
+        });
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: assets.flash_mesh.clone(),
+                material: assets.flash_material.clone(),
+                ..Default::default()
+            })
+            .insert(Flash(0.0));
+    }
+}
+
+/// the position of the black hole entity in the scene
+fn black_hole_translation(
+    state: &GalaxyState,
+    transforms: &Query<&Transform, (Without<Flash>, Without<Jet>)>,
+) -> Option<Vec3> {
+    let entity = *state.entities.first()?;
+    transforms.get(entity).ok().map(|t| t.translation)
+}
+
+fn flash(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<GalaxyState>,
+    transforms: Query<&Transform, (Without<Flash>, Without<Jet>)>,
+    mut flashes: Query<(Entity, &mut Flash, &mut Transform)>,
+) {
+    let center = black_hole_translation(&state, &transforms).unwrap_or_default();
+    for (entity, mut flash, mut transform) in flashes.iter_mut() {
+        flash.0 += time.delta_seconds();
+        if flash.0 >= FLASH_TIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // quickly up to full size, then shrinking away
+        let t = flash.0 / FLASH_TIME;
+        let size = FLASH_SCALE * (t * 4.0).min(1.0) * (1.0 - t);
+        transform.translation = center;
+        transform.scale = Vec3::splat(size.max(0.01));
+    }
+}
+
+fn jets(
+    time: Res<Time>,
+    state: Res<GalaxyState>,
+    transforms: Query<&Transform, (Without<Flash>, Without<Jet>)>,
+    mut jets: Query<(&Jet, &mut Transform)>,
+) {
+    let center = match black_hole_translation(&state, &transforms) {
+        Some(center) => center,
+        None => return,
+    };
+    let seconds = time.seconds_since_startup() as f32;
+    for (jet, mut transform) in jets.iter_mut() {
+        // both flicker a little out of step
+        let flicker = 0.85 + 0.15 * (seconds * 13.0 + jet.0 * 1.7).sin();
+        let length = JET_LENGTH * flicker;
+        transform.scale = Vec3::new(1.0, flicker, 1.0);
+        transform.translation = center + Vec3::new(0.0, 0.0, jet.0 * length / 2.0);
+        transform.rotation = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+    }
+}
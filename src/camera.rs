@@ -0,0 +1,383 @@
+//! The user controlled cameras: the orbit around the galaxy and a free-flying camera.
+//!
+//! The orbit turns by `camera_speed` on its own. Dragging with the left mouse button
+//! rotates it around its center, dragging with the right button pans the center and the
+//! mouse wheel zooms. Drags and the wheel over the UI panels are left to the panels.
+//!
+//! Selecting a star makes the orbit follow it, centered on the star while it moves, until
+//! the selection is cleared. Panning is off while following.
+//!
+//! `F` switches between the orbit and the fly camera. The fly camera starts where the orbit
+//! left it: `W` and `S` move forward and back, `A` and `D` sideways, `Q` and `E` down and up, moving the mouse
+//! with the right button held looks around. `Shift` flies faster, `Ctrl` slower. The
+//! director takes over from either camera while it is active, and flying turns it off.
+//!
+//! The render frame floats with the camera: once the orbit center is off the render
+//! origin by a tenth of the orbit distance, or the fly camera by `FLY_RECENTER`, the frame
+//! moves its origin there, and zooming in below `RESCALE_NEAR` or out beyond `RESCALE_FAR`
+//! rescales the frame so the orbit is `FRAME_DISTANCE` units again. The camera moves with
+//! the frame, so the view doesn't change, but the stars around it are always small f32
+//! numbers: close-ups down to `MAX_ZOOM` times the whole galaxy stay sharp, far from the
+//! center as well. Zooming out stops at the scale of the start, and the director gets that
+//! frame back.
+//!
+//! A planar run looks straight down on the plane with an orthographic camera instead, see
+//! `SimulationParameters::planar`: the orbit only turns around the z axis, the wheel
+//! zooms by the size of the view, and there is no fly camera and no director.
+
+use crate::{
+    director::DirectorSettings, render_frame::RenderFrame, selection::Selection, state::GalaxyState,
+};
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::camera::{Camera, CameraProjection, OrthographicProjection, PerspectiveProjection},
+};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+/// fly speed in render units per second
+const FLY_SPEED: f32 = 500.0;
+/// speed factor while `Shift` or `Ctrl` is held
+const FAST_FACTOR: f32 = 5.0;
+const SLOW_FACTOR: f32 = 0.2;
+/// radians per pixel of mouse movement
+const LOOK_SENSITIVITY: f32 = 0.003;
+/// the fly camera can't look straight up or down, where its yaw is undefined
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+/// zoom factor per line of the mouse wheel
+const ZOOM_STEP: f32 = 1.1;
+/// pixels of a touchpad scroll that count as one line
+const PIXELS_PER_LINE: f32 = 20.0;
+/// orbit distances in render units, the far limit stays inside the far plane
+const MIN_DISTANCE: f32 = 20.0;
+const MAX_DISTANCE: f32 = 8000.0;
+/// following a star zooms in to at least this distance
+const FOLLOW_DISTANCE: f32 = 400.0;
+/// orbit distances that rescale the render frame, and the distance after it
+const RESCALE_NEAR: f32 = 250.0;
+const RESCALE_FAR: f32 = 4000.0;
+const FRAME_DISTANCE: f32 = 1000.0;
+/// the largest render frame scale relative to the one at the start
+const MAX_ZOOM: f64 = 1e9;
+/// render units the fly camera goes from the origin before the frame follows it
+const FLY_RECENTER: f32 = 2000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CameraMode {
+    #[default]
+    Orbit,
+    /// the orbit centered on the selected star
+    Follow,
+    /// free flight, the angles are those of the view direction, around and above the
+    /// galaxy plane
+    Fly { yaw: f32, pitch: f32 },
+}
+
+/// the orbit of a camera around `center`, in render units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamera {
+    pub center: Vec3,
+    pub distance: f32,
+    /// angle around the z axis
+    pub azimuth: f32,
+    /// angle above the galaxy plane
+    pub elevation: f32,
+}
+
+impl Default for OrbitCamera {
+    /// the whole galaxy seen from 45° above the plane
+    fn default() -> Self {
+        Self {
+            center: Vec3::ZERO,
+            distance: 2500.0 * 2f32.sqrt(),
+            azimuth: 0.0,
+            elevation: FRAC_PI_4,
+        }
+    }
+}
+
+impl OrbitCamera {
+    pub fn eye(&self) -> Vec3 {
+        let direction = Vec3::new(
+            self.elevation.cos() * self.azimuth.cos(),
+            self.elevation.cos() * self.azimuth.sin(),
+            self.elevation.sin(),
+        );
+        self.center + direction * self.distance
+    }
+}
+
+/// the ray through the cursor, its origin on the near plane and its unit direction, for
+/// the perspective and the orthographic camera
+pub fn cursor_ray(
+    windows: &Windows,
+    camera: &Camera,
+    transform: &GlobalTransform,
+) -> Option<(Vec3, Vec3)> {
+    let window = windows.get(camera.window)?;
+    let cursor = window.cursor_position()?;
+    let ndc = Vec2::new(
+        cursor.x / window.width() * 2.0 - 1.0,
+        cursor.y / window.height() * 2.0 - 1.0,
+    );
+    let to_world = transform.compute_matrix() * camera.projection_matrix.inverse();
+    let near = to_world.project_point3(ndc.extend(0.0));
+    let farther = to_world.project_point3(ndc.extend(0.5));
+    Some((near, (farther - near).normalize()))
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CameraMode>()
+            .add_system(float_origin.system().before("sync"))
+            .add_system(switch_mode.system().before("camera"))
+            .add_system(
+                follow_selection
+                    .system()
+                    .after("selection")
+                    .before("camera"),
+            )
+            // after the star transforms, so the followed star doesn't lag a frame behind
+            .add_system(orbit.system().label("camera").after("sync"))
+            .add_system(fly.system().label("camera").after("sync"))
+            .add_system(zoom_orthographic.system().after("camera"));
+    }
+}
+
+fn switch_mode(
+    keys: Res<Input<KeyCode>>,
+    parameters: Res<galaxy_core::SimulationParameters>,
+    mut mode: ResMut<CameraMode>,
+    mut director: ResMut<DirectorSettings>,
+    cameras: Query<&Transform, With<PerspectiveProjection>>,
+) {
+    if !keys.just_pressed(KeyCode::F) || parameters.planar {
+        return;
+    }
+    *mode = match *mode {
+        CameraMode::Orbit | CameraMode::Follow => {
+            let forward = cameras
+                .single()
+                .map_or(-Vec3::Z, |transform| transform.rotation * -Vec3::Z);
+            director.active = false;
+            info!("fly camera");
+            CameraMode::Fly {
+                yaw: forward.y.atan2(forward.x),
+                pitch: forward
+                    .z
+                    .clamp(-1.0, 1.0)
+                    .asin()
+                    .clamp(-MAX_PITCH, MAX_PITCH),
+            }
+        }
+        CameraMode::Fly { .. } => {
+            info!("orbit camera");
+            CameraMode::Orbit
+        }
+    };
+}
+
+/// a new selection starts following it, clearing the selection ends following
+fn follow_selection(
+    selection: Res<Selection>,
+    mut mode: ResMut<CameraMode>,
+    mut director: ResMut<DirectorSettings>,
+    mut cameras: Query<&mut OrbitCamera>,
+) {
+    if selection.is_changed() && selection.0.is_some() {
+        if *mode != CameraMode::Follow {
+            info!("camera follows the selected star");
+        }
+        *mode = CameraMode::Follow;
+        director.active = false;
+        if let Ok(mut orbit) = cameras.single_mut() {
+            orbit.distance = orbit.distance.min(FOLLOW_DISTANCE);
+        }
+    } else if selection.0.is_none() && *mode == CameraMode::Follow {
+        info!("orbit camera");
+        *mode = CameraMode::Orbit;
+    }
+}
+
+/// moves and rescales the render frame around the camera of the last frame, before the
+/// star transforms are written
+fn float_origin(
+    mut home: Local<Option<RenderFrame>>,
+    mode: Res<CameraMode>,
+    director: Res<DirectorSettings>,
+    mut frame: ResMut<RenderFrame>,
+    mut cameras: Query<(&mut Transform, &mut OrbitCamera)>,
+) {
+    // the frame of the first update, after the startup systems placed it
+    let home = *home.get_or_insert(*frame);
+    let (mut transform, mut orbit) = match cameras.single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let old = *frame;
+    let mut new = old;
+    if director.active {
+        // the shots of the director are measured in the frame of the start
+        new = home;
+    } else if let CameraMode::Fly { .. } = *mode {
+        if transform.translation.length() > FLY_RECENTER {
+            new.origin = old.physics_position(transform.translation);
+        }
+    } else {
+        let rescale = orbit.distance < RESCALE_NEAR
+            || (orbit.distance > RESCALE_FAR && old.scale > home.scale);
+        if rescale || orbit.center.length() > 0.1 * orbit.distance {
+            new.origin = old.physics_position(orbit.center);
+        }
+        if rescale {
+            let distance = orbit.distance as f64 / old.scale;
+            new.scale = (FRAME_DISTANCE as f64 / distance).clamp(home.scale, home.scale * MAX_ZOOM);
+        }
+    }
+    if new.origin == old.origin && new.scale == old.scale {
+        return;
+    }
+    let moved = |p: Vec3| new.render_position(old.physics_position(p));
+    transform.translation = moved(transform.translation);
+    orbit.center = moved(orbit.center);
+    orbit.distance = (orbit.distance as f64 * new.scale / old.scale) as f32;
+    *frame = new;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn orbit(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    interactions: Query<&Interaction>,
+    mut cameras: Query<(&mut Transform, &mut OrbitCamera)>,
+    parameters: Res<galaxy_core::SimulationParameters>,
+    director: Res<DirectorSettings>,
+    mode: Res<CameraMode>,
+    selection: Res<Selection>,
+    state: Res<GalaxyState>,
+    stars: Query<&Transform, Without<OrbitCamera>>,
+) {
+    let dragged: Vec2 = motion.iter().map(|e| &e.delta).sum();
+    let lines: f32 = wheel
+        .iter()
+        .map(|e| match e.unit {
+            MouseScrollUnit::Line => e.y,
+            MouseScrollUnit::Pixel => e.y / PIXELS_PER_LINE,
+        })
+        .sum();
+    let following = *mode == CameraMode::Follow;
+    if director.active || !(following || *mode == CameraMode::Orbit) {
+        return;
+    }
+    let (mut transform, mut orbit) = match cameras.single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let over_ui = interactions.iter().any(|i| *i != Interaction::None);
+    // `Shift` with the left button is the slingshot
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    if !over_ui {
+        if buttons.pressed(MouseButton::Left) && !shift {
+            orbit.azimuth -= dragged.x * LOOK_SENSITIVITY;
+            orbit.elevation =
+                (orbit.elevation + dragged.y * LOOK_SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        if buttons.pressed(MouseButton::Right) && !following {
+            // one pixel moves the center about one pixel on the screen
+            let right = transform.rotation * Vec3::X;
+            let up = transform.rotation * Vec3::Y;
+            let scale = orbit.distance * LOOK_SENSITIVITY * 0.5;
+            orbit.center += (up * dragged.y - right * dragged.x) * scale;
+        }
+        orbit.distance =
+            (orbit.distance * ZOOM_STEP.powf(-lines)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+    orbit.azimuth = (orbit.azimuth + time.delta_seconds() * parameters.camera_speed) % (2.0 * PI);
+    if following {
+        let followed = selection
+            .0
+            .and_then(|id| state.particles.index_of(id))
+            .and_then(|index| stars.get(state.entities[index]).ok());
+        if let Some(star) = followed {
+            orbit.center = star.translation;
+        }
+    }
+
+    if parameters.planar {
+        // the top of the screen points where a tilted orbit would look
+        let up = -Vec3::new(orbit.azimuth.cos(), orbit.azimuth.sin(), 0.0);
+        transform.translation = orbit.center + Vec3::Z * orbit.distance;
+        transform.look_at(orbit.center, up);
+    } else {
+        transform.translation = orbit.eye();
+        transform.look_at(orbit.center, Vec3::Z);
+    }
+}
+
+/// the orthographic camera shows as much of the plane as the perspective camera at the
+/// orbit distance
+fn zoom_orthographic(
+    mut cameras: Query<
+        (&OrbitCamera, &mut OrthographicProjection, &mut Camera),
+        Changed<OrbitCamera>,
+    >,
+) {
+    for (orbit, mut projection, mut camera) in cameras.iter_mut() {
+        projection.scale = orbit.distance * (PerspectiveProjection::default().fov * 0.5).tan();
+        camera.projection_matrix = projection.get_projection_matrix();
+    }
+}
+
+fn fly(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut mode: ResMut<CameraMode>,
+    director: Res<DirectorSettings>,
+    mut camera_transforms: Query<&mut Transform, With<PerspectiveProjection>>,
+) {
+    let (yaw, pitch) = match &mut *mode {
+        CameraMode::Fly { yaw, pitch } => (yaw, pitch),
+        _ => return,
+    };
+    let looked: Vec2 = motion.iter().map(|e| &e.delta).sum();
+    if director.active {
+        return;
+    }
+    if buttons.pressed(MouseButton::Right) {
+        *yaw -= looked.x * LOOK_SENSITIVITY;
+        *pitch = (*pitch - looked.y * LOOK_SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+    let forward = Vec3::new(
+        pitch.cos() * yaw.cos(),
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+    );
+    let right = forward.cross(Vec3::Z).normalize();
+
+    let axis = |positive: KeyCode, negative: KeyCode| {
+        keys.pressed(positive) as i32 as f32 - keys.pressed(negative) as i32 as f32
+    };
+    let direction = forward * axis(KeyCode::W, KeyCode::S)
+        + right * axis(KeyCode::D, KeyCode::A)
+        + Vec3::Z * axis(KeyCode::E, KeyCode::Q);
+    let mut speed = FLY_SPEED;
+    if keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift) {
+        speed *= FAST_FACTOR;
+    }
+    if keys.pressed(KeyCode::LControl) || keys.pressed(KeyCode::RControl) {
+        speed *= SLOW_FACTOR;
+    }
+
+    if let Ok(mut transform) = camera_transforms.single_mut() {
+        transform.translation += direction * (speed * time.delta_seconds());
+        let target = transform.translation + forward;
+        transform.look_at(target, Vec3::Z);
+    }
+}
@@ -0,0 +1,354 @@
+//! Screenshots and frame captures as PNG files, for pictures and time-lapse videos.
+//!
+//! `F12` saves the current view as `screenshot-<milliseconds>.png` into the working
+//! directory. `--capture-every N` saves every `N`th rendered frame as `frame-00000.png`,
+//! `frame-00001.png` and so on into the `--capture` directory, `frames` by default, which
+//! `ffmpeg -i frames/frame-%05d.png galaxy.mp4` turns into a video.
+//!
+//! The window's swap chain can't be read back in Bevy 0.5, so a captured frame is drawn a
+//! second time by a pass of the render graph into a texture of the window size, and copied
+//! into a buffer. The next frame maps the buffer and a thread writes the file, frames that
+//! aren't captured cost nothing. The pass draws the 3D scene with the main pass entities,
+//! so the text and the buttons of the UI aren't in the pictures. The frames of `--video`
+//! come from here as well.
+
+use crate::{state::GalaxyState, video::Video};
+use bevy::{
+    prelude::*,
+    render::{
+        pass::{
+            LoadOp, Operations, PassDescriptor, RenderPassDepthStencilAttachmentDescriptor,
+            TextureAttachment,
+        },
+        render_graph::{
+            base::{self, MainPass},
+            Node, PassNode, RenderGraph, ResourceSlotInfo, ResourceSlots, WindowTextureNode,
+        },
+        renderer::{
+            BufferId, BufferInfo, BufferMapMode, BufferUsage, RenderContext, RenderResourceContext,
+        },
+        texture::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsage},
+    },
+    window::WindowId,
+};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const CAPTURE_PASS: &str = "capture_pass";
+const CAPTURE_TEXTURE: &str = "capture_texture";
+/// a pixel of the window texture format
+const BYTES_PER_PIXEL: usize = 4;
+
+/// the numbered frames of `--capture-every`
+pub struct CaptureSettings {
+    pub directory: PathBuf,
+    /// rendered frames between the captures
+    pub every: u64,
+}
+
+/// where a captured frame goes
+enum Destination {
+    File(PathBuf),
+    Video,
+}
+
+/// a frame copied into a buffer by the render graph, waiting to be written
+struct Copied {
+    destination: Destination,
+    buffer: BufferId,
+    width: u32,
+    height: u32,
+    /// bytes per row in the buffer, rows are aligned for the copy
+    row: usize,
+}
+
+/// what the systems and the capture pass hand to each other
+#[derive(Default)]
+struct Shared {
+    /// the next frames to capture
+    requested: VecDeque<Destination>,
+    copied: Option<Copied>,
+}
+
+#[derive(Default)]
+struct Capture {
+    shared: Arc<Mutex<Shared>>,
+    /// rendered frames so far
+    frames: u64,
+    /// numbered frames so far
+    captured: u64,
+}
+
+/// draws the main pass into the capture texture and copies it, only when a frame is
+/// requested
+struct CaptureNode {
+    pass: PassNode<&'static MainPass>,
+    /// input slot of the capture texture
+    target: usize,
+    shared: Arc<Mutex<Shared>>,
+    /// the frame drawn in this run of the graph
+    capturing: Option<Destination>,
+}
+
+impl Node for CaptureNode {
+    fn input(&self) -> &[ResourceSlotInfo] {
+        self.pass.input()
+    }
+
+    fn prepare(&mut self, world: &mut World) {
+        {
+            let mut shared = self.shared.lock().unwrap();
+            // one buffer at a time, the next frame takes it before asking for another
+            if shared.copied.is_none() {
+                self.capturing = shared.requested.pop_front();
+            }
+        }
+        if self.capturing.is_some() {
+            self.pass.prepare(world);
+        }
+    }
+
+    fn update(
+        &mut self,
+        world: &World,
+        render_context: &mut dyn RenderContext,
+        input: &ResourceSlots,
+        output: &mut ResourceSlots,
+    ) {
+        let destination = match self.capturing.take() {
+            Some(destination) => destination,
+            None => return,
+        };
+        let window = world
+            .get_resource::<Windows>()
+            .and_then(|windows| windows.get_primary());
+        let (width, height) = match window {
+            Some(window) if window.physical_width() > 0 && window.physical_height() > 0 => {
+                (window.physical_width(), window.physical_height())
+            }
+            _ => return,
+        };
+        let texture = match input.get(self.target).and_then(|slot| slot.get_texture()) {
+            Some(texture) => texture,
+            None => return,
+        };
+        self.pass.update(world, render_context, input, output);
+
+        let resources = render_context.resources();
+        let row = resources.get_aligned_texture_size(width as usize * BYTES_PER_PIXEL);
+        let buffer = resources.create_buffer(BufferInfo {
+            size: row * height as usize,
+            buffer_usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        render_context.copy_texture_to_buffer(
+            texture,
+            [0, 0, 0],
+            0,
+            buffer,
+            0,
+            row as u32,
+            Extent3d::new(width, height, 1),
+        );
+        self.shared.lock().unwrap().copied = Some(Copied {
+            destination,
+            buffer,
+            width,
+            height,
+            row,
+        });
+    }
+}
+
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let capture = Capture::default();
+        add_capture_pass(app.world_mut(), capture.shared.clone());
+        app.insert_resource(capture)
+            .add_system_to_stage(CoreStage::First, save.system())
+            .add_system_to_stage(CoreStage::Last, request.system());
+    }
+}
+
+/// the capture pass draws like the main pass, into its own texture, and reuses the depth
+/// and multisampled textures of the main pass after it
+fn add_capture_pass(world: &mut World, shared: Arc<Mutex<Shared>>) {
+    let samples = world.get_resource::<Msaa>().map_or(1, |msaa| msaa.samples);
+    let msaa = Msaa { samples };
+    let mut graph = world
+        .get_resource_mut::<RenderGraph>()
+        .expect("the capture needs the render plugin");
+
+    let mut pass = PassNode::<&MainPass>::new(PassDescriptor {
+        color_attachments: vec![msaa.color_attachment_descriptor(
+            TextureAttachment::Input("color_attachment".to_string()),
+            TextureAttachment::Input("color_resolve_target".to_string()),
+            Operations {
+                load: LoadOp::Clear(Color::BLACK),
+                store: true,
+            },
+        )],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachmentDescriptor {
+            attachment: TextureAttachment::Input("depth".to_string()),
+            depth_ops: Some(Operations {
+                load: LoadOp::Clear(1.0),
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+        sample_count: samples,
+    });
+    pass.use_default_clear_color(0);
+    pass.add_camera(base::camera::CAMERA_3D);
+    let target_slot = if samples > 1 {
+        "color_resolve_target"
+    } else {
+        "color_attachment"
+    };
+    let target = pass
+        .input()
+        .iter()
+        .position(|slot| slot.name == target_slot)
+        .expect("the pass has a color attachment");
+
+    graph.add_node(
+        CAPTURE_TEXTURE,
+        WindowTextureNode::new(
+            WindowId::primary(),
+            TextureDescriptor {
+                size: Extent3d::new(1, 1, 1),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::default(),
+                usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::COPY_SRC,
+            },
+        ),
+    );
+    graph.add_node(
+        CAPTURE_PASS,
+        CaptureNode {
+            pass,
+            target,
+            shared,
+            capturing: None,
+        },
+    );
+    graph
+        .add_node_edge(base::node::MAIN_PASS, CAPTURE_PASS)
+        .unwrap();
+    graph
+        .add_slot_edge(
+            CAPTURE_TEXTURE,
+            WindowTextureNode::OUT_TEXTURE,
+            CAPTURE_PASS,
+            target_slot,
+        )
+        .unwrap();
+    graph
+        .add_slot_edge(
+            base::node::MAIN_DEPTH_TEXTURE,
+            WindowTextureNode::OUT_TEXTURE,
+            CAPTURE_PASS,
+            "depth",
+        )
+        .unwrap();
+    if samples > 1 {
+        graph
+            .add_slot_edge(
+                base::node::MAIN_SAMPLED_COLOR_ATTACHMENT,
+                WindowTextureNode::OUT_TEXTURE,
+                CAPTURE_PASS,
+                "color_attachment",
+            )
+            .unwrap();
+    }
+}
+
+fn request(
+    keys: Res<Input<KeyCode>>,
+    settings: Option<Res<CaptureSettings>>,
+    state: Res<GalaxyState>,
+    video: Option<ResMut<Video>>,
+    mut capture: ResMut<Capture>,
+) {
+    let capture = &mut *capture;
+    let mut shared = capture.shared.lock().unwrap();
+    if keys.just_pressed(KeyCode::F12) {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = PathBuf::from(format!("screenshot-{}.png", millis));
+        info!("saving the view to {}", path.display());
+        shared.requested.push_back(Destination::File(path));
+    }
+    if let Some(settings) = settings {
+        if capture.frames.is_multiple_of(settings.every) {
+            let name = format!("frame-{:05}.png", capture.captured);
+            let path = settings.directory.join(name);
+            shared.requested.push_back(Destination::File(path));
+            capture.captured += 1;
+        }
+    }
+    if video.is_some_and(|mut video| video.wants_frame(state.steps)) {
+        shared.requested.push_back(Destination::Video);
+    }
+    capture.frames += 1;
+}
+
+/// reads the frame copied by the last run of the render graph and writes it
+fn save(
+    capture: Res<Capture>,
+    render: Res<Box<dyn RenderResourceContext>>,
+    video: Option<ResMut<Video>>,
+) {
+    let copied = match capture.shared.lock().unwrap().copied.take() {
+        Some(copied) => copied,
+        None => return,
+    };
+    let Copied {
+        destination,
+        buffer,
+        width,
+        height,
+        row,
+    } = copied;
+    let pixels = width as usize * BYTES_PER_PIXEL;
+    let image = RefCell::new(Vec::with_capacity(pixels * height as usize));
+    render.map_buffer(buffer, BufferMapMode::Read);
+    render.read_mapped_buffer(buffer, 0..(row * height as usize) as u64, &|data, _| {
+        let mut image = image.borrow_mut();
+        for row in data.chunks_exact(row) {
+            // the window format is BGRA, the alpha of the additive glow isn't meaningful
+            for bgra in row[..pixels].chunks_exact(BYTES_PER_PIXEL) {
+                image.extend_from_slice(&[bgra[2], bgra[1], bgra[0], 255]);
+            }
+        }
+    });
+    render.unmap_buffer(buffer);
+    render.remove_buffer(buffer);
+    let image = image.into_inner();
+    let path = match destination {
+        Destination::File(path) => path,
+        Destination::Video => {
+            if let Some(mut video) = video {
+                video.write_frame(&image, width, height);
+            }
+            return;
+        }
+    };
+    // encoding takes longer than a frame
+    std::thread::spawn(move || {
+        if let Err(e) = image::save_buffer(&path, &image, width, height, image::ColorType::Rgba8) {
+            error!("can't save {}: {}", path.display(), e);
+        }
+    });
+}
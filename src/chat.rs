@@ -0,0 +1,226 @@
+//! Lets the viewers of a live stream vote in an IRC chat, Twitch chat works the same way.
+//!
+//! Chat messages `!spawn`, `!faster`, `!slower` and `!camera` count as votes, everybody has
+//! one vote per round. At the end of every round the action with the most votes happens:
+//! a heavy perturber flies in, time runs twice as fast or slow, or the camera orbit
+//! switches between still, forward and backward.
+//!
+//! `GALAXY_CHAT_CHANNEL` enables it. `GALAXY_CHAT_SERVER` defaults to Twitch,
+//! `GALAXY_CHAT_NICK` and `GALAXY_CHAT_PASSWORD` default to an anonymous read only login.
+
+use crate::{spawning::SpawnRequests, state::GalaxyRng};
+use bevy::{math::DVec3, prelude::*};
+use galaxy_core::{SimulationParameters, Star};
+use rand::Rng;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+pub struct ChatSettings {
+    /// IRC server as host:port, without TLS
+    pub server: String,
+    /// channel without the leading #, for Twitch the streamer's name, empty disables chat
+    pub channel: String,
+    pub nick: String,
+    /// for Twitch an `oauth:` token, empty logs in without one
+    pub password: String,
+    /// seconds per voting round
+    pub round: f64,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        let var = |name, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        Self {
+            server: var("GALAXY_CHAT_SERVER", "irc.chat.twitch.tv:6667"),
+            channel: var("GALAXY_CHAT_CHANNEL", ""),
+            nick: var("GALAXY_CHAT_NICK", "justinfan31415"),
+            password: var("GALAXY_CHAT_PASSWORD", ""),
+            round: 20.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Action {
+    Spawn,
+    Faster,
+    Slower,
+    Camera,
+}
+
+impl Action {
+    fn parse(message: &str) -> Option<Self> {
+        match message.split_whitespace().next()? {
+            "!spawn" => Some(Action::Spawn),
+            "!faster" => Some(Action::Faster),
+            "!slower" => Some(Action::Slower),
+            "!camera" => Some(Action::Camera),
+            _ => None,
+        }
+    }
+}
+
+/// votes from the chat thread as (user, action)
+struct Votes(Mutex<Receiver<(String, Action)>>);
+
+/// the current round, the latest vote of every user counts
+#[derive(Default)]
+struct Round {
+    elapsed: f64,
+    votes: HashMap<String, Action>,
+}
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ChatSettings>()
+            .add_startup_system(connect.system())
+            .add_system(count_votes.system());
+    }
+}
+
+fn connect(mut commands: Commands, settings: Res<ChatSettings>) {
+    if settings.channel.is_empty() {
+        return;
+    }
+    let stream = match TcpStream::connect(&settings.server) {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("can't connect to chat server {}: {}", settings.server, e);
+            return;
+        }
+    };
+    let mut login = String::new();
+    if !settings.password.is_empty() {
+        login += &format!("PASS {}\r\n", settings.password);
+    }
+    login += &format!(
+        "NICK {}\r\nJOIN #{}\r\n",
+        settings.nick,
+        settings.channel.to_lowercase()
+    );
+    if let Err(e) = (&stream).write_all(login.as_bytes()) {
+        error!("can't log in to chat server {}: {}", settings.server, e);
+        return;
+    }
+    info!("counting votes in #{}", settings.channel);
+
+    let (sender, receiver) = channel();
+    thread::spawn(move || read_chat(stream, sender));
+    commands.insert_resource(Votes(Mutex::new(receiver)));
+}
+
+/// runs on its own thread until the connection or the app is closed
+fn read_chat(stream: TcpStream, votes: Sender<(String, Action)>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("chat connection lost: {}", e);
+                return;
+            }
+        };
+        if let Some(server) = line.strip_prefix("PING ") {
+            let _ = writer.write_all(format!("PONG {}\r\n", server).as_bytes());
+            continue;
+        }
+        // :nick!user@host PRIVMSG #channel :message
+        let mut parts = line.splitn(4, ' ');
+        let (prefix, command, _channel, message) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(prefix), Some(command), Some(channel), Some(message)) => {
+                    (prefix, command, channel, message)
+                }
+                _ => continue,
+            };
+        if command != "PRIVMSG" {
+            continue;
+        }
+        let user = prefix
+            .trim_start_matches(':')
+            .split('!')
+            .next()
+            .unwrap_or("");
+        if let Some(action) = Action::parse(message.trim_start_matches(':')) {
+            if votes.send((user.to_string(), action)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn count_votes(
+    time: Res<Time>,
+    votes: Option<Res<Votes>>,
+    settings: Res<ChatSettings>,
+    mut round: Local<Round>,
+    mut parameters: ResMut<SimulationParameters>,
+    mut requests: ResMut<SpawnRequests>,
+    mut rng: ResMut<GalaxyRng>,
+) {
+    let votes = match votes {
+        Some(votes) => votes,
+        None => return,
+    };
+    round.votes.extend(votes.0.lock().unwrap().try_iter());
+    round.elapsed += time.delta_seconds_f64();
+    if round.elapsed < settings.round {
+        return;
+    }
+    round.elapsed = 0.0;
+
+    let mut tally = BTreeMap::new();
+    for action in round.votes.values() {
+        *tally.entry(*action).or_insert(0) += 1;
+    }
+    round.votes.clear();
+    // ties go to the action listed first
+    let winner = match tally
+        .into_iter()
+        .max_by_key(|&(action, count)| (count, std::cmp::Reverse(action)))
+    {
+        Some((action, count)) => {
+            info!("the chat voted for {:?} with {} votes", action, count);
+            action
+        }
+        None => return,
+    };
+
+    match winner {
+        Action::Spawn => {
+            let angle = rng.0.gen_range(0.0..std::f64::consts::TAU);
+            let direction = DVec3::new(angle.cos(), angle.sin(), 0.0);
+            requests.spawn.push(Star {
+                position: direction * 1.5 * parameters.galaxy_diameter,
+                // head for the center and reach it in about ten seconds
+                velocity: -direction * (0.15 * parameters.galaxy_diameter / parameters.time_factor),
+                acceleration: DVec3::default(),
+                mass: parameters.star_mass_to,
+            });
+        }
+        Action::Faster => parameters.time_factor *= 2.0,
+        Action::Slower => parameters.time_factor /= 2.0,
+        Action::Camera => {
+            parameters.camera_speed = if parameters.camera_speed == 0.0 {
+                0.2
+            } else if parameters.camera_speed > 0.0 {
+                -0.2
+            } else {
+                0.0
+            }
+        }
+    }
+}
@@ -0,0 +1,620 @@
+//! Command line flags, they override the config file.
+//!
+//! `cargo run --release -- --help` lists them. Every flag is optional, without flags the
+//! app runs like before.
+
+use clap::{App, Arg, ArgMatches};
+use galaxy::{coloring::Colormap, lagrange};
+use galaxy_core::{
+    benchmark::{self, Benchmark},
+    config::{AdaptiveConfig, SimulationConfig},
+    Error, ParticleId,
+};
+use std::{net::SocketAddr, path::PathBuf, str::FromStr};
+
+/// the parsed flags
+#[derive(Debug, Default)]
+pub struct Options {
+    /// config file instead of `galaxy.toml`
+    pub config: Option<PathBuf>,
+    /// seed of the initial stars, random when not set
+    pub seed: Option<u64>,
+    /// physics seconds after which the app quits
+    pub duration: Option<f64>,
+    /// snapshot to start from instead of a new galaxy
+    pub load: Option<PathBuf>,
+    /// file `F5` and `F9` save to and load from
+    pub snapshot: Option<PathBuf>,
+    /// jets out of the black hole
+    pub jets: bool,
+    /// stars as glowing sprites instead of cubes
+    pub sprites: bool,
+    /// strength of the glow around bright stars
+    pub bloom: Option<f32>,
+    /// star catalog to start from instead of a random galaxy, see `[catalog]`
+    pub catalog: Option<PathBuf>,
+    /// trajectory file to record every physics step to
+    pub record: Option<PathBuf>,
+    /// trajectory file to play back instead of simulating
+    pub replay: Option<PathBuf>,
+    /// batch run without a window
+    pub headless: bool,
+    /// Kepler orbit instead of a run
+    pub validate: bool,
+    /// timed physics steps instead of a run
+    pub bench: Option<Benchmark>,
+    pub bench_format: benchmark::Format,
+    /// physics steps of a batch run
+    pub steps: Option<u64>,
+    /// physics steps between the snapshots of a batch run, 0 for only the last
+    pub snapshot_every: u64,
+    /// directory of the snapshots of a batch run
+    pub output: Option<PathBuf>,
+    /// batch snapshots in the GADGET-2 HDF5 format instead of JSON
+    pub hdf5: bool,
+    /// directory of the CSV exports
+    pub export: Option<PathBuf>,
+    /// physics steps between the CSV exports
+    pub export_every: Option<u64>,
+    /// directory of the captured frames
+    pub capture: Option<PathBuf>,
+    /// rendered frames between the captured frames
+    pub capture_every: Option<u64>,
+    /// video file the rendered frames are encoded into
+    pub video: Option<PathBuf>,
+    /// frames per second of the video
+    pub video_fps: Option<f64>,
+    /// physics steps per video frame
+    pub video_steps: Option<u64>,
+    /// Alembic point cache every physics step is recorded to
+    pub alembic: Option<PathBuf>,
+    /// addresses of all nodes of a distributed simulation
+    pub nodes: Vec<SocketAddr>,
+    /// the node of this process in `nodes`
+    pub rank: usize,
+    /// ids of the pair whose Lagrange points are shown from the start
+    pub lagrange: Option<(ParticleId, ParticleId)>,
+    /// ids of the pair whose Roche lobe is shown
+    pub roche: Option<(ParticleId, ParticleId)>,
+    /// settings of the A/B copy, e.g. `integrator=euler`
+    pub compare: Option<String>,
+    /// linking length of the clump finder in galaxy diameters
+    pub clumps: Option<f64>,
+    /// seconds without input until the attract mode starts
+    pub attract: Option<f64>,
+    /// physics positions per trail
+    pub trails: Option<usize>,
+    /// CSV file the structure measurements are appended to
+    pub structure: Option<PathBuf>,
+    /// physics steps between the structure measurements
+    pub structure_every: Option<u64>,
+    pub colormap: Colormap,
+    /// shows the energy and the angular momentum
+    pub energy: bool,
+    /// physics steps between the energy measurements
+    pub energy_every: Option<u64>,
+    /// physics steps between the samples of the potential map
+    pub potential: Option<u64>,
+    /// grid points along each side of the potential map
+    pub potential_resolution: Option<usize>,
+    pub timeline: bool,
+    pub panel: bool,
+    /// the director has the camera from the start
+    pub director: bool,
+    /// forces from the GPU
+    #[cfg(feature = "gpu")]
+    pub gpu: bool,
+    /// memory mapped file the stars are shared through
+    #[cfg(feature = "ipc")]
+    pub publish: Option<PathBuf>,
+    /// memory mapped file of another run whose stars are shown
+    #[cfg(feature = "ipc")]
+    pub view: Option<PathBuf>,
+    /// Rhai scenario script
+    #[cfg(feature = "scripting")]
+    pub script: Option<PathBuf>,
+    /// the parameters set by flags, applied after the config file
+    pub overrides: SimulationConfig,
+}
+
+fn app() -> App<'static, 'static> {
+    let value = |name: &'static str, value_name: &'static str, help: &'static str| {
+        Arg::with_name(name)
+            .long(name)
+            .value_name(value_name)
+            .takes_value(true)
+            .help(help)
+    };
+    let app = App::new("galaxy")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Galaxy simulator")
+        .arg(value(
+            "config",
+            "PATH",
+            "Config file, instead of galaxy.toml",
+        ))
+        .arg(value("load", "PATH", "Start from this snapshot"))
+        .arg(value(
+            "snapshot",
+            "PATH",
+            "Snapshot file of F5 and F9, instead of snapshot.json",
+        ))
+        .arg(
+            Arg::with_name("jets")
+                .long("jets")
+                .help("Show jets out of the black hole"),
+        )
+        .arg(
+            Arg::with_name("sprites")
+                .long("sprites")
+                .help("Draw the stars as glowing sprites instead of cubes"),
+        )
+        .arg(value(
+            "bloom",
+            "STRENGTH",
+            "Let bright stars glow, e.g. 0.1",
+        ))
+        .arg(value(
+            "catalog",
+            "PATH",
+            "Start from the stars of this HYG or Gaia CSV catalog",
+        ))
+        .arg(value(
+            "record",
+            "PATH",
+            "Record the positions of every step to a trajectory file",
+        ))
+        .arg(value(
+            "replay",
+            "PATH",
+            "Play back a trajectory file instead of simulating",
+        ))
+        .arg(
+            Arg::with_name("headless")
+                .long("headless")
+                .help("Run without a window and save snapshots, see --steps"),
+        )
+        .arg(
+            Arg::with_name("validate")
+                .long("validate")
+                .help("Integrate a circular two-body orbit, print its errors and quit"),
+        )
+        .arg(
+            Arg::with_name("bench")
+                .long("bench")
+                .value_name("steps=N stars=N")
+                .min_values(0)
+                .multiple(true)
+                .help(
+                    "Time the gravity and the integration of headless steps, print them and quit",
+                ),
+        )
+        .arg(value(
+            "bench-format",
+            "FORMAT",
+            "Timings of --bench as json or csv, default json",
+        ))
+        .arg(value(
+            "steps",
+            "N",
+            "Physics steps of a headless run, instead of --duration, or per orbit of --validate",
+        ))
+        .arg(value(
+            "snapshot-every",
+            "N",
+            "Steps between the snapshots of a headless run, 0 saves only the last",
+        ))
+        .arg(value(
+            "output",
+            "DIR",
+            "Directory of the snapshots of a headless run, instead of output",
+        ))
+        .arg(
+            Arg::with_name("hdf5")
+                .long("hdf5")
+                .help("Save the snapshots of a headless run in the GADGET-2 HDF5 format"),
+        )
+        .arg(value(
+            "export",
+            "DIR",
+            "Write the stars as CSV files into this directory",
+        ))
+        .arg(value(
+            "export-every",
+            "N",
+            "Physics steps between the CSV exports, default 60",
+        ))
+        .arg(value(
+            "capture",
+            "DIR",
+            "Directory of the frames of --capture-every, instead of frames",
+        ))
+        .arg(value(
+            "capture-every",
+            "N",
+            "Save every Nth rendered frame as a numbered PNG",
+        ))
+        .arg(value(
+            "video",
+            "PATH",
+            "Encode the view into an mp4 or webm video with ffmpeg",
+        ))
+        .arg(value(
+            "video-fps",
+            "FPS",
+            "Frames per second of the video, default 30",
+        ))
+        .arg(value(
+            "video-steps",
+            "N",
+            "Physics steps per video frame, default 1",
+        ))
+        .arg(value("stars", "N", "Number of stars"))
+        .arg(value(
+            "tracers",
+            "N",
+            "Massless test particles that feel gravity but exert none",
+        ))
+        .arg(value(
+            "preset",
+            "NAME",
+            "Initial stars: box, plummer, exponential, king, spiral, collision or three-body",
+        ))
+        .arg(value("seed", "SEED", "Seed of the random initial stars"))
+        .arg(value(
+            "duration",
+            "SECONDS",
+            "Quit after this many seconds of physics, at the tick rate",
+        ))
+        .arg(value("tick-rate", "HZ", "Physics steps per second"))
+        .arg(value(
+            "render-rate",
+            "HZ",
+            "Rendered frames per second, 0 is unlimited",
+        ))
+        .arg(value(
+            "integrator",
+            "NAME",
+            "euler, leapfrog, rk4 or hermite",
+        ))
+        .arg(value(
+            "opening-angle",
+            "ANGLE",
+            "Barnes–Hut opening angle, 0 is the exact sum",
+        ))
+        .arg(value(
+            "softening",
+            "LENGTH",
+            "Plummer softening length, in meters or with a unit like 0.1pc",
+        ))
+        .arg(value(
+            "time-factor",
+            "TIME",
+            "Simulated time per physics second, in seconds or with a unit like 1Myr",
+        ))
+        .arg(value("threads", "N", "Worker threads, 0 uses all cores"))
+        .arg(
+            Arg::with_name("planar")
+                .long("planar")
+                .help("Keep the stars in the galaxy plane and look at it from above"),
+        )
+        .arg(value(
+            "adaptive",
+            "ACCURACY",
+            "Split steps so no star moves more than this fraction of |v|/|a|, e.g. 0.05",
+        ))
+        .arg(
+            value(
+                "min-dt",
+                "SECONDS",
+                "Shortest substep of --adaptive, default a hundredth of a step",
+            )
+            .requires("adaptive"),
+        )
+        .arg(
+            value(
+                "max-dt",
+                "SECONDS",
+                "Longest substep of --adaptive, default a whole step",
+            )
+            .requires("adaptive"),
+        )
+        .arg(value(
+            "nodes",
+            "ADDRESSES",
+            "Comma separated ip:port of all nodes of a distributed simulation",
+        ))
+        .arg(value(
+            "rank",
+            "N",
+            "The node of this process in --nodes, from 0",
+        ))
+        .arg(value(
+            "alembic",
+            "PATH",
+            "Record every physics step into an Alembic point cache",
+        ))
+        .arg(value(
+            "lagrange",
+            "ID,ID",
+            "Show the Lagrange points of these two stars, 0 is the black hole",
+        ))
+        .arg(value(
+            "roche",
+            "ID,ID",
+            "Show the Roche lobe of the lighter of these two stars",
+        ))
+        .arg(value(
+            "compare",
+            "SETTINGS",
+            "Run a copy with other settings next to it, e.g. integrator=euler,softening=1e10",
+        ))
+        .arg(value(
+            "clumps",
+            "LENGTH",
+            "Find clumps with this linking length in galaxy diameters, e.g. 0.005",
+        ))
+        .arg(value(
+            "attract",
+            "SECONDS",
+            "Start the attract mode after this many seconds without input",
+        ))
+        .arg(value(
+            "trails",
+            "LENGTH",
+            "Keep this many physics positions in the trails of T",
+        ))
+        .arg(value(
+            "structure",
+            "PATH",
+            "Append the core and Lagrangian radii to this CSV file",
+        ))
+        .arg(value(
+            "structure-every",
+            "N",
+            "Physics steps between the structure samples, default 60",
+        ))
+        .arg(value(
+            "colormap",
+            "NAME",
+            "Colormap of V and the potential: viridis or inferno",
+        ))
+        .arg(
+            Arg::with_name("energy")
+                .long("energy")
+                .help("Show the energy and the angular momentum with their drift"),
+        )
+        .arg(value(
+            "energy-every",
+            "N",
+            "Physics steps between the energy measurements, default 60",
+        ))
+        .arg(value(
+            "potential",
+            "STEPS",
+            "Show the potential beneath the galaxy, sampled every this many physics steps",
+        ))
+        .arg(value(
+            "potential-resolution",
+            "N",
+            "Grid points along each side of the potential, default 64",
+        ))
+        .arg(
+            Arg::with_name("timeline")
+                .long("timeline")
+                .help("Show a timeline of the mergers, escapes and accretions"),
+        )
+        .arg(
+            Arg::with_name("panel")
+                .long("panel")
+                .help("Show buttons for the parameters"),
+        )
+        .arg(
+            Arg::with_name("director")
+                .long("director")
+                .help("Start with the automatic camera of D"),
+        );
+    #[cfg(feature = "gpu")]
+    let app = app.arg(
+        Arg::with_name("gpu")
+            .long("gpu")
+            .help("Compute the forces in a GPU compute shader"),
+    );
+    #[cfg(feature = "ipc")]
+    let app = app
+        .arg(value(
+            "publish",
+            "PATH",
+            "Share the stars with viewers through this memory mapped file",
+        ))
+        .arg(value(
+            "view",
+            "PATH",
+            "Show the stars shared by another run instead of simulating",
+        ));
+    #[cfg(feature = "scripting")]
+    let app = app.arg(value("script", "PATH", "Run this Rhai scenario script"));
+    app
+}
+
+/// the value of flag `name` parsed as `T`, `None` when it is not given
+fn number<T: FromStr>(matches: &ArgMatches, name: &str) -> galaxy_core::Result<Option<T>> {
+    match matches.value_of(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| Error::InvalidValue {
+            name: format!("--{}", name),
+            value: value.to_string(),
+        }),
+        None => Ok(None),
+    }
+}
+
+/// parses the process arguments, exits with the usage for `--help` and unknown flags
+pub fn parse() -> galaxy_core::Result<Options> {
+    let matches = app().get_matches();
+    let duration = number::<f64>(&matches, "duration")?;
+    if duration.is_some_and(|duration| duration <= 0.0) {
+        return Err(Error::InvalidValue {
+            name: "--duration".to_string(),
+            value: matches.value_of("duration").unwrap_or_default().to_string(),
+        });
+    }
+    let tick_rate = number::<f64>(&matches, "tick-rate")?;
+    if tick_rate.is_some_and(|rate| rate <= 0.0) {
+        return Err(Error::InvalidValue {
+            name: "--tick-rate".to_string(),
+            value: matches
+                .value_of("tick-rate")
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+    let capture_every = number::<u64>(&matches, "capture-every")?;
+    if capture_every == Some(0) {
+        return Err(Error::InvalidValue {
+            name: "--capture-every".to_string(),
+            value: "0".to_string(),
+        });
+    }
+    let video_fps = number::<f64>(&matches, "video-fps")?;
+    if video_fps.is_some_and(|fps| fps <= 0.0) {
+        return Err(Error::InvalidValue {
+            name: "--video-fps".to_string(),
+            value: matches
+                .value_of("video-fps")
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+    let video_steps = number::<u64>(&matches, "video-steps")?;
+    if video_steps == Some(0) {
+        return Err(Error::InvalidValue {
+            name: "--video-steps".to_string(),
+            value: "0".to_string(),
+        });
+    }
+    let adaptive = match number::<f64>(&matches, "adaptive")? {
+        Some(accuracy) => {
+            // a substep of 0 never ends the step, bounds the wrong way round can't be met
+            let positive = |name: &str| match number::<f64>(&matches, name)? {
+                Some(value) if value.is_nan() || value <= 0.0 => Err(Error::InvalidValue {
+                    name: format!("--{}", name),
+                    value: value.to_string(),
+                }),
+                value => Ok(value),
+            };
+            if accuracy.is_nan() || accuracy <= 0.0 {
+                return Err(Error::InvalidValue {
+                    name: "--adaptive".to_string(),
+                    value: accuracy.to_string(),
+                });
+            }
+            let adaptive = AdaptiveConfig {
+                accuracy,
+                min_dt: positive("min-dt")?,
+                max_dt: positive("max-dt")?,
+            };
+            if let (Some(min), Some(max)) = (adaptive.min_dt, adaptive.max_dt) {
+                if min > max {
+                    return Err(Error::InvalidValue {
+                        name: "--min-dt above --max-dt".to_string(),
+                        value: min.to_string(),
+                    });
+                }
+            }
+            Some(adaptive)
+        }
+        None => None,
+    };
+    let nodes = match matches.value_of("nodes") {
+        Some(list) => list
+            .split(',')
+            .map(|address| {
+                address.trim().parse().map_err(|_| Error::InvalidValue {
+                    name: "--nodes".to_string(),
+                    value: address.to_string(),
+                })
+            })
+            .collect::<galaxy_core::Result<_>>()?,
+        None => Vec::new(),
+    };
+    let pair = |name: &str| {
+        matches
+            .value_of(name)
+            .map(|value| lagrange::parse_pair(&format!("--{}", name), value))
+            .transpose()
+    };
+    Ok(Options {
+        config: matches.value_of_os("config").map(PathBuf::from),
+        seed: number(&matches, "seed")?,
+        duration,
+        load: matches.value_of_os("load").map(PathBuf::from),
+        snapshot: matches.value_of_os("snapshot").map(PathBuf::from),
+        catalog: matches.value_of_os("catalog").map(PathBuf::from),
+        jets: matches.is_present("jets"),
+        sprites: matches.is_present("sprites"),
+        bloom: number(&matches, "bloom")?,
+        record: matches.value_of_os("record").map(PathBuf::from),
+        replay: matches.value_of_os("replay").map(PathBuf::from),
+        headless: matches.is_present("headless"),
+        validate: matches.is_present("validate"),
+        bench: match matches.values_of("bench") {
+            Some(values) => Some(values.collect::<Vec<_>>().join(" ").parse()?),
+            None if matches.is_present("bench") => Some(Benchmark::default()),
+            None => None,
+        },
+        bench_format: number(&matches, "bench-format")?.unwrap_or_default(),
+        steps: number(&matches, "steps")?,
+        snapshot_every: number(&matches, "snapshot-every")?.unwrap_or(0),
+        output: matches.value_of_os("output").map(PathBuf::from),
+        hdf5: matches.is_present("hdf5"),
+        export: matches.value_of_os("export").map(PathBuf::from),
+        export_every: number(&matches, "export-every")?,
+        capture: matches.value_of_os("capture").map(PathBuf::from),
+        capture_every,
+        video: matches.value_of_os("video").map(PathBuf::from),
+        video_fps,
+        video_steps,
+        alembic: matches.value_of_os("alembic").map(PathBuf::from),
+        nodes,
+        rank: number(&matches, "rank")?.unwrap_or(0),
+        lagrange: pair("lagrange")?,
+        roche: pair("roche")?,
+        compare: matches.value_of("compare").map(str::to_string),
+        clumps: number(&matches, "clumps")?,
+        attract: number(&matches, "attract")?,
+        trails: number(&matches, "trails")?,
+        structure: matches.value_of_os("structure").map(PathBuf::from),
+        structure_every: number(&matches, "structure-every")?,
+        colormap: number(&matches, "colormap")?.unwrap_or_default(),
+        energy: matches.is_present("energy"),
+        energy_every: number(&matches, "energy-every")?,
+        potential: number(&matches, "potential")?,
+        potential_resolution: number(&matches, "potential-resolution")?,
+        timeline: matches.is_present("timeline"),
+        panel: matches.is_present("panel"),
+        director: matches.is_present("director"),
+        #[cfg(feature = "gpu")]
+        gpu: matches.is_present("gpu"),
+        #[cfg(feature = "ipc")]
+        publish: matches.value_of_os("publish").map(PathBuf::from),
+        #[cfg(feature = "ipc")]
+        view: matches.value_of_os("view").map(PathBuf::from),
+        #[cfg(feature = "scripting")]
+        script: matches.value_of_os("script").map(PathBuf::from),
+        overrides: SimulationConfig {
+            number_of_stars: number(&matches, "stars")?,
+            tracers: number(&matches, "tracers")?,
+            preset: number(&matches, "preset")?,
+            tick_rate,
+            render_rate: number(&matches, "render-rate")?,
+            integrator: number(&matches, "integrator")?,
+            opening_angle: number(&matches, "opening-angle")?,
+            softening: number(&matches, "softening")?,
+            time_factor: number(&matches, "time-factor")?,
+            threads: number(&matches, "threads")?,
+            planar: matches.is_present("planar").then_some(true),
+            adaptive,
+            ..Default::default()
+        },
+    })
+}
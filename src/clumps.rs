@@ -0,0 +1,134 @@
+//! Finds clumps of stars with friends-of-friends, for tidal clumps and satellites.
+//!
+//! `--clumps <linking length>` in galaxy diameters, e.g. `0.005`, regroups the stars
+//! once per tick rate worth of steps and tags every star entity with its `Clump`. `C`
+//! colors the clumps, the largest ones get their own color, stars in no clump keep theirs.
+
+use crate::{spawning::StarAssets, state::GalaxyState};
+use bevy::prelude::*;
+use galaxy_core::{clumps::friends_of_friends, SimulationParameters};
+
+/// distinct colors for the largest clumps, the others share the last one
+const PALETTE: [Color; 8] = [
+    Color::rgb(6.0, 1.2, 1.2),
+    Color::rgb(1.2, 6.0, 1.2),
+    Color::rgb(1.5, 2.5, 8.0),
+    Color::rgb(6.0, 1.2, 6.0),
+    Color::rgb(1.2, 6.0, 6.0),
+    Color::rgb(6.0, 3.5, 0.8),
+    Color::rgb(3.5, 6.0, 0.8),
+    Color::rgb(3.0, 3.0, 3.0),
+];
+
+pub struct ClumpSettings {
+    /// friends are closer than this, in galaxy diameters
+    pub linking_length: f64,
+    /// smaller groups aren't clumps
+    pub min_members: usize,
+    /// physics steps between the searches
+    pub interval: u64,
+    pub colored: bool,
+}
+
+impl ClumpSettings {
+    pub fn new(linking_length: f64, parameters: &SimulationParameters) -> Self {
+        Self {
+            linking_length,
+            min_members: 8,
+            interval: (1.0 / parameters.physics_dt).round().max(1.0) as u64,
+            colored: false,
+        }
+    }
+}
+
+/// clump of a star entity by size rank, the largest is 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clump(pub Option<usize>);
+
+struct ClumpMaterials(Vec<Handle<StandardMaterial>>);
+
+/// searches so far, the coloring is redone after each, and the clumps found last
+#[derive(Default)]
+struct Searches {
+    count: u64,
+    clumps: usize,
+}
+
+pub struct ClumpPlugin;
+
+impl Plugin for ClumpPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Searches>()
+            .add_startup_system(setup.system())
+            .add_system_to_stage(crate::PHYSICS_STAGE, find.system().after("moving"))
+            .add_system(toggle.system())
+            .add_system(color.system());
+    }
+}
+
+fn setup(mut commands: Commands, mut materials: ResMut<Assets<StandardMaterial>>) {
+    let palette = PALETTE
+        .iter()
+        .map(|&color| materials.add(color.into()))
+        .collect();
+    commands.insert_resource(ClumpMaterials(palette));
+}
+
+fn find(
+    mut commands: Commands,
+    mut next_step: Local<u64>,
+    mut searches: ResMut<Searches>,
+    settings: Res<ClumpSettings>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+) {
+    if state.steps < *next_step {
+        return;
+    }
+    *next_step = state.steps + settings.interval;
+    let clumps = friends_of_friends(
+        &state.particles,
+        settings.linking_length * parameters.galaxy_diameter,
+        settings.min_members,
+    );
+    for (entity, clump) in state.entities.iter().zip(&clumps.of_particle) {
+        commands.entity(*entity).insert(Clump(*clump));
+    }
+    if searches.count == 0 || clumps.len() != searches.clumps {
+        info!("{} clumps, sizes {:?}", clumps.len(), clumps.sizes);
+    }
+    searches.count += 1;
+    searches.clumps = clumps.len();
+}
+
+fn toggle(keys: Res<Input<KeyCode>>, mut settings: ResMut<ClumpSettings>) {
+    if keys.just_pressed(KeyCode::C) {
+        settings.colored = !settings.colored;
+    }
+}
+
+fn color(
+    mut colored: Local<(bool, u64)>,
+    searches: Res<Searches>,
+    settings: Res<ClumpSettings>,
+    assets: Res<StarAssets>,
+    palette: Res<ClumpMaterials>,
+    state: Res<GalaxyState>,
+    mut stars: Query<(&Clump, &mut Handle<StandardMaterial>)>,
+) {
+    if *colored == (settings.colored, searches.count) {
+        return;
+    }
+    *colored = (settings.colored, searches.count);
+    // the black hole keeps its color
+    for (i, entity) in state.entities.iter().enumerate().skip(1) {
+        if let Ok((clump, mut material)) = stars.get_mut(*entity) {
+            *material = match clump.0 {
+                Some(clump) if settings.colored => {
+                    palette.0[clump.min(palette.0.len() - 1)].clone()
+                }
+                _ => assets.material(&state.particles, i),
+            };
+        }
+    }
+}
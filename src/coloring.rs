@@ -0,0 +1,240 @@
+//! Colors the stars by a physical quantity, to make the dynamics visible.
+//!
+//! `V` cycles the `ColorMode` from the uniform star color through mass, speed,
+//! acceleration, distance from the center and the galaxy a star started in, for the
+//! `GalaxyId`s of the `[[galaxies]]` of the config. The values of the current stars are mapped
+//! between their smallest and largest one, logarithmically except for the distance, onto
+//! a perceptual colormap, viridis by default or `--colormap inferno`. The colormap is
+//! split into a few shared materials, so recoloring doesn't create any. The black hole
+//! keeps its color, the clump colors of `C` show while the mode is uniform.
+
+use crate::{
+    spawning::StarAssets,
+    state::{GalaxyId, GalaxyState},
+};
+use bevy::prelude::*;
+use std::str::FromStr;
+
+/// materials along the colormap
+const STEPS: usize = 32;
+/// the colormaps go from 0 to 1, the stars are brighter
+const BRIGHTNESS: f32 = 5.0;
+
+/// the quantity the stars are colored by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    #[default]
+    Uniform,
+    Mass,
+    Speed,
+    Acceleration,
+    Distance,
+    Galaxy,
+}
+
+impl ColorMode {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            ColorMode::Uniform => ColorMode::Mass,
+            ColorMode::Mass => ColorMode::Speed,
+            ColorMode::Speed => ColorMode::Acceleration,
+            ColorMode::Acceleration => ColorMode::Distance,
+            ColorMode::Distance => ColorMode::Galaxy,
+            ColorMode::Galaxy => ColorMode::Uniform,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ColorMode::Uniform => "uniform",
+            ColorMode::Mass => "mass",
+            ColorMode::Speed => "speed",
+            ColorMode::Acceleration => "acceleration",
+            ColorMode::Distance => "distance from center",
+            ColorMode::Galaxy => "galaxy",
+        }
+    }
+
+    /// the values of all particles, `None` for the uniform color, stars of no galaxy get
+    /// the lowest color
+    fn values(self, state: &GalaxyState, galaxies: &Query<&GalaxyId>) -> Option<Vec<f64>> {
+        let particles = &state.particles;
+        let center = particles.positions.first().copied().unwrap_or_default();
+        let values = match self {
+            ColorMode::Uniform => return None,
+            ColorMode::Mass => particles.masses.clone(),
+            ColorMode::Speed => particles.velocities.iter().map(|v| v.length()).collect(),
+            ColorMode::Acceleration => particles.accelerations.iter().map(|a| a.length()).collect(),
+            ColorMode::Distance => particles
+                .positions
+                .iter()
+                .map(|p| p.distance(center))
+                .collect(),
+            ColorMode::Galaxy => state
+                .entities
+                .iter()
+                .map(|&entity| galaxies.get(entity).map_or(-1.0, |galaxy| galaxy.0 as f64))
+                .collect(),
+        };
+        Some(values)
+    }
+
+    /// masses, speeds and accelerations span orders of magnitude
+    fn logarithmic(self) -> bool {
+        !matches!(self, ColorMode::Distance | ColorMode::Galaxy)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Inferno,
+}
+
+impl FromStr for Colormap {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name {
+            "viridis" => Ok(Colormap::Viridis),
+            "inferno" => Ok(Colormap::Inferno),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Colormap {
+    /// the colors at 0, 1/8, 2/8 and so on up to 1, from matplotlib
+    fn stops(self) -> [[f32; 3]; 9] {
+        match self {
+            Colormap::Viridis => [
+                [0.267, 0.005, 0.329],
+                [0.275, 0.196, 0.494],
+                [0.231, 0.322, 0.545],
+                [0.173, 0.447, 0.557],
+                [0.129, 0.569, 0.549],
+                [0.157, 0.682, 0.502],
+                [0.369, 0.788, 0.384],
+                [0.678, 0.863, 0.188],
+                [0.992, 0.906, 0.145],
+            ],
+            Colormap::Inferno => [
+                [0.001, 0.000, 0.014],
+                [0.122, 0.047, 0.282],
+                [0.333, 0.059, 0.427],
+                [0.533, 0.133, 0.416],
+                [0.729, 0.212, 0.333],
+                [0.890, 0.349, 0.200],
+                [0.976, 0.557, 0.035],
+                [0.976, 0.796, 0.208],
+                [0.988, 1.000, 0.643],
+            ],
+        }
+    }
+
+    /// the red, green and blue from 0 to 1 at `t` from 0 to 1
+    pub(crate) fn rgb(self, t: f32) -> [f32; 3] {
+        let stops = self.stops();
+        let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (x as usize).min(stops.len() - 2);
+        let f = x - i as f32;
+        [0, 1, 2].map(|c| stops[i][c] + (stops[i + 1][c] - stops[i][c]) * f)
+    }
+
+    /// the star color at `t` from 0 to 1
+    fn color(self, t: f32) -> Color {
+        let [r, g, b] = self.rgb(t);
+        Color::rgb(r * BRIGHTNESS, g * BRIGHTNESS, b * BRIGHTNESS)
+    }
+}
+
+struct ColormapMaterials(Vec<Handle<StandardMaterial>>);
+
+pub struct ColoringPlugin;
+
+impl Plugin for ColoringPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ColorMode>()
+            .init_resource::<Colormap>()
+            .add_startup_system(setup.system())
+            .add_system(toggle.system())
+            .add_system(color.system().after("sync"));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    colormap: Res<Colormap>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let steps = (0..STEPS)
+        .map(|i| {
+            let color = colormap.color(i as f32 / (STEPS - 1) as f32);
+            materials.add(color.into())
+        })
+        .collect();
+    commands.insert_resource(ColormapMaterials(steps));
+}
+
+fn toggle(keys: Res<Input<KeyCode>>, mut mode: ResMut<ColorMode>) {
+    if keys.just_pressed(KeyCode::V) {
+        *mode = mode.next();
+        info!("coloring the stars by {}", mode.name());
+    }
+}
+
+fn color(
+    mut colored: Local<Option<(ColorMode, u64)>>,
+    mode: Res<ColorMode>,
+    state: Res<GalaxyState>,
+    assets: Res<StarAssets>,
+    colormap: Res<ColormapMaterials>,
+    galaxies: Query<&GalaxyId>,
+    mut materials: Query<&mut Handle<StandardMaterial>>,
+) {
+    // only after a change of the mode or a physics step
+    if *colored == Some((*mode, state.steps)) {
+        return;
+    }
+    let previous = colored.replace((*mode, state.steps));
+    let values = match mode.values(&state, &galaxies) {
+        Some(values) => values,
+        None => {
+            // back to the star color once, then the clumps may color them again
+            if previous.is_some_and(|(previous, _)| previous != ColorMode::Uniform) {
+                for (i, entity) in state.entities.iter().enumerate().skip(1) {
+                    if let Ok(mut material) = materials.get_mut(*entity) {
+                        *material = assets.material(&state.particles, i);
+                    }
+                }
+            }
+            return;
+        }
+    };
+
+    // zeros have no logarithm, they get the lowest color
+    let scale = |value: f64| match mode.logarithmic() {
+        true if value > 0.0 => Some(value.log10()),
+        true => None,
+        false => Some(value),
+    };
+    let (low, high) = values
+        .iter()
+        .skip(1)
+        .filter_map(|&value| scale(value))
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+    let range = (high - low).max(f64::MIN_POSITIVE);
+    for (entity, &value) in state.entities.iter().zip(&values).skip(1) {
+        let t = scale(value).map_or(0.0, |value| ((value - low) / range) as f32);
+        let step = &colormap.0[((t * (STEPS - 1) as f32).round() as usize).min(STEPS - 1)];
+        if let Ok(mut material) = materials.get_mut(*entity) {
+            // cloning a handle costs a message, most stars keep theirs
+            if *material != *step {
+                *material = step.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! A/B mode: a copy of the galaxy runs with other solver settings next to the original.
+//!
+//! `--compare integrator=euler` starts the copy from the same stars as the main
+//! simulation, with the listed settings changed, more are separated by commas, e.g.
+//! `integrator=euler,softening=1e10`. The original is shown on the left, the
+//! copy in blue on the right, under the same camera, so the divergence caused by the
+//! numerical choices is directly visible. The mean distance between the same stars in both
+//! runs is logged every few seconds.
+
+use crate::{
+    render_frame::RenderFrame,
+    spawning::{spawn_star, star_bundle, StarAssets},
+    state::{GalaxyState, SimulationState},
+    PHYSICS_STAGE, PHYSICS_TIMESTEP,
+};
+use bevy::{core::FixedTimesteps, math::DVec3, prelude::*};
+use galaxy_core::{Error, ParticleId, Simulation, SimulationParameters};
+use std::collections::HashMap;
+
+/// distance from the center of either galaxy to the middle between them, in diameters
+const HALF_SPACING: f64 = 0.6;
+/// seconds between the divergence reports
+const REPORT_INTERVAL: f64 = 5.0;
+
+/// the parameters of the copy, `parameters` with the settings in `list` of flag `name`
+pub fn parse_parameters(
+    name: &str,
+    list: &str,
+    parameters: &SimulationParameters,
+) -> galaxy_core::Result<SimulationParameters> {
+    let mut changed = parameters.clone();
+    for setting in list.split(',') {
+        let invalid = || Error::InvalidValue {
+            name: name.to_string(),
+            value: setting.to_string(),
+        };
+        let (key, value) = setting.split_once('=').ok_or_else(invalid)?;
+        let value = value.trim();
+        let number = || value.parse::<f64>().map_err(|_| invalid());
+        match key.trim() {
+            "integrator" => changed.integrator = value.parse().map_err(|_| invalid())?,
+            "softening" => changed.softening = number()?,
+            "min_gravity_distance" => changed.min_gravity_distance = number()?,
+            "g" => changed.g = number()?,
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(changed)
+}
+
+/// the parameters of the copy, until the copy is created from the initial stars
+pub struct CompareParameters(pub SimulationParameters);
+
+/// the copy and its star entities, in the order of its particles
+struct Comparison {
+    simulation: Simulation,
+    previous_positions: Vec<DVec3>,
+    entities: Vec<Entity>,
+}
+
+pub struct ComparePlugin;
+
+impl Plugin for ComparePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // after the main setup, whose stars are copied
+        app.add_startup_system_to_stage(StartupStage::PostStartup, setup.system())
+            .add_system_to_stage(PHYSICS_STAGE, step.system().after("moving"))
+            .add_system(sync_transforms.system())
+            .add_system(report.system());
+    }
+}
+
+/// the frame of the copy, to the right of the original
+fn copy_frame(frame: &RenderFrame, parameters: &SimulationParameters) -> RenderFrame {
+    RenderFrame {
+        origin: frame.origin - DVec3::X * (2.0 * HALF_SPACING * parameters.galaxy_diameter),
+        ..*frame
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    state: Res<GalaxyState>,
+    copy: Res<CompareParameters>,
+    parameters: Res<SimulationParameters>,
+    assets: Res<StarAssets>,
+    mut frame: ResMut<RenderFrame>,
+) {
+    // the original moves to the left
+    frame.origin += DVec3::X * (HALF_SPACING * parameters.galaxy_diameter);
+
+    let simulation = Simulation::new(state.particles.clone(), copy.0.clone());
+    let entities = (0..simulation.len())
+        .map(|i| {
+            let material = if i == 0 {
+                assets.black_hole_material.clone()
+            } else {
+                assets.comparison_material.clone()
+            };
+            spawn_star(&mut commands, &assets, star_bundle(&assets, material))
+        })
+        .collect();
+    commands.insert_resource(Comparison {
+        previous_positions: simulation.positions().to_vec(),
+        simulation,
+        entities,
+    });
+}
+
+fn step(mut comparison: ResMut<Comparison>, simulation: Res<SimulationState>) {
+    profiling::scope!("comparison");
+    let comparison = &mut *comparison;
+    comparison
+        .previous_positions
+        .copy_from_slice(comparison.simulation.positions());
+    if simulation.stepped {
+        comparison.simulation.step();
+    }
+}
+
+/// interpolates like the main `sync_transforms`
+fn sync_transforms(
+    fixed_timesteps: Res<FixedTimesteps>,
+    comparison: Res<Comparison>,
+    frame: Res<RenderFrame>,
+    parameters: Res<SimulationParameters>,
+    mut transforms: Query<&mut Transform>,
+) {
+    let alpha = fixed_timesteps
+        .get(PHYSICS_TIMESTEP)
+        .map_or(1.0, |state| state.overstep_percentage().min(1.0));
+    let frame = copy_frame(&frame, &parameters);
+    let positions = comparison
+        .previous_positions
+        .iter()
+        .zip(comparison.simulation.positions());
+    for (entity, (previous, current)) in comparison.entities.iter().zip(positions) {
+        if let Ok(mut transform) = transforms.get_mut(*entity) {
+            let translation = frame.render_position(previous.lerp(*current, alpha));
+            if transform.translation != translation {
+                transform.translation = translation;
+            }
+        }
+    }
+}
+
+/// logs the mean distance between the same stars in both runs
+fn report(
+    time: Res<Time>,
+    mut since_last: Local<f64>,
+    comparison: Res<Comparison>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+) {
+    *since_last += time.delta_seconds_f64();
+    if *since_last < REPORT_INTERVAL {
+        return;
+    }
+    *since_last = 0.0;
+
+    // the stars of the original by id, a linear search per star is quadratic
+    let original = &state.particles;
+    let indices: HashMap<ParticleId, usize> = original
+        .ids
+        .iter()
+        .enumerate()
+        .map(|(i, &id)| (id, i))
+        .collect();
+    let copy = &comparison.simulation.particles;
+    let distances: Vec<f64> = copy
+        .ids
+        .iter()
+        .zip(&copy.positions)
+        .filter_map(|(id, position)| {
+            let i = *indices.get(id)?;
+            Some((original.positions[i] - *position).length())
+        })
+        .collect();
+    if !distances.is_empty() {
+        let mean = distances.iter().sum::<f64>() / distances.len() as f64;
+        info!(
+            "comparison: mean divergence {:.3e} m, {:.2}% of the diameter",
+            mean,
+            100.0 * mean / parameters.galaxy_diameter
+        );
+    }
+}
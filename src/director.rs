@@ -0,0 +1,274 @@
+//! An automatic camera for unattended displays, it flies between interesting places.
+//!
+//! Every shot lasts `shot_length` seconds and circles its subject slowly: the latest
+//! merger, the latest close encounter, the densest region and the whole galaxy take turns,
+//! subjects that don't exist right now are skipped. A caption names the subject, it needs
+//! the UI font at `assets/fonts/caption.ttf`.
+//!
+//! `--director` starts with the director in charge, `D` switches between it and the
+//! normal camera orbit.
+
+use crate::{
+    camera::CameraMode,
+    events::{CollisionEvent, MergeEvent},
+    render_frame::RenderFrame,
+    state::GalaxyState,
+    UI_FONT,
+};
+use bevy::{
+    math::DVec3,
+    prelude::*,
+    render::camera::{Camera, PerspectiveProjection},
+};
+use galaxy_core::SimulationParameters;
+use std::collections::HashMap;
+
+pub struct DirectorSettings {
+    pub active: bool,
+    /// seconds per shot, including the transition
+    pub shot_length: f32,
+    /// seconds of the flight from one shot to the next
+    pub transition: f32,
+    /// side of the cells the densest region is searched in, in galaxy diameters
+    pub cell_size: f64,
+    /// radians per second the camera circles its subject
+    pub orbit_speed: f32,
+}
+
+impl Default for DirectorSettings {
+    fn default() -> Self {
+        Self {
+            active: false,
+            shot_length: 12.0,
+            transition: 3.0,
+            cell_size: 0.04,
+            orbit_speed: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Merger,
+    Encounter,
+    Densest,
+    Overview,
+}
+
+/// the order the kinds take turns in
+const KINDS: [Kind; 4] = [Kind::Merger, Kind::Encounter, Kind::Densest, Kind::Overview];
+
+#[derive(Debug, Clone, Copy)]
+enum Subject {
+    /// follows a star
+    Star(Entity),
+    /// a fixed place in physics coordinates
+    Place(DVec3),
+}
+
+struct Shot {
+    subject: Subject,
+    /// camera distance from the subject in render units
+    distance: f32,
+}
+
+#[derive(Default)]
+struct Director {
+    shot: Option<Shot>,
+    elapsed: f32,
+    /// index into `KINDS` of the next shot
+    next: usize,
+    /// eye and look at point when the current shot started
+    from: (Vec3, Vec3),
+    /// look at point of the last frame
+    look: Vec3,
+    angle: f32,
+    merger: Option<Entity>,
+    encounter: Option<Entity>,
+}
+
+/// the caption text entity
+struct Caption;
+
+pub struct DirectorPlugin;
+
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<DirectorSettings>()
+            .init_resource::<Director>()
+            .add_startup_system(setup.system())
+            .add_system(toggle.system())
+            .add_system(remember_events.system())
+            .add_system(direct.system().label("camera"));
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(40.0),
+                    bottom: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(UI_FONT),
+                    font_size: 36.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Caption);
+}
+
+fn toggle(
+    keys: Res<Input<KeyCode>>,
+    mode: Res<CameraMode>,
+    parameters: Res<SimulationParameters>,
+    mut settings: ResMut<DirectorSettings>,
+) {
+    // the shots are perspective views, the top-down camera of a planar run has none
+    if parameters.planar {
+        settings.active = false;
+        return;
+    }
+    // `D` moves the fly camera sideways
+    if keys.just_pressed(KeyCode::D) && !matches!(*mode, CameraMode::Fly { .. }) {
+        settings.active = !settings.active;
+        info!(
+            "camera director {}",
+            if settings.active { "on" } else { "off" }
+        );
+    }
+}
+
+fn remember_events(
+    mut director: ResMut<Director>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut merges: EventReader<MergeEvent>,
+) {
+    if let Some(e) = collisions.iter().last() {
+        director.encounter = Some(e.a);
+    }
+    if let Some(e) = merges.iter().last() {
+        director.merger = Some(e.survivor);
+    }
+}
+
+/// center of the cell with the most stars, the black hole alone doesn't count
+fn densest_place(state: &GalaxyState, cell_size: f64) -> Option<DVec3> {
+    let mut counts = HashMap::new();
+    for p in state.particles.positions.iter().skip(1) {
+        let cell = (*p / cell_size).floor();
+        *counts
+            .entry((cell.x as i64, cell.y as i64, cell.z as i64))
+            .or_insert(0) += 1;
+    }
+    let ((x, y, z), _) = counts.into_iter().max_by_key(|&(_, count)| count)?;
+    Some((DVec3::new(x as f64, y as f64, z as f64) + DVec3::splat(0.5)) * cell_size)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn direct(
+    time: Res<Time>,
+    settings: Res<DirectorSettings>,
+    mut director: ResMut<Director>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+    frame: Res<RenderFrame>,
+    stars: Query<&Transform, Without<Camera>>,
+    mut cameras: Query<&mut Transform, (With<Camera>, With<PerspectiveProjection>)>,
+    mut captions: Query<&mut Text, With<Caption>>,
+) {
+    // also logged, the caption stays invisible without the font
+    let mut caption = |text: &str| {
+        if !text.is_empty() {
+            info!("director: {}", text);
+        }
+        for mut caption in captions.iter_mut() {
+            if caption.sections[0].value != text {
+                caption.sections[0].value = text.to_string();
+            }
+        }
+    };
+    let mut camera = match cameras.single_mut() {
+        Ok(camera) if settings.active => camera,
+        _ => {
+            caption("");
+            director.shot = None;
+            return;
+        }
+    };
+    let director = &mut *director;
+    let star_position = |subject: Subject| match subject {
+        Subject::Star(entity) => stars.get(entity).ok().map(|t| t.translation),
+        Subject::Place(p) => Some(frame.render_position(p)),
+    };
+
+    director.elapsed += time.delta_seconds();
+    let lost = director
+        .shot
+        .as_ref()
+        .is_none_or(|shot| star_position(shot.subject).is_none());
+    if lost || director.elapsed >= settings.shot_length {
+        // the next kind that has a subject, the overview always has one
+        let cell_size = settings.cell_size * parameters.galaxy_diameter;
+        let (kind, subject) = (0..KINDS.len())
+            .map(|i| KINDS[(director.next + i) % KINDS.len()])
+            .find_map(|kind| {
+                let subject = match kind {
+                    Kind::Merger => director.merger.map(Subject::Star),
+                    Kind::Encounter => director.encounter.map(Subject::Star),
+                    Kind::Densest => densest_place(&state, cell_size).map(Subject::Place),
+                    Kind::Overview => Some(Subject::Place(DVec3::default())),
+                };
+                subject
+                    .filter(|&subject| star_position(subject).is_some())
+                    .map(|subject| (kind, subject))
+            })
+            .unwrap_or((Kind::Overview, Subject::Place(DVec3::default())));
+        director.next = (KINDS.iter().position(|&k| k == kind).unwrap_or(0) + 1) % KINDS.len();
+        // every event is shown once
+        match kind {
+            Kind::Merger => director.merger = None,
+            Kind::Encounter => director.encounter = None,
+            _ => {}
+        }
+        let (distance, text) = match kind {
+            Kind::Merger => (150.0, "Two stars have merged"),
+            Kind::Encounter => (150.0, "A close encounter"),
+            Kind::Densest => (400.0, "The most crowded region"),
+            Kind::Overview => (2500.0, "The whole galaxy"),
+        };
+        caption(text);
+        director.from = (camera.translation, director.look);
+        director.elapsed = 0.0;
+        director.shot = Some(Shot { subject, distance });
+    }
+
+    let shot = match &director.shot {
+        Some(shot) => shot,
+        None => return,
+    };
+    let target = star_position(shot.subject).unwrap_or_default();
+    director.angle += time.delta_seconds() * settings.orbit_speed;
+    let offset = Vec3::new(director.angle.cos(), director.angle.sin(), 1.0) * shot.distance;
+    let (mut eye, mut look) = (target + offset, target);
+    if director.elapsed < settings.transition {
+        let t = director.elapsed / settings.transition;
+        let smooth = t * t * (3.0 - 2.0 * t);
+        eye = director.from.0.lerp(eye, smooth);
+        look = director.from.1.lerp(look, smooth);
+    }
+    camera.translation = eye;
+    camera.look_at(look, Vec3::Z);
+    director.look = look;
+}
@@ -0,0 +1,121 @@
+//! Conservation diagnostics at the top of the window, the standard sanity check of an
+//! N-body code.
+//!
+//! `--energy` computes the kinetic and potential energy and the angular momentum
+//! every `--energy-every` physics steps, 60 by default, and shows them with their
+//! drift since the first sample. A good integrator keeps the drift of an isolated galaxy
+//! small, captures, mergers and a restart from the panel change the totals on purpose. The
+//! text needs the UI font.
+
+use crate::{state::GalaxyState, UI_FONT};
+use bevy::prelude::*;
+use galaxy_core::{
+    diagnostics::{conserved, Conserved},
+    SimulationParameters,
+};
+
+/// how often the quantities are measured
+pub struct EnergySettings {
+    /// physics steps between the samples
+    pub interval: u64,
+}
+
+#[derive(Default)]
+struct EnergyLog {
+    next_step: u64,
+    initial: Option<Conserved>,
+    latest: Conserved,
+    /// samples taken so far, tells the text when to update
+    samples: usize,
+}
+
+struct EnergyText;
+
+pub struct EnergyPlugin;
+
+impl Plugin for EnergyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<EnergyLog>()
+            .add_startup_system(setup.system())
+            .add_system_to_stage(crate::PHYSICS_STAGE, sample.system().after("moving"))
+            .add_system(update_text.system());
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(40.0),
+                    top: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(UI_FONT),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(EnergyText);
+}
+
+fn sample(
+    mut log: ResMut<EnergyLog>,
+    settings: Res<EnergySettings>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+) {
+    // paused steps don't count
+    if state.steps < log.next_step {
+        return;
+    }
+    log.next_step = state.steps + settings.interval.max(1);
+    let latest = conserved(&state.particles, &parameters);
+    log.initial.get_or_insert(latest);
+    log.latest = latest;
+    log.samples += 1;
+}
+
+/// a drift in percent, or a dash when the initial value was 0
+fn percent(drift: Option<f64>) -> String {
+    match drift {
+        Some(drift) => format!("{:+.4}%", drift * 100.0),
+        None => "-".to_string(),
+    }
+}
+
+fn update_text(
+    log: Res<EnergyLog>,
+    mut shown: Local<usize>,
+    mut texts: Query<&mut Text, With<EnergyText>>,
+) {
+    let initial = match log.initial {
+        Some(initial) if *shown != log.samples => initial,
+        _ => return,
+    };
+    *shown = log.samples;
+    let latest = &log.latest;
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!(
+            "kinetic {:.4e} J\n\
+             potential {:.4e} J\n\
+             total {:.4e} J, drift {}\n\
+             angular momentum {:.4e} kg m²/s, drift {}",
+            latest.kinetic,
+            latest.potential,
+            latest.energy(),
+            percent(latest.energy_drift(&initial)),
+            latest.angular_momentum.length(),
+            percent(latest.angular_momentum_drift(&initial)),
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::{math::DVec3, prelude::*};
+
+/// two stars came closer than the minimum gravity distance
+#[derive(Debug, Clone, Copy)]
+pub struct CollisionEvent {
+    pub a: Entity,
+    pub b: Entity,
+    pub position: DVec3,
+}
+
+/// two stars were merged into one, the absorbed entity goes back to the star pool afterwards
+/// and may be reused for a later star
+#[derive(Debug, Clone, Copy)]
+pub struct MergeEvent {
+    pub survivor: Entity,
+    pub absorbed: Entity,
+    pub position: DVec3,
+    pub mass: f64,
+}
+
+/// a star left the galaxy
+#[derive(Debug, Clone, Copy)]
+pub struct EscapeEvent {
+    pub star: Entity,
+    pub position: DVec3,
+    pub velocity: DVec3,
+}
+
+/// a star was swallowed by the black hole, the star entity goes back to the star pool
+/// afterwards and may be reused for a later star
+#[derive(Debug, Clone, Copy)]
+pub struct AccretionEvent {
+    pub black_hole: Entity,
+    pub star: Entity,
+    pub mass: f64,
+}
+
+/// a star exploded, not raised yet: it belongs to stellar evolution, which doesn't exist,
+/// the readers are in place for when it does
+#[derive(Debug, Clone, Copy)]
+pub struct SupernovaEvent {
+    pub star: Entity,
+    pub position: DVec3,
+    pub mass: f64,
+}
+
+/// registers the simulation events, physics writes them and presentation systems read them
+pub struct SimulationEventsPlugin;
+
+impl Plugin for SimulationEventsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<CollisionEvent>()
+            .add_event::<MergeEvent>()
+            .add_event::<EscapeEvent>()
+            .add_event::<AccretionEvent>()
+            .add_event::<SupernovaEvent>()
+            .add_system(log_events.system());
+    }
+}
+
+fn log_events(
+    mut collisions: EventReader<CollisionEvent>,
+    mut merges: EventReader<MergeEvent>,
+    mut escapes: EventReader<EscapeEvent>,
+    mut accretions: EventReader<AccretionEvent>,
+    mut supernovae: EventReader<SupernovaEvent>,
+) {
+    for e in collisions.iter() {
+        debug!("collision of {:?} and {:?} at {:?}", e.a, e.b, e.position);
+    }
+    for e in merges.iter() {
+        info!(
+            "{:?} merged into {:?} at {:?}, new mass {:e} kg",
+            e.absorbed, e.survivor, e.position, e.mass
+        );
+    }
+    for e in escapes.iter() {
+        info!(
+            "{:?} escaped at {:?} with {:e} m/s",
+            e.star,
+            e.position,
+            e.velocity.length()
+        );
+    }
+    for e in accretions.iter() {
+        info!(
+            "{:?} swallowed {:?} with {:e} kg",
+            e.black_hole, e.star, e.mass
+        );
+    }
+    for e in supernovae.iter() {
+        info!(
+            "{:?} went supernova at {:?} with {:e} kg",
+            e.star, e.position, e.mass
+        );
+    }
+}
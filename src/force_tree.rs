@@ -0,0 +1,140 @@
+//! Debug view of the Barnes–Hut octree, `B` shows and hides it.
+//!
+//! Every cell of the tree of the current positions is drawn as a dim wireframe cube. While
+//! a star is selected, the cells whose monopole pulled on it are drawn again in orange and
+//! the leaves whose stars pulled on it one by one in white, so the effect of
+//! `--opening-angle` on a single star can be seen while tuning it. With the opening angle
+//! 0 of the exact sum no cell is approximated. The tree is rebuilt every frame while shown,
+//! which is slow for large galaxies.
+
+use crate::{
+    lagrange::line_mesh, render_frame::RenderFrame, selection::Selection, state::GalaxyState,
+};
+use bevy::prelude::*;
+use galaxy_core::{
+    octree::{Cell, Octree},
+    DVec3, SimulationParameters,
+};
+
+const TOGGLE: KeyCode = KeyCode::B;
+
+/// what a line mesh of cells shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cells {
+    All,
+    /// the monopoles pulling on the selected star
+    Approximated,
+    /// the leaves summed directly for the selected star
+    Direct,
+}
+
+#[derive(Default)]
+struct ForceTreeView {
+    shown: bool,
+}
+
+pub struct ForceTreePlugin;
+
+impl Plugin for ForceTreePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ForceTreeView>()
+            .add_startup_system(setup.system())
+            .add_system(toggle.system())
+            .add_system(update.system().after("sync").after("selection"));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (cells, color) in [
+        (Cells::All, Color::rgb(0.15, 0.25, 0.4)),
+        (Cells::Approximated, Color::rgb(1.0, 0.55, 0.1)),
+        (Cells::Direct, Color::WHITE),
+    ] {
+        let mut bundle = PbrBundle {
+            // a placeholder until the first cells are drawn, empty buffers can't be drawn
+            mesh: meshes.add(line_mesh(vec![[0.0; 3]; 2])),
+            material: materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        bundle.visible.is_visible = false;
+        commands.spawn_bundle(bundle).insert(cells);
+    }
+}
+
+fn toggle(keys: Res<Input<KeyCode>>, mut view: ResMut<ForceTreeView>) {
+    if keys.just_pressed(TOGGLE) {
+        view.shown = !view.shown;
+    }
+}
+
+/// the 12 edges of every cell as line segments in render space
+fn wireframe<'a>(cells: impl Iterator<Item = &'a Cell>, frame: &RenderFrame) -> Vec<[f32; 3]> {
+    let mut positions = Vec::new();
+    for cell in cells {
+        let corner = |i: usize| {
+            let sign = |bit: usize| if i & bit != 0 { 1.0 } else { -1.0 };
+            let offset = DVec3::new(sign(1), sign(2), sign(4)) * cell.half_size;
+            frame.render_position(cell.center + offset).into()
+        };
+        for i in 0..8 {
+            // each corner to its neighbour along the axes in which it is at the low side
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    positions.push(corner(i));
+                    positions.push(corner(i | bit));
+                }
+            }
+        }
+    }
+    positions
+}
+
+fn update(
+    view: Res<ForceTreeView>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+    selection: Res<Selection>,
+    frame: Res<RenderFrame>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut lines: Query<(&Cells, &Handle<Mesh>, &mut Visible)>,
+) {
+    if !view.shown {
+        if view.is_changed() {
+            for (_, _, mut visible) in lines.iter_mut() {
+                visible.is_visible = false;
+            }
+        }
+        return;
+    }
+    if !(view.is_changed() || state.is_changed() || selection.is_changed() || frame.is_changed()) {
+        return;
+    }
+    profiling::scope!("force tree");
+    let particles = &state.particles;
+    let tree = Octree::new(particles);
+    let cells: Vec<Cell> = tree.cells().filter(|cell| cell.mass > 0.0).collect();
+    let interactions = selection
+        .0
+        .and_then(|id| particles.index_of(id))
+        .map(|i| tree.interactions(particles, i, &parameters))
+        .unwrap_or_default();
+    for (kind, mesh, mut visible) in lines.iter_mut() {
+        let positions = match kind {
+            Cells::All => wireframe(cells.iter(), &frame),
+            Cells::Approximated => wireframe(interactions.approximated.iter(), &frame),
+            Cells::Direct => wireframe(interactions.direct.iter(), &frame),
+        };
+        visible.is_visible = !positions.is_empty();
+        if let Some(mesh) = meshes.get_mut(mesh).filter(|_| visible.is_visible) {
+            *mesh = line_mesh(positions);
+        }
+    }
+}
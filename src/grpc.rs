@@ -0,0 +1,375 @@
+//! gRPC service for orchestration systems, defined in `proto/galaxy.proto`.
+//!
+//! The server listens on `GALAXY_GRPC`, default `127.0.0.1:50051`. Like the HTTP API it
+//! hands every request to the app through a channel, the state stream is fed from a
+//! broadcast channel that is only filled while somebody is subscribed.
+
+use crate::{
+    spawning::SpawnRequests,
+    state::{GalaxyState, SimulationState},
+};
+use bevy::{math::DVec3, prelude::*};
+use galaxy_core::{SimulationParameters, Star};
+use std::{
+    pin::Pin,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+use tokio::sync::{broadcast, oneshot};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status};
+
+/// the messages of `proto/galaxy.proto`
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Empty {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TimeFactor {
+        #[prost(double, tag = "1")]
+        pub value: f64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Stats {
+        #[prost(uint64, tag = "1")]
+        pub steps: u64,
+        #[prost(double, tag = "2")]
+        pub time: f64,
+        #[prost(uint64, tag = "3")]
+        pub stars: u64,
+        #[prost(bool, tag = "4")]
+        pub paused: bool,
+        #[prost(double, tag = "5")]
+        pub time_factor: f64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StreamRequest {
+        #[prost(uint32, tag = "1")]
+        pub decimation: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct State {
+        #[prost(uint64, tag = "1")]
+        pub steps: u64,
+        #[prost(double, tag = "2")]
+        pub time: f64,
+        #[prost(double, repeated, tag = "3")]
+        pub positions: Vec<f64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Star {
+        #[prost(double, tag = "1")]
+        pub x: f64,
+        #[prost(double, tag = "2")]
+        pub y: f64,
+        #[prost(double, tag = "3")]
+        pub z: f64,
+        #[prost(double, tag = "4")]
+        pub vx: f64,
+        #[prost(double, tag = "5")]
+        pub vy: f64,
+        #[prost(double, tag = "6")]
+        pub vz: f64,
+        #[prost(double, tag = "7")]
+        pub mass: f64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Snapshot {
+        #[prost(double, tag = "1")]
+        pub time: f64,
+        #[prost(message, repeated, tag = "2")]
+        pub stars: Vec<Star>,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/galaxy.Galaxy.rs"));
+}
+
+use proto::galaxy_server::{Galaxy, GalaxyServer};
+
+pub struct GrpcSettings {
+    /// address the server listens on
+    pub address: String,
+    /// state stream messages per second
+    pub rate: f64,
+}
+
+impl Default for GrpcSettings {
+    fn default() -> Self {
+        Self {
+            address: std::env::var("GALAXY_GRPC").unwrap_or_else(|_| "127.0.0.1:50051".to_string()),
+            rate: 30.0,
+        }
+    }
+}
+
+enum Command {
+    Pause,
+    Resume,
+    SetTimeFactor(f64),
+    Stats(oneshot::Sender<proto::Stats>),
+    GetSnapshot(oneshot::Sender<proto::Snapshot>),
+    LoadSnapshot(proto::Snapshot),
+}
+
+/// receiving side of the channel from the server
+struct CommandReceiver(Mutex<Receiver<Command>>);
+
+/// sending side of the state stream
+struct StateSender(broadcast::Sender<Arc<proto::State>>);
+
+struct Service {
+    commands: Sender<Command>,
+    states: broadcast::Sender<Arc<proto::State>>,
+}
+
+impl Service {
+    // the handlers return tonic's large Status anyway
+    #[allow(clippy::result_large_err)]
+    fn send(&self, command: Command) -> Result<(), Status> {
+        self.commands
+            .send(command)
+            .map_err(|_| Status::unavailable("simulation stopped"))
+    }
+
+    /// sends a command that expects a reply and waits for it
+    async fn ask<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T, Status> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(command(sender))?;
+        receiver
+            .await
+            .map_err(|_| Status::unavailable("simulation stopped"))
+    }
+}
+
+type StateStream = Pin<Box<dyn Stream<Item = Result<proto::State, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Galaxy for Service {
+    async fn pause(&self, _: Request<proto::Empty>) -> Result<Response<proto::Empty>, Status> {
+        self.send(Command::Pause)?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn resume(&self, _: Request<proto::Empty>) -> Result<Response<proto::Empty>, Status> {
+        self.send(Command::Resume)?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn set_time_factor(
+        &self,
+        request: Request<proto::TimeFactor>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let factor = request.into_inner().value;
+        if factor.is_nan() || factor <= 0.0 {
+            return Err(Status::invalid_argument("expected a positive time factor"));
+        }
+        self.send(Command::SetTimeFactor(factor))?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn get_stats(&self, _: Request<proto::Empty>) -> Result<Response<proto::Stats>, Status> {
+        self.ask(Command::Stats).await.map(Response::new)
+    }
+
+    type StreamStateStream = StateStream;
+
+    async fn stream_state(
+        &self,
+        request: Request<proto::StreamRequest>,
+    ) -> Result<Response<StateStream>, Status> {
+        let decimation = request.into_inner().decimation.max(1) as usize;
+        // a client too slow for the stream misses states instead of stalling the others
+        let stream = BroadcastStream::new(self.states.subscribe()).filter_map(move |state| {
+            let state = state.ok()?;
+            let positions = state
+                .positions
+                .chunks_exact(3)
+                .step_by(decimation)
+                .flatten()
+                .copied()
+                .collect();
+            Some(Ok(proto::State {
+                steps: state.steps,
+                time: state.time,
+                positions,
+            }))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_snapshot(
+        &self,
+        _: Request<proto::Empty>,
+    ) -> Result<Response<proto::Snapshot>, Status> {
+        self.ask(Command::GetSnapshot).await.map(Response::new)
+    }
+
+    async fn load_snapshot(
+        &self,
+        request: Request<proto::Snapshot>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let snapshot = request.into_inner();
+        if snapshot.stars.is_empty() {
+            return Err(Status::invalid_argument(
+                "a snapshot needs at least the black hole",
+            ));
+        }
+        self.send(Command::LoadSnapshot(snapshot))?;
+        Ok(Response::new(proto::Empty {}))
+    }
+}
+
+pub struct GrpcPlugin;
+
+impl Plugin for GrpcPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GrpcSettings>()
+            .add_startup_system(start_server.system())
+            .add_system(process_commands.system())
+            .add_system(send_state.system());
+    }
+}
+
+fn start_server(mut commands: Commands, settings: Res<GrpcSettings>) {
+    let address = match settings.address.parse() {
+        Ok(address) => address,
+        Err(_) => {
+            error!("invalid gRPC address {}", settings.address);
+            return;
+        }
+    };
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            error!("can't start the gRPC runtime: {}", e);
+            return;
+        }
+    };
+
+    let (sender, receiver) = channel();
+    let (states, _) = broadcast::channel(4);
+    let service = Service {
+        commands: sender,
+        states: states.clone(),
+    };
+    info!("gRPC service on {}", address);
+    thread::spawn(move || {
+        let server = Server::builder()
+            .add_service(GalaxyServer::new(service))
+            .serve(address);
+        if let Err(e) = runtime.block_on(server) {
+            error!("gRPC server stopped: {}", e);
+        }
+    });
+    commands.insert_resource(CommandReceiver(Mutex::new(receiver)));
+    commands.insert_resource(StateSender(states));
+}
+
+fn to_proto(star: Star) -> proto::Star {
+    let (p, v) = (star.position, star.velocity);
+    proto::Star {
+        x: p.x,
+        y: p.y,
+        z: p.z,
+        vx: v.x,
+        vy: v.y,
+        vz: v.z,
+        mass: star.mass,
+    }
+}
+
+fn from_proto(star: &proto::Star) -> Star {
+    Star {
+        position: DVec3::new(star.x, star.y, star.z),
+        velocity: DVec3::new(star.vx, star.vy, star.vz),
+        acceleration: DVec3::default(),
+        mass: star.mass,
+    }
+}
+
+fn process_commands(
+    receiver: Option<Res<CommandReceiver>>,
+    mut simulation: ResMut<SimulationState>,
+    mut parameters: ResMut<SimulationParameters>,
+    mut state: ResMut<GalaxyState>,
+    mut requests: ResMut<SpawnRequests>,
+) {
+    let receiver = match receiver {
+        Some(receiver) => receiver,
+        None => return,
+    };
+    let receiver = receiver.0.lock().unwrap();
+    while let Ok(command) = receiver.try_recv() {
+        match command {
+            Command::Pause => simulation.paused = true,
+            Command::Resume => simulation.paused = false,
+            Command::SetTimeFactor(factor) => parameters.time_factor = factor,
+            Command::Stats(reply) => {
+                let _ = reply.send(proto::Stats {
+                    steps: state.steps,
+                    time: state.time,
+                    stars: state.particles.len() as u64,
+                    paused: simulation.paused,
+                    time_factor: parameters.time_factor,
+                });
+            }
+            Command::GetSnapshot(reply) => {
+                let _ = reply.send(proto::Snapshot {
+                    time: state.time,
+                    stars: state.particles.stars().map(to_proto).collect(),
+                });
+            }
+            Command::LoadSnapshot(snapshot) => {
+                // all despawns are done before the spawns, so the new stars keep their order
+                requests.despawn.extend_from_slice(&state.particles.ids);
+                requests.spawn.extend(snapshot.stars.iter().map(from_proto));
+                state.time = snapshot.time;
+            }
+        }
+    }
+}
+
+fn send_state(
+    time: Res<Time>,
+    mut since_last: Local<f64>,
+    sender: Option<Res<StateSender>>,
+    settings: Res<GrpcSettings>,
+    state: Res<GalaxyState>,
+) {
+    let sender = match sender {
+        Some(sender) if sender.0.receiver_count() > 0 => sender,
+        _ => return,
+    };
+    *since_last += time.delta_seconds_f64();
+    if *since_last < 1.0 / settings.rate {
+        return;
+    }
+    *since_last = 0.0;
+
+    let positions = state
+        .particles
+        .positions
+        .iter()
+        .flat_map(|p| [p.x, p.y, p.z])
+        .collect();
+    let _ = sender.0.send(Arc::new(proto::State {
+        steps: state.steps,
+        time: state.time,
+        positions,
+    }));
+}
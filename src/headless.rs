@@ -0,0 +1,235 @@
+//! Batch runs without a window for offline experiments, started with `--headless`.
+//!
+//! The solver of galaxy-core advances the stars by `--steps` physics steps, or the steps
+//! of `--duration`, without Bevy. Every `--snapshot-every` steps and after the last one
+//! the stars are saved as numbered snapshots into the `--output` directory, which
+//! `--load` resumes, or with `--hdf5` as GADGET-2 HDF5 files for astronomy tools.
+//! `--record` and `--export` write the trajectory and the CSV tables like in a windowed
+//! run. Escaping stars are frozen like in the app. The forces are always summed on the
+//! CPU, a distributed run or a GPU isn't set up.
+//!
+//! Built with `http` the control API answers between the steps, a paused run waits for
+//! `POST /resume` and `POST /snapshot` saves a numbered snapshot into the output
+//! directory. Built with `websocket` the positions are streamed, but no events, the
+//! collisions and merges of the app come from its entities.
+
+#[cfg(feature = "http")]
+use galaxy::http::{Control, HttpControl, HttpSettings, Run};
+#[cfg(feature = "websocket")]
+use galaxy::websocket::{positions_frame, FrameSender, WebSocketSettings};
+use galaxy_core::{
+    error::ensure_output_dir, export::CsvExport, gadget, trajectory::TrajectoryWriter, Result,
+    Simulation,
+};
+use std::{
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// physics steps of a batch run without `--steps` and `--duration`
+pub const DEFAULT_STEPS: u64 = 1000;
+/// directory of the snapshots without `--output`
+pub const DEFAULT_OUTPUT: &str = "output";
+/// wall clock time between looking for requests while paused
+const PAUSED_POLL: Duration = Duration::from_millis(50);
+
+/// the batch settings from the command line
+#[derive(Debug)]
+pub struct Batch {
+    pub steps: u64,
+    /// steps between saved snapshots, 0 saves only the last state
+    pub snapshot_every: u64,
+    pub output: PathBuf,
+    /// snapshots in the GADGET-2 HDF5 format instead of JSON
+    pub hdf5: bool,
+    pub record: Option<PathBuf>,
+    pub export: Option<CsvExport>,
+}
+
+/// the servers of the enabled features, answered between the steps
+#[derive(Default)]
+struct Services {
+    #[cfg(feature = "http")]
+    http: Option<HttpControl>,
+    #[cfg(feature = "websocket")]
+    websocket: Option<(FrameSender, WebSocketSettings)>,
+    /// set and cleared by the control API
+    paused: bool,
+    /// wall clock time spent in the physics steps so far, for the metrics
+    #[cfg_attr(not(feature = "http"), allow(dead_code))]
+    step_time: Duration,
+    /// when the previous positions frame was sent
+    #[cfg(feature = "websocket")]
+    last_frame: Option<Instant>,
+}
+
+impl Services {
+    /// starts the servers on the addresses of the windowed app, one that can't listen is
+    /// reported and left out
+    fn start() -> Self {
+        #[allow(unused_mut)]
+        let mut services = Self::default();
+        #[cfg(feature = "http")]
+        {
+            let settings = HttpSettings::default();
+            match HttpControl::start(&settings.address) {
+                Ok(control) => {
+                    println!("control API on http://{}", settings.address);
+                    services.http = Some(control);
+                }
+                Err(e) => eprintln!("can't start HTTP server: {}", e),
+            }
+        }
+        #[cfg(feature = "websocket")]
+        {
+            let settings = WebSocketSettings::default();
+            match FrameSender::start(&settings.address) {
+                Ok(sender) => {
+                    println!(
+                        "streaming to WebSocket clients on ws://{}",
+                        settings.address
+                    );
+                    services.websocket = Some((sender, settings));
+                }
+                Err(e) => eprintln!("can't start WebSocket server: {}", e),
+            }
+        }
+        services
+    }
+
+    /// answers the requests and streams the positions, waits while the run is paused
+    #[cfg_attr(
+        not(any(feature = "http", feature = "websocket")),
+        allow(unused_variables)
+    )]
+    fn serve(&mut self, simulation: &mut Simulation, batch: &Batch) {
+        loop {
+            #[cfg(feature = "http")]
+            self.answer(simulation, batch);
+            #[cfg(feature = "websocket")]
+            self.stream(simulation);
+            if !self.paused {
+                return;
+            }
+            thread::sleep(PAUSED_POLL);
+        }
+    }
+
+    #[cfg(feature = "http")]
+    fn answer(&mut self, simulation: &mut Simulation, batch: &Batch) {
+        let control = match &mut self.http {
+            Some(control) => control,
+            None => return,
+        };
+        let save = || {
+            save(simulation, batch)
+                .map(|path| format!("saved to {}\n", path.display()))
+                .map_err(|e| format!("{}\n", e))
+        };
+        let controls = control.process(&Run {
+            particles: &simulation.particles,
+            parameters: &simulation.parameters,
+            paused: self.paused,
+            steps: simulation.steps,
+            time: simulation.time,
+            step_time: self.step_time,
+            save: &save,
+        });
+        for control in controls {
+            match control {
+                Control::Pause => self.paused = true,
+                Control::Resume => self.paused = false,
+                Control::TimeFactor(factor) => simulation.parameters.time_factor = factor,
+            }
+        }
+    }
+
+    #[cfg(feature = "websocket")]
+    fn stream(&mut self, simulation: &Simulation) {
+        let (sender, settings) = match &self.websocket {
+            Some(websocket) => websocket,
+            None => return,
+        };
+        let now = Instant::now();
+        let interval = Duration::from_secs_f64(1.0 / settings.rate);
+        if self.last_frame.is_some_and(|last| now - last < interval) {
+            return;
+        }
+        self.last_frame = Some(now);
+        sender.send(positions_frame(
+            simulation.steps,
+            simulation.time,
+            &simulation.particles.positions,
+            settings.decimation,
+            simulation.parameters.galaxy_diameter,
+        ));
+    }
+}
+
+/// steps the simulation, returns after the last snapshot is written
+pub fn run(mut simulation: Simulation, batch: &Batch) -> Result<()> {
+    ensure_output_dir(&batch.output)?;
+    let mut trajectory = match &batch.record {
+        Some(path) => Some(TrajectoryWriter::create(path)?),
+        None => None,
+    };
+    let mut services = Services::start();
+    println!(
+        "{} stars, {} steps of {:.3e} s",
+        simulation.len(),
+        batch.steps,
+        simulation.parameters.physics_dt * simulation.parameters.time_factor
+    );
+    let started = Instant::now();
+    let first = simulation.steps;
+    for _ in 0..batch.steps {
+        services.serve(&mut simulation, batch);
+        let step_started = Instant::now();
+        simulation.step();
+        services.step_time += step_started.elapsed();
+        if let Some(writer) = &mut trajectory {
+            writer.write_frame(simulation.time, &simulation.particles)?;
+        }
+        if let Some(export) = &batch.export {
+            if export.is_due(simulation.steps) {
+                export.write(simulation.steps, simulation.time, &simulation.particles)?;
+            }
+        }
+        let done = simulation.steps - first;
+        let periodic = batch.snapshot_every > 0 && done.is_multiple_of(batch.snapshot_every);
+        if periodic || done == batch.steps {
+            save(&simulation, batch)?;
+        }
+    }
+    if let Some(writer) = trajectory {
+        writer.finish()?;
+    }
+    let seconds = started.elapsed().as_secs_f64();
+    println!(
+        "done in {:.1} s, {:.1} steps per second",
+        seconds,
+        batch.steps as f64 / seconds.max(f64::EPSILON)
+    );
+    Ok(())
+}
+
+/// saves the numbered snapshot of the current step, returns its path
+fn save(simulation: &Simulation, batch: &Batch) -> Result<PathBuf> {
+    let extension = if batch.hdf5 { "hdf5" } else { "json" };
+    let path = batch
+        .output
+        .join(format!("snapshot-{:08}.{}", simulation.steps, extension));
+    if batch.hdf5 {
+        gadget::save(&path, simulation.time, &simulation.particles)?;
+    } else {
+        simulation.snapshot().save(&path)?;
+    }
+    println!(
+        "step {}, {:.3e} s: {}",
+        simulation.steps,
+        simulation.time,
+        path.display()
+    );
+    Ok(path)
+}
@@ -0,0 +1,334 @@
+//! Small REST endpoint to control a running simulation:
+//!
+//! * `POST /pause` and `POST /resume`
+//! * `POST /time_factor` with the new simulated seconds per physics second as body
+//! * `POST /snapshot` saves the stars to the snapshot file, like `F5`
+//! * `GET /stats` returns a JSON object with steps, time, stars, paused and time factor
+//! * `GET /metrics` returns the same and the solver timings and energy drift for Prometheus
+//!
+//! `HttpControl` owns the server and answers the requests between physics steps, the
+//! plugin polls it every frame and a headless run after every step.
+
+use crate::{
+    snapshot::SnapshotFile,
+    state::{GalaxyState, SimulationState},
+};
+use bevy::prelude::*;
+use galaxy_core::{diagnostics::total_energy, Error, Particles, SimulationParameters};
+use std::{
+    fmt::Write,
+    io,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tiny_http::{Method, Request, Response, Server};
+
+pub struct HttpSettings {
+    /// address the server listens on
+    pub address: String,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            address: std::env::var("GALAXY_HTTP").unwrap_or_else(|_| "127.0.0.1:8080".to_string()),
+        }
+    }
+}
+
+enum Command {
+    Pause,
+    Resume,
+    SetTimeFactor(f64),
+    Snapshot(Sender<Result<String, String>>),
+    Stats(Sender<String>),
+    Metrics(Sender<String>),
+}
+
+/// the running server, its requests are answered by `process`
+pub struct HttpControl {
+    receiver: Mutex<Receiver<Command>>,
+    baseline: MetricsBaseline,
+}
+
+/// what a request asks the owner of the simulation to change
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Pause,
+    Resume,
+    TimeFactor(f64),
+}
+
+/// the simulation the requests are answered from
+pub struct Run<'a> {
+    pub particles: &'a Particles,
+    pub parameters: &'a SimulationParameters,
+    pub paused: bool,
+    pub steps: u64,
+    pub time: f64,
+    /// wall clock time spent in the physics steps so far
+    pub step_time: Duration,
+    /// saves a snapshot for `POST /snapshot`, the reply is the message or the error
+    pub save: &'a dyn Fn() -> Result<String, String>,
+}
+
+impl HttpControl {
+    /// starts the server thread listening on `address`
+    pub fn start(address: &str) -> galaxy_core::Result<Self> {
+        let server = Server::http(address).map_err(|e| Error::Network {
+            peer: address.to_string(),
+            source: io::Error::other(e),
+        })?;
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                handle(request, &sender);
+            }
+        });
+        Ok(Self {
+            receiver: Mutex::new(receiver),
+            baseline: MetricsBaseline::default(),
+        })
+    }
+
+    /// answers the requests that came in since the last call, returns the changes they ask
+    /// for in order
+    pub fn process(&mut self, run: &Run) -> Vec<Control> {
+        if self.baseline.initial_energy.is_none() {
+            self.baseline.initial_energy = Some(total_energy(run.particles, run.parameters));
+        }
+        let mut controls = Vec::new();
+        let receiver = self.receiver.get_mut().unwrap();
+        while let Ok(command) = receiver.try_recv() {
+            match command {
+                Command::Pause => controls.push(Control::Pause),
+                Command::Resume => controls.push(Control::Resume),
+                Command::SetTimeFactor(factor) => controls.push(Control::TimeFactor(factor)),
+                Command::Snapshot(reply) => {
+                    let _ = reply.send((run.save)());
+                }
+                Command::Stats(reply) => {
+                    let _ = reply.send(format!(
+                        "{{\"steps\":{},\"time\":{:e},\"stars\":{},\"paused\":{},\"time_factor\":{:e}}}\n",
+                        run.steps,
+                        run.time,
+                        run.particles.len(),
+                        run.paused,
+                        run.parameters.time_factor
+                    ));
+                }
+                Command::Metrics(reply) => {
+                    let _ = reply.send(metrics(&mut self.baseline, run));
+                }
+            }
+        }
+        controls
+    }
+}
+
+pub struct HttpPlugin;
+
+impl Plugin for HttpPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<HttpSettings>()
+            .add_startup_system(start_server.system())
+            .add_system(process_commands.system());
+    }
+}
+
+fn start_server(mut commands: Commands, settings: Res<HttpSettings>) {
+    match HttpControl::start(&settings.address) {
+        Ok(control) => {
+            info!("control API on http://{}", settings.address);
+            commands.insert_resource(control);
+        }
+        Err(e) => error!("can't start HTTP server: {}", e),
+    }
+}
+
+fn respond(request: Request, status: u16, body: String) {
+    let _ = request.respond(Response::from_string(body).with_status_code(status));
+}
+
+/// sends a command that expects a reply and waits for it, `None` when the app has quit
+fn ask<T>(commands: &Sender<Command>, command: impl FnOnce(Sender<T>) -> Command) -> Option<T> {
+    let (sender, receiver) = channel();
+    commands.send(command(sender)).ok()?;
+    receiver.recv().ok()
+}
+
+/// runs on the server thread, everything touching the simulation goes through the channel
+fn handle(mut request: Request, commands: &Sender<Command>) {
+    let command = match (request.method(), request.url()) {
+        (Method::Post, "/pause") => Command::Pause,
+        (Method::Post, "/resume") => Command::Resume,
+        (Method::Post, "/time_factor") => {
+            let mut body = String::new();
+            let _ = request.as_reader().read_to_string(&mut body);
+            match body.trim().parse() {
+                Ok(factor) if factor > 0.0 => Command::SetTimeFactor(factor),
+                _ => return respond(request, 400, "expected a positive number\n".to_string()),
+            }
+        }
+        (Method::Post, "/snapshot") => {
+            return match ask(commands, Command::Snapshot) {
+                Some(Ok(message)) => respond(request, 200, message),
+                Some(Err(message)) => respond(request, 500, message),
+                None => respond(request, 503, "simulation stopped\n".to_string()),
+            };
+        }
+        (Method::Get, "/stats") => {
+            return match ask(commands, Command::Stats) {
+                Some(stats) => respond(request, 200, stats),
+                None => respond(request, 503, "simulation stopped\n".to_string()),
+            };
+        }
+        (Method::Get, "/metrics") => {
+            return match ask(commands, Command::Metrics) {
+                Some(metrics) => respond(request, 200, metrics),
+                None => respond(request, 503, "simulation stopped\n".to_string()),
+            };
+        }
+        _ => return respond(request, 404, "not found\n".to_string()),
+    };
+    if commands.send(command).is_err() {
+        return respond(request, 503, "simulation stopped\n".to_string());
+    }
+    respond(request, 200, "ok\n".to_string())
+}
+
+/// what the metrics are measured against
+#[derive(Default)]
+struct MetricsBaseline {
+    initial_energy: Option<f64>,
+    /// time and step count of the previous scrape
+    last_scrape: Option<(Instant, u64)>,
+    /// step count and total energy of the last computed one, scrapes between two steps
+    /// don't sum it again
+    energy: Option<(u64, f64)>,
+}
+
+/// appends one metric in the Prometheus text format
+fn metric(text: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, kind);
+    let _ = writeln!(text, "{} {:e}", name, value);
+}
+
+fn metrics(baseline: &mut MetricsBaseline, run: &Run) -> String {
+    let now = Instant::now();
+    let steps_per_second = match baseline.last_scrape {
+        Some((time, steps)) => (run.steps - steps) as f64 / (now - time).as_secs_f64(),
+        None => 0.0,
+    };
+    baseline.last_scrape = Some((now, run.steps));
+    let energy = match baseline.energy {
+        Some((steps, energy)) if steps == run.steps => energy,
+        _ => {
+            let energy = total_energy(run.particles, run.parameters);
+            baseline.energy = Some((run.steps, energy));
+            energy
+        }
+    };
+    // e.g. massless stars start without energy, there is nothing to be relative to
+    let initial_energy = baseline.initial_energy.unwrap_or_default();
+    let energy_drift = if initial_energy != 0.0 {
+        (energy - initial_energy) / initial_energy.abs()
+    } else {
+        0.0
+    };
+
+    let mut text = String::new();
+    let values = [
+        (
+            "galaxy_steps_total",
+            "counter",
+            "Physics steps done so far.",
+            run.steps as f64,
+        ),
+        (
+            "galaxy_steps_per_second",
+            "gauge",
+            "Physics steps per second since the previous scrape.",
+            steps_per_second,
+        ),
+        (
+            "galaxy_step_seconds_total",
+            "counter",
+            "Wall clock seconds spent in the physics steps.",
+            run.step_time.as_secs_f64(),
+        ),
+        (
+            "galaxy_simulated_seconds",
+            "gauge",
+            "Simulated seconds so far.",
+            run.time,
+        ),
+        (
+            "galaxy_stars",
+            "gauge",
+            "Number of stars.",
+            run.particles.len() as f64,
+        ),
+        (
+            "galaxy_energy_drift",
+            "gauge",
+            "Relative change of the total energy since the start, 0 if it started at 0.",
+            energy_drift,
+        ),
+        (
+            "galaxy_time_factor",
+            "gauge",
+            "Simulated seconds per physics second.",
+            run.parameters.time_factor,
+        ),
+        (
+            "galaxy_paused",
+            "gauge",
+            "1 while the simulation is paused.",
+            run.paused as u8 as f64,
+        ),
+    ];
+    for (name, kind, help, value) in values.iter() {
+        metric(&mut text, name, kind, help, *value);
+    }
+    text
+}
+
+fn process_commands(
+    control: Option<ResMut<HttpControl>>,
+    mut simulation: ResMut<SimulationState>,
+    mut parameters: ResMut<SimulationParameters>,
+    state: Res<GalaxyState>,
+    file: Res<SnapshotFile>,
+) {
+    let mut control = match control {
+        Some(control) => control,
+        None => return,
+    };
+    let save = || {
+        file.save(&state)
+            .map(|()| format!("saved to {}\n", file.0.display()))
+            .map_err(|e| format!("{}\n", e))
+    };
+    let controls = control.process(&Run {
+        particles: &state.particles,
+        parameters: &parameters,
+        paused: simulation.paused,
+        steps: state.steps,
+        time: state.time,
+        step_time: state.step_time,
+        save: &save,
+    });
+    for control in controls {
+        match control {
+            Control::Pause => simulation.paused = true,
+            Control::Resume => simulation.paused = false,
+            Control::TimeFactor(factor) => parameters.time_factor = factor,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Inspector of the selected star in the lower left corner.
+//!
+//! While a star is selected it shows its mass, position, speed, acceleration and distance
+//! from the black hole in the galactic center, updated every frame. Without a selection
+//! the panel is hidden. The text needs the UI font.
+
+use crate::{selection::Selection, state::GalaxyState, UI_FONT};
+use bevy::prelude::*;
+use galaxy_core::units::{Length, Mass, Speed};
+
+/// the panel, hidden without a selection
+struct Inspector;
+
+struct InspectorText;
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system(update.system().after("selection"));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(6.0)),
+                display: Display::None,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.1, 0.1, 0.2, 0.8).into()),
+            ..Default::default()
+        })
+        .insert(Inspector)
+        .with_children(|panel| {
+            panel
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load(UI_FONT),
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(InspectorText);
+        });
+}
+
+fn update(
+    selection: Res<Selection>,
+    state: Res<GalaxyState>,
+    mut panels: Query<&mut Style, With<Inspector>>,
+    mut texts: Query<&mut Text, With<InspectorText>>,
+) {
+    let particles = &state.particles;
+    let index = selection.0.and_then(|id| particles.index_of(id));
+    if let Ok(mut style) = panels.single_mut() {
+        let display = if index.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    let (index, mut text) = match (index, texts.single_mut()) {
+        (Some(index), Ok(text)) => (index, text),
+        _ => return,
+    };
+
+    let position = particles.positions[index];
+    // the black hole rests at index 0, the origin when there is none
+    let center = particles.positions.first().copied().unwrap_or_default();
+    text.sections[0].value = format!(
+        "star {}\n\
+         mass {}\n\
+         position ({}, {}, {})\n\
+         speed {}\n\
+         acceleration {:.3e} m/s²\n\
+         distance from center {}",
+        particles.ids[index].0,
+        Mass(particles.masses[index]),
+        Length(position.x),
+        Length(position.y),
+        Length(position.z),
+        Speed(particles.velocities[index].length()),
+        particles.accelerations[index].length(),
+        Length((position - center).length()),
+    );
+}
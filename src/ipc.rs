@@ -0,0 +1,263 @@
+//! Shares the stars with viewer processes through a memory mapped file.
+//!
+//! The process started with `--publish <file>` simulates and writes the stars to the
+//! file after every frame. Any number of processes started with `--view <file>` render
+//! them without simulating, they can be closed and restarted without affecting the run.
+//!
+//! Layout, all values in native byte order: magic u64, sequence u64 (odd while the
+//! publisher writes), capacity u64, count u64, steps u64, time f64, then capacity times
+//! x, y, z and mass as f64.
+
+use crate::{spawning::SpawnRequests, state::GalaxyState};
+use bevy::{math::DVec3, prelude::*};
+use galaxy_core::{Error, Star};
+use memmap2::{Mmap, MmapMut};
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    sync::atomic::{fence, AtomicU64, Ordering},
+};
+
+const MAGIC: u64 = u64::from_le_bytes(*b"GALAXYSM");
+const SEQUENCE: usize = 8;
+const CAPACITY: usize = 16;
+const COUNT: usize = 24;
+const STEPS: usize = 32;
+const TIME: usize = 40;
+const HEADER: usize = 48;
+/// bytes per star
+const STAR: usize = 32;
+const MIN_CAPACITY: usize = 1024;
+
+fn file_size(capacity: usize) -> u64 {
+    (HEADER + capacity * STAR) as u64
+}
+
+/// the sequence counter, the maps are page aligned so the offset is aligned as well
+fn sequence(map: &[u8]) -> &AtomicU64 {
+    unsafe { &*(map.as_ptr().add(SEQUENCE) as *const AtomicU64) }
+}
+
+fn read_u64(base: *const u8, offset: usize) -> u64 {
+    unsafe { (base.add(offset) as *const u64).read_volatile() }
+}
+
+fn read_f64(base: *const u8, offset: usize) -> f64 {
+    f64::from_bits(read_u64(base, offset))
+}
+
+fn write_u64(base: *mut u8, offset: usize, value: u64) {
+    unsafe { (base.add(offset) as *mut u64).write_volatile(value) }
+}
+
+fn write_f64(base: *mut u8, offset: usize, value: f64) {
+    write_u64(base, offset, value.to_bits())
+}
+
+/// writing side, owned by the simulating process
+pub struct Publisher {
+    path: PathBuf,
+    file: File,
+    map: MmapMut,
+}
+
+impl Publisher {
+    pub fn create(path: &Path) -> galaxy_core::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| Error::io(path, e))?;
+        let map = Self::map(path, &file, MIN_CAPACITY)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            map,
+        })
+    }
+
+    fn map(path: &Path, file: &File, capacity: usize) -> galaxy_core::Result<MmapMut> {
+        file.set_len(file_size(capacity))
+            .map_err(|e| Error::io(path, e))?;
+        let mut map = unsafe { MmapMut::map_mut(file) }.map_err(|e| Error::io(path, e))?;
+        let base = map.as_mut_ptr();
+        write_u64(base, 0, MAGIC);
+        write_u64(base, CAPACITY, capacity as u64);
+        Ok(map)
+    }
+
+    fn capacity(&self) -> usize {
+        read_u64(self.map.as_ptr(), CAPACITY) as usize
+    }
+
+    fn publish(&mut self, state: &GalaxyState) -> galaxy_core::Result<()> {
+        let particles = &state.particles;
+        if particles.len() > self.capacity() {
+            // viewers keep their old, shorter map until they see the larger count
+            let capacity = particles.len().next_power_of_two();
+            self.map = Self::map(&self.path, &self.file, capacity)?;
+        }
+
+        let base = self.map.as_mut_ptr();
+        let sequence = sequence(&self.map);
+        let start = sequence.load(Ordering::Relaxed);
+        sequence.store(start.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        write_u64(base, COUNT, particles.len() as u64);
+        write_u64(base, STEPS, state.steps);
+        write_f64(base, TIME, state.time);
+        for (i, (p, &mass)) in particles
+            .positions
+            .iter()
+            .zip(&particles.masses)
+            .enumerate()
+        {
+            let offset = HEADER + i * STAR;
+            write_f64(base, offset, p.x);
+            write_f64(base, offset + 8, p.y);
+            write_f64(base, offset + 16, p.z);
+            write_f64(base, offset + 24, mass);
+        }
+        sequence.store(start.wrapping_add(2), Ordering::Release);
+        Ok(())
+    }
+}
+
+pub fn publish(mut publisher: ResMut<Publisher>, state: Res<GalaxyState>) {
+    profiling::scope!("publish");
+    if let Err(e) = publisher.publish(&state) {
+        error!("can't publish the stars: {}", e);
+    }
+}
+
+/// one consistent copy of the published stars
+struct Frame {
+    steps: u64,
+    time: f64,
+    /// position and mass of every star
+    stars: Vec<(DVec3, f64)>,
+}
+
+/// reading side, any number of processes can view the same file
+pub struct Viewer {
+    path: PathBuf,
+    map: Mmap,
+}
+
+impl Viewer {
+    pub fn open(path: &Path) -> galaxy_core::Result<Self> {
+        let map = Self::map(path)?;
+        if map.len() < HEADER || read_u64(map.as_ptr(), 0) != MAGIC {
+            return Err(Error::Parse {
+                path: path.to_path_buf(),
+                message: "not a file published by the galaxy simulator".to_string(),
+            });
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            map,
+        })
+    }
+
+    fn map(path: &Path) -> galaxy_core::Result<Mmap> {
+        let file = File::open(path).map_err(|e| Error::io(path, e))?;
+        unsafe { Mmap::map(&file) }.map_err(|e| Error::io(path, e))
+    }
+
+    /// the stars as they are now, as initial conditions for the viewer
+    pub fn stars(&mut self) -> galaxy_core::Result<Vec<Star>> {
+        Ok(self
+            .read()?
+            .stars
+            .into_iter()
+            .map(|(position, mass)| Star {
+                position,
+                mass,
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    /// waits until the publisher is not in the middle of writing and copies everything
+    fn read(&mut self) -> galaxy_core::Result<Frame> {
+        loop {
+            let base = self.map.as_ptr();
+            let start = sequence(&self.map).load(Ordering::Acquire);
+            if start % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let count = read_u64(base, COUNT) as usize;
+            if HEADER + count * STAR > self.map.len() {
+                // the publisher grew the file
+                self.map = Self::map(&self.path)?;
+                continue;
+            }
+            let frame = Frame {
+                steps: read_u64(base, STEPS),
+                time: read_f64(base, TIME),
+                stars: (0..count)
+                    .map(|i| {
+                        let offset = HEADER + i * STAR;
+                        let position = DVec3::new(
+                            read_f64(base, offset),
+                            read_f64(base, offset + 8),
+                            read_f64(base, offset + 16),
+                        );
+                        (position, read_f64(base, offset + 24))
+                    })
+                    .collect(),
+            };
+            fence(Ordering::Acquire);
+            if sequence(&self.map).load(Ordering::Relaxed) == start {
+                return Ok(frame);
+            }
+        }
+    }
+}
+
+/// takes the place of the physics step in a viewer process
+pub fn follow(
+    mut viewer: ResMut<Viewer>,
+    mut state: ResMut<GalaxyState>,
+    mut requests: ResMut<SpawnRequests>,
+) {
+    profiling::scope!("follow");
+    let frame = match viewer.read() {
+        Ok(frame) => frame,
+        Err(e) => {
+            error!("can't read the published stars: {}", e);
+            return;
+        }
+    };
+    let state = &mut *state;
+    state
+        .previous_positions
+        .copy_from_slice(&state.particles.positions);
+    state.steps = frame.steps;
+    state.time = frame.time;
+
+    let particles = &mut state.particles;
+    for (i, &(position, mass)) in frame.stars.iter().enumerate().take(particles.len()) {
+        particles.positions[i] = position;
+        particles.masses[i] = mass;
+    }
+    // only the number of stars has to match, the publisher's order is taken as it is
+    if frame.stars.len() > particles.len() {
+        requests.spawn.extend(
+            frame.stars[particles.len()..]
+                .iter()
+                .map(|&(position, mass)| Star {
+                    position,
+                    mass,
+                    ..Default::default()
+                }),
+        );
+    } else {
+        requests
+            .despawn
+            .extend_from_slice(&particles.ids[frame.stars.len()..]);
+    }
+}
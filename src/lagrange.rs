@@ -0,0 +1,226 @@
+//! Shows the Lagrange points and zero-velocity curves of a pair of stars.
+//!
+//! `L` pairs the selected star with the black hole, a second `L` right after on another
+//! selected star pairs the two stars instead. `L` without a selection hides the points
+//! again, `--lagrange <id>,<id>` picks a first pair by particle id, `0,1` is the
+//! black hole and the first star. The five points are drawn as markers, the
+//! zero-velocity curves through L1, L2 and L3 as lines in the orbit plane. The curves are
+//! drawn a second time zoomed in on the lighter body, whose L1 and L2 are usually too
+//! close to it to show at the scale of the whole orbit.
+
+use crate::{render_frame::RenderFrame, selection::Selection, state::GalaxyState};
+use bevy::{prelude::*, render::pipeline::PrimitiveTopology};
+use galaxy_core::{lagrange::RotatingFrame, Error, ParticleId, Particles, SimulationParameters};
+
+/// grid cells per side of the sampled zero-velocity curves
+const RESOLUTION: usize = 200;
+const PAIR: KeyCode = KeyCode::L;
+
+/// the pair the points are shown for, none while hidden
+#[derive(Default)]
+pub struct LagrangePair {
+    pub pair: Option<(ParticleId, ParticleId)>,
+    /// the star paired with the black hole by the last `L`, the next one pairs it with
+    /// another star
+    first: Option<ParticleId>,
+}
+
+impl LagrangePair {
+    pub fn new(a: ParticleId, b: ParticleId) -> Self {
+        Self {
+            pair: Some((a, b)),
+            first: None,
+        }
+    }
+}
+
+/// two different particle ids as `<id>,<id>` from the value of flag `name`
+pub fn parse_pair(name: &str, value: &str) -> galaxy_core::Result<(ParticleId, ParticleId)> {
+    let ids: Vec<u64> = value
+        .split(',')
+        .map(|id| id.trim().parse())
+        .collect::<Result<_, _>>()
+        .unwrap_or_default();
+    match ids[..] {
+        [a, b] if a != b => Ok((ParticleId(a), ParticleId(b))),
+        _ => Err(Error::InvalidValue {
+            name: name.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+/// the rotating frame of the pair, `None` when one of them has merged or was removed
+pub fn rotating_frame(
+    particles: &Particles,
+    (a, b): (ParticleId, ParticleId),
+    parameters: &SimulationParameters,
+) -> Option<RotatingFrame> {
+    let body = |id| {
+        particles.index_of(id).map(|i| {
+            (
+                particles.positions[i],
+                particles.velocities[i],
+                particles.masses[i],
+            )
+        })
+    };
+    RotatingFrame::new(body(a)?, body(b)?, parameters.g)
+}
+
+/// marker of L1 to L5, by index
+struct Marker(usize);
+
+/// the line mesh of the zero-velocity curves
+struct Curves;
+
+pub struct LagrangePlugin;
+
+impl Plugin for LagrangePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LagrangePair>()
+            .add_startup_system(setup.system())
+            .add_system(choose.system().after("selection"))
+            .add_system(update.system());
+    }
+}
+
+/// unconnected line segments, two positions each
+pub fn line_mesh(positions: Vec<[f32; 3]>) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    let count = positions.len();
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    // the PBR pipeline expects normals and uvs, unlit lines ignore them
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; count]);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; count]);
+    mesh
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let unlit = |color| StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..Default::default()
+    };
+    let marker_mesh = meshes.add(Mesh::from(shape::Icosphere {
+        radius: 4.0,
+        subdivisions: 1,
+    }));
+    let marker_material = materials.add(unlit(Color::rgb(1.0, 0.3, 1.0)));
+    for i in 0..5 {
+        let mut bundle = PbrBundle {
+            mesh: marker_mesh.clone(),
+            material: marker_material.clone(),
+            ..Default::default()
+        };
+        bundle.visible.is_visible = false;
+        commands.spawn_bundle(bundle).insert(Marker(i));
+    }
+
+    let mut bundle = PbrBundle {
+        // a placeholder until the first curves are computed, empty buffers can't be drawn
+        mesh: meshes.add(line_mesh(vec![[0.0; 3]; 2])),
+        material: materials.add(unlit(Color::rgb(0.4, 0.8, 1.0))),
+        ..Default::default()
+    };
+    bundle.visible.is_visible = false;
+    commands.spawn_bundle(bundle).insert(Curves);
+}
+
+fn choose(
+    keys: Res<Input<KeyCode>>,
+    selection: Res<Selection>,
+    state: Res<GalaxyState>,
+    mut pair: ResMut<LagrangePair>,
+) {
+    if !keys.just_pressed(PAIR) {
+        return;
+    }
+    let selected = match selection.0 {
+        Some(selected) => selected,
+        None => {
+            *pair = LagrangePair::default();
+            return;
+        }
+    };
+    match pair.first {
+        Some(first) if first != selected => *pair = LagrangePair::new(first, selected),
+        _ => {
+            let black_hole = match state.particles.ids.first() {
+                Some(&black_hole) => black_hole,
+                None => return,
+            };
+            pair.pair = Some((black_hole, selected));
+            pair.first = Some(selected);
+        }
+    }
+}
+
+fn update(
+    pair: Res<LagrangePair>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+    frame: Res<RenderFrame>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut markers: Query<(&Marker, &mut Transform, &mut Visible), Without<Curves>>,
+    mut curves: Query<(&Handle<Mesh>, &mut Visible), With<Curves>>,
+) {
+    profiling::scope!("lagrange");
+    let rotating = match pair
+        .pair
+        .and_then(|pair| rotating_frame(&state.particles, pair, &parameters))
+    {
+        Some(rotating) => rotating,
+        None => {
+            for (_, _, mut visible) in markers.iter_mut() {
+                visible.is_visible = false;
+            }
+            for (_, mut visible) in curves.iter_mut() {
+                visible.is_visible = false;
+            }
+            return;
+        }
+    };
+
+    let points = rotating.lagrange_points();
+    for (marker, mut transform, mut visible) in markers.iter_mut() {
+        transform.translation = frame.render_position(points[marker.0]);
+        visible.is_visible = true;
+    }
+
+    let d = rotating.separation();
+    let zoom = 2.0 * (points[1] - rotating.secondary).length();
+    let mut segments = Vec::new();
+    for point in &points[..3] {
+        let level = rotating.effective_potential(*point);
+        segments.extend(rotating.zero_velocity_curve(
+            level,
+            rotating.center_of_mass,
+            1.5 * d,
+            RESOLUTION,
+        ));
+        if zoom < 0.1 * d {
+            segments.extend(rotating.zero_velocity_curve(
+                level,
+                rotating.secondary,
+                zoom,
+                RESOLUTION,
+            ));
+        }
+    }
+    let positions: Vec<[f32; 3]> = segments
+        .iter()
+        .flatten()
+        .map(|p| frame.render_position(*p).into())
+        .collect();
+    for (mesh, mut visible) in curves.iter_mut() {
+        visible.is_visible = !positions.is_empty();
+        if let Some(mesh) = meshes.get_mut(mesh).filter(|_| visible.is_visible) {
+            *mesh = line_mesh(positions.clone());
+        }
+    }
+}
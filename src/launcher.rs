@@ -0,0 +1,152 @@
+//! Slingshot for new stars: hold `Shift`, press the left mouse button on the galaxy plane,
+//! pull back and release to launch a star from the pressed point. Without `Shift` the left
+//! button turns the camera orbit.
+//!
+//! The star flies away from the pulled direction, fast enough to cover the pulled distance
+//! in one second. While pulling, the predicted path of the star through the current stars
+//! is drawn, for the next ten seconds at the normal tick rate. Presses on the UI panels
+//! don't start a launch.
+
+use crate::{
+    camera::{cursor_ray, OrbitCamera},
+    lagrange::line_mesh,
+    render_frame::RenderFrame,
+    spawning::SpawnRequests,
+    state::GalaxyState,
+};
+use bevy::{math::DVec3, prelude::*, render::camera::Camera};
+use galaxy_core::{integrator, SimulationParameters, Star};
+
+/// physics steps of the predicted path
+const PREVIEW_STEPS: usize = 600;
+/// seconds the launched star takes for the pulled distance
+const LAUNCH_SECONDS: f64 = 1.0;
+
+/// physics position of the press, while pulling
+#[derive(Default)]
+struct Pull(Option<DVec3>);
+
+/// the line mesh of the pull and the predicted path
+struct Preview;
+
+pub struct LauncherPlugin;
+
+impl Plugin for LauncherPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Pull>()
+            .add_startup_system(setup.system())
+            .add_system(launch.system());
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut bundle = PbrBundle {
+        mesh: meshes.add(line_mesh(vec![[0.0; 3]; 2])),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.3, 1.0, 0.4),
+            unlit: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    bundle.visible.is_visible = false;
+    commands.spawn_bundle(bundle).insert(Preview);
+}
+
+/// where the ray from the camera through the cursor meets the render space plane z = 0
+fn cursor_on_plane(
+    windows: &Windows,
+    camera: &Camera,
+    transform: &GlobalTransform,
+) -> Option<Vec3> {
+    let (origin, direction) = cursor_ray(windows, camera, transform)?;
+    if direction.z.abs() < f32::EPSILON {
+        return None;
+    }
+    let t = -origin.z / direction.z;
+    if t > 0.0 {
+        Some(origin + direction * t)
+    } else {
+        None
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn launch(
+    mut pull: ResMut<Pull>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    interactions: Query<&Interaction>,
+    cameras: Query<(&Camera, &GlobalTransform), With<OrbitCamera>>,
+    frame: Res<RenderFrame>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+    mut requests: ResMut<SpawnRequests>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut previews: Query<(&Handle<Mesh>, &mut Visible), With<Preview>>,
+) {
+    let cursor = cameras
+        .single()
+        .ok()
+        .and_then(|(camera, transform)| cursor_on_plane(&windows, camera, transform))
+        .map(|point| frame.physics_position(point));
+
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    if buttons.just_pressed(MouseButton::Left)
+        && shift
+        && interactions.iter().all(|i| *i == Interaction::None)
+    {
+        pull.0 = cursor;
+    }
+    let start = match pull.0 {
+        Some(start) => start,
+        None => return,
+    };
+    let velocity = cursor.map_or(DVec3::default(), |cursor| {
+        (start - cursor) / (LAUNCH_SECONDS * parameters.time_factor)
+    });
+
+    if buttons.just_released(MouseButton::Left) {
+        pull.0 = None;
+        for (_, mut visible) in previews.iter_mut() {
+            visible.is_visible = false;
+        }
+        requests.spawn.push(Star {
+            position: start,
+            velocity,
+            acceleration: DVec3::default(),
+            mass: 0.5 * (parameters.star_mass_from + parameters.star_mass_to),
+        });
+        return;
+    }
+
+    let path = integrator::trajectory(
+        &state.particles,
+        start,
+        velocity,
+        &parameters,
+        PREVIEW_STEPS,
+    );
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(2 * (path.len() + 1));
+    if let Some(cursor) = cursor {
+        positions.push(frame.render_position(start).into());
+        positions.push(frame.render_position(cursor).into());
+    }
+    let mut previous = start;
+    for point in path {
+        positions.push(frame.render_position(previous).into());
+        positions.push(frame.render_position(point).into());
+        previous = point;
+    }
+    for (mesh, mut visible) in previews.iter_mut() {
+        visible.is_visible = true;
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            *mesh = line_mesh(positions.clone());
+        }
+    }
+}
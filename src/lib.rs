@@ -0,0 +1,359 @@
+//! The galaxy simulation in Bevy, for the `Galaxy` app and other Bevy apps embedding it.
+//!
+//! `GalaxySimulationPlugin` is the simulation itself: it spawns the stars of the
+//! `SimulationParameters` resource, or of `state::InitialStars`, steps them in the physics
+//! stage and moves their entities to the interpolated positions. The camera, the UI and the
+//! optional subsystems are plugins of their own modules that an app adds as it likes,
+//! `app::GalaxyApp` adds them the way the `Galaxy` binary runs them, picked by its command
+//! line. `physics` is the simulation without Bevy, for tests and batch runs.
+//!
+//! ```no_run
+//! use bevy::prelude::*;
+//! use galaxy::{GalaxySimulationPlugin, SimulationParameters};
+//!
+//! App::build()
+//!     .insert_resource(SimulationParameters::default())
+//!     .add_plugins(DefaultPlugins)
+//!     .add_plugin(GalaxySimulationPlugin)
+//!     .add_plugin(galaxy::camera::CameraPlugin)
+//!     .run();
+//! ```
+//!
+//! The plugin reads its settings when it is added, so the resources that change it come
+//! before: `SimulationParameters` for the physics timestep, `video::Video` for a step per
+//! frame, and a `replay::Replay` or an `ipc::Viewer` that move the stars instead of the
+//! physics. Other plugins adding systems to the physics stage come after it.
+
+pub mod app;
+pub mod attract;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod black_hole;
+pub mod camera;
+pub mod capture;
+#[cfg(feature = "chat")]
+pub mod chat;
+pub mod clumps;
+pub mod coloring;
+pub mod compare;
+pub mod director;
+pub mod energy;
+pub mod events;
+pub mod force_tree;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod inspector;
+#[cfg(feature = "ipc")]
+pub mod ipc;
+pub mod lagrange;
+pub mod launcher;
+pub mod merging;
+#[cfg(feature = "midi")]
+pub mod midi;
+#[cfg(feature = "osc")]
+pub mod osc;
+pub mod panel;
+pub mod potential;
+pub mod recording;
+pub mod render_frame;
+pub mod replay;
+pub mod roche;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod selection;
+pub mod snapshot;
+pub mod spawning;
+pub mod sprites;
+pub mod state;
+pub mod stats;
+pub mod structure;
+pub mod timeline;
+pub mod trails;
+pub mod video;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+use bevy::utils::Instant;
+use bevy::{
+    app::AppExit,
+    core::{FixedTimestep, FixedTimesteps},
+    prelude::*,
+    tasks::ComputeTaskPool,
+};
+use events::{CollisionEvent, EscapeEvent, SimulationEventsPlugin};
+/// the simulation without Bevy
+pub use galaxy_core as physics;
+use galaxy_core::{distributed::Node, initial_conditions};
+pub use galaxy_core::{SimulationConfig, SimulationParameters, Star};
+use rand::{rngs::StdRng, SeedableRng};
+use render_frame::RenderFrame;
+use spawning::{SpawnRequests, StarAssets, StarPool};
+use state::{
+    GalaxyId, GalaxyRng, GalaxyState, InitialStars, InitialTime, ParticleIndex, SimulationState,
+};
+
+/// font of all ui text, relative to the assets directory, the repository doesn't ship one
+pub const UI_FONT: &str = "fonts/caption.ttf";
+
+/// the stage of the physics steps, before `CoreStage::Update`
+pub const PHYSICS_STAGE: &str = "physics";
+pub const PHYSICS_TIMESTEP: &str = "physics_timestep";
+
+/// entities per task in the parallel per-star systems
+const PARALLEL_BATCH_SIZE: usize = 1024;
+
+/// above this many stars the exact O(n²) sum is too slow to watch
+pub const DIRECT_SUM_STARS: usize = 20_000;
+
+/// the stars, their physics and their entities
+pub struct GalaxySimulationPlugin;
+
+impl Plugin for GalaxySimulationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let world = app.world_mut();
+        let parameters = world
+            .get_resource_or_insert_with(SimulationParameters::default)
+            .clone();
+        if !world.contains_resource::<GalaxyRng>() {
+            world.insert_resource(GalaxyRng(StdRng::from_entropy()));
+        }
+        let replay = world.contains_resource::<replay::Replay>();
+        #[cfg(feature = "ipc")]
+        let viewer = world.contains_resource::<ipc::Viewer>();
+        #[cfg(not(feature = "ipc"))]
+        let viewer = false;
+
+        // a video takes its steps per rendered frame, not per wall clock time
+        let physics = if world.contains_resource::<video::Video>() {
+            SystemStage::parallel().with_run_criteria(video::steps_per_frame.system())
+        } else {
+            SystemStage::parallel().with_run_criteria(
+                FixedTimestep::step(parameters.physics_dt).with_label(PHYSICS_TIMESTEP),
+            )
+        };
+        let physics = physics.with_system(
+            spawning::process_spawn_requests
+                .system()
+                .label("spawn")
+                .after("moving"),
+        );
+        // a replay shows the recorded stars and a viewer the stars of the publishing
+        // process instead of simulating them
+        let physics = if replay {
+            app.add_plugin(replay::ReplayPlugin);
+            physics.with_system(replay::play.system().label("moving"))
+        } else if viewer {
+            #[cfg(feature = "ipc")]
+            let physics = physics.with_system(ipc::follow.system().label("moving"));
+            physics
+        } else {
+            physics.with_system(moving.system().label("moving"))
+        };
+
+        app.init_resource::<SimulationState>()
+            .init_resource::<RenderFrame>()
+            .init_resource::<StarPool>()
+            .init_resource::<SpawnRequests>()
+            .init_resource::<StarAssets>()
+            .add_plugin(SimulationEventsPlugin)
+            .add_startup_system(spawn_stars.system())
+            .add_stage_before(CoreStage::Update, PHYSICS_STAGE, physics)
+            .add_system(sync_transforms.system().label("sync"));
+    }
+}
+
+fn spawn_stars(
+    mut commands: Commands,
+    assets: Res<StarAssets>,
+    mut pool: ResMut<StarPool>,
+    parameters: Res<SimulationParameters>,
+    initial: Option<Res<InitialStars>>,
+    initial_time: Option<Res<InitialTime>>,
+    mut rng: ResMut<GalaxyRng>,
+) {
+    // cube
+    let mut state = GalaxyState {
+        time: initial_time.map_or(0.0, |time| time.0),
+        ..Default::default()
+    };
+    // the stars of the galaxies are tagged with theirs, after the massless star in the center
+    let stars: Vec<(Star, Option<GalaxyId>)> = match initial {
+        Some(initial) => initial.0.iter().map(|&star| (star, None)).collect(),
+        None if !parameters.galaxies.is_empty() => {
+            let galaxies = initial_conditions::galaxies(&mut rng.0, &parameters);
+            std::iter::once((Star::default(), None))
+                .chain(galaxies.into_iter().enumerate().flat_map(|(k, stars)| {
+                    stars.into_iter().map(move |star| (star, Some(GalaxyId(k))))
+                }))
+                .collect()
+        }
+        None => initial_conditions::generate(&mut rng.0, &parameters)
+            .into_iter()
+            .map(|star| (star, None))
+            .collect(),
+    };
+    for (i, (star, galaxy)) in stars.into_iter().enumerate() {
+        let material = if i == 0 {
+            assets.black_hole_material.clone()
+        } else if star.mass == 0.0 {
+            assets.tracer_material.clone()
+        } else {
+            assets.star_material.clone()
+        };
+        let entity = spawning::spawn_star(
+            &mut commands,
+            &assets,
+            spawning::star_bundle(&assets, material),
+        );
+        let index = state.push(star, entity);
+        commands.entity(entity).insert(index);
+        if let Some(galaxy) = galaxy {
+            commands.entity(entity).insert(galaxy);
+        }
+    }
+    if state.particles.len() > DIRECT_SUM_STARS && parameters.opening_angle == 0.0 {
+        warn!(
+            "the exact forces of {} stars are slow, --opening-angle 0.5 uses Barnes-Hut",
+            state.particles.len()
+        );
+    }
+    commands.insert_resource(state);
+    spawning::prewarm_pool(
+        &mut commands,
+        &assets,
+        &mut pool,
+        parameters.spawn_pool_size,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn moving(
+    mut state: ResMut<GalaxyState>,
+    mut collisions: EventWriter<CollisionEvent>,
+    mut escapes: EventWriter<EscapeEvent>,
+    mut exit: EventWriter<AppExit>,
+    parameters: Res<SimulationParameters>,
+    mut simulation: ResMut<SimulationState>,
+    node: Option<ResMut<Node>>,
+    fixed_timesteps: Res<FixedTimesteps>,
+    mut batched: Local<u64>,
+    #[cfg(feature = "gpu")] mut gpu: Option<ResMut<galaxy_core::gpu::GpuGravity>>,
+) {
+    profiling::scope!("moving");
+    let state = &mut *state;
+
+    // keep the old positions for interpolation, paused stars rest at the current ones
+    state
+        .previous_positions
+        .copy_from_slice(&state.particles.positions);
+    let stepped = simulation.take_step();
+    // the particles stay on the GPU for all the steps of a frame, which are taken in its
+    // last tick and read back once, the frame interpolates over all of them
+    #[cfg(feature = "gpu")]
+    let resident =
+        node.is_none() && gpu.is_some() && galaxy_core::gpu::GpuGravity::resident(&parameters);
+    #[cfg(not(feature = "gpu"))]
+    let resident = false;
+    let steps = if resident {
+        *batched += stepped as u64;
+        let last_tick = fixed_timesteps
+            .get(PHYSICS_TIMESTEP)
+            .is_none_or(|state| state.overstep_percentage() < 1.0);
+        if !last_tick {
+            return;
+        }
+        std::mem::take(&mut *batched)
+    } else {
+        stepped as u64
+    };
+    if steps == 0 {
+        return;
+    }
+
+    let started = Instant::now();
+    let mut encounters = Vec::new();
+    let report = |i, j| encounters.push((i, j));
+    match node {
+        Some(mut node) => {
+            if let Err(e) = node.step(&mut state.particles, &parameters, report) {
+                // the other nodes can't continue without this one either
+                error!("distributed step failed: {}", e);
+                exit.send(AppExit);
+                return;
+            }
+        }
+        #[cfg(feature = "gpu")]
+        None if gpu.is_some() => {
+            let gpu = gpu.as_mut().expect("checked by the guard");
+            let result = if resident {
+                gpu.advance(&mut state.particles, &parameters, steps, report)
+            } else {
+                gpu.step(&mut state.particles, &parameters, report)
+            };
+            if let Err(e) = result {
+                error!("gpu step failed: {}", e);
+                exit.send(AppExit);
+                return;
+            }
+        }
+        None => galaxy_core::step(&mut state.particles, &parameters, report),
+    }
+    state.step_time += started.elapsed();
+    state.steps += steps;
+    state.time += steps as f64 * parameters.physics_dt * parameters.time_factor;
+    for (i, j) in encounters {
+        collisions.send(CollisionEvent {
+            a: state.entities[i],
+            b: state.entities[j],
+            position: state.particles.positions[i],
+        });
+    }
+
+    let particles = &mut state.particles;
+    for i in galaxy_core::escape(particles, &state.previous_positions, &parameters) {
+        escapes.send(EscapeEvent {
+            star: state.entities[i],
+            position: particles.positions[i],
+            velocity: particles.velocities[i],
+        });
+    }
+}
+
+/// interpolate between the last two physics states by the accumulator fraction
+///
+/// All render positions are computed in one pass over the solver arrays, then written
+/// to the transforms in parallel batches. Transforms are only written when the
+/// position really changed, so resting stars don't trigger Bevy's change detection and
+/// transform propagation.
+fn sync_transforms(
+    fixed_timesteps: Res<FixedTimesteps>,
+    state: Res<GalaxyState>,
+    mut render_positions: Local<Vec<Vec3>>,
+    mut query: Query<(&ParticleIndex, &mut Transform)>,
+    pool: Res<ComputeTaskPool>,
+    frame: Res<RenderFrame>,
+) {
+    profiling::scope!("transform sync");
+    let alpha = fixed_timesteps
+        .get(PHYSICS_TIMESTEP)
+        .map_or(1.0, |state| state.overstep_percentage().min(1.0));
+
+    render_positions.clear();
+    render_positions.extend(
+        state
+            .previous_positions
+            .iter()
+            .zip(&state.particles.positions)
+            .map(|(previous, current)| frame.render_position(previous.lerp(*current, alpha))),
+    );
+
+    let render_positions = &*render_positions;
+    query.par_for_each_mut(&pool, PARALLEL_BATCH_SIZE, |(index, mut transform)| {
+        let translation = render_positions[index.0];
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    });
+}
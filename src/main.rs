@@ -1,194 +1,339 @@
-use bevy::{math::DVec3, prelude::*, render::camera::Camera};
-use rand::Rng;
-use std::f32::consts::PI;
+mod cli;
+mod headless;
 
-#[derive(Debug, Clone, Copy)]
-struct Star {
-    position: DVec3,
-    velocity: DVec3,
-    acceleration: DVec3,
-    mass: f64,
-}
-
-#[derive(Default)]
-struct CameraAngle(f32);
-
-const G: f64 = 6.674e-11;
-const NUMBER_OF_STARS: usize = 1000;
-const black_hole_mass: f64 = 0.0;
-const star_mass_from: f64 = 1.0e29;
-const star_mass_to: f64 = 1.0e32;
-const galaxy_diameter: f64 = 1.0e13;
-const time_factor: f64 = 1.0e14;
-const spin_factor: f64 = 1e-5;
-const max_velocity: f64 = 1e-2;
-const max_acceleration: f64 = 1e-1;
-const min_gravity_distance: f64 = 1.0e1;
-const camera_speed: f32 = 0.0;
-
-fn main() {
-    App::build()
-        .insert_resource(Msaa { samples: 4 })
-        .insert_resource(WindowDescriptor {
-            vsync: false, // Disabled for this demo to remove vsync as a source of input latency
-            ..Default::default()
-        })
-        .insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)))
-        .insert_resource(CameraAngle(0.0))
-        .add_plugins(DefaultPlugins) // PickingPlugin provides core picking systems and must be registered first
-        .add_startup_system(setup.system())
-        .add_system(moving.system())
-        .add_system(camera_orbit.system())
-        .run();
-}
+#[cfg(feature = "ipc")]
+use galaxy::ipc;
+#[cfg(feature = "scripting")]
+use galaxy::scripting;
+use galaxy::{
+    app::{GalaxyApp, StopAfter},
+    capture, compare, energy, potential,
+    render_frame::RenderFrame,
+    replay, structure, timeline, video,
+};
+use galaxy_core::{
+    alembic::PointCache,
+    benchmark,
+    catalog::{self, CatalogConfig},
+    distributed::Node,
+    error::ensure_output_dir,
+    export::CsvExport,
+    initial_conditions,
+    trajectory::TrajectoryWriter,
+    units::PARSEC,
+    validation, Error, Integrator, Simulation, SimulationConfig, SimulationParameters, Snapshot,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{net::SocketAddr, path::Path};
 
-/// set up a simple 3D scene
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    let mut rng = rand::thread_rng();
+/// physics steps between the CSV exports without `--export-every`
+const DEFAULT_EXPORT_EVERY: u64 = 60;
 
-    // cube
-    for i in 0..NUMBER_OF_STARS {
-        // create star
-        let px = rng.gen_range(-galaxy_diameter..galaxy_diameter);
-        let py = rng.gen_range(-galaxy_diameter..galaxy_diameter);
-        let pz = rng.gen_range(-galaxy_diameter / 10.0..galaxy_diameter / 10.0);
-        let mut star = Star {
-            position: if i == 0 {
-                DVec3::default()
-            } else {
-                DVec3::new(px, py, pz)
-            },
-            velocity: DVec3::default(),
-            acceleration: DVec3::default(),
-            mass: if i == 0 { black_hole_mass } else { rng.gen_range(star_mass_from..star_mass_to) },
-        };
+/// directory of the frames of `--capture-every` without `--capture`
+const DEFAULT_CAPTURE: &str = "frames";
 
-        // spin it
-        let angle: f64 = ang::atan2(px, py).in_radians();
-        star.acceleration = DVec3::new(angle.cos() * spin_factor, angle.sin() * spin_factor, 0.0);
-        star.velocity = star.acceleration;
+/// frames per second of `--video` without `--video-fps`
+const DEFAULT_VIDEO_FPS: f64 = 30.0;
 
-        commands
-            .spawn_bundle(PbrBundle {
-                mesh: meshes.add(Mesh::from(shape::Cube { size: 3.0 })),
-                material: materials.add(if i == 0 {
-                    Color::rgb(2.0, 2.0, 8.0).into()
-                } else {
-                    Color::rgb(5.9, 5.9, 1.2).into()
-                }),
-                transform: Transform::from_xyz(0.0, 0.5, 0.0),
-                ..Default::default()
-            })
-            .insert(star);
-    }
+/// physics steps between the samples of `--structure` and `--energy` without their `-every`
+const DEFAULT_MEASURE_EVERY: u64 = 60;
 
-    // light
-    commands.spawn_bundle(LightBundle {
-        transform: Transform::from_xyz(-2000.0, 2000.0, 1000.0),
-        ..Default::default()
-    });
+/// config file read at startup when it exists and `--config` names no other
+const CONFIG_FILE: &str = "galaxy.toml";
 
-    // camera
-    let mut camera = PerspectiveCameraBundle::default();
-    camera.transform = Transform::from_xyz(0.0, 0.0, 2500.0);
-    camera.transform.look_at(Vec3::ZERO, Vec3::Y);
-    camera.perspective_projection.near = 1.0;
-    camera.perspective_projection.far = 10000.0;
-    commands.spawn_bundle(camera);
-}
-
-fn limit_length(v: &mut DVec3, len: f64) {
-    if v.length() > len {
-        *v = v.normalize() * len;
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
     }
 }
 
-fn moving(time: Res<Time>, mut query: Query<(&mut Star, &mut Transform)>) {
-    // based on this code: https://rosettacode.org/wiki/N-body_problem#C.23
+/// everything that can fail is checked here, before a window is opened
+fn run() -> galaxy_core::Result<()> {
+    #[cfg(feature = "tracy")]
+    tracy_client::Client::start();
 
-    // copy stars to local vector
-    let mut stars = Vec::<Star>::new();
-    for (star, _) in query.iter_mut() {
-        stars.push(star.clone());
+    let options = cli::parse()?;
+    let mut parameters = SimulationParameters::default();
+    // the flags override the config file
+    let config = match options.config.clone() {
+        Some(path) => Some(SimulationConfig::load(&path)?),
+        None if Path::new(CONFIG_FILE).exists() => {
+            Some(SimulationConfig::load(CONFIG_FILE.as_ref())?)
+        }
+        None => None,
+    };
+    if let Some(config) = &config {
+        config.apply(&mut parameters);
     }
-
-    // update accelerations
-    for i in 0..stars.len() {
-        stars[i].acceleration = DVec3::default();
-        for j in 0..stars.len() {
-            if i != j {
-                let distance = stars[j].position - stars[i].position;
-                let distance_length = distance.length();
-                if distance_length > min_gravity_distance {
-                    let temp = G * stars[j].mass / distance_length.powf(3.0);
-                    stars[i].acceleration += distance * temp;
-                }
-            }
+    // the same seed gives the same galaxy and the same random events
+    let seed = options
+        .seed
+        .or_else(|| config.as_ref().and_then(|c| c.seed));
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    options.overrides.apply(&mut parameters);
+    // the path from the command line keeps the column mapping of the config
+    let catalog = match (options.catalog.clone(), config.and_then(|c| c.catalog)) {
+        (Some(path), catalog) => Some(CatalogConfig {
+            path,
+            ..catalog.unwrap_or_default()
+        }),
+        (None, catalog) => catalog,
+    };
+    let catalog = match catalog {
+        Some(catalog) => {
+            let stars = catalog.load()?;
+            // the view fits the galaxy diameter, so make it fit the catalog
+            parameters.galaxy_diameter = catalog::extent(&stars).max(PARSEC);
+            parameters.number_of_stars = stars.len();
+            Some(stars)
         }
+        None => None,
+    };
+    let snapshot = match &options.load {
+        Some(path) => Some(Snapshot::load(path)?),
+        None => None,
+    };
+    let export = match &options.export {
+        Some(dir) => Some(CsvExport::new(
+            dir,
+            options.export_every.unwrap_or(DEFAULT_EXPORT_EVERY),
+        )?),
+        None => None,
+    };
+    if options.validate {
+        let steps = options.steps.unwrap_or(validation::DEFAULT_STEPS_PER_ORBIT);
+        let error = validation::circular_orbit(&parameters, steps);
+        println!(
+            "circular Kepler orbit, {} integrator, {} steps per orbit",
+            parameters.integrator.name(),
+            steps
+        );
+        println!(
+            "period {:.6e} s, expected {:.6e} s, relative error {:.3e}",
+            error.period,
+            error.expected_period,
+            error.period_error()
+        );
+        println!("energy drift {:.3e}", error.energy_drift);
+        println!("radius drift {:.3e}", error.radius_drift);
+        return Ok(());
     }
-
-    let time_delta = time.delta().as_secs_f64();
-    for i in 0..stars.len() {
-        // update velocities
-        limit_length(&mut stars[i].acceleration, max_velocity);
-        let mut acceleration = stars[i].acceleration;
-        stars[i].velocity += acceleration;
-        limit_length(&mut stars[i].velocity, max_acceleration);
-
-        // update positions
-        let delta = stars[i].velocity + stars[i].acceleration * 0.5;
-        stars[i].position += delta * time_delta * time_factor;
-        limit_length(&mut stars[i].position, 2.0 * galaxy_diameter);
+    if let Some(bench) = options.bench {
+        if let Some(stars) = bench.stars {
+            parameters.number_of_stars = stars;
+        }
+        // the same galaxy in every run, to compare the timings
+        let mut rng = StdRng::seed_from_u64(seed.unwrap_or_default());
+        let mut particles = initial_conditions::generate(&mut rng, &parameters)
+            .into_iter()
+            .collect();
+        let timings = benchmark::run(&mut particles, &parameters, bench.steps);
+        println!("{}", timings.format(options.bench_format).trim_end());
+        return Ok(());
     }
-    stars[0].position = DVec3::default();
-
-    // calculate scale factor and offset to center all starts in a 1000 size box
-    let mut min: DVec3 = stars[0].position;
-    let mut max: DVec3 = stars[0].position;
-    for i in 0..stars.len() {
-        let star = stars[i];
-        min.x = star.position.x.min(min.x);
-        min.y = star.position.y.min(min.y);
-        min.z = star.position.z.min(min.z);
-        max.x = star.position.x.max(min.x);
-        max.y = star.position.y.max(min.y);
-        max.z = star.position.z.max(min.z);
+    if options.headless {
+        let steps = match (options.steps, options.duration) {
+            (Some(steps), _) => steps,
+            (None, Some(duration)) => StopAfter::new(duration, &parameters).0,
+            (None, None) => headless::DEFAULT_STEPS,
+        };
+        let batch = headless::Batch {
+            steps,
+            snapshot_every: options.snapshot_every,
+            output: options
+                .output
+                .unwrap_or_else(|| headless::DEFAULT_OUTPUT.into()),
+            hdf5: options.hdf5,
+            record: options.record,
+            export,
+        };
+        let simulation = match (snapshot, catalog) {
+            (Some(snapshot), _) => Simulation::from_snapshot(&snapshot, parameters),
+            (None, Some(stars)) => Simulation::new(stars.into_iter().collect(), parameters),
+            (None, None) => Simulation::new(
+                initial_conditions::generate(&mut rng, &parameters)
+                    .into_iter()
+                    .collect(),
+                parameters,
+            ),
+        };
+        return headless::run(simulation, &batch);
+    }
+    let node = connect_node(&options.nodes, options.rank, &parameters, &mut rng)?;
+    let compare = match &options.compare {
+        Some(list) => Some(compare::parse_parameters("--compare", list, &parameters)?),
+        None => None,
+    };
+    let mut replay = match &options.replay {
+        Some(path) => Some(replay::Replay::open(path)?),
+        None => None,
+    };
+    #[cfg(feature = "ipc")]
+    let mut viewer = match &options.view {
+        Some(path) => Some(ipc::Viewer::open(path)?),
+        None => None,
+    };
+    // the stars of a viewer, a node, a replay, a snapshot or a catalog, in this order
+    let mut initial_stars = catalog;
+    let mut initial_time = None;
+    if let Some(snapshot) = snapshot {
+        initial_stars = Some(snapshot.stars);
+        initial_time = Some(snapshot.time);
+    }
+    if let Some(replay) = &mut replay {
+        initial_stars = Some(replay.initial_stars()?);
+    }
+    let node = match node {
+        Some((node, stars)) => {
+            initial_stars = Some(stars);
+            Some(node)
+        }
+        None => None,
+    };
+    #[cfg(feature = "ipc")]
+    if let Some(viewer) = &mut viewer {
+        initial_stars = Some(viewer.stars()?);
     }
-    let max_delta = (max.x - min.x).max(max.y - min.y).max(max.z - min.z);
-    //let ofs = min + (max - min) * 0.5;
-    let ofs = DVec3::default();
-
-    //let scale = 1000.0 / max_delta;
-    let scale = 1000.0 / galaxy_diameter;
 
-    // update graphics
-    for (i, (mut star, mut transform)) in query.iter_mut().enumerate() {
-        let v = (stars[i].position - ofs) * scale;
-        transform.translation = Vec3::new(v.x as f32, v.y as f32, v.z as f32);
-        //transform.scale = scale;
-        *star = stars[i];
+    let mut app = GalaxyApp::new(parameters.clone(), rng);
+    app.initial_stars = initial_stars;
+    app.initial_time = initial_time;
+    app.node = node;
+    app.stop_after = options
+        .duration
+        .map(|duration| StopAfter::new(duration, &parameters));
+    app.jets = options.jets;
+    app.sprites = options.sprites;
+    app.bloom = options.bloom;
+    app.snapshot_file = options.snapshot;
+    app.colormap = options.colormap;
+    app.director = options.director;
+    app.capture = match options.capture_every {
+        Some(every) => {
+            let directory = options.capture.unwrap_or_else(|| DEFAULT_CAPTURE.into());
+            ensure_output_dir(&directory)?;
+            Some(capture::CaptureSettings { directory, every })
+        }
+        None => None,
+    };
+    app.video = match options.video {
+        Some(path) => {
+            video::VideoSettings::check_ffmpeg()?;
+            Some(video::VideoSettings {
+                path,
+                fps: options.video_fps.unwrap_or(DEFAULT_VIDEO_FPS),
+                steps: options.video_steps.unwrap_or(1),
+            })
+        }
+        None => None,
+    };
+    app.replay = replay;
+    app.alembic = match &options.alembic {
+        Some(path) => Some(PointCache::create(
+            path,
+            1.0 / parameters.physics_dt,
+            RenderFrame::fit_galaxy(&parameters).scale,
+        )?),
+        None => None,
+    };
+    app.export = export;
+    app.trajectory = match &options.record {
+        Some(path) => Some(TrajectoryWriter::create(path)?),
+        None => None,
+    };
+    app.lagrange = options.lagrange;
+    app.roche = options.roche;
+    app.compare = compare;
+    app.clumps = options.clumps;
+    app.attract = options.attract;
+    app.timeline = options.timeline;
+    app.timeline_file = match (&options.replay, &options.record) {
+        (Some(path), _) => Some(timeline::TimelineFile::Replay(timeline::events_path(path))),
+        (None, Some(path)) => Some(timeline::TimelineFile::Record(timeline::events_path(path))),
+        (None, None) => None,
+    };
+    app.panel = options.panel;
+    app.structure = match &options.structure {
+        Some(path) => Some(structure::StructureLog::create(
+            path,
+            options.structure_every.unwrap_or(DEFAULT_MEASURE_EVERY),
+        )?),
+        None => None,
+    };
+    app.trails = options.trails;
+    app.energy = if options.energy {
+        Some(energy::EnergySettings {
+            interval: options.energy_every.unwrap_or(DEFAULT_MEASURE_EVERY),
+        })
+    } else {
+        None
+    };
+    app.potential = match options.potential {
+        Some(interval) => Some(potential::PotentialSettings {
+            interval,
+            resolution: options
+                .potential_resolution
+                .unwrap_or(potential::DEFAULT_RESOLUTION),
+        }),
+        None => None,
+    };
+    #[cfg(feature = "gpu")]
+    if options.gpu {
+        app.gpu = Some(galaxy_core::gpu::GpuGravity::new()?);
     }
+    #[cfg(feature = "ipc")]
+    {
+        app.viewer = viewer;
+        app.publisher = match &options.publish {
+            Some(path) => Some(ipc::Publisher::create(path)?),
+            None => None,
+        };
+    }
+    #[cfg(feature = "scripting")]
+    {
+        app.script = match &options.script {
+            Some(path) => Some(scripting::Script::load(path)?),
+            None => None,
+        };
+    }
+    app.build().run();
+    Ok(())
 }
 
-fn camera_orbit(
-    time: Res<Time>,
-    mut camera_transforms: Query<&mut Transform, With<Camera>>,
-    mut angle: ResMut<CameraAngle>,
-) {
-    if let Ok(mut transform) = camera_transforms.single_mut() {
-        let time_delta = time.delta().as_secs_f32();
-        let len = 2500.0;
-        let x = angle.0.cos() * len;
-        let y = angle.0.sin() * len;
-        *transform.translation = Vec3::new(x, y, len).into();
-        transform.look_at(Vec3::ZERO, Vec3::Z);
-        angle.0 += time_delta * camera_speed;
-        if angle.0 > 2.0 * PI {
-            angle.0 -= 2.0 * PI;
-        }
+/// joins the distributed simulation of `--nodes`, if any, and gets the black hole and the
+/// block of the initial stars of this node from node 0
+fn connect_node(
+    addresses: &[SocketAddr],
+    rank: usize,
+    parameters: &SimulationParameters,
+    rng: &mut StdRng,
+) -> galaxy_core::Result<Option<(Node, Vec<galaxy_core::Star>)>> {
+    if addresses.is_empty() {
+        return Ok(None);
+    }
+    if rank >= addresses.len() {
+        return Err(Error::InvalidValue {
+            name: "--rank".to_string(),
+            value: rank.to_string(),
+        });
+    }
+    if parameters.integrator == Integrator::Hermite {
+        // the nodes only exchange accelerations, Hermite needs the jerks too
+        return Err(Error::InvalidValue {
+            name: "the integrator of a distributed simulation".to_string(),
+            value: parameters.integrator.name().to_string(),
+        });
     }
+    let node = Node::connect(rank, addresses)?;
+    let stars = if rank == 0 {
+        initial_conditions::generate(rng, parameters)
+    } else {
+        Vec::new()
+    };
+    let stars = node.share_stars(stars)?;
+    Ok(Some((node, stars)))
 }
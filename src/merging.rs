@@ -0,0 +1,72 @@
+//! Touching stars merge into one in the scene.
+//!
+//! With a `merge_density` in the config, two stars closer than the sum of their radii
+//! become one heavier star. The absorbed entity goes back to the spawn pool, a surviving
+//! star grows with the cube root of its mass, the black hole keeps its size, and each
+//! merger raises a `MergeEvent`.
+
+use crate::{
+    events::MergeEvent,
+    spawning::SpawnRequests,
+    state::{GalaxyState, SimulationState},
+};
+use bevy::prelude::*;
+use galaxy_core::{merging, SimulationParameters};
+
+pub struct MergingPlugin;
+
+impl Plugin for MergingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_to_stage(
+            crate::PHYSICS_STAGE,
+            merge
+                .system()
+                .after("moving")
+                .after("accretion")
+                .before("spawn"),
+        );
+    }
+}
+
+/// merges the touching stars after a physics step
+fn merge(
+    mut state: ResMut<GalaxyState>,
+    mut requests: ResMut<SpawnRequests>,
+    mut merges: EventWriter<MergeEvent>,
+    mut transforms: Query<&mut Transform>,
+    parameters: Res<SimulationParameters>,
+    simulation: Res<SimulationState>,
+) {
+    if !simulation.stepped {
+        return;
+    }
+    let pairs = merging::touching(&state.particles, &parameters);
+    if pairs.is_empty() {
+        return;
+    }
+    let state = &mut *state;
+    for (survivor, absorbed) in pairs {
+        let (survivor_id, absorbed_id) =
+            (state.particles.ids[survivor], state.particles.ids[absorbed]);
+        // swallowed by the black hole in this step already
+        if requests.despawn.contains(&survivor_id) || requests.despawn.contains(&absorbed_id) {
+            continue;
+        }
+        let before = state.particles.masses[survivor];
+        merging::merge_into(&mut state.particles, &parameters, survivor, absorbed);
+        let mass = state.particles.masses[survivor];
+        requests.despawn.push(absorbed_id);
+        merges.send(MergeEvent {
+            survivor: state.entities[survivor],
+            absorbed: state.entities[absorbed],
+            position: state.particles.positions[survivor],
+            mass,
+        });
+        // the black hole keeps its mesh, like when it swallows a star
+        if survivor != 0 && before > 0.0 {
+            if let Ok(mut transform) = transforms.get_mut(state.entities[survivor]) {
+                transform.scale *= (mass / before).cbrt() as f32;
+            }
+        }
+    }
+}
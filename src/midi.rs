@@ -0,0 +1,186 @@
+//! Live parameters controlled by the knobs and faders of a MIDI controller.
+//!
+//! The first MIDI input whose name contains `GALAXY_MIDI` is used, or the first one at all.
+//! Every control change message is looked up in `MidiSettings::mappings`, the controller
+//! value 0..=127 is mapped onto the range of the target parameter.
+
+use crate::sprites::Bloom;
+use bevy::prelude::*;
+use galaxy_core::SimulationParameters;
+use midir::{Ignore, MidiInput, MidiInputConnection};
+use std::sync::{
+    mpsc::{channel, Receiver},
+    Mutex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiTarget {
+    TimeFactor,
+    /// the gravity softening length, `softening`
+    Softening,
+    CameraSpeed,
+    /// the strength of `sprites::Bloom`, only with `--bloom`, which makes room for the halos
+    Bloom,
+}
+
+#[derive(Debug, Clone)]
+pub struct CcMapping {
+    /// control change number
+    pub controller: u8,
+    pub target: MidiTarget,
+    pub min: f64,
+    pub max: f64,
+    /// interpolate the exponent, for values spanning several orders of magnitude
+    pub logarithmic: bool,
+}
+
+impl CcMapping {
+    fn value(&self, cc_value: u8) -> f64 {
+        let t = cc_value.min(127) as f64 / 127.0;
+        if self.logarithmic {
+            (self.min.ln() + t * (self.max.ln() - self.min.ln())).exp()
+        } else {
+            self.min + t * (self.max - self.min)
+        }
+    }
+}
+
+pub struct MidiSettings {
+    /// part of the input port name, empty uses the first port
+    pub port: String,
+    pub mappings: Vec<CcMapping>,
+}
+
+impl Default for MidiSettings {
+    fn default() -> Self {
+        Self {
+            port: std::env::var("GALAXY_MIDI").unwrap_or_default(),
+            mappings: vec![
+                CcMapping {
+                    controller: 1,
+                    target: MidiTarget::TimeFactor,
+                    min: 1e4,
+                    max: 1e8,
+                    logarithmic: true,
+                },
+                CcMapping {
+                    controller: 2,
+                    target: MidiTarget::Softening,
+                    min: 1e9,
+                    max: 1e12,
+                    logarithmic: true,
+                },
+                CcMapping {
+                    controller: 3,
+                    target: MidiTarget::CameraSpeed,
+                    min: -1.0,
+                    max: 1.0,
+                    logarithmic: false,
+                },
+                CcMapping {
+                    controller: 4,
+                    target: MidiTarget::Bloom,
+                    min: 0.0,
+                    max: 0.5,
+                    logarithmic: false,
+                },
+            ],
+        }
+    }
+}
+
+/// the open input, messages arrive as (controller, value) on its callback thread
+struct MidiConnection {
+    _connection: Mutex<MidiInputConnection<()>>,
+    changes: Mutex<Receiver<(u8, u8)>>,
+}
+
+pub struct MidiPlugin;
+
+impl Plugin for MidiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<MidiSettings>()
+            .add_startup_system(connect.system())
+            .add_system(apply_changes.system());
+    }
+}
+
+fn connect(mut commands: Commands, settings: Res<MidiSettings>) {
+    let mut input = match MidiInput::new("galaxy") {
+        Ok(input) => input,
+        Err(e) => {
+            error!("can't open MIDI input: {}", e);
+            return;
+        }
+    };
+    input.ignore(Ignore::All);
+    let port = input.ports().into_iter().find(|port| {
+        input
+            .port_name(port)
+            .is_ok_and(|name| name.contains(&settings.port))
+    });
+    let port = match port {
+        Some(port) => port,
+        None => {
+            warn!("no MIDI input matching '{}' found", settings.port);
+            return;
+        }
+    };
+    let name = input.port_name(&port).unwrap_or_default();
+
+    let (sender, receiver) = channel();
+    let connection = input.connect(
+        &port,
+        "galaxy-input",
+        move |_, message, _| {
+            // control change on any channel: status 0xBn, controller, value
+            if let [status, controller, value] = *message {
+                if status & 0xf0 == 0xb0 {
+                    let _ = sender.send((controller, value));
+                }
+            }
+        },
+        (),
+    );
+    match connection {
+        Ok(connection) => {
+            info!("MIDI controller {} connected", name);
+            commands.insert_resource(MidiConnection {
+                _connection: Mutex::new(connection),
+                changes: Mutex::new(receiver),
+            });
+        }
+        Err(e) => error!("can't connect to MIDI input {}: {}", name, e),
+    }
+}
+
+fn apply_changes(
+    connection: Option<Res<MidiConnection>>,
+    settings: Res<MidiSettings>,
+    mut parameters: ResMut<SimulationParameters>,
+    mut bloom: Option<ResMut<Bloom>>,
+) {
+    let connection = match connection {
+        Some(connection) => connection,
+        None => return,
+    };
+    for (controller, cc_value) in connection.changes.lock().unwrap().try_iter() {
+        for mapping in settings
+            .mappings
+            .iter()
+            .filter(|m| m.controller == controller)
+        {
+            let value = mapping.value(cc_value);
+            match mapping.target {
+                MidiTarget::TimeFactor => parameters.time_factor = value,
+                MidiTarget::Softening => parameters.softening = value,
+                MidiTarget::CameraSpeed => parameters.camera_speed = value as f32,
+                MidiTarget::Bloom => {
+                    if let Some(bloom) = &mut bloom {
+                        bloom.0 = value as f32;
+                    }
+                }
+            }
+        }
+    }
+}
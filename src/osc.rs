@@ -0,0 +1,135 @@
+//! Sends simulation quantities as OSC messages over UDP, e.g. to SuperCollider or Max.
+//!
+//! All arguments are floats in SI units:
+//!
+//! * `/galaxy/core_density` stars per cubic meter within `core_radius` of the center
+//! * `/galaxy/star` radius and speed of the selected star
+//! * `/galaxy/merge` x, y, z and mass of every merger
+//!
+//! The first two are sent `rate` times per second, mergers as they happen.
+
+use crate::{events::MergeEvent, state::GalaxyState};
+use bevy::prelude::*;
+use galaxy_core::{diagnostics, ParticleId, SimulationParameters};
+use rosc::{encoder, OscMessage, OscPacket, OscType};
+use std::net::UdpSocket;
+
+pub struct OscSettings {
+    /// address the messages are sent to
+    pub address: String,
+    /// messages per second for the continuous quantities
+    pub rate: f64,
+    /// radius of the core for the density, in galaxy diameters
+    pub core_radius: f64,
+    /// star whose radius and speed are sent
+    pub selected: ParticleId,
+}
+
+impl Default for OscSettings {
+    fn default() -> Self {
+        Self {
+            address: std::env::var("GALAXY_OSC").unwrap_or_else(|_| "127.0.0.1:57120".to_string()),
+            rate: 30.0,
+            core_radius: 0.1,
+            selected: ParticleId(1),
+        }
+    }
+}
+
+struct OscSocket(UdpSocket);
+
+impl OscSocket {
+    fn send(&self, address: &str, args: Vec<OscType>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: address.to_string(),
+            args,
+        });
+        if let Ok(bytes) = encoder::encode(&packet) {
+            // nobody listening is fine, the messages are just dropped
+            let _ = self.0.send(&bytes);
+        }
+    }
+}
+
+pub struct OscPlugin;
+
+impl Plugin for OscPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<OscSettings>()
+            .add_startup_system(open_socket.system())
+            .add_system(send_quantities.system())
+            .add_system(send_merges.system());
+    }
+}
+
+fn open_socket(mut commands: Commands, settings: Res<OscSettings>) {
+    let socket = UdpSocket::bind("0.0.0.0:0").and_then(|socket| {
+        socket.connect(&settings.address)?;
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    });
+    match socket {
+        Ok(socket) => {
+            info!("sending OSC to {}", settings.address);
+            commands.insert_resource(OscSocket(socket));
+        }
+        Err(e) => error!("can't send OSC to {}: {}", settings.address, e),
+    }
+}
+
+fn send_quantities(
+    time: Res<Time>,
+    mut since_last: Local<f64>,
+    socket: Option<Res<OscSocket>>,
+    settings: Res<OscSettings>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+) {
+    let socket = match socket {
+        Some(socket) => socket,
+        None => return,
+    };
+    *since_last += time.delta_seconds_f64();
+    if *since_last < 1.0 / settings.rate {
+        return;
+    }
+    *since_last = 0.0;
+
+    let particles = &state.particles;
+    let radius = settings.core_radius * parameters.galaxy_diameter;
+    socket.send(
+        "/galaxy/core_density",
+        vec![OscType::Double(diagnostics::core_density(
+            particles, radius,
+        ))],
+    );
+
+    if let Some(i) = particles.index_of(settings.selected) {
+        socket.send(
+            "/galaxy/star",
+            vec![
+                OscType::Double(particles.positions[i].length()),
+                OscType::Double(particles.velocities[i].length()),
+            ],
+        );
+    }
+}
+
+fn send_merges(socket: Option<Res<OscSocket>>, mut merges: EventReader<MergeEvent>) {
+    let socket = match socket {
+        Some(socket) => socket,
+        None => return,
+    };
+    for e in merges.iter() {
+        let p = e.position;
+        socket.send(
+            "/galaxy/merge",
+            vec![
+                OscType::Double(p.x),
+                OscType::Double(p.y),
+                OscType::Double(p.z),
+                OscType::Double(e.mass),
+            ],
+        );
+    }
+}
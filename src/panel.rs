@@ -0,0 +1,476 @@
+//! Parameter panel for exploring the simulation interactively.
+//!
+//! `--panel` shows a control per parameter. The time factor, the softening, the gravity
+//! scale and the camera speed apply immediately. The star count and the black hole mass
+//! only apply to a new galaxy, they are marked with `*` while they differ from the running
+//! one, until "apply & restart" replaces the stars.
+//!
+//! With the `egui` feature the panel is an egui side panel on the left with a slider per
+//! parameter, all logarithmic but the camera speed. They span a factor of `SPAN` around
+//! the start value either way, the star count goes up to `MAX_STARS`. Without it the panel is built from Bevy UI nodes like the other panels, a row of `-`
+//! and `+` buttons per parameter on the right, whose text needs the UI font.
+
+use crate::{
+    spawning::SpawnRequests,
+    state::{GalaxyRng, GalaxyState},
+};
+use bevy::prelude::*;
+use galaxy_core::{
+    initial_conditions,
+    units::{Length, Mass, TimeSpan},
+    SimulationParameters,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Setting {
+    Stars,
+    TimeFactor,
+    Softening,
+    BlackHoleMass,
+    Gravity,
+    CameraSpeed,
+}
+
+const SETTINGS: [Setting; 6] = [
+    Setting::Stars,
+    Setting::TimeFactor,
+    Setting::Softening,
+    Setting::BlackHoleMass,
+    Setting::Gravity,
+    Setting::CameraSpeed,
+];
+
+/// the values for the next galaxy
+#[derive(Clone, Copy, PartialEq)]
+struct Pending {
+    number_of_stars: usize,
+    black_hole_mass: f64,
+}
+
+pub struct PanelPlugin;
+
+impl Plugin for PanelPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(init_pending.system());
+        #[cfg(feature = "egui")]
+        sliders::build(app);
+        #[cfg(not(feature = "egui"))]
+        buttons::build(app);
+    }
+}
+
+fn init_pending(mut commands: Commands, parameters: Res<SimulationParameters>) {
+    commands.insert_resource(Pending {
+        number_of_stars: parameters.number_of_stars,
+        black_hole_mass: parameters.black_hole_mass,
+    });
+}
+
+/// the text of the setting, with `*` while it waits for a restart
+fn label(setting: Setting, parameters: &SimulationParameters, pending: &Pending) -> String {
+    let restart = |changed: bool| if changed { " *" } else { "" };
+    match setting {
+        Setting::Stars => format!(
+            "stars {}{}",
+            pending.number_of_stars,
+            restart(pending.number_of_stars != parameters.number_of_stars)
+        ),
+        Setting::TimeFactor => format!("time factor {:.2}/s", TimeSpan(parameters.time_factor)),
+        Setting::Softening => format!("softening {:.2}", Length(parameters.softening)),
+        Setting::BlackHoleMass => format!(
+            "black hole {:.2}{}",
+            Mass(pending.black_hole_mass),
+            restart(pending.black_hole_mass != parameters.black_hole_mass)
+        ),
+        Setting::Gravity => format!(
+            "gravity x{:.2}",
+            parameters.g / SimulationParameters::default().g
+        ),
+        Setting::CameraSpeed => format!("camera speed {:.2}", parameters.camera_speed),
+    }
+}
+
+/// applies the pending values and replaces everything but the black hole by a new galaxy
+fn restart(
+    parameters: &mut SimulationParameters,
+    pending: &Pending,
+    requests: &mut SpawnRequests,
+    state: &mut GalaxyState,
+    rng: &mut GalaxyRng,
+) {
+    parameters.number_of_stars = pending.number_of_stars;
+    parameters.black_hole_mass = pending.black_hole_mass;
+    requests
+        .despawn
+        .extend(state.particles.ids.iter().skip(1).copied());
+    let stars = initial_conditions::generate(&mut rng.0, parameters);
+    // the collision preset keeps its black holes in the galaxies
+    if let (Some(mass), Some(center)) = (state.particles.masses.first_mut(), stars.first()) {
+        *mass = center.mass;
+    }
+    requests.spawn.extend(stars.into_iter().skip(1));
+    info!(
+        "restart with {} stars and a black hole of {:.2}",
+        parameters.number_of_stars,
+        Mass(parameters.black_hole_mass)
+    );
+}
+
+/// the `-` and `+` buttons of Bevy UI
+#[cfg(not(feature = "egui"))]
+mod buttons {
+    use super::{restart, Pending, Setting, SETTINGS};
+    use crate::{
+        spawning::SpawnRequests,
+        state::{GalaxyRng, GalaxyState},
+        UI_FONT,
+    };
+    use bevy::prelude::*;
+    use galaxy_core::SimulationParameters;
+
+    /// factor of a `-` or `+` click on the logarithmic settings
+    const STEP_FACTOR: f64 = 1.5;
+    /// change of the camera speed per click in radians per second
+    const CAMERA_STEP: f32 = 0.05;
+    const ROW_HEIGHT: f32 = 24.0;
+    const LABEL_WIDTH: f32 = 220.0;
+    const BUTTON_WIDTH: f32 = 28.0;
+
+    #[derive(Clone, Copy)]
+    enum Action {
+        Lower(Setting),
+        Raise(Setting),
+        Restart,
+    }
+
+    struct PanelButton(Action);
+
+    struct Label(Setting);
+
+    struct PanelMaterials {
+        button: Handle<ColorMaterial>,
+        hovered: Handle<ColorMaterial>,
+    }
+
+    pub(super) fn build(app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system(click.system())
+            .add_system(highlight.system())
+            .add_system(update_labels.system());
+    }
+
+    fn setup(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+    ) {
+        let font = asset_server.load(UI_FONT);
+        let panel = PanelMaterials {
+            button: materials.add(Color::rgba(0.2, 0.2, 0.3, 0.8).into()),
+            hovered: materials.add(Color::rgba(0.3, 0.3, 0.5, 0.9).into()),
+        };
+        let empty = materials.add(Color::NONE.into());
+        let text = |value: &str| TextBundle {
+            text: Text::with_section(
+                value,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        };
+        let button = |width: f32| ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(width), Val::Px(ROW_HEIGHT - 2.0)),
+                margin: Rect {
+                    left: Val::Px(2.0),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: panel.button.clone(),
+            ..Default::default()
+        };
+
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        right: Val::Px(20.0),
+                        top: Val::Px(20.0),
+                        ..Default::default()
+                    },
+                    // the ui grows upwards, reversed the first row is on top
+                    flex_direction: FlexDirection::ColumnReverse,
+                    ..Default::default()
+                },
+                material: empty.clone(),
+                ..Default::default()
+            })
+            .with_children(|panel| {
+                for &setting in &SETTINGS {
+                    panel
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Auto, Val::Px(ROW_HEIGHT)),
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            },
+                            material: empty.clone(),
+                            ..Default::default()
+                        })
+                        .with_children(|row| {
+                            row.spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(LABEL_WIDTH), Val::Px(ROW_HEIGHT)),
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                material: empty.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|label| {
+                                label.spawn_bundle(text("")).insert(Label(setting));
+                            });
+                            for (action, symbol) in
+                                [(Action::Lower(setting), "-"), (Action::Raise(setting), "+")]
+                            {
+                                row.spawn_bundle(button(BUTTON_WIDTH))
+                                    .insert(PanelButton(action))
+                                    .with_children(|button| {
+                                        button.spawn_bundle(text(symbol));
+                                    });
+                            }
+                        });
+                }
+                panel
+                    .spawn_bundle(button(LABEL_WIDTH + 2.0 * (BUTTON_WIDTH + 2.0)))
+                    .insert(PanelButton(Action::Restart))
+                    .with_children(|button| {
+                        button.spawn_bundle(text("apply & restart"));
+                    });
+            });
+        commands.insert_resource(panel);
+    }
+
+    fn click(
+        buttons: Query<(&PanelButton, &Interaction), Changed<Interaction>>,
+        mut parameters: ResMut<SimulationParameters>,
+        mut pending: ResMut<Pending>,
+        mut requests: ResMut<SpawnRequests>,
+        mut state: ResMut<GalaxyState>,
+        mut rng: ResMut<GalaxyRng>,
+    ) {
+        for (button, interaction) in buttons.iter() {
+            if *interaction != Interaction::Clicked {
+                continue;
+            }
+            match button.0 {
+                Action::Lower(setting) => change(setting, false, &mut parameters, &mut pending),
+                Action::Raise(setting) => change(setting, true, &mut parameters, &mut pending),
+                Action::Restart => restart(
+                    &mut parameters,
+                    &pending,
+                    &mut requests,
+                    &mut state,
+                    &mut rng,
+                ),
+            }
+        }
+    }
+
+    fn change(
+        setting: Setting,
+        up: bool,
+        parameters: &mut SimulationParameters,
+        pending: &mut Pending,
+    ) {
+        let factor = if up { STEP_FACTOR } else { 1.0 / STEP_FACTOR };
+        match setting {
+            Setting::Stars => {
+                let stars = (pending.number_of_stars as f64 * factor).round() as usize;
+                // the black hole and at least one star
+                pending.number_of_stars = stars.max(2);
+            }
+            Setting::TimeFactor => parameters.time_factor *= factor,
+            Setting::Softening => parameters.softening *= factor,
+            Setting::BlackHoleMass => {
+                // from no black hole the first step is the heaviest star
+                let heaviest = parameters.star_mass_to;
+                let mass = pending.black_hole_mass * factor;
+                pending.black_hole_mass = if up && mass < heaviest {
+                    heaviest
+                } else if !up && mass < heaviest {
+                    0.0
+                } else {
+                    mass
+                };
+            }
+            Setting::Gravity => parameters.g *= factor,
+            Setting::CameraSpeed => {
+                let step = if up { CAMERA_STEP } else { -CAMERA_STEP };
+                parameters.camera_speed += step;
+            }
+        }
+    }
+
+    fn highlight(
+        materials: Res<PanelMaterials>,
+        mut buttons: Query<(&Interaction, &mut Handle<ColorMaterial>), Changed<Interaction>>,
+    ) {
+        for (interaction, mut material) in buttons.iter_mut() {
+            *material = match interaction {
+                Interaction::None => materials.button.clone(),
+                _ => materials.hovered.clone(),
+            };
+        }
+    }
+
+    fn update_labels(
+        parameters: Res<SimulationParameters>,
+        pending: Res<Pending>,
+        mut labels: Query<(&Label, &mut Text)>,
+    ) {
+        for (label, mut text) in labels.iter_mut() {
+            let value = super::label(label.0, &parameters, &pending);
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+        }
+    }
+}
+
+/// the egui side panel with a slider per setting
+#[cfg(feature = "egui")]
+mod sliders {
+    use super::{label, restart, Pending, Setting, SETTINGS};
+    use crate::{
+        spawning::SpawnRequests,
+        state::{GalaxyRng, GalaxyState},
+    };
+    use bevy::prelude::*;
+    use bevy_egui::{
+        egui::{SidePanel, Slider},
+        EguiContext, EguiPlugin,
+    };
+    use galaxy_core::SimulationParameters;
+    use std::ops::RangeInclusive;
+
+    /// the sliders reach this factor below and above the start values
+    const SPAN: f64 = 1000.0;
+    const MAX_STARS: usize = 100_000;
+    /// radians per second either way
+    const MAX_CAMERA_SPEED: f32 = 1.0;
+    const WIDTH: f32 = 260.0;
+
+    /// the ranges of the logarithmic sliders, from the parameters at the start
+    struct Ranges {
+        time_factor: RangeInclusive<f64>,
+        softening: RangeInclusive<f64>,
+        black_hole_mass: RangeInclusive<f64>,
+        g: RangeInclusive<f64>,
+    }
+
+    pub(super) fn build(app: &mut AppBuilder) {
+        if !app.world().contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+        app.add_startup_system(setup.system())
+            .add_system(side_panel.system());
+    }
+
+    fn around(value: f64) -> RangeInclusive<f64> {
+        value / SPAN..=value * SPAN
+    }
+
+    fn setup(mut commands: Commands, parameters: Res<SimulationParameters>) {
+        // a softening of 0 still gets a range, up to a thousandth of the galaxy
+        let softening = if parameters.softening > 0.0 {
+            around(parameters.softening)
+        } else {
+            0.0..=parameters.galaxy_diameter / SPAN
+        };
+        // 0 is no black hole, the smallest other mass is the heaviest star
+        let black_hole = parameters.black_hole_mass.max(parameters.star_mass_to);
+        commands.insert_resource(Ranges {
+            time_factor: around(parameters.time_factor),
+            softening,
+            black_hole_mass: 0.0..=black_hole * SPAN,
+            g: around(parameters.g),
+        });
+    }
+
+    fn side_panel(
+        egui: Res<EguiContext>,
+        ranges: Res<Ranges>,
+        mut parameters: ResMut<SimulationParameters>,
+        mut pending: ResMut<Pending>,
+        mut requests: ResMut<SpawnRequests>,
+        mut state: ResMut<GalaxyState>,
+        mut rng: ResMut<GalaxyRng>,
+    ) {
+        // edited on copies, so the resources only change when a slider moves
+        let mut changed = (*parameters).clone();
+        let mut next = *pending;
+        let mut apply = false;
+        let heaviest = parameters.star_mass_to;
+        SidePanel::left("parameters", WIDTH).show(egui.ctx(), |ui| {
+            ui.heading("parameters");
+            for &setting in &SETTINGS {
+                let text = label(setting, &changed, &next);
+                let slider = match setting {
+                    Setting::Stars => Slider::new(&mut next.number_of_stars, 2..=MAX_STARS),
+                    Setting::TimeFactor => {
+                        Slider::new(&mut changed.time_factor, ranges.time_factor.clone())
+                    }
+                    Setting::Softening => {
+                        Slider::new(&mut changed.softening, ranges.softening.clone())
+                            .smallest_positive(*ranges.softening.end() / SPAN / SPAN)
+                    }
+                    Setting::BlackHoleMass => {
+                        Slider::new(&mut next.black_hole_mass, ranges.black_hole_mass.clone())
+                            .smallest_positive(heaviest)
+                    }
+                    Setting::Gravity => Slider::new(&mut changed.g, ranges.g.clone()),
+                    Setting::CameraSpeed => Slider::new(
+                        &mut changed.camera_speed,
+                        -MAX_CAMERA_SPEED..=MAX_CAMERA_SPEED,
+                    ),
+                };
+                ui.add(
+                    slider
+                        .logarithmic(setting != Setting::CameraSpeed)
+                        .show_value(false)
+                        .text(text),
+                );
+            }
+            ui.separator();
+            apply = ui.button("apply & restart").clicked();
+        });
+        let moved = changed.time_factor != parameters.time_factor
+            || changed.softening != parameters.softening
+            || changed.g != parameters.g
+            || changed.camera_speed != parameters.camera_speed;
+        if moved {
+            *parameters = changed;
+        }
+        if next != *pending {
+            *pending = next;
+        }
+        if apply {
+            restart(
+                &mut parameters,
+                &pending,
+                &mut requests,
+                &mut state,
+                &mut rng,
+            );
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! The gravitational potential of the stars beneath the galaxy, to see the wells forming
+//! around the black hole and the clumps.
+//!
+//! `--potential <steps>` samples the potential every this many physics steps on a grid
+//! of `--potential-resolution` points along each side, 64 by default. The grid covers
+//! the moving stars around the black hole in the galaxy plane and sits a little below
+//! them. Its depth is mapped onto the colormap of `coloring` logarithmically, so the
+//! shallow wells of clumps show next to the deep one in the center. `P` switches between
+//! the flat heatmap, a heightfield sinking into the wells and neither. A sample sums over
+//! every star for each grid point, over the octree with `--opening-angle` above 0.
+
+use crate::{coloring::Colormap, render_frame::RenderFrame, state::GalaxyState};
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::Indices,
+        pipeline::PrimitiveTopology,
+        texture::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use galaxy_core::{potential::PotentialMap, DVec3, SimulationParameters};
+
+const TOGGLE: KeyCode = KeyCode::P;
+pub const DEFAULT_RESOLUTION: usize = 64;
+/// how much the depth is stretched before the logarithm, larger shows shallower wells
+const CONTRAST: f64 = 100.0;
+/// the deepest well of the heightfield sinks by this much of the half size of the grid
+const HEIGHT: f64 = 0.5;
+/// gap between the grid and the lowest star in half sizes of the grid
+const GAP: f64 = 0.1;
+
+/// how often and how finely the potential is sampled
+pub struct PotentialSettings {
+    /// physics steps between the samples
+    pub interval: u64,
+    /// grid points along each side
+    pub resolution: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum View {
+    #[default]
+    Heatmap,
+    Heightfield,
+    Hidden,
+}
+
+impl View {
+    fn next(self) -> Self {
+        match self {
+            View::Heatmap => View::Heightfield,
+            View::Heightfield => View::Hidden,
+            View::Hidden => View::Heatmap,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PotentialField {
+    map: Option<PotentialMap>,
+    /// the physics step of the next sample
+    next_step: u64,
+}
+
+struct PotentialSurface {
+    texture: Handle<Texture>,
+}
+
+pub struct PotentialPlugin;
+
+impl Plugin for PotentialPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<View>()
+            .init_resource::<PotentialField>()
+            .init_resource::<Colormap>()
+            .add_startup_system(setup.system())
+            .add_system(toggle.system())
+            .add_system(sample.system().label("potential").after("sync"))
+            .add_system(update_surface.system().after("potential"));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    settings: Res<PotentialSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    let resolution = settings.resolution.max(2) as u32;
+    let texture = textures.add(Texture::new(
+        Extent3d::new(resolution, resolution, 1),
+        TextureDimension::D2,
+        vec![0; (resolution * resolution * 4) as usize],
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+    let mut bundle = PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Plane { size: 1.0 })),
+        material: materials.add(StandardMaterial {
+            // dimmer than the stars above it
+            base_color: Color::rgb(0.6, 0.6, 0.6),
+            base_color_texture: Some(texture.clone()),
+            unlit: true,
+            double_sided: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    bundle.visible.is_visible = false;
+    commands
+        .spawn_bundle(bundle)
+        .insert(PotentialSurface { texture });
+}
+
+fn toggle(keys: Res<Input<KeyCode>>, mut view: ResMut<View>) {
+    if keys.just_pressed(TOGGLE) {
+        *view = view.next();
+    }
+}
+
+fn sample(
+    view: Res<View>,
+    settings: Res<PotentialSettings>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+    mut field: ResMut<PotentialField>,
+) {
+    let due = field.map.is_none() || state.steps >= field.next_step || view.is_changed();
+    if *view == View::Hidden || !due {
+        return;
+    }
+    let particles = &state.particles;
+    let center = particles.positions.first().copied().unwrap_or_default();
+    let moving = || (0..particles.len()).filter(|&i| !particles.frozen[i]);
+    let half_size = moving()
+        .map(|i| {
+            let offset = particles.positions[i] - center;
+            offset.x.abs().max(offset.y.abs())
+        })
+        .fold(0.0, f64::max);
+    if half_size <= 0.0 {
+        return;
+    }
+    let bottom = moving()
+        .map(|i| particles.positions[i].z)
+        .fold(f64::INFINITY, f64::min);
+    let plane = DVec3::new(center.x, center.y, bottom - GAP * half_size);
+    field.map = Some(PotentialMap::sample(
+        particles,
+        &parameters,
+        plane,
+        half_size,
+        settings.resolution,
+    ));
+    field.next_step = state.steps + settings.interval.max(1);
+}
+
+/// how deep each sample is from 0 at the highest to 1 at the deepest, logarithmically
+fn depths(map: &PotentialMap) -> Vec<f64> {
+    let (min, max) = map.range();
+    let span = max - min;
+    map.values
+        .iter()
+        .map(|&value| {
+            let depth = if span > 0.0 {
+                (max - value) / span
+            } else {
+                0.0
+            };
+            (1.0 + CONTRAST * depth).ln() / (1.0 + CONTRAST).ln()
+        })
+        .collect()
+}
+
+/// the grid as triangles in render space, sunk by the depths for the heightfield
+fn grid_mesh(map: &PotentialMap, depths: &[f64], sink: bool, frame: &RenderFrame) -> Mesh {
+    let n = map.resolution;
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(n * n);
+    let mut uvs = Vec::with_capacity(n * n);
+    for row in 0..n {
+        for column in 0..n {
+            let mut point = map.point(column, row);
+            if sink {
+                point.z -= HEIGHT * map.half_size * depths[row * n + column];
+            }
+            positions.push(frame.render_position(point).into());
+            uvs.push([column as f32 / (n - 1) as f32, row as f32 / (n - 1) as f32]);
+        }
+    }
+    let mut indices = Vec::with_capacity(6 * (n - 1) * (n - 1));
+    for row in 0..n - 1 {
+        for column in 0..n - 1 {
+            let i = (row * n + column) as u32;
+            let (right, up) = (i + 1, i + n as u32);
+            indices.extend([i, right, up + 1, i, up + 1, up]);
+        }
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    // the PBR pipeline expects normals, the unlit surface ignores them
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 0.0, 1.0]; positions.len()],
+    );
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn update_surface(
+    view: Res<View>,
+    field: Res<PotentialField>,
+    frame: Res<RenderFrame>,
+    colormap: Res<Colormap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut surfaces: Query<(&PotentialSurface, &Handle<Mesh>, &mut Visible)>,
+) {
+    if !(view.is_changed() || field.is_changed() || frame.is_changed()) {
+        return;
+    }
+    for (surface, mesh, mut visible) in surfaces.iter_mut() {
+        let map = match &field.map {
+            Some(map) if *view != View::Hidden => map,
+            _ => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+        visible.is_visible = true;
+        let depths = depths(map);
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            *mesh = grid_mesh(map, &depths, *view == View::Heightfield, &frame);
+        }
+        if field.is_changed() {
+            if let Some(texture) = textures.get_mut(&surface.texture) {
+                texture.data = depths
+                    .iter()
+                    .flat_map(|&depth| {
+                        let [r, g, b] = colormap.rgb(depth as f32);
+                        [r, g, b, 1.0].map(|c| (c * 255.0).round() as u8)
+                    })
+                    .collect();
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Records the stars while the simulation runs, as an Alembic point cache or as a
+//! trajectory file for replays.
+//!
+//! `--alembic <file>` starts the Alembic recording. Every physics step becomes one frame, at
+//! the physics tick rate as frame rate the cache plays back as fast as the run was
+//! watched. Positions are in the render units of the viewer, the galaxy fits into a box
+//! of size 1000. The file is complete when the app is closed.
+//!
+//! `--record <file>` writes the positions of every physics step to a compact trajectory
+//! file, which `--replay <file>` plays back.
+//!
+//! `--export <dir>` writes a CSV table of all stars every `--export-every` physics steps
+//! into the directory, for analysis with pandas.
+
+use crate::state::{GalaxyState, SimulationState};
+use bevy::{app::AppExit, prelude::*};
+use galaxy_core::{alembic::PointCache, export::CsvExport, trajectory::TrajectoryWriter};
+
+/// the cache being written, `None` after an error or when the app closes
+pub struct Recording(pub Option<PointCache>);
+
+pub fn record(
+    mut recording: ResMut<Recording>,
+    state: Res<GalaxyState>,
+    simulation: Res<SimulationState>,
+) {
+    profiling::scope!("record");
+    if simulation.paused {
+        return;
+    }
+    if let Some(cache) = &mut recording.0 {
+        if let Err(e) = cache.write_frame(&state.particles) {
+            error!("recording stopped: {}", e);
+            recording.0 = None;
+        }
+    }
+}
+
+pub fn finish(mut exits: EventReader<AppExit>, mut recording: ResMut<Recording>) {
+    if exits.iter().next().is_none() {
+        return;
+    }
+    if let Some(cache) = recording.0.take() {
+        match cache.finish() {
+            Ok(frames) => info!("recorded {} frames", frames),
+            Err(e) => error!("can't finish the recording: {}", e),
+        }
+    }
+}
+
+/// the trajectory file being written, `None` after an error or when the app closes
+pub struct TrajectoryRecording(pub Option<TrajectoryWriter>);
+
+pub fn record_trajectory(
+    mut recording: ResMut<TrajectoryRecording>,
+    state: Res<GalaxyState>,
+    simulation: Res<SimulationState>,
+) {
+    profiling::scope!("record trajectory");
+    if !simulation.stepped {
+        return;
+    }
+    if let Some(writer) = &mut recording.0 {
+        if let Err(e) = writer.write_frame(state.time, &state.particles) {
+            error!("trajectory recording stopped: {}", e);
+            recording.0 = None;
+        }
+    }
+}
+
+pub fn finish_trajectory(
+    mut exits: EventReader<AppExit>,
+    mut recording: ResMut<TrajectoryRecording>,
+) {
+    if exits.iter().next().is_none() {
+        return;
+    }
+    if let Some(writer) = recording.0.take() {
+        match writer.finish() {
+            Ok(frames) => info!("recorded {} trajectory frames", frames),
+            Err(e) => error!("can't finish the trajectory recording: {}", e),
+        }
+    }
+}
+
+/// the CSV exports, `None` after an error
+pub struct Export(pub Option<CsvExport>);
+
+pub fn export_csv(
+    mut export: ResMut<Export>,
+    state: Res<GalaxyState>,
+    simulation: Res<SimulationState>,
+) {
+    profiling::scope!("export");
+    if !simulation.stepped {
+        return;
+    }
+    if let Some(csv) = &export.0 {
+        if !csv.is_due(state.steps) {
+            return;
+        }
+        if let Err(e) = csv.write(state.steps, state.time, &state.particles) {
+            error!("export stopped: {}", e);
+            export.0 = None;
+        }
+    }
+}
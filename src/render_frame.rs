@@ -0,0 +1,49 @@
+use bevy::{
+    math::{DVec3, Vec3},
+    prelude::*,
+};
+use galaxy_core::SimulationParameters;
+
+/// maps f64 physics coordinates to f32 render space
+///
+/// Positions are made relative to `origin` and scaled while still in f64, only the
+/// small result is converted to f32. The camera moves the origin to what it looks at and
+/// scales with its zoom, see `camera`, which keeps close-ups free of jitter even far away
+/// from the galactic center.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderFrame {
+    /// physics position shown at the render space origin
+    pub origin: DVec3,
+    /// render units per meter
+    pub scale: f64,
+}
+
+impl RenderFrame {
+    /// the whole galaxy in a 1000 size box around the center
+    pub fn fit_galaxy(parameters: &SimulationParameters) -> Self {
+        Self {
+            origin: DVec3::default(),
+            scale: 1000.0 / parameters.galaxy_diameter,
+        }
+    }
+
+    pub fn render_position(&self, position: DVec3) -> Vec3 {
+        let v = (position - self.origin) * self.scale;
+        Vec3::new(v.x as f32, v.y as f32, v.z as f32)
+    }
+
+    /// the inverse of `render_position`
+    pub fn physics_position(&self, position: Vec3) -> DVec3 {
+        DVec3::new(position.x as f64, position.y as f64, position.z as f64) / self.scale
+            + self.origin
+    }
+}
+
+impl FromWorld for RenderFrame {
+    fn from_world(world: &mut World) -> Self {
+        let parameters = world
+            .get_resource::<SimulationParameters>()
+            .expect("RenderFrame needs the SimulationParameters");
+        RenderFrame::fit_galaxy(parameters)
+    }
+}
@@ -0,0 +1,213 @@
+//! Replays a recorded trajectory file instead of simulating, started with `--replay`.
+//!
+//! The stars move through the recorded positions without any physics. The time factor sets
+//! the playback speed in simulated seconds per second like in a live run, so `+` and `-`
+//! play faster and slower, `Space` pauses and `N` steps while paused. `Left` and `Right`
+//! seek back and forth by a twentieth of the recording, `Home` and `End` jump to its start
+//! and end. The progress is shown in the lower right corner, the text needs the UI font.
+
+use crate::{
+    spawning::SpawnRequests,
+    state::{GalaxyState, SimulationState},
+    UI_FONT,
+};
+use bevy::prelude::*;
+use galaxy_core::{
+    trajectory::TrajectoryReader, units::TimeSpan, Error, SimulationParameters, Star,
+};
+use std::path::Path;
+
+/// part of the recording a `Left` or `Right` press seeks
+const SEEK_FRACTION: f64 = 0.05;
+
+pub struct Replay {
+    reader: TrajectoryReader,
+    /// simulated time of the playback
+    time: f64,
+    /// frame currently in the stars
+    shown: Option<usize>,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> galaxy_core::Result<Self> {
+        let reader = TrajectoryReader::open(path)?;
+        if reader.is_empty() {
+            return Err(Error::Parse {
+                path: path.to_path_buf(),
+                message: "no frames recorded".to_string(),
+            });
+        }
+        Ok(Self {
+            time: reader.times()[0],
+            reader,
+            shown: None,
+        })
+    }
+
+    /// the stars of the first frame, the app starts with them
+    pub fn initial_stars(&mut self) -> galaxy_core::Result<Vec<Star>> {
+        Ok(self
+            .reader
+            .frame(0)?
+            .into_iter()
+            .map(|position| Star {
+                position,
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    /// moves the playback to `time`, within the recording
+    pub fn seek(&mut self, time: f64) {
+        self.time = time.clamp(self.start(), self.end());
+    }
+
+    fn start(&self) -> f64 {
+        self.reader.times()[0]
+    }
+
+    fn end(&self) -> f64 {
+        self.reader.times()[self.reader.len() - 1]
+    }
+}
+
+struct ProgressText;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system(seek.system())
+            .add_system(update_progress.system());
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(UI_FONT),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ProgressText);
+}
+
+/// takes the place of the physics step, shows the frame at the playback time
+pub fn play(
+    mut replay: ResMut<Replay>,
+    mut simulation: ResMut<SimulationState>,
+    parameters: Res<SimulationParameters>,
+    mut state: ResMut<GalaxyState>,
+    mut requests: ResMut<SpawnRequests>,
+) {
+    profiling::scope!("replay");
+    if simulation.take_step() {
+        replay.time += parameters.physics_dt * parameters.time_factor;
+    }
+    let end = replay.end();
+    if replay.time >= end {
+        replay.time = end;
+        if !simulation.paused {
+            info!("replay finished");
+            simulation.paused = true;
+        }
+    }
+    let index = replay.reader.index_at(replay.time);
+    if replay.shown == Some(index) {
+        return;
+    }
+    let positions = match replay.reader.frame(index) {
+        Ok(positions) => positions,
+        Err(e) => {
+            error!("can't read the recording: {}", e);
+            return;
+        }
+    };
+    replay.shown = Some(index);
+
+    let state = &mut *state;
+    state
+        .previous_positions
+        .copy_from_slice(&state.particles.positions);
+    state.steps = index as u64;
+    state.time = replay.reader.times()[index];
+    let particles = &mut state.particles;
+    for (i, &position) in positions.iter().enumerate().take(particles.len()) {
+        particles.positions[i] = position;
+    }
+    // only the number of stars has to match, the recorded order is taken as it is
+    if positions.len() > particles.len() {
+        requests
+            .spawn
+            .extend(positions[particles.len()..].iter().map(|&position| Star {
+                position,
+                ..Default::default()
+            }));
+    } else {
+        requests
+            .despawn
+            .extend_from_slice(&particles.ids[positions.len()..]);
+    }
+}
+
+fn seek(keys: Res<Input<KeyCode>>, mut replay: ResMut<Replay>) {
+    let (start, end) = (replay.start(), replay.end());
+    let step = (end - start) * SEEK_FRACTION;
+    let time = if keys.just_pressed(KeyCode::Home) {
+        start
+    } else if keys.just_pressed(KeyCode::End) {
+        end
+    } else if keys.just_pressed(KeyCode::Left) {
+        replay.time - step
+    } else if keys.just_pressed(KeyCode::Right) {
+        replay.time + step
+    } else {
+        return;
+    };
+    replay.seek(time);
+}
+
+fn update_progress(
+    replay: Res<Replay>,
+    simulation: Res<SimulationState>,
+    parameters: Res<SimulationParameters>,
+    mut texts: Query<&mut Text, With<ProgressText>>,
+) {
+    let (start, end) = (replay.start(), replay.end());
+    let progress = if end > start {
+        (replay.time - start) / (end - start)
+    } else {
+        1.0
+    };
+    let value = format!(
+        "replay {:.0}%, frame {} of {}, {}{}, time factor {:.2}/s",
+        progress * 100.0,
+        replay.shown.map_or(0, |index| index + 1),
+        replay.reader.len(),
+        TimeSpan(replay.time),
+        if simulation.paused { ", paused" } else { "" },
+        TimeSpan(parameters.time_factor),
+    );
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Shows the Roche lobe and the tidal radius of a star orbiting a heavier body.
+//!
+//! `--roche <id>,<id>` picks the pair by particle id like `--lagrange`, e.g. `0,5`
+//! for the fifth star around the black hole. The lobe is drawn as a wireframe around the
+//! lighter body, the tidal radius as a circle in the orbit plane. Both follow the orbit,
+//! stars of the lighter body's group that leave the lobe are stripped by the tide.
+
+use crate::{
+    lagrange::{line_mesh, rotating_frame},
+    render_frame::RenderFrame,
+    state::GalaxyState,
+};
+use bevy::{
+    math::DVec3,
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use galaxy_core::{ParticleId, SimulationParameters};
+use std::collections::BTreeSet;
+
+/// subdivisions of the icosphere whose vertices sample the lobe surface
+const SUBDIVISIONS: usize = 3;
+const CIRCLE_SEGMENTS: usize = 128;
+
+pub struct RochePair(pub ParticleId, pub ParticleId);
+
+/// unit directions of the sampled surface points and the edges between them
+struct LobeSamples {
+    directions: Vec<DVec3>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Default for LobeSamples {
+    fn default() -> Self {
+        let sphere = Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: SUBDIVISIONS,
+        });
+        let directions = match sphere.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float3(positions)) => positions
+                .iter()
+                .map(|p| DVec3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let triangles: Vec<usize> = match sphere.indices() {
+            Some(Indices::U32(indices)) => indices.iter().map(|&i| i as usize).collect(),
+            Some(Indices::U16(indices)) => indices.iter().map(|&i| i as usize).collect(),
+            None => Vec::new(),
+        };
+        let mut edges = BTreeSet::new();
+        for triangle in triangles.chunks_exact(3) {
+            for k in 0..3 {
+                let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        Self {
+            directions,
+            edges: edges.into_iter().collect(),
+        }
+    }
+}
+
+/// the line mesh of the lobe and the circle
+struct Lobe;
+
+pub struct RochePlugin;
+
+impl Plugin for RochePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LobeSamples>()
+            .add_startup_system(setup.system())
+            .add_system(update.system());
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut bundle = PbrBundle {
+        mesh: meshes.add(line_mesh(vec![[0.0; 3]; 2])),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(1.0, 0.6, 0.2),
+            unlit: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    bundle.visible.is_visible = false;
+    commands.spawn_bundle(bundle).insert(Lobe);
+}
+
+fn update(
+    pair: Res<RochePair>,
+    samples: Res<LobeSamples>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+    frame: Res<RenderFrame>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut lobes: Query<(&Handle<Mesh>, &mut Visible), With<Lobe>>,
+) {
+    profiling::scope!("roche");
+    let rotating = rotating_frame(&state.particles, (pair.0, pair.1), &parameters);
+    for (mesh, mut visible) in lobes.iter_mut() {
+        visible.is_visible = rotating.is_some();
+        let (rotating, mesh) = match (rotating, meshes.get_mut(mesh)) {
+            (Some(rotating), Some(mesh)) => (rotating, mesh),
+            _ => continue,
+        };
+
+        let l1 = rotating.lagrange_points()[0];
+        let surface: Vec<DVec3> = samples
+            .directions
+            .iter()
+            .map(|&direction| {
+                rotating.secondary + direction * rotating.roche_lobe_radius(direction, l1)
+            })
+            .collect();
+        let mut segments: Vec<DVec3> = samples
+            .edges
+            .iter()
+            .flat_map(|&(a, b)| [surface[a], surface[b]])
+            .collect();
+
+        let radius = rotating.tidal_radius();
+        let on_circle = |i: usize| {
+            let angle = i as f64 / CIRCLE_SEGMENTS as f64 * std::f64::consts::TAU;
+            rotating.secondary + (rotating.x * angle.cos() + rotating.y * angle.sin()) * radius
+        };
+        segments.extend((0..CIRCLE_SEGMENTS).flat_map(|i| [on_circle(i), on_circle(i + 1)]));
+
+        *mesh = line_mesh(
+            segments
+                .iter()
+                .map(|p| frame.render_position(*p).into())
+                .collect(),
+        );
+    }
+}
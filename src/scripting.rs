@@ -0,0 +1,453 @@
+//! Rhai scenario scripts, loaded from the file in `--script`.
+//!
+//! A script can define these functions, all of them are optional:
+//!
+//! * `init()` runs before the start, stars spawned there replace the generated galaxy,
+//!   the first one is the black hole
+//! * `on_step(time, stars)` runs after every physics step with the simulated seconds
+//!   and the number of stars
+//! * `on_collision(x, y, z)`, `on_merge(x, y, z, mass)`, `on_escape(x, y, z)`,
+//!   `on_accretion(mass)` and `on_supernova(x, y, z, mass)` run for the events, the
+//!   last one not yet, nothing raises supernovae so far
+//!
+//! and call these functions to change the simulation, all numbers are floats in SI units
+//! and the star ids integers:
+//!
+//! * `spawn(x, y, z, vx, vy, vz, mass)` adds a star
+//! * `spawn_cluster(x, y, z, vx, vy, vz, stars, diameter, mass)` adds a Plummer sphere of
+//!   `stars` stars, a star cluster or a dwarf galaxy
+//! * `set_position(id, x, y, z)`, `set_velocity(id, vx, vy, vz)` and `set_mass(id, mass)`
+//!   change a star, `remove(id)` takes it out
+//! * `at(time, "name")` calls the script function `name()` once the simulated time reaches
+//!   `time` seconds, e.g. `at(5.0 * units::GYR, "inject_dwarf")`
+//! * `set_time_factor(factor)` changes the simulated seconds per physics second
+//! * `pause()` and `resume()`
+//!
+//! and query the stars after the last physics step:
+//!
+//! * `time()` and `star_ids()`, the first id is the black hole
+//! * `position(id)` and `velocity(id)` as `[x, y, z]`, `mass(id)`, `()` for unknown ids
+//! * `query_nearest(x, y, z)`, the id of the star closest to the point, -1 without stars
+//!
+//! `units::YEAR`, `units::MYR`, `units::GYR`, `units::PARSEC`, `units::KPC` and
+//! `units::SOLAR_MASS` convert to SI units. Changes requested from the event handlers
+//! take effect after the next physics step, `init()` can only spawn and schedule.
+
+use crate::{
+    events::{AccretionEvent, CollisionEvent, EscapeEvent, MergeEvent, SupernovaEvent},
+    spawning::SpawnRequests,
+    state::{GalaxyRng, GalaxyState, InitialStars, SimulationState},
+};
+use bevy::{math::DVec3, prelude::*};
+use galaxy_core::{
+    initial_conditions,
+    units::{GYR, KPC, MYR, PARSEC, SOLAR_MASS, YEAR},
+    Error, ParticleId, SimulationParameters, Star,
+};
+use rand::Rng;
+use rhai::{Array, Dynamic, Engine, FuncArgs, Module, Scope, AST, INT};
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// a Plummer sphere of `spawn_cluster`
+struct Cluster {
+    position: DVec3,
+    velocity: DVec3,
+    stars: usize,
+    diameter: f64,
+    mass: f64,
+}
+
+impl Cluster {
+    fn stars(&self, rng: &mut impl Rng, parameters: &SimulationParameters) -> Vec<Star> {
+        let mut stars = initial_conditions::plummer_cluster(
+            rng,
+            self.stars,
+            self.diameter,
+            self.mass,
+            parameters,
+        );
+        for star in &mut stars {
+            star.position += self.position;
+            star.velocity += self.velocity;
+        }
+        stars
+    }
+}
+
+enum ScriptCommand {
+    Spawn(Star),
+    SpawnCluster(Cluster),
+    SetPosition(ParticleId, DVec3),
+    SetVelocity(ParticleId, DVec3),
+    SetMass(ParticleId, f64),
+    Remove(ParticleId),
+    SetTimeFactor(f64),
+    Pause,
+    Resume,
+}
+
+type CommandQueue = Arc<Mutex<Vec<ScriptCommand>>>;
+
+/// the stars as the query functions see them, copied after every physics step
+#[derive(Default)]
+struct View {
+    time: f64,
+    ids: Vec<ParticleId>,
+    positions: Vec<DVec3>,
+    velocities: Vec<DVec3>,
+    masses: Vec<f64>,
+}
+
+impl View {
+    fn update(&mut self, state: &GalaxyState) {
+        let particles = &state.particles;
+        self.time = state.time;
+        self.ids.clone_from(&particles.ids);
+        self.positions.clone_from(&particles.positions);
+        self.velocities.clone_from(&particles.velocities);
+        self.masses.clone_from(&particles.masses);
+    }
+
+    fn index(&self, id: INT) -> Option<usize> {
+        self.ids.iter().position(|other| other.0 as INT == id)
+    }
+}
+
+/// `[x, y, z]` for the script
+fn array(v: DVec3) -> Dynamic {
+    let array: Array = vec![v.x.into(), v.y.into(), v.z.into()];
+    array.into()
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    commands: CommandQueue,
+    view: Arc<Mutex<View>>,
+    /// the simulated time and the function of the `at` calls not made yet
+    scheduled: Arc<Mutex<Vec<(f64, String)>>>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> galaxy_core::Result<Self> {
+        let source = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let commands = CommandQueue::default();
+        let mut engine = Engine::new();
+
+        let queue = commands.clone();
+        engine.register_fn(
+            "spawn",
+            move |x: f64, y: f64, z: f64, vx: f64, vy: f64, vz: f64, mass: f64| {
+                queue.lock().unwrap().push(ScriptCommand::Spawn(Star {
+                    position: DVec3::new(x, y, z),
+                    velocity: DVec3::new(vx, vy, vz),
+                    acceleration: DVec3::default(),
+                    mass,
+                }));
+            },
+        );
+        let queue = commands.clone();
+        engine.register_fn(
+            "spawn_cluster",
+            move |x: f64,
+                  y: f64,
+                  z: f64,
+                  vx: f64,
+                  vy: f64,
+                  vz: f64,
+                  stars: INT,
+                  diameter: f64,
+                  mass: f64| {
+                queue
+                    .lock()
+                    .unwrap()
+                    .push(ScriptCommand::SpawnCluster(Cluster {
+                        position: DVec3::new(x, y, z),
+                        velocity: DVec3::new(vx, vy, vz),
+                        stars: stars.max(0) as usize,
+                        diameter,
+                        mass,
+                    }));
+            },
+        );
+        let queue = commands.clone();
+        engine.register_fn("set_position", move |id: INT, x: f64, y: f64, z: f64| {
+            let position = DVec3::new(x, y, z);
+            queue
+                .lock()
+                .unwrap()
+                .push(ScriptCommand::SetPosition(ParticleId(id as u64), position));
+        });
+        let queue = commands.clone();
+        engine.register_fn("set_velocity", move |id: INT, vx: f64, vy: f64, vz: f64| {
+            let velocity = DVec3::new(vx, vy, vz);
+            queue
+                .lock()
+                .unwrap()
+                .push(ScriptCommand::SetVelocity(ParticleId(id as u64), velocity));
+        });
+        let queue = commands.clone();
+        engine.register_fn("set_mass", move |id: INT, mass: f64| {
+            queue
+                .lock()
+                .unwrap()
+                .push(ScriptCommand::SetMass(ParticleId(id as u64), mass));
+        });
+        let queue = commands.clone();
+        engine.register_fn("remove", move |id: INT| {
+            queue
+                .lock()
+                .unwrap()
+                .push(ScriptCommand::Remove(ParticleId(id as u64)));
+        });
+        let scheduled = Arc::new(Mutex::new(Vec::new()));
+        let schedule = scheduled.clone();
+        engine.register_fn("at", move |time: f64, name: &str| {
+            schedule.lock().unwrap().push((time, name.to_string()));
+        });
+
+        let view = Arc::new(Mutex::new(View::default()));
+        let stars = view.clone();
+        engine.register_fn("time", move || stars.lock().unwrap().time);
+        let stars = view.clone();
+        engine.register_fn("star_ids", move || -> Array {
+            let view = stars.lock().unwrap();
+            view.ids.iter().map(|id| (id.0 as INT).into()).collect()
+        });
+        let stars = view.clone();
+        engine.register_fn("position", move |id: INT| {
+            let view = stars.lock().unwrap();
+            view.index(id)
+                .map_or(Dynamic::UNIT, |i| array(view.positions[i]))
+        });
+        let stars = view.clone();
+        engine.register_fn("velocity", move |id: INT| {
+            let view = stars.lock().unwrap();
+            view.index(id)
+                .map_or(Dynamic::UNIT, |i| array(view.velocities[i]))
+        });
+        let stars = view.clone();
+        engine.register_fn("mass", move |id: INT| {
+            let view = stars.lock().unwrap();
+            view.index(id)
+                .map_or(Dynamic::UNIT, |i| view.masses[i].into())
+        });
+        let stars = view.clone();
+        engine.register_fn("query_nearest", move |x: f64, y: f64, z: f64| -> INT {
+            let view = stars.lock().unwrap();
+            let point = DVec3::new(x, y, z);
+            view.positions
+                .iter()
+                .map(|p| p.distance_squared(point))
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or(-1, |(i, _)| view.ids[i].0 as INT)
+        });
+
+        let mut units = Module::new();
+        for (name, value) in [
+            ("YEAR", YEAR),
+            ("MYR", MYR),
+            ("GYR", GYR),
+            ("PARSEC", PARSEC),
+            ("KPC", KPC),
+            ("SOLAR_MASS", SOLAR_MASS),
+        ] {
+            units.set_var(name, value);
+        }
+        engine.register_static_module("units", units.into());
+
+        let queue = commands.clone();
+        engine.register_fn("set_time_factor", move |factor: f64| {
+            queue
+                .lock()
+                .unwrap()
+                .push(ScriptCommand::SetTimeFactor(factor));
+        });
+        let queue = commands.clone();
+        engine.register_fn("pause", move || {
+            queue.lock().unwrap().push(ScriptCommand::Pause);
+        });
+        let queue = commands.clone();
+        engine.register_fn("resume", move || {
+            queue.lock().unwrap().push(ScriptCommand::Resume);
+        });
+
+        let ast = engine.compile(&source).map_err(|e| Error::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        Ok(Self {
+            engine,
+            ast,
+            commands,
+            view,
+            scheduled,
+        })
+    }
+
+    fn has_function(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name)
+    }
+
+    /// calls a script function if it exists, script errors are logged and don't stop the simulation
+    fn call(&self, name: &str, args: impl FuncArgs) {
+        if self.has_function(name) {
+            let result = self
+                .engine
+                .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, args);
+            if let Err(e) = result {
+                error!("script function {} failed: {}", name, e);
+            }
+        }
+    }
+
+    fn take_commands(&self) -> Vec<ScriptCommand> {
+        std::mem::take(&mut *self.commands.lock().unwrap())
+    }
+
+    /// runs `init()`, the stars it spawned are the initial conditions, without any the
+    /// galaxy of the settings stays
+    fn initial_stars(
+        &self,
+        rng: &mut impl Rng,
+        parameters: &SimulationParameters,
+    ) -> Option<Vec<Star>> {
+        if !self.has_function("init") {
+            return None;
+        }
+        self.call("init", ());
+        let mut stars = Vec::new();
+        for command in self.take_commands() {
+            match command {
+                ScriptCommand::Spawn(star) => stars.push(star),
+                ScriptCommand::SpawnCluster(cluster) => {
+                    stars.extend(cluster.stars(rng, parameters))
+                }
+                _ => warn!("init() can only spawn stars"),
+            }
+        }
+        Some(stars).filter(|stars: &Vec<Star>| !stars.is_empty())
+    }
+
+    /// the functions of the `at` calls whose time has come, in the order of their times
+    fn due(&self, time: f64) -> Vec<String> {
+        let mut scheduled = self.scheduled.lock().unwrap();
+        let mut due: Vec<(f64, String)> = Vec::new();
+        scheduled.retain(|(at, name)| {
+            let waiting = *at > time;
+            if !waiting {
+                due.push((*at, name.clone()));
+            }
+            waiting
+        });
+        due.sort_by(|a, b| a.0.total_cmp(&b.0));
+        due.into_iter().map(|(_, name)| name).collect()
+    }
+}
+
+pub struct ScriptingPlugin(pub Mutex<Option<Script>>);
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let script = match self.0.lock().unwrap().take() {
+            Some(script) => script,
+            None => return,
+        };
+        let parameters = app
+            .world()
+            .get_resource::<SimulationParameters>()
+            .cloned()
+            .unwrap_or_default();
+        let stars = match app.world_mut().get_resource_mut::<GalaxyRng>() {
+            Some(mut rng) => script.initial_stars(&mut rng.0, &parameters),
+            None => script.initial_stars(&mut rand::thread_rng(), &parameters),
+        };
+        if let Some(stars) = stars {
+            app.insert_resource(InitialStars(stars));
+        }
+        app.insert_resource(script)
+            .add_system_to_stage(crate::PHYSICS_STAGE, step_hook.system().after("moving"))
+            .add_system(event_hooks.system());
+    }
+}
+
+fn step_hook(
+    script: Res<Script>,
+    mut state: ResMut<GalaxyState>,
+    mut rng: ResMut<GalaxyRng>,
+    mut requests: ResMut<SpawnRequests>,
+    mut parameters: ResMut<SimulationParameters>,
+    mut simulation: ResMut<SimulationState>,
+) {
+    script.view.lock().unwrap().update(&state);
+    for name in script.due(state.time) {
+        script.call(&name, ());
+    }
+    script.call("on_step", (state.time, state.particles.len() as f64));
+    let state = &mut *state;
+    for command in script.take_commands() {
+        // ids the stars no longer have are ignored
+        let index = |id| state.particles.index_of(id);
+        match command {
+            ScriptCommand::Spawn(star) => requests.spawn.push(star),
+            ScriptCommand::SpawnCluster(cluster) => requests
+                .spawn
+                .extend(cluster.stars(&mut rng.0, &parameters)),
+            ScriptCommand::SetPosition(id, position) => {
+                if let Some(i) = index(id) {
+                    state.particles.positions[i] = position;
+                    // jumps instead of sliding over from the old position
+                    state.previous_positions[i] = position;
+                }
+            }
+            ScriptCommand::SetVelocity(id, velocity) => {
+                if let Some(i) = index(id) {
+                    state.particles.velocities[i] = velocity;
+                }
+            }
+            ScriptCommand::SetMass(id, mass) => {
+                if let Some(i) = index(id) {
+                    state.particles.masses[i] = mass;
+                }
+            }
+            ScriptCommand::Remove(id) => requests.despawn.push(id),
+            ScriptCommand::SetTimeFactor(factor) => parameters.time_factor = factor,
+            ScriptCommand::Pause => simulation.paused = true,
+            ScriptCommand::Resume => simulation.paused = false,
+        }
+    }
+}
+
+fn event_hooks(
+    script: Res<Script>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut merges: EventReader<MergeEvent>,
+    mut escapes: EventReader<EscapeEvent>,
+    mut accretions: EventReader<AccretionEvent>,
+    mut supernovae: EventReader<SupernovaEvent>,
+) {
+    for e in collisions.iter() {
+        let p = e.position;
+        script.call("on_collision", (p.x, p.y, p.z));
+    }
+    for e in merges.iter() {
+        let p = e.position;
+        script.call("on_merge", (p.x, p.y, p.z, e.mass));
+    }
+    for e in escapes.iter() {
+        let p = e.position;
+        script.call("on_escape", (p.x, p.y, p.z));
+    }
+    for e in accretions.iter() {
+        script.call("on_accretion", (e.mass,));
+    }
+    for e in supernovae.iter() {
+        let p = e.position;
+        script.call("on_supernova", (p.x, p.y, p.z, e.mass));
+    }
+}
@@ -0,0 +1,149 @@
+//! The star the user picked: a click on a star selects it, `Tab` the next star in particle
+//! order and `Escape` clears the selection. The inspector shows the selected star.
+//!
+//! A click is a left press and release without dragging, so turning the orbit doesn't
+//! select anything. The black hole rests in the center and is never selected.
+
+use crate::{
+    camera::{cursor_ray, OrbitCamera},
+    state::{GalaxyState, ParticleIndex},
+};
+use bevy::{
+    prelude::*,
+    render::camera::{Camera, OrthographicProjection, PerspectiveProjection},
+};
+use galaxy_core::ParticleId;
+
+/// a star closer to the cursor than this many pixels on the screen is clicked
+const PICK_RADIUS: f32 = 12.0;
+/// half the size of the star cubes in render units
+const STAR_RADIUS: f32 = 1.5;
+/// a press moved further than this many pixels is a drag, not a click
+const CLICK_SLOP: f32 = 4.0;
+
+/// the selected star, `None` when nothing is selected
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Selection(pub Option<ParticleId>);
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Selection>()
+            .add_system(pick.system().label("selection"))
+            .add_system(cycle.system().label("selection"))
+            .add_system(forget_removed.system().label("selection"));
+    }
+}
+
+/// the camera and its projection, perspective or the orthographic one of a planar run
+type CameraItem<'a> = (
+    &'a Camera,
+    &'a GlobalTransform,
+    Option<&'a PerspectiveProjection>,
+    Option<&'a OrthographicProjection>,
+);
+
+#[allow(clippy::too_many_arguments)]
+fn pick(
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    mut pressed_at: Local<Option<Vec2>>,
+    interactions: Query<&Interaction>,
+    cameras: Query<CameraItem, With<OrbitCamera>>,
+    state: Res<GalaxyState>,
+    stars: Query<(&ParticleIndex, &GlobalTransform)>,
+    mut selection: ResMut<Selection>,
+) {
+    let (camera, camera_transform, perspective, orthographic) = match cameras.single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let cursor = match windows
+        .get(camera.window)
+        .and_then(|window| window.cursor_position())
+    {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    // `Shift` with the left button is the slingshot
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    if buttons.just_pressed(MouseButton::Left) {
+        let free = interactions.iter().all(|i| *i == Interaction::None);
+        *pressed_at = if free && !shift { Some(cursor) } else { None };
+    }
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    let clicked = pressed_at
+        .take()
+        .is_some_and(|pressed| pressed.distance(cursor) <= CLICK_SLOP);
+    if !clicked {
+        return;
+    }
+    let (eye, direction) = match cursor_ray(&windows, camera, camera_transform) {
+        Some(ray) => ray,
+        None => return,
+    };
+    let height = windows
+        .get(camera.window)
+        .map_or(1.0, |window| window.height());
+
+    // the stars are a few pixels small, so the ray hits a sphere around each of at least
+    // `PICK_RADIUS` pixels at its depth, the hit closest to the camera wins, the pixels of
+    // the orthographic camera of a planar run have the same size at every depth
+    let (pixel, pixel_per_depth, far) = match (perspective, orthographic) {
+        (Some(p), _) => (0.0, 2.0 * (p.fov * 0.5).tan() / height, p.far),
+        (None, Some(o)) => ((o.top - o.bottom) * o.scale / height, 0.0, o.far),
+        (None, None) => return,
+    };
+    let closest = stars
+        .iter()
+        .filter(|(index, _)| index.0 != 0)
+        .filter_map(|(index, transform)| {
+            let offset = transform.translation - eye;
+            let depth = offset.dot(direction);
+            if depth <= 0.0 || depth > far {
+                return None;
+            }
+            let miss = (offset - direction * depth).length();
+            let radius = (PICK_RADIUS * (pixel + depth * pixel_per_depth)).max(STAR_RADIUS);
+            (miss <= radius).then_some((depth, *index))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+    if let Some(id) = closest.and_then(|(_, index)| state.id(index)) {
+        info!("selected star {}", id.0);
+        selection.0 = Some(id);
+    }
+}
+
+fn cycle(keys: Res<Input<KeyCode>>, state: Res<GalaxyState>, mut selection: ResMut<Selection>) {
+    if keys.just_pressed(KeyCode::Escape) && selection.0.is_some() {
+        selection.0 = None;
+        return;
+    }
+    if !keys.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    let ids = &state.particles.ids;
+    // the star after the selected one, the black hole at index 0 is skipped
+    let next = selection
+        .0
+        .and_then(|id| state.particles.index_of(id))
+        .map_or(1, |i| i + 1);
+    let next = if next < ids.len() { next } else { 1 };
+    if let Some(&id) = ids.get(next) {
+        info!("selected star {}", id.0);
+        selection.0 = Some(id);
+    }
+}
+
+/// a merged, escaped or removed star can't stay selected
+fn forget_removed(state: Res<GalaxyState>, mut selection: ResMut<Selection>) {
+    if let Some(id) = selection.0 {
+        if state.particles.index_of(id).is_none() {
+            selection.0 = None;
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Saving and loading the running simulation as a snapshot file.
+//!
+//! `F5` saves all stars and the simulated time to the snapshot file, `F9` replaces the
+//! stars with the ones saved there. The file is `snapshot.json` unless `--snapshot` names
+//! another, `--load` starts the app from a snapshot instead of a new galaxy. A distributed
+//! node saves the black hole and its own block of the stars and doesn't load.
+//!
+//! A file ending in `.hdf5` or `.h5` is saved in the GADGET-2 HDF5 format for astronomy
+//! tools instead, those files can't be loaded back.
+
+use crate::{spawning::SpawnRequests, state::GalaxyState};
+use bevy::prelude::*;
+use galaxy_core::{distributed::Node, gadget, Snapshot};
+use std::path::PathBuf;
+
+/// snapshot file when `--snapshot` names no other
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+/// the file `F5` and `F9` save to and load from
+pub struct SnapshotFile(pub PathBuf);
+
+impl Default for SnapshotFile {
+    fn default() -> Self {
+        Self(SNAPSHOT_FILE.into())
+    }
+}
+
+impl SnapshotFile {
+    pub fn save(&self, state: &GalaxyState) -> galaxy_core::Result<()> {
+        if gadget::is_hdf5(&self.0) {
+            gadget::save(&self.0, state.time, &state.particles)
+        } else {
+            Snapshot::new(state.time, &state.particles).save(&self.0)
+        }
+    }
+}
+
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SnapshotFile>()
+            .add_system(save_and_load.system());
+    }
+}
+
+fn save_and_load(
+    keys: Res<Input<KeyCode>>,
+    file: Res<SnapshotFile>,
+    node: Option<Res<Node>>,
+    mut state: ResMut<GalaxyState>,
+    mut requests: ResMut<SpawnRequests>,
+) {
+    if keys.just_pressed(KeyCode::F5) {
+        match file.save(&state) {
+            Ok(()) => info!(
+                "saved {} stars to {}",
+                state.particles.len(),
+                file.0.display()
+            ),
+            Err(e) => error!("{}", e),
+        }
+    }
+    if keys.just_pressed(KeyCode::F9) && node.is_none() {
+        if gadget::is_hdf5(&file.0) {
+            return warn!("{} is only written, not loaded", file.0.display());
+        }
+        let snapshot = match Snapshot::load(&file.0) {
+            Ok(snapshot) => snapshot,
+            Err(e) => return error!("{}", e),
+        };
+        // all despawns are done before the spawns, so the new stars keep their order
+        requests.despawn.extend_from_slice(&state.particles.ids);
+        requests.spawn.extend(snapshot.stars.iter().copied());
+        state.time = snapshot.time;
+        info!(
+            "loaded {} stars from {}",
+            snapshot.stars.len(),
+            file.0.display()
+        );
+    }
+}
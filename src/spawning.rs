@@ -0,0 +1,162 @@
+use crate::{
+    sprites::Sprites,
+    state::{GalaxyState, ParticleIndex},
+};
+use bevy::{prelude::*, render::render_graph::base::MainPass};
+use galaxy_core::{ParticleId, Particles, Star};
+
+/// mesh and materials shared by all star entities, created once at startup, so the assets
+/// don't grow with the number of stars
+pub struct StarAssets {
+    pub mesh: Handle<Mesh>,
+    pub star_material: Handle<StandardMaterial>,
+    pub black_hole_material: Handle<StandardMaterial>,
+    /// the massless tracers, dim so the stars stand out among many of them
+    pub tracer_material: Handle<StandardMaterial>,
+    /// the stars of the `--compare` copy
+    pub comparison_material: Handle<StandardMaterial>,
+    /// the stars are drawn as sprites, not with the mesh
+    pub sprites: bool,
+}
+
+impl FromWorld for StarAssets {
+    fn from_world(world: &mut World) -> Self {
+        let sprites = world.contains_resource::<Sprites>();
+        let mesh = world
+            .get_resource_mut::<Assets<Mesh>>()
+            .expect("StarAssets needs the render plugins")
+            .add(Mesh::from(shape::Cube { size: 3.0 }));
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .expect("StarAssets needs the PBR plugin");
+        StarAssets {
+            mesh,
+            star_material: materials.add(Color::rgb(5.9, 5.9, 1.2).into()),
+            // glows by itself instead of reflecting the light
+            black_hole_material: materials.add(StandardMaterial {
+                base_color: Color::BLACK,
+                emissive: Color::rgb(2.0, 2.0, 8.0),
+                ..Default::default()
+            }),
+            tracer_material: materials.add(Color::rgb(0.4, 0.6, 0.9).into()),
+            comparison_material: materials.add(Color::rgb(1.2, 3.0, 5.9).into()),
+            sprites,
+        }
+    }
+}
+
+impl StarAssets {
+    /// the uncolored material of the particle at `index`
+    pub fn material(&self, particles: &Particles, index: usize) -> Handle<StandardMaterial> {
+        if index == 0 {
+            self.black_hole_material.clone()
+        } else if particles.is_tracer(index) {
+            self.tracer_material.clone()
+        } else {
+            self.star_material.clone()
+        }
+    }
+}
+
+/// hidden star entities ready for reuse, so spawning doesn't allocate mid-run
+#[derive(Default)]
+pub struct StarPool {
+    free: Vec<Entity>,
+}
+
+/// stars to add or remove, applied in one batch after the physics step
+#[derive(Default)]
+pub struct SpawnRequests {
+    pub spawn: Vec<Star>,
+    pub despawn: Vec<ParticleId>,
+}
+
+pub fn star_bundle(assets: &StarAssets, material: Handle<StandardMaterial>) -> PbrBundle {
+    PbrBundle {
+        mesh: assets.mesh.clone(),
+        material,
+        transform: Transform::from_xyz(0.0, 0.5, 0.0),
+        ..Default::default()
+    }
+}
+
+/// spawns a star entity of `star_bundle`
+///
+/// With sprites it has no pipelines of its own, so Bevy neither draws it nor uploads its
+/// material and transform, which would cost more than the physics for 100k stars.
+pub fn spawn_star(commands: &mut Commands, assets: &StarAssets, bundle: PbrBundle) -> Entity {
+    let mut star = commands.spawn_bundle(bundle);
+    if assets.sprites {
+        star.remove::<RenderPipelines>()
+            .remove::<Draw>()
+            .remove::<MainPass>();
+    }
+    star.id()
+}
+
+/// fills the pool with `count` hidden entities
+pub fn prewarm_pool(
+    commands: &mut Commands,
+    assets: &StarAssets,
+    pool: &mut StarPool,
+    count: usize,
+) {
+    for _ in 0..count {
+        let mut bundle = star_bundle(assets, assets.star_material.clone());
+        bundle.visible.is_visible = false;
+        pool.free.push(spawn_star(commands, assets, bundle));
+    }
+}
+
+pub fn process_spawn_requests(
+    mut commands: Commands,
+    mut requests: ResMut<SpawnRequests>,
+    mut state: ResMut<GalaxyState>,
+    mut pool: ResMut<StarPool>,
+    assets: Res<StarAssets>,
+    mut visibles: Query<&mut Visible>,
+    mut looks: Query<(&mut Transform, &mut Handle<StandardMaterial>)>,
+) {
+    if requests.spawn.is_empty() && requests.despawn.is_empty() {
+        return;
+    }
+    profiling::scope!("spawn requests");
+
+    for id in requests.despawn.drain(..) {
+        if let Some(index) = state.particles.index_of(id) {
+            let (_, entity) = state.swap_remove(index);
+            if let Some(&moved) = state.entities.get(index) {
+                commands.entity(moved).insert(ParticleIndex(index));
+            }
+            if let Ok(mut visible) = visibles.get_mut(entity) {
+                visible.is_visible = false;
+            }
+            commands.entity(entity).remove::<ParticleIndex>();
+            pool.free.push(entity);
+        }
+    }
+
+    for star in requests.spawn.drain(..) {
+        let entity = match pool.free.pop() {
+            Some(entity) => {
+                if let Ok(mut visible) = visibles.get_mut(entity) {
+                    visible.is_visible = true;
+                }
+                // a merged star may have grown and a colored one has the material of its
+                // old value
+                if let Ok((mut transform, mut material)) = looks.get_mut(entity) {
+                    transform.scale = Vec3::ONE;
+                    *material = assets.star_material.clone();
+                }
+                entity
+            }
+            None => spawn_star(
+                &mut commands,
+                &assets,
+                star_bundle(&assets, assets.star_material.clone()),
+            ),
+        };
+        let index = state.push(star, entity);
+        commands.entity(entity).insert(index);
+    }
+}
@@ -0,0 +1,282 @@
+//! Stars as glowing sprites facing the camera instead of lit cubes, for large galaxies,
+//! and the bloom around bright stars.
+//!
+//! `--sprites` draws every star entity with the star mesh as a quad in one shared mesh,
+//! with a custom pipeline that adds up a soft glow computed in the fragment shader. So the
+//! whole galaxy is a single draw call and the stars shine through each other like in
+//! photographs. The star entities stay as they are, with their cube drawing turned off:
+//! their transforms, materials and visibility still decide where a sprite is, its color
+//! and its size, so selection, coloring and merging work the same.
+//!
+//! The star colors go beyond 1, which the renderer clips. The shader tonemaps them
+//! instead, keeping the hue, and `--bloom STRENGTH` spreads the part above 1 into a wide
+//! halo around each star, also around the cubes without `--sprites`. The halos add up, so
+//! dense regions and the core glow. It is a bloom per star, not a blur of the whole
+//! frame, Bevy 0.5 has no HDR render target for that. The strength is written into the
+//! vertices with the colors, so it can change while running, e.g. from a MIDI knob.
+
+use crate::{camera::OrbitCamera, spawning::StarAssets};
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        pipeline::{
+            BlendFactor, BlendOperation, BlendState, CullMode, PipelineDescriptor,
+            PrimitiveTopology, RenderPipeline,
+        },
+        shader::{ShaderStage, ShaderStages},
+    },
+    utils::HashMap,
+};
+
+/// edge length of a sprite in render units, about twice the star cubes for the halo
+const SPRITE_SIZE: f32 = 8.0;
+/// edge length of the quads when they also hold the bloom
+const BLOOM_SIZE: f32 = 24.0;
+const ATTRIBUTE_COLOR: &str = "Vertex_Color";
+const ATTRIBUTE_BLOOM: &str = "Vertex_Bloom";
+
+const VERTEX_SHADER: &str = r#"
+#version 450
+layout(location = 0) in vec3 Vertex_Position;
+layout(location = 1) in vec2 Vertex_Uv;
+layout(location = 2) in vec3 Vertex_Color;
+layout(location = 3) in float Vertex_Bloom;
+layout(location = 0) out vec2 v_Uv;
+layout(location = 1) out vec3 v_Color;
+layout(location = 2) out float v_Bloom;
+
+layout(set = 0, binding = 0) uniform CameraViewProj {
+    mat4 ViewProj;
+};
+layout(set = 1, binding = 0) uniform Transform {
+    mat4 Model;
+};
+
+void main() {
+    v_Uv = Vertex_Uv;
+    v_Color = Vertex_Color;
+    v_Bloom = Vertex_Bloom;
+    gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+}
+"#;
+
+/// `CORE_SCALE` and `CORE` are replaced by the settings
+const FRAGMENT_SHADER: &str = r#"
+#version 450
+layout(location = 0) in vec2 v_Uv;
+layout(location = 1) in vec3 v_Color;
+layout(location = 2) in float v_Bloom;
+layout(location = 0) out vec4 o_Target;
+
+void main() {
+    float channel = max(v_Color.r, max(v_Color.g, v_Color.b));
+    // a bright core in a faint halo, fading out to its edge
+    vec2 uv = v_Uv * CORE_SCALE;
+    float r2 = dot(uv, uv);
+    float glow = (exp(-16.0 * r2) + 0.2 * exp(-4.0 * r2)) * max(1.0 - r2, 0.0);
+    vec3 core = CORE * glow * v_Color / max(channel, 1.0);
+    // what would clip spreads over the whole quad
+    float q2 = dot(v_Uv, v_Uv);
+    vec3 excess = max(v_Color - vec3(1.0), vec3(0.0));
+    vec3 bloom = v_Bloom * excess * exp(-6.0 * q2) * max(1.0 - q2, 0.0);
+    o_Target = vec4(core + bloom, 1.0);
+}
+"#;
+
+/// the stars are only drawn as sprites when this resource exists
+pub struct Sprites;
+
+/// strength of the bloom, without `Sprites` only the halos are drawn
+///
+/// The quads are only large enough for the halos when it exists at the start, it can be
+/// changed afterwards.
+pub struct Bloom(pub f32);
+
+/// the entity with the mesh of all sprites
+struct StarSprites;
+
+/// edge length of the quads in render units
+struct QuadSize(f32);
+
+pub struct SpritesPlugin;
+
+impl Plugin for SpritesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system(update.system().after("sync").after("camera"));
+    }
+}
+
+/// a quad for each center, size and color, facing the camera with `right` and `up`
+///
+/// The buffers of `mesh` are reused while the number of quads stays the same, only the
+/// positions, colors and the bloom are written, so 100k stars don't allocate every frame.
+fn write_quads(
+    mesh: &mut Mesh,
+    quads: &[(Vec3, f32, [f32; 3])],
+    right: Vec3,
+    up: Vec3,
+    bloom: f32,
+) {
+    const CORNERS: [[f32; 2]; 4] = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+    let vertices = 4 * quads.len();
+    let reusable = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .is_some_and(|positions| positions.len() == vertices);
+    if !reusable {
+        let uvs: Vec<[f32; 2]> = (0..vertices).map(|v| CORNERS[v % 4]).collect();
+        let indices = (0..quads.len() as u32)
+            .flat_map(|i| {
+                let first = 4 * i;
+                [first, first + 1, first + 2, first, first + 2, first + 3]
+            })
+            .collect();
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0f32; 3]; vertices]);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_attribute(
+            ATTRIBUTE_COLOR,
+            VertexAttributeValues::from(vec![[0.0f32; 3]; vertices]),
+        );
+        mesh.set_attribute(
+            ATTRIBUTE_BLOOM,
+            VertexAttributeValues::from(vec![0.0f32; vertices]),
+        );
+        mesh.set_indices(Some(Indices::U32(indices)));
+    }
+    if let Some(VertexAttributeValues::Float3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    {
+        for (corners, &(center, size, _)) in positions.chunks_exact_mut(4).zip(quads) {
+            let half = size / 2.0;
+            for (corner, [x, y]) in corners.iter_mut().zip(CORNERS) {
+                *corner = (center + (right * x + up * y) * half).into();
+            }
+        }
+    }
+    if let Some(VertexAttributeValues::Float3(colors)) = mesh.attribute_mut(ATTRIBUTE_COLOR) {
+        for (corners, &(_, _, color)) in colors.chunks_exact_mut(4).zip(quads) {
+            corners.fill(color);
+        }
+    }
+    if let Some(VertexAttributeValues::Float(values)) = mesh.attribute_mut(ATTRIBUTE_BLOOM) {
+        values.fill(bloom);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    sprites: Option<Res<Sprites>>,
+    bloom: Option<Res<Bloom>>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let size = if bloom.is_some() {
+        BLOOM_SIZE
+    } else {
+        SPRITE_SIZE
+    };
+    let fragment = FRAGMENT_SHADER
+        .replace("CORE_SCALE", &format!("{:.3}", size / SPRITE_SIZE))
+        .replace("CORE", if sprites.is_some() { "1.0" } else { "0.0" });
+    let mut pipeline = PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, VERTEX_SHADER)),
+        fragment: Some(shaders.add(Shader::from_glsl(ShaderStage::Fragment, &fragment))),
+    });
+    // the glow adds up, so the sprites don't need to be sorted or hide each other
+    pipeline.color_target_states[0].color_blend = BlendState {
+        src_factor: BlendFactor::One,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    };
+    if let Some(depth) = &mut pipeline.depth_stencil {
+        depth.depth_write_enabled = false;
+    }
+    pipeline.primitive.cull_mode = CullMode::None;
+
+    commands
+        .spawn_bundle(MeshBundle {
+            // a black placeholder until the first update, empty buffers can't be drawn
+            mesh: meshes.add({
+                let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+                write_quads(
+                    &mut mesh,
+                    &[(Vec3::ZERO, 0.0, [0.0; 3])],
+                    Vec3::X,
+                    Vec3::Y,
+                    0.0,
+                );
+                mesh
+            }),
+            render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                pipelines.add(pipeline),
+            )]),
+            visible: Visible {
+                is_visible: true,
+                // after the opaque meshes, which hide the stars behind them
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(StarSprites);
+    commands.insert_resource(QuadSize(size));
+}
+
+/// the color of a star material, beyond 1 for bright ones
+fn sprite_color(material: &StandardMaterial) -> [f32; 3] {
+    let (base, glow) = (material.base_color, material.emissive);
+    [
+        base.r() + glow.r(),
+        base.g() + glow.g(),
+        base.b() + glow.b(),
+    ]
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update(
+    size: Res<QuadSize>,
+    bloom: Option<Res<Bloom>>,
+    assets: Res<StarAssets>,
+    materials: Res<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut quads: Local<Vec<(Vec3, f32, [f32; 3])>>,
+    cameras: Query<&Transform, With<OrbitCamera>>,
+    stars: Query<(
+        &Handle<Mesh>,
+        &Handle<StandardMaterial>,
+        &Transform,
+        &Visible,
+    )>,
+    sprites: Query<&Handle<Mesh>, With<StarSprites>>,
+) {
+    profiling::scope!("sprites");
+    let camera = match cameras.iter().next() {
+        Some(camera) => camera.rotation,
+        None => return,
+    };
+    // there are only a few star materials, but they may change, by id because cloning a
+    // handle costs a message
+    let mut colors = HashMap::default();
+    quads.clear();
+    quads.extend(
+        stars
+            .iter()
+            .filter(|(mesh, _, _, visible)| **mesh == assets.mesh && visible.is_visible)
+            .map(|(_, material, transform, _)| {
+                let color = *colors
+                    .entry(material.id)
+                    .or_insert_with(|| materials.get(material).map_or([1.0; 3], sprite_color));
+                (transform.translation, size.0 * transform.scale.x, color)
+            }),
+    );
+    if quads.is_empty() {
+        return;
+    }
+    let bloom = bloom.map_or(0.0, |bloom| bloom.0);
+    for handle in sprites.iter() {
+        if let Some(mesh) = meshes.get_mut(handle) {
+            write_quads(mesh, &quads, camera * Vec3::X, camera * Vec3::Y, bloom);
+        }
+    }
+}
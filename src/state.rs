@@ -0,0 +1,87 @@
+use bevy::{math::DVec3, prelude::*};
+use galaxy_core::{ParticleId, Particles, Star};
+use rand::rngs::StdRng;
+use std::time::Duration;
+
+/// current index of a star entity in the `GalaxyState` arrays, kept up to date on removals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParticleIndex(pub usize);
+
+/// the galaxy of `SimulationParameters::galaxies` a star entity started in, by index, stars
+/// launched or spawned later have none
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GalaxyId(pub usize);
+
+/// stars to start with instead of the generated galaxy, the first one is the black hole
+pub struct InitialStars(pub Vec<Star>);
+
+/// simulated seconds to start at, from a loaded snapshot
+pub struct InitialTime(pub f64);
+
+/// the random numbers of the app, from the seed of the run when there is one, so a seeded
+/// run starts with the same galaxy and draws the same random events
+pub struct GalaxyRng(pub StdRng);
+
+/// run control of the simulation
+#[derive(Debug, Default)]
+pub struct SimulationState {
+    pub paused: bool,
+    /// physics steps still to take while paused, one per press of `N`
+    pub single_steps: u32,
+    /// the last physics tick advanced the stars, set by `moving` for the systems after it
+    pub stepped: bool,
+}
+
+impl SimulationState {
+    /// whether the next physics tick advances the stars, consuming a single step when paused
+    pub fn take_step(&mut self) -> bool {
+        self.stepped = if !self.paused {
+            true
+        } else if self.single_steps > 0 {
+            self.single_steps -= 1;
+            true
+        } else {
+            false
+        };
+        self.stepped
+    }
+}
+
+/// solver side state of all stars, updated in place by the physics, the star entities only
+/// carry their index into it
+#[derive(Default)]
+pub struct GalaxyState {
+    pub particles: Particles,
+    /// positions at the end of the previous physics step, for render interpolation
+    pub previous_positions: Vec<DVec3>,
+    /// entity of each particle, same order as the particle arrays
+    pub entities: Vec<Entity>,
+    /// physics steps done so far
+    pub steps: u64,
+    /// simulated seconds so far
+    pub time: f64,
+    /// wall clock time spent in the physics steps so far
+    pub step_time: Duration,
+}
+
+impl GalaxyState {
+    pub fn push(&mut self, star: Star, entity: Entity) -> ParticleIndex {
+        let index = ParticleIndex(self.entities.len());
+        self.previous_positions.push(star.position);
+        self.entities.push(entity);
+        self.particles.push(star);
+        index
+    }
+
+    /// id of the particle a star entity stands for
+    pub fn id(&self, index: ParticleIndex) -> Option<ParticleId> {
+        self.particles.ids.get(index.0).copied()
+    }
+
+    /// removes the particle at `index` from all arrays, the last particle takes its place
+    pub fn swap_remove(&mut self, index: usize) -> (Star, Entity) {
+        self.previous_positions.swap_remove(index);
+        let entity = self.entities.swap_remove(index);
+        (self.particles.swap_remove(index), entity)
+    }
+}
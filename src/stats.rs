@@ -0,0 +1,182 @@
+//! Performance and progress at a glance, `F3` shows and hides the statistics.
+//!
+//! The overlay shows the rendered frames per second, the wall clock time of a physics
+//! step, the number of stars still moving, the simulated time in millions of years and the
+//! energy drift since the overlay first measured it. The energy is an O(n²) sum, so it is
+//! measured once a second while the overlay is shown, and not at all above
+//! `DIRECT_SUM_STARS` stars. The massless tracers are counted on their own line. With the `[[galaxies]]` of the config it also counts the moving
+//! stars and their mass for each galaxy they started in. The text needs the UI font.
+
+use crate::{
+    state::{GalaxyId, GalaxyState},
+    DIRECT_SUM_STARS, UI_FONT,
+};
+use bevy::{
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+use galaxy_core::{
+    diagnostics::total_energy,
+    units::{Mass, TimeSpan},
+    SimulationParameters,
+};
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+/// wall clock seconds between the updates of the text
+const REFRESH: f64 = 1.0;
+
+#[derive(Default)]
+struct StatsLog {
+    /// wall clock seconds of the last update
+    refreshed: Option<f64>,
+    /// physics steps and the time spent in them at the last update
+    steps: u64,
+    step_time: Duration,
+    initial_energy: Option<f64>,
+}
+
+struct StatsText;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
+            .init_resource::<StatsLog>()
+            .add_startup_system(setup.system())
+            .add_system(toggle.system())
+            .add_system(update_text.system());
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mut text = TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(20.0),
+                top: Val::Percent(35.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: asset_server.load(UI_FONT),
+                font_size: 16.0,
+                color: Color::WHITE,
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    };
+    text.visible.is_visible = false;
+    commands.spawn_bundle(text).insert(StatsText);
+}
+
+fn toggle(
+    keys: Res<Input<KeyCode>>,
+    mut log: ResMut<StatsLog>,
+    mut texts: Query<&mut Visible, With<StatsText>>,
+) {
+    if !keys.just_pressed(KeyCode::F3) {
+        return;
+    }
+    for mut visible in texts.iter_mut() {
+        visible.is_visible = !visible.is_visible;
+    }
+    // shown again, the text updates right away
+    log.refreshed = None;
+}
+
+fn update_text(
+    time: Res<Time>,
+    diagnostics: Res<Diagnostics>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+    mut log: ResMut<StatsLog>,
+    galaxies: Query<&GalaxyId>,
+    mut texts: Query<(&mut Text, &Visible), With<StatsText>>,
+) {
+    let now = time.seconds_since_startup();
+    if log
+        .refreshed
+        .is_some_and(|refreshed| now - refreshed < REFRESH)
+    {
+        return;
+    }
+    let mut text = match texts.iter_mut().find(|(_, visible)| visible.is_visible) {
+        Some((text, _)) => text,
+        None => return,
+    };
+    log.refreshed = Some(now);
+
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.average())
+        .unwrap_or_default();
+    // the average over the steps since the last update, a loaded snapshot may go back
+    let steps = state.steps.saturating_sub(log.steps);
+    let step_time = match steps {
+        0 => "-".to_string(),
+        _ => format!(
+            "{:.2} ms",
+            state.step_time.saturating_sub(log.step_time).as_secs_f64() * 1000.0 / steps as f64
+        ),
+    };
+    log.steps = state.steps;
+    log.step_time = state.step_time;
+
+    let particles = &state.particles;
+    // the black hole isn't a star
+    let (tracers, stars): (Vec<usize>, Vec<usize>) = (1..particles.len())
+        .filter(|&i| !particles.frozen[i])
+        .partition(|&i| particles.is_tracer(i));
+    let drift = if particles.len() <= DIRECT_SUM_STARS {
+        let energy = total_energy(particles, &parameters);
+        let initial = *log.initial_energy.get_or_insert(energy);
+        match initial {
+            initial if initial != 0.0 => {
+                format!("{:+.4}%", (energy - initial) / initial.abs() * 100.0)
+            }
+            _ => "-".to_string(),
+        }
+    } else {
+        "- (too many stars)".to_string()
+    };
+    text.sections[0].value = format!(
+        "{:.0} fps\n\
+         physics step {}\n\
+         {} stars\n\
+         {} simulated\n\
+         energy drift {}",
+        fps,
+        step_time,
+        stars.len(),
+        TimeSpan(state.time),
+        drift,
+    );
+    if !tracers.is_empty() {
+        let _ = write!(text.sections[0].value, "\n{} tracers", tracers.len());
+    }
+
+    let mut per_galaxy = BTreeMap::new();
+    for (i, &entity) in state.entities.iter().enumerate() {
+        let moving = !particles.frozen[i] && !particles.is_tracer(i);
+        if let (true, Ok(galaxy)) = (moving, galaxies.get(entity)) {
+            let (stars, mass) = per_galaxy.entry(galaxy.0).or_insert((0, 0.0));
+            *stars += 1;
+            *mass += particles.masses[i];
+        }
+    }
+    for (galaxy, (stars, mass)) in per_galaxy {
+        let _ = write!(
+            text.sections[0].value,
+            "\ngalaxy {}: {} stars, {}",
+            galaxy,
+            stars,
+            Mass(mass)
+        );
+    }
+}
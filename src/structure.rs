@@ -0,0 +1,176 @@
+//! Follows the radial structure of the galaxy over time, for studying core collapse and
+//! relaxation quantitatively.
+//!
+//! `--structure structure.csv` computes the core radius, the core density and the 10%,
+//! 50% and 90% Lagrangian radii every `--structure-every` physics steps, 60 by
+//! default, and appends them to the file. A panel in the lower right corner plots the
+//! radii of the last samples: the core radius in red, the Lagrangian radii in yellow, white
+//! and blue from the inside out.
+
+use crate::state::GalaxyState;
+use bevy::prelude::*;
+use galaxy_core::diagnostics::{structure, Structure};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// samples shown in the plot
+const PLOT_SAMPLES: usize = 200;
+const PLOT_WIDTH: f32 = 400.0;
+const PLOT_HEIGHT: f32 = 150.0;
+const SERIES_COLORS: [Color; 4] = [
+    Color::rgb(1.0, 0.3, 0.3),
+    Color::rgb(1.0, 0.9, 0.3),
+    Color::WHITE,
+    Color::rgb(0.4, 0.6, 1.0),
+];
+
+/// the file the samples are appended to and the recent samples for the plot
+pub struct StructureLog {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// physics steps between the samples
+    interval: u64,
+    next_step: u64,
+    history: VecDeque<Structure>,
+    /// samples taken so far, tells the plot when to update
+    samples: usize,
+}
+
+impl StructureLog {
+    pub fn create(path: &Path, interval: u64) -> galaxy_core::Result<Self> {
+        let mut file =
+            BufWriter::new(File::create(path).map_err(|e| galaxy_core::Error::io(path, e))?);
+        writeln!(
+            file,
+            "time,step,core_radius,core_density,lagrangian_10,half_mass_radius,lagrangian_90"
+        )
+        .map_err(|e| galaxy_core::Error::io(path, e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            interval: interval.max(1),
+            next_step: 0,
+            history: VecDeque::with_capacity(PLOT_SAMPLES),
+            samples: 0,
+        })
+    }
+
+    fn write(&mut self, time: f64, step: u64, s: &Structure) -> std::io::Result<()> {
+        let [r10, r50, r90] = s.lagrangian_radii;
+        writeln!(
+            self.file,
+            "{},{},{},{},{},{},{}",
+            time, step, s.core_radius, s.core_density, r10, r50, r90
+        )?;
+        self.file.flush()
+    }
+}
+
+/// one point of the plot
+struct Dot {
+    series: usize,
+    sample: usize,
+}
+
+pub struct StructurePlugin;
+
+impl Plugin for StructurePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_plot.system())
+            .add_system_to_stage(crate::PHYSICS_STAGE, sample.system().after("moving"))
+            .add_system(update_plot.system());
+    }
+}
+
+fn setup_plot(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    let series: Vec<Handle<ColorMaterial>> = SERIES_COLORS
+        .iter()
+        .map(|&color| materials.add(color.into()))
+        .collect();
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(PLOT_WIDTH), Val::Px(PLOT_HEIGHT)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.1, 0.1, 0.2, 0.6).into()),
+            ..Default::default()
+        })
+        .with_children(|panel| {
+            for (i, material) in series.iter().enumerate() {
+                for sample in 0..PLOT_SAMPLES {
+                    let mut dot = NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            size: Size::new(Val::Px(2.0), Val::Px(2.0)),
+                            ..Default::default()
+                        },
+                        material: material.clone(),
+                        ..Default::default()
+                    };
+                    dot.visible.is_visible = false;
+                    panel.spawn_bundle(dot).insert(Dot { series: i, sample });
+                }
+            }
+        });
+}
+
+fn sample(mut log: ResMut<StructureLog>, state: Res<GalaxyState>) {
+    // paused steps don't count
+    if state.steps < log.next_step {
+        return;
+    }
+    log.next_step = state.steps + log.interval;
+    let s = structure(&state.particles);
+    if let Err(e) = log.write(state.time, state.steps, &s) {
+        error!("can't write {}: {}", log.path.display(), e);
+    }
+    if log.history.len() == PLOT_SAMPLES {
+        log.history.pop_front();
+    }
+    log.history.push_back(s);
+    log.samples += 1;
+}
+
+fn update_plot(
+    log: Res<StructureLog>,
+    mut plotted: Local<usize>,
+    mut dots: Query<(&Dot, &mut Style, &mut Visible)>,
+) {
+    if *plotted == log.samples {
+        return;
+    }
+    *plotted = log.samples;
+    let values = |s: &Structure| {
+        let [r10, r50, r90] = s.lagrangian_radii;
+        [s.core_radius, r10, r50, r90]
+    };
+    let max = log
+        .history
+        .iter()
+        .flat_map(values)
+        .fold(0.0f64, f64::max)
+        .max(f64::MIN_POSITIVE);
+    for (dot, mut style, mut visible) in dots.iter_mut() {
+        let s = match log.history.get(dot.sample) {
+            Some(s) => s,
+            None => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+        visible.is_visible = true;
+        style.position.left = Val::Px(dot.sample as f32 / PLOT_SAMPLES as f32 * PLOT_WIDTH);
+        style.position.bottom = Val::Px((values(s)[dot.series] / max) as f32 * (PLOT_HEIGHT - 2.0));
+    }
+}
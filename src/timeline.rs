@@ -0,0 +1,426 @@
+//! Timeline of the simulation events in a panel in the upper left corner.
+//!
+//! `--timeline` records every merger, supernova, escape and accretion with the
+//! simulated time it happened at. The newest are on top, the mouse wheel over the panel
+//! scrolls back. Clicking an entry points the camera at the place of the event, clicking
+//! it again hands the camera back. The rows are colored by the kind of event, their text
+//! needs the UI font.
+//!
+//! With `--record` the entries are also written to a CSV file next to the trajectory,
+//! see `events_path`. A replay runs no physics and has no events of its own, it shows the
+//! entries of that file instead, and clicking one also seeks the replay to its time.
+
+use crate::{
+    events::{AccretionEvent, EscapeEvent, MergeEvent, SupernovaEvent},
+    render_frame::RenderFrame,
+    replay::Replay,
+    state::GalaxyState,
+    UI_FONT,
+};
+use bevy::{
+    input::mouse::MouseWheel, math::DVec3, prelude::*, render::camera::PerspectiveProjection,
+};
+use galaxy_core::{units::TimeSpan, Error};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// entries shown at once
+const ROWS: usize = 12;
+/// older entries are dropped
+const MAX_ENTRIES: usize = 1000;
+/// camera distance from a focused event in render units
+const FOCUS_DISTANCE: f32 = 150.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Merger,
+    Supernova,
+    Escape,
+    Accretion,
+}
+
+const KINDS: [Kind; 4] = [Kind::Merger, Kind::Supernova, Kind::Escape, Kind::Accretion];
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Merger => "merger",
+            Kind::Supernova => "supernova",
+            Kind::Escape => "escape",
+            Kind::Accretion => "accretion",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Kind::Merger => Color::rgba(0.6, 0.3, 0.1, 0.8),
+            Kind::Supernova => Color::rgba(0.6, 0.1, 0.1, 0.8),
+            Kind::Escape => Color::rgba(0.1, 0.2, 0.5, 0.8),
+            Kind::Accretion => Color::rgba(0.3, 0.1, 0.5, 0.8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    kind: Kind,
+    /// simulated seconds
+    time: f64,
+    position: DVec3,
+}
+
+#[derive(Default)]
+struct Timeline {
+    entries: Vec<Entry>,
+    /// entries scrolled back from the newest
+    offset: usize,
+    /// index of the entry the camera looks at
+    focus: Option<usize>,
+    /// the rows need an update
+    changed: bool,
+}
+
+impl Timeline {
+    /// index of the entry shown in `row`
+    fn entry_index(&self, row: usize) -> Option<usize> {
+        let back = self.offset + row;
+        if back < self.entries.len() {
+            Some(self.entries.len() - 1 - back)
+        } else {
+            None
+        }
+    }
+}
+
+/// the event file of the trajectory `--record` writes, e.g. `run.traj.events.csv`
+pub fn events_path(trajectory: &Path) -> PathBuf {
+    let mut path = trajectory.as_os_str().to_owned();
+    path.push(".events.csv");
+    path.into()
+}
+
+/// where the entries are written to or read from
+pub enum TimelineFile {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// the event file being written, `None` after an error
+struct EventWriter(Option<BufWriter<File>>);
+
+/// the entries of an event file with the columns kind, time, x, y, z
+fn read_entries(path: &Path) -> galaxy_core::Result<Vec<Entry>> {
+    let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    text.lines()
+        .enumerate()
+        .skip(1)
+        .map(|(line, row)| {
+            let parse_error = || Error::Parse {
+                path: path.to_path_buf(),
+                message: format!("line {}: expected kind, time, x, y and z", line + 1),
+            };
+            let mut columns = row.split(',').map(str::trim);
+            let kind = columns.next().ok_or_else(parse_error)?;
+            let kind = KINDS
+                .iter()
+                .copied()
+                .find(|k| k.name() == kind)
+                .ok_or_else(parse_error)?;
+            let numbers: Vec<f64> = columns
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| parse_error())?;
+            match numbers[..] {
+                [time, x, y, z] => Ok(Entry {
+                    kind,
+                    time,
+                    position: DVec3::new(x, y, z),
+                }),
+                _ => Err(parse_error()),
+            }
+        })
+        .collect()
+}
+
+fn write_entries(writer: &mut BufWriter<File>, entries: &[Entry]) -> std::io::Result<()> {
+    for entry in entries {
+        let p = entry.position;
+        writeln!(
+            writer,
+            "{},{:e},{:e},{:e},{:e}",
+            entry.kind.name(),
+            entry.time,
+            p.x,
+            p.y,
+            p.z
+        )?;
+    }
+    // events are rare, the file is complete whenever the app stops
+    writer.flush()
+}
+
+struct Row(usize);
+
+/// the panel and its rows, the wheel scrolls while one of them is hovered
+struct ScrollArea;
+
+struct KindMaterials(Vec<(Kind, Handle<ColorMaterial>)>, Handle<ColorMaterial>);
+
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Timeline>()
+            .add_startup_system(setup.system())
+            .add_startup_system(open_file.system())
+            .add_system(record.system())
+            .add_system(scroll.system())
+            .add_system(click.system())
+            .add_system(update_rows.system())
+            .add_system(focus_camera.system().after("camera"));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let kinds = KINDS
+        .iter()
+        .map(|&kind| (kind, materials.add(kind.color().into())))
+        .collect();
+    let empty = materials.add(Color::NONE.into());
+    let font = asset_server.load(UI_FONT);
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(20.0),
+                    top: Val::Px(20.0),
+                    ..Default::default()
+                },
+                // the ui grows upwards, reversed the first row is on top
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: empty.clone(),
+            ..Default::default()
+        })
+        .insert(ScrollArea)
+        .insert(Interaction::default())
+        .with_children(|panel| {
+            for row in 0..ROWS {
+                panel
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(260.0), Val::Px(22.0)),
+                            margin: Rect {
+                                bottom: Val::Px(2.0),
+                                ..Default::default()
+                            },
+                            padding: Rect {
+                                left: Val::Px(6.0),
+                                ..Default::default()
+                            },
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: empty.clone(),
+                        ..Default::default()
+                    })
+                    .insert(Row(row))
+                    .insert(ScrollArea)
+                    .with_children(|button| {
+                        button.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font.clone(),
+                                    font_size: 16.0,
+                                    color: Color::WHITE,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
+        });
+    commands.insert_resource(KindMaterials(kinds, empty));
+}
+
+fn open_file(
+    mut commands: Commands,
+    file: Option<Res<TimelineFile>>,
+    mut timeline: ResMut<Timeline>,
+) {
+    match file.as_deref() {
+        Some(TimelineFile::Record(path)) => {
+            let writer = File::create(path).and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                writeln!(writer, "kind,time,x,y,z")?;
+                Ok(writer)
+            });
+            match writer {
+                Ok(writer) => commands.insert_resource(EventWriter(Some(writer))),
+                Err(e) => error!("can't write the events to {}: {}", path.display(), e),
+            }
+        }
+        Some(TimelineFile::Replay(path)) if path.exists() => match read_entries(path) {
+            Ok(entries) => {
+                timeline.entries = entries;
+                timeline.changed = true;
+            }
+            Err(e) => error!("can't read the events: {}", e),
+        },
+        Some(TimelineFile::Replay(path)) => {
+            info!("no events recorded in {}", path.display())
+        }
+        None => {}
+    }
+}
+
+fn record(
+    mut timeline: ResMut<Timeline>,
+    mut writer: Option<ResMut<EventWriter>>,
+    state: Res<GalaxyState>,
+    mut merges: EventReader<MergeEvent>,
+    mut supernovae: EventReader<SupernovaEvent>,
+    mut escapes: EventReader<EscapeEvent>,
+    mut accretions: EventReader<AccretionEvent>,
+) {
+    let time = state.time;
+    let entries = merges
+        .iter()
+        .map(|e| (Kind::Merger, e.position))
+        .chain(supernovae.iter().map(|e| (Kind::Supernova, e.position)))
+        .chain(escapes.iter().map(|e| (Kind::Escape, e.position)))
+        // the black hole is pinned to the center
+        .chain(
+            accretions
+                .iter()
+                .map(|_| (Kind::Accretion, DVec3::default())),
+        )
+        .map(|(kind, position)| Entry {
+            kind,
+            time,
+            position,
+        })
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return;
+    }
+    if let Some(EventWriter(file)) = writer.as_deref_mut() {
+        if let Some(e) = file.as_mut().and_then(|f| write_entries(f, &entries).err()) {
+            error!("event recording stopped: {}", e);
+            *file = None;
+        }
+    }
+    let timeline = &mut *timeline;
+    timeline.entries.extend(entries);
+    let excess = timeline.entries.len().saturating_sub(MAX_ENTRIES);
+    if excess > 0 {
+        timeline.entries.drain(..excess);
+        timeline.focus = timeline.focus.and_then(|i| i.checked_sub(excess));
+    }
+    timeline.changed = true;
+}
+
+fn scroll(
+    mut timeline: ResMut<Timeline>,
+    mut wheel: EventReader<MouseWheel>,
+    hovered: Query<&Interaction, With<ScrollArea>>,
+) {
+    let lines: f32 = wheel.iter().map(|e| e.y).sum();
+    if lines == 0.0 || hovered.iter().all(|i| *i == Interaction::None) {
+        return;
+    }
+    let last = timeline.entries.len().saturating_sub(ROWS);
+    // up goes back in time
+    let offset = (timeline.offset as f32 + lines.signum()).clamp(0.0, last as f32);
+    timeline.offset = offset as usize;
+    timeline.changed = true;
+}
+
+fn click(
+    mut timeline: ResMut<Timeline>,
+    mut replay: Option<ResMut<Replay>>,
+    rows: Query<(&Row, &Interaction), Changed<Interaction>>,
+) {
+    for (row, interaction) in rows.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if let Some(i) = timeline.entry_index(row.0) {
+            timeline.focus = if timeline.focus == Some(i) {
+                None
+            } else {
+                if let Some(replay) = &mut replay {
+                    replay.seek(timeline.entries[i].time);
+                }
+                Some(i)
+            };
+            timeline.changed = true;
+        }
+    }
+}
+
+fn update_rows(
+    mut timeline: ResMut<Timeline>,
+    materials: Res<KindMaterials>,
+    mut rows: Query<(&Row, &Children, &mut Handle<ColorMaterial>)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !timeline.changed {
+        return;
+    }
+    timeline.changed = false;
+    for (row, children, mut material) in rows.iter_mut() {
+        let entry = timeline
+            .entry_index(row.0)
+            .map(|i| (i, timeline.entries[i]));
+        *material = match entry {
+            Some((_, entry)) => materials
+                .0
+                .iter()
+                .find(|(kind, _)| *kind == entry.kind)
+                .map_or(materials.1.clone(), |(_, material)| material.clone()),
+            None => materials.1.clone(),
+        };
+        let value = match entry {
+            Some((i, entry)) => format!(
+                "{}{}  {}",
+                if timeline.focus == Some(i) { "> " } else { "" },
+                TimeSpan(entry.time),
+                entry.kind.name()
+            ),
+            None => String::new(),
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = value.clone();
+            }
+        }
+    }
+}
+
+fn focus_camera(
+    timeline: Res<Timeline>,
+    frame: Res<RenderFrame>,
+    mut cameras: Query<&mut Transform, With<PerspectiveProjection>>,
+) {
+    let entry = match timeline.focus.and_then(|i| timeline.entries.get(i)) {
+        Some(entry) => entry,
+        None => return,
+    };
+    if let Ok(mut camera) = cameras.single_mut() {
+        let target = frame.render_position(entry.position);
+        camera.translation = target + Vec3::new(0.0, -1.0, 1.0) * FOCUS_DISTANCE;
+        camera.look_at(target, Vec3::Z);
+    }
+}
@@ -0,0 +1,215 @@
+//! Fading trails behind the stars, so orbits and tidal tails stay visible.
+//!
+//! `--trails <length>` keeps the last `length` physics positions of the chosen stars
+//! in a ring buffer each and draws them as polylines that fade out towards the oldest
+//! position. `T` gives the selected star a trail or takes it away, `Shift+T` switches
+//! trails for all stars, which costs memory and time with the length and the star count.
+//! Trails of merged or removed stars disappear with them.
+
+use crate::{
+    render_frame::RenderFrame,
+    selection::Selection,
+    state::{GalaxyState, SimulationState},
+};
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::VertexAttributeValues,
+        pipeline::{
+            BlendFactor, BlendOperation, BlendState, PipelineDescriptor, PrimitiveTopology,
+            RenderPipeline,
+        },
+        shader::{ShaderStage, ShaderStages},
+    },
+    utils::{HashMap, HashSet},
+};
+use galaxy_core::{DVec3, ParticleId};
+use std::collections::VecDeque;
+
+/// color of the newest part of a trail
+const TRAIL_COLOR: [f32; 3] = [0.5, 0.7, 1.0];
+const ATTRIBUTE_COLOR: &str = "Vertex_Color";
+
+const VERTEX_SHADER: &str = r#"
+#version 450
+layout(location = 0) in vec3 Vertex_Position;
+layout(location = 1) in vec3 Vertex_Color;
+layout(location = 0) out vec3 v_Color;
+
+layout(set = 0, binding = 0) uniform CameraViewProj {
+    mat4 ViewProj;
+};
+layout(set = 1, binding = 0) uniform Transform {
+    mat4 Model;
+};
+
+void main() {
+    v_Color = Vertex_Color;
+    gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+#version 450
+layout(location = 0) in vec3 v_Color;
+layout(location = 0) out vec4 o_Target;
+
+void main() {
+    o_Target = vec4(v_Color, 1.0);
+}
+"#;
+
+/// which stars leave a trail and their recent positions
+pub struct Trails {
+    /// positions per trail
+    pub length: usize,
+    /// every star has a trail, not only the chosen ones
+    pub all: bool,
+    chosen: HashSet<ParticleId>,
+    /// the step a trail was extended last and its positions, the newest at the back
+    history: HashMap<ParticleId, (u64, VecDeque<DVec3>)>,
+}
+
+impl Trails {
+    pub fn new(length: usize) -> Self {
+        Self {
+            length: length.max(2),
+            all: false,
+            chosen: HashSet::default(),
+            history: HashMap::default(),
+        }
+    }
+}
+
+/// the entity with the mesh of all trails
+struct TrailLines;
+
+pub struct TrailsPlugin;
+
+impl Plugin for TrailsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system_to_stage(
+                crate::PHYSICS_STAGE,
+                record.system().after("moving").after("spawn"),
+            )
+            .add_system(toggle.system().after("selection"))
+            .add_system(draw.system().after("sync"));
+    }
+}
+
+/// unconnected line segments, two positions and colors each
+fn trail_mesh(positions: Vec<[f32; 3]>, colors: Vec<[f32; 3]>) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(ATTRIBUTE_COLOR, VertexAttributeValues::from(colors));
+    mesh
+}
+
+fn setup(
+    mut commands: Commands,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mut pipeline = PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, VERTEX_SHADER)),
+        fragment: Some(shaders.add(Shader::from_glsl(ShaderStage::Fragment, FRAGMENT_SHADER))),
+    });
+    // added up the faded parts vanish into the background
+    pipeline.color_target_states[0].color_blend = BlendState {
+        src_factor: BlendFactor::One,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    };
+    if let Some(depth) = &mut pipeline.depth_stencil {
+        depth.depth_write_enabled = false;
+    }
+    pipeline.primitive.topology = PrimitiveTopology::LineList;
+
+    let mut bundle = MeshBundle {
+        // a placeholder until the first trail, empty buffers can't be drawn
+        mesh: meshes.add(trail_mesh(vec![[0.0; 3]; 2], vec![[0.0; 3]; 2])),
+        render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+            pipelines.add(pipeline),
+        )]),
+        ..Default::default()
+    };
+    bundle.visible.is_visible = false;
+    bundle.visible.is_transparent = true;
+    commands.spawn_bundle(bundle).insert(TrailLines);
+}
+
+fn toggle(keys: Res<Input<KeyCode>>, selection: Res<Selection>, mut trails: ResMut<Trails>) {
+    if !keys.just_pressed(KeyCode::T) {
+        return;
+    }
+    if keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift) {
+        trails.all = !trails.all;
+        info!(
+            "trails for {} stars",
+            if trails.all { "all" } else { "the chosen" }
+        );
+    } else if let Some(id) = selection.0 {
+        if !trails.chosen.remove(&id) {
+            trails.chosen.insert(id);
+        }
+    }
+}
+
+/// adds the positions after a physics step
+fn record(mut trails: ResMut<Trails>, state: Res<GalaxyState>, simulation: Res<SimulationState>) {
+    if !simulation.stepped {
+        return;
+    }
+    let trails = &mut *trails;
+    let particles = &state.particles;
+    for (&id, &position) in particles.ids.iter().zip(&particles.positions).skip(1) {
+        if !trails.all && !trails.chosen.contains(&id) {
+            continue;
+        }
+        let (extended, positions) = trails.history.entry(id).or_default();
+        *extended = state.steps;
+        if positions.len() == trails.length {
+            positions.pop_front();
+        }
+        positions.push_back(position);
+    }
+    // merged, removed and no longer chosen stars weren't extended
+    let step = state.steps;
+    trails.history.retain(|_, (extended, _)| *extended == step);
+    let history = &trails.history;
+    trails.chosen.retain(|id| history.contains_key(id));
+}
+
+fn draw(
+    trails: Res<Trails>,
+    frame: Res<RenderFrame>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut lines: Query<(&Handle<Mesh>, &mut Visible), With<TrailLines>>,
+) {
+    if !trails.is_changed() && !frame.is_changed() {
+        return;
+    }
+    profiling::scope!("trails");
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    for (_, trail) in trails.history.values() {
+        let fade = |k: usize| {
+            let f = k as f32 / trails.length as f32;
+            TRAIL_COLOR.map(|c| c * f)
+        };
+        for (k, (a, b)) in trail.iter().zip(trail.iter().skip(1)).enumerate() {
+            positions.push(frame.render_position(*a).into());
+            positions.push(frame.render_position(*b).into());
+            colors.push(fade(k + 1));
+            colors.push(fade(k + 2));
+        }
+    }
+    for (mesh, mut visible) in lines.iter_mut() {
+        visible.is_visible = !positions.is_empty();
+        if let Some(mesh) = meshes.get_mut(mesh).filter(|_| visible.is_visible) {
+            *mesh = trail_mesh(positions.clone(), colors.clone());
+        }
+    }
+}
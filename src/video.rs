@@ -0,0 +1,184 @@
+//! Videos of a run, the rendered frames piped into ffmpeg.
+//!
+//! `--video PATH` encodes the view into an mp4, a webm or any other container ffmpeg picks
+//! by the extension, at `--video-fps` frames per second, 30 by default. While recording
+//! the physics doesn't follow the wall clock: every rendered frame advances exactly
+//! `--video-steps` physics steps, 1 by default, and becomes one frame of the video. So the
+//! video plays smoothly at the pace of the simulation, however slow the rendering and the
+//! encoding are. Paused frames are left out.
+//!
+//! `ffmpeg` has to be on the `PATH`. The frames come from the capture pass, see `capture`,
+//! without the UI, and they keep the window size of the first frame, frames of a resized
+//! window are skipped.
+
+use bevy::{app::AppExit, ecs::schedule::ShouldRun, prelude::*};
+use galaxy_core::{Error, Result};
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+const FFMPEG: &str = "ffmpeg";
+
+/// the video of `--video`
+pub struct VideoSettings {
+    pub path: PathBuf,
+    /// frames per second of the video
+    pub fps: f64,
+    /// physics steps per frame
+    pub steps: u64,
+}
+
+impl VideoSettings {
+    /// checks that ffmpeg can be started, before the window opens
+    pub fn check_ffmpeg() -> Result<()> {
+        Command::new(FFMPEG)
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|_| ())
+            .map_err(|e| Error::io(FFMPEG, e))
+    }
+}
+
+/// the running ffmpeg, for frames of one size
+struct Encoder {
+    child: Child,
+    stdin: ChildStdin,
+    width: u32,
+    height: u32,
+}
+
+impl Encoder {
+    fn start(settings: &VideoSettings, width: u32, height: u32) -> std::io::Result<Self> {
+        let mut child = Command::new(FFMPEG)
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-framerate", &settings.fps.to_string()])
+            .args(["-i", "-"])
+            // the usual encoders need even sizes
+            .args([
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(&settings.path)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        Ok(Encoder {
+            child,
+            stdin,
+            width,
+            height,
+        })
+    }
+}
+
+/// the recording, the encoder starts with the first frame
+pub struct Video {
+    settings: VideoSettings,
+    encoder: Option<Encoder>,
+    /// the encoder failed, the rest of the run isn't recorded
+    stopped: bool,
+    /// physics steps at the last recorded frame
+    recorded_step: Option<u64>,
+    frames: u64,
+    /// frames skipped because the window size changed
+    skipped: u64,
+}
+
+impl Video {
+    pub fn new(settings: VideoSettings) -> Self {
+        Video {
+            settings,
+            encoder: None,
+            stopped: false,
+            recorded_step: None,
+            frames: 0,
+            skipped: 0,
+        }
+    }
+
+    /// whether the frame at `steps` physics steps goes into the video, once per step
+    pub fn wants_frame(&mut self, steps: u64) -> bool {
+        if self.stopped || self.recorded_step == Some(steps) {
+            return false;
+        }
+        self.recorded_step = Some(steps);
+        true
+    }
+
+    /// appends a frame of RGBA pixels
+    pub fn write_frame(&mut self, rgba: &[u8], width: u32, height: u32) {
+        if self.stopped {
+            return;
+        }
+        if self.encoder.is_none() {
+            match Encoder::start(&self.settings, width, height) {
+                Ok(encoder) => self.encoder = Some(encoder),
+                Err(e) => {
+                    error!("can't start {}: {}", FFMPEG, e);
+                    self.stopped = true;
+                    return;
+                }
+            }
+        }
+        let encoder = self.encoder.as_mut().expect("started above");
+        if (encoder.width, encoder.height) != (width, height) {
+            if self.skipped == 0 {
+                warn!("the window size changed, its frames aren't recorded");
+            }
+            self.skipped += 1;
+            return;
+        }
+        if let Err(e) = encoder.stdin.write_all(rgba) {
+            error!("video recording stopped: {}", e);
+            self.stopped = true;
+            return;
+        }
+        self.frames += 1;
+    }
+
+    /// closes the input of ffmpeg and waits until the file is complete
+    fn finish(&mut self) {
+        if let Some(Encoder {
+            mut child, stdin, ..
+        }) = self.encoder.take()
+        {
+            drop(stdin);
+            match child.wait() {
+                Ok(status) if status.success() => info!(
+                    "recorded {} frames to {}",
+                    self.frames,
+                    self.settings.path.display()
+                ),
+                Ok(status) => error!("{} failed with {}", FFMPEG, status),
+                Err(e) => error!("{} failed: {}", FFMPEG, e),
+            }
+        }
+        self.stopped = true;
+    }
+}
+
+/// run criteria of the physics stage while recording, `steps` physics steps per frame
+/// instead of the fixed timestep
+pub fn steps_per_frame(video: Res<Video>, mut taken: Local<u64>) -> ShouldRun {
+    if *taken < video.settings.steps.max(1) {
+        *taken += 1;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        *taken = 0;
+        ShouldRun::No
+    }
+}
+
+pub fn finish(mut exits: EventReader<AppExit>, mut video: ResMut<Video>) {
+    if exits.iter().next().is_some() {
+        video.finish();
+    }
+}
@@ -0,0 +1,239 @@
+//! Streams star positions and simulation events to WebSocket clients as binary frames.
+//!
+//! All numbers are little endian. Every frame starts with a kind byte:
+//!
+//! * `0` positions: u64 step, f64 simulated seconds, f64 meters per unit, u32 count,
+//!   then count times x, y, z as i16, each star's position divided by the scale
+//! * `1` collision, `2` merge, `3` escape, `4` accretion, `5` supernova: u64 entity bits
+//!   of the star the event is about, then x, y, z as f64 meters, for accretion the
+//!   position of the black hole, supernovae are not raised yet
+//!
+//! A headless run streams the position frames through `FrameSender` and
+//! `positions_frame` too, but no events, those come from the entities of a windowed run.
+
+use crate::{
+    events::{AccretionEvent, CollisionEvent, EscapeEvent, MergeEvent, SupernovaEvent},
+    state::GalaxyState,
+};
+use bevy::{math::DVec3, prelude::*};
+use galaxy_core::{Error, SimulationParameters};
+use std::{
+    io,
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+use tungstenite::{Message, WebSocket};
+
+pub struct WebSocketSettings {
+    /// address the server listens on
+    pub address: String,
+    /// only every n-th star is sent
+    pub decimation: usize,
+    /// position frames per second
+    pub rate: f64,
+}
+
+impl Default for WebSocketSettings {
+    fn default() -> Self {
+        Self {
+            address: std::env::var("GALAXY_WEBSOCKET")
+                .unwrap_or_else(|_| "127.0.0.1:9001".to_string()),
+            decimation: 1,
+            rate: 30.0,
+        }
+    }
+}
+
+/// sending side of the channel to the server thread
+pub struct FrameSender(Sender<Vec<u8>>);
+
+impl FrameSender {
+    /// starts the server thread listening on `address`
+    pub fn start(address: &str) -> galaxy_core::Result<Self> {
+        let error = |source: io::Error| Error::Network {
+            peer: address.to_string(),
+            source,
+        };
+        let listener = TcpListener::bind(address).map_err(error)?;
+        listener.set_nonblocking(true).map_err(error)?;
+        let (sender, receiver) = channel();
+        thread::spawn(move || serve(listener, receiver));
+        Ok(Self(sender))
+    }
+
+    /// broadcasts the frame to the connected clients
+    pub fn send(&self, frame: Vec<u8>) {
+        let _ = self.0.send(frame);
+    }
+}
+
+pub struct WebSocketPlugin;
+
+impl Plugin for WebSocketPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<WebSocketSettings>()
+            .add_startup_system(start_server.system())
+            .add_system(send_positions.system())
+            .add_system(send_events.system());
+    }
+}
+
+fn start_server(mut commands: Commands, settings: Res<WebSocketSettings>) {
+    match FrameSender::start(&settings.address) {
+        Ok(sender) => {
+            info!(
+                "streaming to WebSocket clients on ws://{}",
+                settings.address
+            );
+            commands.insert_resource(sender);
+        }
+        Err(e) => error!("can't start WebSocket server: {}", e),
+    }
+}
+
+/// accepts clients and broadcasts every frame to all of them until the app quits
+fn serve(listener: TcpListener, frames: Receiver<Vec<u8>>) {
+    let mut clients: Vec<WebSocket<TcpStream>> = Vec::new();
+    loop {
+        while let Ok((stream, address)) = listener.accept() {
+            let _ = stream.set_nonblocking(false);
+            let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+            match tungstenite::accept(stream) {
+                Ok(client) => {
+                    info!("WebSocket client {} connected", address);
+                    clients.push(client);
+                }
+                Err(e) => warn!("WebSocket handshake with {} failed: {}", address, e),
+            }
+        }
+
+        match frames.recv_timeout(Duration::from_millis(50)) {
+            Ok(frame) => {
+                clients.retain_mut(|client| client.send(Message::binary(frame.clone())).is_ok())
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+fn quantize(value: f64, scale: f64) -> i16 {
+    (value / scale)
+        .round()
+        .clamp(i16::MIN as f64, i16::MAX as f64) as i16
+}
+
+fn send_positions(
+    time: Res<Time>,
+    mut since_last: Local<f64>,
+    sender: Option<Res<FrameSender>>,
+    settings: Res<WebSocketSettings>,
+    state: Res<GalaxyState>,
+    parameters: Res<SimulationParameters>,
+) {
+    let sender = match sender {
+        Some(sender) => sender,
+        None => return,
+    };
+    *since_last += time.delta_seconds_f64();
+    if *since_last < 1.0 / settings.rate {
+        return;
+    }
+    *since_last = 0.0;
+
+    sender.send(positions_frame(
+        state.steps,
+        state.time,
+        &state.particles.positions,
+        settings.decimation,
+        parameters.galaxy_diameter,
+    ));
+}
+
+/// the positions frame of every `decimation`-th star
+pub fn positions_frame(
+    steps: u64,
+    time: f64,
+    positions: &[DVec3],
+    decimation: usize,
+    galaxy_diameter: f64,
+) -> Vec<u8> {
+    // the farthest sent coordinate fills the i16s, the scale is in the frame for the clients
+    let decimation = decimation.max(1);
+    let extent = positions
+        .iter()
+        .step_by(decimation)
+        .map(|p| p.abs().max_element())
+        .fold(0.0, f64::max);
+    let scale = if extent > 0.0 {
+        extent
+    } else {
+        galaxy_diameter
+    } / i16::MAX as f64;
+    let count = positions.len().div_ceil(decimation);
+    let mut frame = Vec::with_capacity(29 + count * 6);
+    frame.push(0);
+    frame.extend_from_slice(&steps.to_le_bytes());
+    frame.extend_from_slice(&time.to_le_bytes());
+    frame.extend_from_slice(&scale.to_le_bytes());
+    frame.extend_from_slice(&(count as u32).to_le_bytes());
+    for p in positions.iter().step_by(decimation) {
+        for v in [p.x, p.y, p.z].iter() {
+            frame.extend_from_slice(&quantize(*v, scale).to_le_bytes());
+        }
+    }
+    frame
+}
+
+fn event_frame(kind: u8, entity: Entity, position: DVec3) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(33);
+    frame.push(kind);
+    frame.extend_from_slice(&entity.to_bits().to_le_bytes());
+    for v in [position.x, position.y, position.z].iter() {
+        frame.extend_from_slice(&v.to_le_bytes());
+    }
+    frame
+}
+
+fn send_events(
+    sender: Option<Res<FrameSender>>,
+    state: Res<GalaxyState>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut merges: EventReader<MergeEvent>,
+    mut escapes: EventReader<EscapeEvent>,
+    mut accretions: EventReader<AccretionEvent>,
+    mut supernovae: EventReader<SupernovaEvent>,
+) {
+    let sender = match sender {
+        Some(sender) => sender,
+        None => return,
+    };
+    let frames = collisions
+        .iter()
+        .map(|e| event_frame(1, e.a, e.position))
+        .chain(
+            merges
+                .iter()
+                .map(|e| event_frame(2, e.survivor, e.position)),
+        )
+        .chain(escapes.iter().map(|e| event_frame(3, e.star, e.position)))
+        .chain(accretions.iter().map(|e| {
+            // the swallowed star is gone, report where the black hole is
+            let position = state
+                .entities
+                .iter()
+                .position(|&entity| entity == e.black_hole)
+                .map_or(DVec3::default(), |i| state.particles.positions[i]);
+            event_frame(4, e.star, position)
+        }))
+        .chain(
+            supernovae
+                .iter()
+                .map(|e| event_frame(5, e.star, e.position)),
+        );
+    for frame in frames {
+        sender.send(frame);
+    }
+}